@@ -0,0 +1,140 @@
+//! Scrape latency baseline, at 10k and 100k generated snapshots
+//!
+//! ```sh
+//! cargo bench --bench scrape            # 10000 and 100000 snapshots
+//! cargo bench --bench scrape -- 50000   # custom snapshot counts
+//! ```
+//!
+//! For each count, times parsing and rendering in-process, then scrapes `/metrics` of a
+//! server running `fake-kopia` (uncached, so each scrape runs and parses `kopia`), reporting
+//! the server's peak resident memory (Linux only).
+
+use eyre::Result;
+use kopia_exporter::KopiaSnapshots;
+use kopia_exporter::testkit::{ServerConfig, TestServer};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const KOPIA_EXPORTER_BIN: &str = env!("CARGO_BIN_EXE_kopia-exporter");
+const FAKE_KOPIA_BIN: &str = env!("CARGO_BIN_EXE_fake-kopia");
+
+const DEFAULT_COUNTS: [usize; 2] = [10_000, 100_000];
+const ITERATIONS: usize = 5;
+const SOURCE_COUNT: usize = 100;
+
+fn main() -> Result<()> {
+    // `cargo bench` passes `--bench`, skip flags
+    let counts: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| arg.parse())
+        .collect::<Result<_, _>>()?;
+    let counts = if counts.is_empty() {
+        DEFAULT_COUNTS.to_vec()
+    } else {
+        counts
+    };
+
+    for count in counts {
+        println!("{count} snapshots, {SOURCE_COUNT} sources");
+        bench_in_process(count)?;
+        bench_server(count)?;
+    }
+    Ok(())
+}
+
+fn generated_env(count: usize) -> [(&'static str, String); 2] {
+    [
+        ("FAKE_KOPIA_SNAPSHOT_COUNT", count.to_string()),
+        ("FAKE_KOPIA_SOURCE_COUNT", SOURCE_COUNT.to_string()),
+    ]
+}
+
+fn bench_in_process(count: usize) -> Result<()> {
+    let output = Command::new(FAKE_KOPIA_BIN)
+        .args(["snapshot", "list", "--json"])
+        .envs(generated_env(count))
+        .output()?;
+    eyre::ensure!(output.status.success(), "fake-kopia failed: {output:?}");
+    let json = output.stdout;
+
+    let mut parse_times = vec![];
+    let mut render_times = vec![];
+    let mut metrics_len = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let (snapshots, _) = KopiaSnapshots::new_from_reader_with_report(json.as_slice())?;
+        parse_times.push(start.elapsed());
+
+        let start = Instant::now();
+        let metrics = snapshots.generate_all_metrics(jiff::Timestamp::now());
+        render_times.push(start.elapsed());
+        metrics_len = metrics.len();
+    }
+    println!("  input:  {} bytes", json.len());
+    println!("  parse:  {}", Summary::new(parse_times));
+    println!(
+        "  render: {} ({metrics_len} bytes)",
+        Summary::new(render_times)
+    );
+    Ok(())
+}
+
+fn bench_server(count: usize) -> Result<()> {
+    let mut config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--cache-seconds", "0"]);
+    for (key, value) in generated_env(count) {
+        config = config.with_env(key, value);
+    }
+    let server = TestServer::start(config)?;
+
+    let mut scrape_times = vec![];
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let response = server.get("/metrics")?;
+        scrape_times.push(start.elapsed());
+        eyre::ensure!(
+            response.status_code == 200,
+            "scrape failed: {} {}",
+            response.status_code,
+            response.as_str()?
+        );
+    }
+    println!("  scrape: {}", Summary::new(scrape_times));
+    match peak_rss_kib(server.id()) {
+        Some(kib) => println!("  server peak RSS: {kib} KiB"),
+        None => println!("  server peak RSS: unavailable"),
+    }
+    Ok(())
+}
+
+/// Reads `VmHWM` of the process from `/proc`
+fn peak_rss_kib(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+/// Minimum, median and maximum of the durations
+struct Summary {
+    sorted: Vec<Duration>,
+}
+impl Summary {
+    fn new(mut durations: Vec<Duration>) -> Self {
+        durations.sort();
+        Self { sorted: durations }
+    }
+}
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { sorted } = self;
+        let (Some(min), Some(median), Some(max)) =
+            (sorted.first(), sorted.get(sorted.len() / 2), sorted.last())
+        else {
+            return write!(f, "no samples");
+        };
+        write!(f, "min {min:.1?}, median {median:.1?}, max {max:.1?}")
+    }
+}
@@ -0,0 +1,45 @@
+//! Embeds the build metadata of [`BuildInfo`](src/build_info.rs)
+//!
+//! Each value can be overridden by its environment variable, for builds without a git
+//! checkout (e.g. packaging from a source tarball).
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=KOPIA_EXPORTER_GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if std::path::Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+
+    let git_commit = std::env::var("KOPIA_EXPORTER_GIT_COMMIT")
+        .ok()
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()?;
+            let commit = String::from_utf8(output.stdout).ok()?;
+            output.status.success().then(|| commit.trim().to_owned())
+        })
+        .unwrap_or_else(|| "unknown".to_owned());
+
+    // reproducible builds set SOURCE_DATE_EPOCH
+    let build_timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .or_else(|| {
+            let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+            Some(elapsed.as_secs())
+        })
+        .unwrap_or_default();
+
+    let target = std::env::var("TARGET").unwrap_or_else(|_| "unknown".to_owned());
+
+    println!("cargo:rustc-env=KOPIA_EXPORTER_GIT_COMMIT={git_commit}");
+    println!("cargo:rustc-env=KOPIA_EXPORTER_BUILD_TIMESTAMP={build_timestamp}");
+    println!("cargo:rustc-env=KOPIA_EXPORTER_TARGET={target}");
+}
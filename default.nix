@@ -1,6 +1,7 @@
 {
   lib,
   rustPlatform,
+  installShellFiles,
 }:
 rustPlatform.buildRustPackage rec {
   pname = "kopia-exporter";
@@ -35,6 +36,14 @@ rustPlatform.buildRustPackage rec {
   # Build both kopia-exporter and fake-kopia binaries
   cargoBuildFlags = ["--bin" "kopia-exporter" "--bin" "fake-kopia"];
 
+  nativeBuildInputs = [installShellFiles];
+
+  postInstall = ''
+    mkdir man
+    $out/bin/kopia-exporter generate man --out-dir man
+    installManPage man/*.1
+  '';
+
   meta = with lib; {
     description = "A lightweight Prometheus metrics exporter for Kopia backup repositories";
     license = licenses.mit;
@@ -0,0 +1,20 @@
+//! Parses arbitrary bytes as a `kopia snapshot list --json` output, rendering the metrics of
+//! anything accepted
+//!
+//! Parsing may fail, but must never panic.
+
+#![no_main]
+
+use kopia_exporter::{KopiaSnapshots, LatestSnapshotPolicy, ParseOptions};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let now = jiff::Timestamp::UNIX_EPOCH;
+    if let Ok((snapshots, _)) = KopiaSnapshots::new_from_reader_with_report(data) {
+        let _ = snapshots.generate_all_metrics(now);
+    }
+    let options = ParseOptions::aggregate_only(LatestSnapshotPolicy::Newest);
+    if let Ok((snapshots, _)) = KopiaSnapshots::new_from_reader_with_options(data, options) {
+        let _ = snapshots.generate_all_metrics(now);
+    }
+});
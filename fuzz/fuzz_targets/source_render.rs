@@ -0,0 +1,26 @@
+//! Renders arbitrary sources as `user@host:/path` strings
+//!
+//! Rendering may reject a source, but must never panic, and an accepted source must split
+//! back into the same fields (the rendering is unambiguous) with no control characters.
+
+#![no_main]
+
+use kopia_exporter::kopia::Source;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|fields: (String, String, String)| {
+    let (host, user_name, path) = fields;
+    let source = Source {
+        host,
+        user_name,
+        path,
+    };
+    if let Ok(rendered) = source.render() {
+        let (user_name, rest) = rendered.as_str().split_once('@').expect("contains @");
+        let (host, path) = rest.split_once(':').expect("contains :");
+        assert_eq!(user_name, source.user_name);
+        assert_eq!(host, source.host);
+        assert_eq!(path, source.path);
+        assert!(!rendered.as_str().chars().any(char::is_control));
+    }
+});
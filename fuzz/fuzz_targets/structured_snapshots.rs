@@ -0,0 +1,90 @@
+//! Generates structurally valid snapshot listings with arbitrary field values (timestamps,
+//! sizes, retention reasons), to reach the metrics past the JSON parser
+//!
+//! The listing round-trips through JSON, and rendering the metrics must never panic.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use kopia_exporter::{KopiaSnapshots, SnapshotJson, kopia::Source};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct FuzzSnapshot {
+    id: String,
+    source: u8,
+    start_time: FuzzTime,
+    end_time: FuzzTime,
+    total_size: u64,
+    file_count: u64,
+    error_count: u64,
+    failed_files: u64,
+    retention_reasons: Vec<String>,
+    incomplete: Option<String>,
+}
+
+#[derive(Arbitrary, Debug)]
+enum FuzzTime {
+    Seconds(i64),
+    Raw(String),
+}
+impl FuzzTime {
+    fn render(&self) -> String {
+        match self {
+            Self::Seconds(seconds) => jiff::Timestamp::from_second(*seconds)
+                .map(|timestamp| timestamp.to_string())
+                .unwrap_or_default(),
+            Self::Raw(raw) => raw.clone(),
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    now: i64,
+    snapshots: Vec<FuzzSnapshot>,
+}
+
+fuzz_target!(|input: Input| {
+    let snapshots: Vec<SnapshotJson> = input
+        .snapshots
+        .iter()
+        .map(|snapshot| {
+            let retention_reasons: Vec<&str> = snapshot
+                .retention_reasons
+                .iter()
+                .map(String::as_str)
+                .collect();
+            let mut builder = SnapshotJson::builder()
+                .id(&snapshot.id)
+                .source(Source {
+                    host: "host".to_string(),
+                    user_name: "user".to_string(),
+                    path: format!("/path{}", snapshot.source % 4),
+                })
+                .total_size(snapshot.total_size)
+                .file_count(snapshot.file_count)
+                .error_count(snapshot.error_count)
+                .failed_files(snapshot.failed_files)
+                .retention_reasons(&retention_reasons);
+            if let Some(reason) = &snapshot.incomplete {
+                builder = builder.incomplete(reason);
+            }
+            let mut snapshot_json = builder.build();
+            snapshot_json.start_time = snapshot.start_time.render();
+            snapshot_json.end_time = snapshot.end_time.render();
+            snapshot_json
+        })
+        .collect();
+    let Ok(now) = jiff::Timestamp::from_second(input.now) else {
+        return;
+    };
+
+    let (direct, _) = KopiaSnapshots::new_from_snapshots_with_report(snapshots.clone());
+    let _ = direct.generate_all_metrics(now);
+
+    let json = serde_json::to_vec(&snapshots).expect("serializable");
+    let (parsed, _) =
+        KopiaSnapshots::new_from_reader_with_report(json.as_slice()).expect("valid listing");
+    let _ = parsed.generate_all_metrics(now);
+});
@@ -131,6 +131,8 @@ in {
             Group = cfg.group;
             Restart = "always";
             RestartSec = "10s";
+            # invalid command line or configuration, restarting will not help
+            RestartPreventExitStatus = "2 78";
 
             # Security hardening
             NoNewPrivileges = true;
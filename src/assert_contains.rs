@@ -103,5 +103,115 @@ to be contained in found string:
         }
         s
     }
+
+    /// Asserts that the [`ToString`] representation contains none of the specified lines
+    ///
+    /// Returns the string for convenient chanining
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kopia_exporter::AssertContains;
+    /// "metric_a 1
+    /// metric_b 2".assert_not_contains_lines(&[
+    ///     "metric_c 3",
+    ///     "metric_b", // not the entire line
+    /// ]);
+    /// ```
+    ///
+    /// ```should_panic
+    /// # use kopia_exporter::AssertContains;
+    /// "metric_a 1
+    /// metric_b 2".assert_not_contains_lines(&[
+    ///     "metric_b 2", // <-- panic: present in input string
+    /// ]);
+    /// ```
+    #[track_caller]
+    fn assert_not_contains_lines(&self, lines: &[&str]) -> String {
+        assert!(
+            !lines.is_empty(),
+            "refusing empty list for assert_not_contains_lines"
+        );
+
+        let s = self.to_string();
+        for line in lines {
+            assert!(
+                !s.lines().any(|l| l == *line),
+                r#"expected line:
+"""
+{line}
+"""
+to be absent from found string:
+"""
+{s}
+""""#
+            );
+        }
+        s
+    }
+
+    /// Asserts that at least one line of the [`ToString`] representation matches the
+    /// `predicate`
+    ///
+    /// Returns the string for convenient chanining
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kopia_exporter::AssertContains;
+    /// "# TYPE metric_a gauge
+    /// metric_a{source=\"a\"} 12".assert_contains_matching(|line| {
+    ///     line.strip_prefix("metric_a{source=\"a\"} ")
+    ///         .is_some_and(|value| value.parse::<u64>().is_ok())
+    /// });
+    /// ```
+    ///
+    /// ```should_panic
+    /// # use kopia_exporter::AssertContains;
+    /// "metric_a 1".assert_contains_matching(|line| line.starts_with("metric_b ")); // panic: no matching line
+    /// ```
+    #[track_caller]
+    fn assert_contains_matching(&self, predicate: impl Fn(&str) -> bool) -> String {
+        self.assert_matching_count(predicate, 1..)
+    }
+
+    /// Asserts that the number of lines of the [`ToString`] representation matching the
+    /// `predicate` is within `expected_count`
+    ///
+    /// Returns the string for convenient chanining
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kopia_exporter::AssertContains;
+    /// "metric_a 1
+    /// metric_a 2
+    /// metric_b 3"
+    ///     .assert_matching_count(|line| line.starts_with("metric_a "), 2..=2)
+    ///     .assert_matching_count(|line| line.starts_with("metric_c "), ..1);
+    /// ```
+    ///
+    /// ```should_panic
+    /// # use kopia_exporter::AssertContains;
+    /// "metric_a 1
+    /// metric_a 2".assert_matching_count(|line| line.starts_with("metric_a "), 1..=1); // panic: 2 matching lines
+    /// ```
+    #[track_caller]
+    fn assert_matching_count(
+        &self,
+        predicate: impl Fn(&str) -> bool,
+        expected_count: impl std::ops::RangeBounds<usize> + std::fmt::Debug,
+    ) -> String {
+        let s = self.to_string();
+        let count = s.lines().filter(|l| predicate(l)).count();
+        assert!(
+            expected_count.contains(&count),
+            r#"expected {expected_count:?} matching lines, found {count} in string:
+"""
+{s}
+""""#
+        );
+        s
+    }
 }
 impl<T> AssertContains for T where T: ToString {}
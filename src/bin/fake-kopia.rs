@@ -1,8 +1,8 @@
 //! A fake kopia binary for testing and development.
 //!
 //! This binary mimics the behavior of the real kopia CLI tool,
-//! providing sample JSON output for snapshot listings and basic
-//! repository status commands. Used primarily for testing the
+//! providing sample JSON output for snapshot listings, repository
+//! status and maintenance info commands. Used primarily for testing the
 //! kopia-exporter without requiring a real kopia installation.
 
 use clap::{Parser, Subcommand};
@@ -30,6 +30,11 @@ enum Commands {
         #[command(subcommand)]
         action: RepositoryAction,
     },
+    /// Maintenance operations
+    Maintenance {
+        #[command(subcommand)]
+        action: MaintenanceAction,
+    },
 }
 
 #[derive(Subcommand)]
@@ -45,7 +50,142 @@ enum SnapshotAction {
 #[derive(Subcommand)]
 enum RepositoryAction {
     /// Show repository status
-    Status,
+    Status {
+        /// Output in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MaintenanceAction {
+    /// Show maintenance parameters and history
+    Info {
+        /// Output in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Failure injected by `FAKE_KOPIA_FAILURE`, for exercising error paths
+#[derive(Clone, Copy, Debug)]
+enum Failure {
+    /// Exit with `FAKE_KOPIA_EXIT_CODE` (default 1), after writing `FAKE_KOPIA_STDERR`
+    Exit,
+    /// Print output which is not JSON
+    MalformedJson,
+    /// Print the JSON output, cut off in the middle
+    TruncatedJson,
+    /// Print nothing
+    Empty,
+}
+impl std::str::FromStr for Failure {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "exit" => Ok(Self::Exit),
+            "malformed-json" => Ok(Self::MalformedJson),
+            "truncated-json" => Ok(Self::TruncatedJson),
+            "empty" => Ok(Self::Empty),
+            _ => Err(eyre::eyre!(
+                "unknown FAKE_KOPIA_FAILURE {s:?}, expected exit, malformed-json, truncated-json or empty"
+            )),
+        }
+    }
+}
+
+/// Latency distribution from `FAKE_KOPIA_LATENCY`, added after any fixed sleep
+#[derive(Clone, Copy, Debug)]
+enum Latency {
+    /// `uniform:MIN:MAX`, sleep a uniformly random number of seconds
+    Uniform { min: f64, max: f64 },
+    /// `spike:N:SECS`, sleep for SECS every Nth invocation (counted by
+    /// `FAKE_KOPIA_COUNTER_FILE`), otherwise not at all
+    Spike { every: u64, secs: f64 },
+}
+impl std::str::FromStr for Latency {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            ["uniform", min, max] => Ok(Self::Uniform {
+                min: min.parse()?,
+                max: max.parse()?,
+            }),
+            ["spike", every, secs] => Ok(Self::Spike {
+                every: every.parse()?,
+                secs: secs.parse()?,
+            }),
+            _ => Err(eyre::eyre!(
+                "unknown FAKE_KOPIA_LATENCY {s:?}, expected uniform:MIN:MAX or spike:N:SECS"
+            )),
+        }
+    }
+}
+impl Latency {
+    fn duration(self, invocation: &Invocation) -> std::time::Duration {
+        let secs = match self {
+            Self::Uniform { min, max } => min + (max - min) * invocation.random(0),
+            Self::Spike { every, secs } => {
+                if every > 0 && invocation.number.is_multiple_of(every) {
+                    secs
+                } else {
+                    0.0
+                }
+            }
+        };
+        std::time::Duration::from_secs_f64(secs.max(0.0))
+    }
+}
+
+/// Number and random seed of this invocation
+struct Invocation {
+    /// 1-based count of invocations sharing the `FAKE_KOPIA_COUNTER_FILE`, or 1 without one
+    number: u64,
+    /// From `FAKE_KOPIA_SEED`, or the time and process ID if unset
+    seed: u64,
+}
+impl Invocation {
+    fn new() -> Result<Self> {
+        let number = match std::env::var_os("FAKE_KOPIA_COUNTER_FILE") {
+            // not atomic, concurrent invocations may share a number
+            Some(path) => {
+                let previous: u64 = std::fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|count| count.trim().parse().ok())
+                    .unwrap_or(0);
+                std::fs::write(&path, (previous + 1).to_string())?;
+                previous + 1
+            }
+            None => 1,
+        };
+        let seed = if let Ok(seed) = std::env::var("FAKE_KOPIA_SEED") {
+            seed.parse()?
+        } else {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.subsec_nanos());
+            u64::from(nanos) ^ u64::from(std::process::id()).rotate_left(32)
+        };
+        Ok(Self { number, seed })
+    }
+
+    /// Returns a random number in `[0, 1)`, distinct for each `stream`, deterministic for a
+    /// given seed and invocation number (splitmix64)
+    fn random(&self, stream: u64) -> f64 {
+        let mut z = self
+            .seed
+            .wrapping_add(self.number.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .wrapping_add(stream.wrapping_mul(0xD1B5_4A32_D192_ED03));
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        #[expect(clippy::cast_precision_loss)] // 53 bits are exact
+        let random = (z >> 11) as f64 / (1u64 << 53) as f64;
+        random
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -70,6 +210,11 @@ fn main() -> Result<()> {
         eprintln!("fake-kopia-test-stderr");
     }
 
+    // Write a warning to stderr (also on success), as kopia does e.g. for a deprecated flag
+    if let Ok(warning) = std::env::var("FAKE_KOPIA_WARNING") {
+        eprintln!("{warning}");
+    }
+
     if let Some(sleep) = sleep {
         match sleep {
             Sleep::ForSecs(secs) => {
@@ -81,9 +226,29 @@ fn main() -> Result<()> {
         }
     }
 
+    let invocation = Invocation::new()?;
+    if let Ok(latency) = std::env::var("FAKE_KOPIA_LATENCY") {
+        let latency: Latency = latency.parse()?;
+        std::thread::sleep(latency.duration(&invocation));
+    }
+
+    let failure = std::env::var("FAKE_KOPIA_FAILURE")
+        .ok()
+        .map(|failure| failure.parse::<Failure>())
+        .transpose()?;
+    // with a failure rate, fail randomly (by default, exiting unsuccessfully)
+    let failure = match std::env::var("FAKE_KOPIA_FAILURE_RATE") {
+        Ok(rate) => {
+            let rate: f64 = rate.parse()?;
+            (invocation.random(1) < rate).then_some(failure.unwrap_or(Failure::Exit))
+        }
+        Err(_) => failure,
+    };
+
     match cli.command {
-        Commands::Snapshot { action } => handle_snapshot_command(&action)?,
-        Commands::Repository { action } => handle_repository_command(&action),
+        Commands::Snapshot { action } => handle_snapshot_command(&action, failure)?,
+        Commands::Repository { action } => handle_repository_command(&action, failure)?,
+        Commands::Maintenance { action } => handle_maintenance_command(&action, failure)?,
     }
 
     Ok(())
@@ -100,15 +265,27 @@ fn log_invocation(sleep: Option<Sleep>) -> Result<()> {
     Ok(())
 }
 
-fn handle_snapshot_command(action: &SnapshotAction) -> Result<()> {
+fn handle_snapshot_command(action: &SnapshotAction, failure: Option<Failure>) -> Result<()> {
     match action {
         SnapshotAction::List { json } => {
             if *json {
-                if let Ok(mb_str) = std::env::var("FAKE_KOPIA_LARGE_OUTPUT_MB") {
+                let bytes = read_fixture(
+                    "FAKE_KOPIA_SNAPSHOTS_FILE",
+                    include_str!("../sample_kopia-snapshot-list.json"),
+                )?;
+                // fixtures may contain invalid UTF-8, printed as-is
+                let content = String::from_utf8_lossy(&bytes);
+                if let Some(failure) = failure {
+                    print_failure(failure, &content);
+                } else if let Ok(count) = std::env::var("FAKE_KOPIA_SNAPSHOT_COUNT") {
+                    let source_count =
+                        std::env::var("FAKE_KOPIA_SOURCE_COUNT").map_or(Ok(1), |s| s.parse())?;
+                    print_generated_snapshots(&content, count.parse()?, source_count)?;
+                } else if let Ok(mb_str) = std::env::var("FAKE_KOPIA_LARGE_OUTPUT_MB") {
                     let target_mb: usize = mb_str.parse()?;
-                    print_large_snapshots(target_mb)?;
+                    print_large_snapshots(&content, target_mb)?;
                 } else {
-                    print_sample_snapshots();
+                    std::io::Write::write_all(&mut std::io::stdout(), &bytes)?;
                 }
                 Ok(())
             } else {
@@ -118,27 +295,101 @@ fn handle_snapshot_command(action: &SnapshotAction) -> Result<()> {
     }
 }
 
-fn handle_repository_command(action: &RepositoryAction) {
+fn print_failure(failure: Failure, content: &str) {
+    match failure {
+        Failure::Exit => {
+            let stderr = std::env::var("FAKE_KOPIA_STDERR")
+                .unwrap_or_else(|_| "ERROR fake-kopia failure".to_owned());
+            let exit_code = std::env::var("FAKE_KOPIA_EXIT_CODE")
+                .ok()
+                .and_then(|code| code.parse().ok())
+                .unwrap_or(1);
+            eprintln!("{stderr}");
+            std::process::exit(exit_code);
+        }
+        Failure::MalformedJson => println!("this is not JSON"),
+        Failure::TruncatedJson => {
+            // cut within the second snapshot (or halfway, for fewer snapshots)
+            let cut = content
+                .match_indices("\"id\"")
+                .nth(1)
+                .map_or(content.len() / 2, |(index, _)| index + 4);
+            print!("{}", &content[..cut]);
+        }
+        Failure::Empty => {}
+    }
+}
+
+fn handle_repository_command(action: &RepositoryAction, failure: Option<Failure>) -> Result<()> {
     match action {
-        RepositoryAction::Status => {
+        RepositoryAction::Status { json: true } => {
+            let content = read_fixture(
+                "FAKE_KOPIA_REPOSITORY_STATUS_FILE",
+                include_str!("../sample_kopia-repository-status.json"),
+            )?;
+            print_json(&String::from_utf8_lossy(&content), failure);
+        }
+        RepositoryAction::Status { json: false } => {
             println!("Repository status: OK");
             println!("Connected to: fake-repository");
         }
     }
+    Ok(())
+}
+
+fn handle_maintenance_command(action: &MaintenanceAction, failure: Option<Failure>) -> Result<()> {
+    match action {
+        MaintenanceAction::Info { json: true } => {
+            let content = read_fixture(
+                "FAKE_KOPIA_MAINTENANCE_INFO_FILE",
+                include_str!("../sample_kopia-maintenance-info.json"),
+            )?;
+            print_json(&String::from_utf8_lossy(&content), failure);
+        }
+        MaintenanceAction::Info { json: false } => {
+            println!("Owner: kopia-system@milton");
+            println!("Quick Cycle: enabled, interval 1h0m0s");
+            println!("Full Cycle: enabled, interval 24h0m0s");
+        }
+    }
+    Ok(())
 }
 
-fn print_sample_snapshots() {
-    let content = include_str!("../sample_kopia-snapshot-list.json");
-    print!("{content}");
+fn print_json(content: &str, failure: Option<Failure>) {
+    match failure {
+        Some(failure) => print_failure(failure, content),
+        None => print!("{content}"),
+    }
+}
+
+/// Reads the JSON output of a command, from the fixture selected by the environment:
+///
+/// - `var` unset: the embedded `default`
+/// - `var` is a file: its contents
+/// - `var` is a directory: the file `<FAKE_KOPIA_SCENARIO>.json` in it
+fn read_fixture(var: &str, default: &str) -> Result<Vec<u8>> {
+    let Some(path) = std::env::var_os(var) else {
+        return Ok(default.as_bytes().to_vec());
+    };
+    let mut path = std::path::PathBuf::from(path);
+    if path.is_dir() {
+        let scenario = std::env::var("FAKE_KOPIA_SCENARIO").map_err(|_| {
+            eyre::eyre!(
+                "FAKE_KOPIA_SCENARIO is required to select a scenario in {}",
+                path.display()
+            )
+        })?;
+        path.push(format!("{scenario}.json"));
+    }
+    std::fs::read(&path).map_err(|e| eyre::eyre!("failed to read fixture {}: {e}", path.display()))
 }
 
-fn print_large_snapshots(target_mb: usize) -> Result<()> {
+fn print_large_snapshots(sample_content: &str, target_mb: usize) -> Result<()> {
     use std::io::{self, Write};
 
     let target_bytes = target_mb * 1024 * 1024;
 
     // Parse the sample snapshots
-    let sample_content = include_str!("../sample_kopia-snapshot-list.json");
     let snapshots: Vec<serde_json::Value> = serde_json::from_str(sample_content)?;
 
     // Ensure we have at least one snapshot to use as a template
@@ -155,6 +406,7 @@ fn print_large_snapshots(target_mb: usize) -> Result<()> {
     } else {
         eyre::bail!("Sample JSON snapshot must have source.path field");
     }
+    marker_start["id"] = serde_json::json!("large-output-test-start");
 
     // Create a special marker snapshot for the end
     let mut marker_end = snapshots[0].clone();
@@ -165,6 +417,7 @@ fn print_large_snapshots(target_mb: usize) -> Result<()> {
     } else {
         eyre::bail!("Sample JSON snapshot must have source.path field");
     }
+    marker_end["id"] = serde_json::json!("large-output-test-end");
 
     // Pre-compute the JSON strings to know exact sizes
     let marker_start_json = serde_json::to_string(&marker_start)?;
@@ -191,8 +444,10 @@ fn print_large_snapshots(target_mb: usize) -> Result<()> {
     // Keep adding snapshots until we reach the target size
     let mut index = 0;
     while current_bytes < target_bytes - bytes_reserved_for_end {
-        let snapshot = &snapshots[index % snapshots.len()];
-        let snapshot_json = serde_json::to_string(snapshot)?;
+        // unique IDs, as the exporter skips duplicates
+        let mut snapshot = snapshots[index % snapshots.len()].clone();
+        snapshot["id"] = serde_json::json!(format!("large{index:08x}"));
+        let snapshot_json = serde_json::to_string(&snapshot)?;
         let snapshot_total_bytes = snapshot_overhead_bytes + snapshot_json.len();
 
         // Check if adding this snapshot would exceed our target
@@ -211,3 +466,38 @@ fn print_large_snapshots(target_mb: usize) -> Result<()> {
     handle.flush()?;
     Ok(())
 }
+
+/// Prints `count` snapshots cycling through the sample, spread evenly over `source_count`
+/// sources (`/generated-<n>` paths) with unique IDs
+fn print_generated_snapshots(
+    sample_content: &str,
+    count: usize,
+    source_count: usize,
+) -> Result<()> {
+    use std::io::{self, Write};
+
+    let snapshots: Vec<serde_json::Value> = serde_json::from_str(sample_content)?;
+    if snapshots.is_empty() {
+        eyre::bail!("Sample JSON must contain at least one snapshot");
+    }
+    let source_count = source_count.max(1);
+
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout.lock());
+    write!(handle, "[")?;
+    for index in 0..count {
+        let mut snapshot = snapshots[index % snapshots.len()].clone();
+        snapshot["id"] = serde_json::json!(format!("generated{index:08x}"));
+        snapshot["source"]["path"] =
+            serde_json::json!(format!("/generated-{}", index % source_count));
+        let separator = if index == 0 { "" } else { "," };
+        write!(
+            handle,
+            "{separator}\n {}",
+            serde_json::to_string(&snapshot)?
+        )?;
+    }
+    write!(handle, "\n]")?;
+    handle.flush()?;
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Version and build metadata, for triaging reports from mixed-version deployments
+
+use crate::metrics::{CustomMetric, MetricType};
+use std::fmt;
+
+/// Version and build metadata of this crate, embedded at build time
+///
+/// Also exported as the `kopia_exporter_build_info` metric, see [`Self::to_metric`].
+#[derive(Clone, Debug)]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: &'static str,
+    /// Abbreviated git commit hash, or `"unknown"` if built outside a git checkout
+    pub git_commit: &'static str,
+    /// Build time in seconds since the Unix epoch (`SOURCE_DATE_EPOCH`, if set)
+    pub build_timestamp: i64,
+    /// Target triple, e.g. `"x86_64-unknown-linux-gnu"`
+    pub target: &'static str,
+    /// Enabled cargo features
+    pub features: Vec<&'static str>,
+}
+impl BuildInfo {
+    /// Returns the metadata of the running build
+    #[must_use]
+    pub fn current() -> Self {
+        let features = [
+            ("cli", cfg!(feature = "cli")),
+            ("prometheus-client", cfg!(feature = "prometheus-client")),
+            ("push", cfg!(feature = "push")),
+            ("testkit", cfg!(feature = "testkit")),
+            ("tokio", cfg!(feature = "tokio")),
+        ];
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: env!("KOPIA_EXPORTER_GIT_COMMIT"),
+            build_timestamp: env!("KOPIA_EXPORTER_BUILD_TIMESTAMP")
+                .parse()
+                .unwrap_or_default(),
+            target: env!("KOPIA_EXPORTER_TARGET"),
+            features: features
+                .into_iter()
+                .filter_map(|(feature, enabled)| enabled.then_some(feature))
+                .collect(),
+        }
+    }
+
+    /// Returns the `kopia_exporter_build_info` metric, labeling a constant value of 1 with the
+    /// `version`, `commit`, `target` and (comma-separated) `features`
+    ///
+    /// Register with [`KopiaSnapshots::with_custom_metric`](crate::KopiaSnapshots::with_custom_metric) to include it in all outputs.
+    #[must_use]
+    pub fn to_metric(&self) -> CustomMetric {
+        let Self {
+            version,
+            git_commit,
+            build_timestamp: _,
+            target,
+            features,
+        } = self;
+        let features = features.join(",");
+        CustomMetric::new(
+            "kopia_exporter_build_info",
+            "Build information of the exporter, with a constant value of 1",
+            MetricType::Gauge,
+        )
+        .with_sample(
+            &[
+                ("version", version),
+                ("commit", git_commit),
+                ("target", target),
+                ("features", &features),
+            ],
+            1,
+        )
+    }
+
+    /// Returns the build time, if representable
+    #[must_use]
+    pub fn build_time(&self) -> Option<jiff::Timestamp> {
+        jiff::Timestamp::from_second(self.build_timestamp).ok()
+    }
+}
+
+/// Multi-line summary, as printed by `kopia-exporter version`
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            version,
+            git_commit,
+            build_timestamp,
+            target,
+            features,
+        } = self;
+        writeln!(f, "kopia-exporter {version}")?;
+        writeln!(f, "commit: {git_commit}")?;
+        match self.build_time() {
+            Some(build_time) => writeln!(f, "built: {build_time}")?,
+            None => writeln!(f, "built: {build_timestamp}")?,
+        }
+        writeln!(f, "target: {target}")?;
+        write!(f, "features: {}", features.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildInfo;
+    use crate::AssertContains as _;
+
+    #[test]
+    fn current() {
+        let build_info = BuildInfo::current();
+        assert_eq!(build_info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!build_info.git_commit.is_empty());
+        assert!(build_info.build_time().is_some());
+        assert_eq!(
+            build_info.features.contains(&"push"),
+            cfg!(feature = "push")
+        );
+
+        build_info.to_string().assert_contains_lines(&[
+            &format!("kopia-exporter {}", env!("CARGO_PKG_VERSION")),
+            &format!("target: {}", build_info.target),
+        ]);
+
+        build_info.to_metric().to_string().assert_contains_lines(&[
+            "# TYPE kopia_exporter_build_info gauge",
+            &format!(
+                r#"kopia_exporter_build_info{{version="{}",commit="{}",target="{}",features="{}"}} 1"#,
+                build_info.version,
+                build_info.git_commit,
+                build_info.target,
+                build_info.features.join(","),
+            ),
+        ]);
+    }
+}
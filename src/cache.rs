@@ -0,0 +1,366 @@
+//! Cache of the fetched snapshots, shared by the HTTP endpoints and the push loop
+
+use crate::KopiaSnapshots;
+use crate::fetch::FetchConfig;
+use crate::metrics::PrerenderedMetrics;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct TimedSnapshots {
+    snapshots: Arc<FetchedSnapshots>,
+    created_at: Instant,
+}
+
+/// Fetched snapshots, with the Prometheus metrics prerendered
+#[derive(Debug)]
+pub struct FetchedSnapshots {
+    /// Snapshots as fetched
+    pub snapshots: KopiaSnapshots,
+    /// Prometheus metrics of the snapshots, rendered once per fetch
+    pub prometheus: PrerenderedMetrics,
+    /// Timestamp of every Prometheus sample, if enabled
+    pub timestamp: Option<jiff::Timestamp>,
+}
+impl FetchedSnapshots {
+    /// Prerenders the metrics of the snapshots, with the fetch time as the timestamp of every
+    /// sample if `sample_timestamps`
+    #[must_use]
+    pub fn new(snapshots: KopiaSnapshots, sample_timestamps: bool) -> Self {
+        let timestamp = sample_timestamps.then(|| snapshots.fetched_at()).flatten();
+        Self {
+            prometheus: snapshots.prerender_metrics(timestamp),
+            snapshots,
+            timestamp,
+        }
+    }
+}
+
+/// Snapshots cache with stale-while-revalidate semantics
+///
+/// Once expired, the cached snapshots are still served while a background thread
+/// fetches fresh snapshots. Fetch failures are cached separately, to avoid running
+/// `kopia` for every request while the repository is unavailable.
+#[derive(Clone, Debug)]
+pub struct SnapshotCache {
+    fetch_config: FetchConfig,
+    cache_duration: Duration,
+    error_cache_duration: Duration,
+    sample_timestamps: bool,
+    state: Arc<Mutex<CacheState>>,
+}
+#[derive(Debug, Default)]
+struct CacheState {
+    current: Option<TimedSnapshots>,
+    refreshing: bool,
+    last_error: Option<(String, Instant)>,
+}
+impl SnapshotCache {
+    /// Creates an empty cache, keeping snapshots for `cache_duration` and failures for
+    /// `error_cache_duration`
+    #[must_use]
+    pub fn new(
+        fetch_config: FetchConfig,
+        cache_duration: Duration,
+        error_cache_duration: Duration,
+        sample_timestamps: bool,
+    ) -> Self {
+        Self {
+            fetch_config,
+            cache_duration,
+            error_cache_duration,
+            sample_timestamps,
+            state: Arc::default(),
+        }
+    }
+
+    /// Returns the settings for fetching the snapshots
+    #[must_use]
+    pub fn fetch_config(&self) -> &FetchConfig {
+        &self.fetch_config
+    }
+
+    /// Returns the cached snapshots, only blocking to fetch if nothing is cached
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if fetching failed (or failed recently, within the error cache duration)
+    /// and nothing is cached
+    pub fn get(&self) -> eyre::Result<Arc<FetchedSnapshots>> {
+        let mut state = self.lock();
+        let recent_error = self.recent_error(&state);
+        match (&state.current, &recent_error) {
+            (None, Some(_)) => self.fetch_config.stats.record_cached_failure(),
+            (current, _) => self
+                .fetch_config
+                .stats
+                .record_cache_lookup(current.is_some()),
+        }
+        match (&state.current, recent_error) {
+            (Some(cached), recent_error) => {
+                let snapshots = Arc::clone(&cached.snapshots);
+                if self.is_expired(cached) && recent_error.is_none() && !state.refreshing {
+                    state.refreshing = true;
+                    let cache = self.clone();
+                    std::thread::spawn(move || cache.refresh());
+                }
+                Ok(snapshots)
+            }
+            (None, Some(error)) => Err(eyre::eyre!("{error} (cached failure)")),
+            (None, None) => {
+                let result = self.fetch(&self.fetch_config);
+                self.store(&mut state, result)
+            }
+        }
+    }
+
+    /// Returns the cached snapshots for pushing, fetching in the calling thread once expired
+    ///
+    /// Pushing and serving share the cache, so `kopia` runs at most once per cache duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`Self::get`], and if refreshing the expired
+    /// snapshots failed
+    pub fn get_fresh(&self) -> eyre::Result<Arc<FetchedSnapshots>> {
+        let mut state = self.lock();
+        let recent_error = self.recent_error(&state);
+        let cached = state
+            .current
+            .as_ref()
+            .map(|cached| (Arc::clone(&cached.snapshots), self.is_expired(cached)));
+        match (cached, recent_error) {
+            (Some((snapshots, expired)), recent_error) => {
+                if !expired || recent_error.is_some() || state.refreshing {
+                    return Ok(snapshots);
+                }
+                // fetch without holding the lock, to keep serving the stale snapshots
+                state.refreshing = true;
+                drop(state);
+                let result = self.fetch(&self.fetch_config);
+                let mut state = self.lock();
+                state.refreshing = false;
+                self.store(&mut state, result)
+            }
+            (None, Some(error)) => Err(eyre::eyre!("{error} (cached failure)")),
+            (None, None) => {
+                let result = self.fetch(&self.fetch_config);
+                self.store(&mut state, result)
+            }
+        }
+    }
+
+    /// Returns the message of the last failure, if within the error cache duration
+    fn recent_error(&self, state: &CacheState) -> Option<String> {
+        state
+            .last_error
+            .as_ref()
+            .filter(|(_, failed_at)| failed_at.elapsed() < self.error_cache_duration)
+            .map(|(message, _)| message.clone())
+    }
+
+    fn is_expired(&self, cached: &TimedSnapshots) -> bool {
+        // the snapshots file is reloaded by `watch_snapshots_file` once changed
+        self.fetch_config.snapshots_file.is_none()
+            && cached.created_at.elapsed() >= self.cache_duration
+    }
+
+    /// Fetches snapshots into the empty cache, with the specified kopia timeout
+    pub fn warm_up(&self, timeout: Duration) {
+        let fetch_config = FetchConfig {
+            kopia_timeout: timeout,
+            ..self.fetch_config.clone()
+        };
+        let result = self.fetch(&fetch_config);
+        let mut state = self.lock();
+        if let Err(e) = self.store(&mut state, result) {
+            eprintln!("Error warming up the cache: {e}");
+        }
+    }
+
+    fn fetch(&self, fetch_config: &FetchConfig) -> eyre::Result<FetchedSnapshots> {
+        let snapshots = fetch_config.fetch()?;
+        Ok(FetchedSnapshots::new(snapshots, self.sample_timestamps))
+    }
+
+    /// Reloads the snapshots file whenever it changes (including when replaced by a rename),
+    /// keeping the previous snapshots if reading the changed file fails
+    ///
+    /// Polls the metadata, as watching with inotify or kqueue needs platform bindings.
+    pub fn watch_snapshots_file(&self, path: &Path) {
+        const POLL_INTERVAL: Duration = Duration::from_secs(1);
+        // the path is checked (not an open file), to see the file replacing it
+        let version = || {
+            std::fs::metadata(path)
+                .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+                .ok()
+        };
+        let mut loaded = version();
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let current = version();
+            if current == loaded {
+                continue;
+            }
+            loaded = current;
+            let result = self.fetch(&self.fetch_config);
+            let mut state = self.lock();
+            if let Err(e) = self.store(&mut state, result) {
+                eprintln!(
+                    "Error reloading {}, serving the previous snapshots: {e}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    fn refresh(&self) {
+        // fetch without holding the lock, to keep serving the stale snapshots
+        let result = self.fetch(&self.fetch_config);
+        let mut state = self.lock();
+        state.refreshing = false;
+        if let Err(e) = self.store(&mut state, result) {
+            eprintln!("Error refreshing snapshots, serving stale data: {e}");
+        }
+    }
+
+    fn store(
+        &self,
+        state: &mut CacheState,
+        result: eyre::Result<FetchedSnapshots>,
+    ) -> eyre::Result<Arc<FetchedSnapshots>> {
+        match result {
+            Ok(snapshots) => {
+                let snapshots = Arc::new(snapshots);
+                state.last_error = None;
+                if !self.cache_duration.is_zero() {
+                    state.current = Some(TimedSnapshots {
+                        snapshots: Arc::clone(&snapshots),
+                        created_at: Instant::now(),
+                    });
+                }
+                Ok(snapshots)
+            }
+            Err(e) => {
+                state.last_error = Some((format!("{e:#}"), Instant::now()));
+                Err(e)
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotCache;
+    use crate::fetch::FetchConfig;
+    use crate::kopia::KopiaCommand;
+    use crate::{AssertContains as _, ParseOptions};
+    use std::os::unix::fs::PermissionsExt as _;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn sample_file() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/sample_kopia-snapshot-list.json")
+    }
+
+    fn new_cache(
+        kopia: KopiaCommand,
+        snapshots_file: Option<PathBuf>,
+        cache_duration: Duration,
+    ) -> SnapshotCache {
+        let fetch_config = FetchConfig {
+            kopia,
+            snapshots_file,
+            kopia_timeout: Duration::from_secs(10),
+            parse_options: ParseOptions::default(),
+            max_sources: None,
+            repo_id: None,
+            source_rollups: false,
+            repo_quota_bytes: None,
+            expected_retention: vec![],
+            expected_intervals: vec![],
+            #[cfg(feature = "history")]
+            history_db: None,
+            #[cfg(feature = "history")]
+            history_window_days: 30,
+            health_thresholds: None,
+            stats: Arc::default(),
+        };
+        SnapshotCache::new(fetch_config, cache_duration, Duration::from_mins(1), false)
+    }
+
+    #[test]
+    fn shares_cached_snapshots() {
+        let cache = new_cache(
+            KopiaCommand::new("kopia"),
+            Some(sample_file()),
+            Duration::from_mins(1),
+        );
+        let first = cache.get().expect("sample file readable");
+        let second = cache.get().expect("cached");
+        let pushed = cache.get_fresh().expect("cached");
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&first, &pushed));
+
+        // only requests count as cache lookups
+        pushed
+            .snapshots
+            .generate_all_metrics(jiff::Timestamp::now())
+            .assert_contains_lines(&[
+                "kopia_exporter_cache_hits_total 1",
+                "kopia_exporter_cache_misses_total 1",
+            ]);
+    }
+
+    #[test]
+    fn caches_failures() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let missing = dir.path().join("missing.json");
+        let cache = new_cache(
+            KopiaCommand::new("kopia"),
+            Some(missing),
+            Duration::from_mins(1),
+        );
+        let error = cache.get().expect_err("missing file");
+        assert!(!error.to_string().contains("(cached failure)"), "{error}");
+
+        for error in [
+            cache.get().expect_err("cached failure"),
+            cache.get_fresh().expect_err("cached failure"),
+        ] {
+            assert!(error.to_string().ends_with("(cached failure)"), "{error}");
+        }
+    }
+
+    #[test]
+    fn get_fresh_refreshes_expired() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let kopia = dir.path().join("kopia");
+        let script = format!("#!/bin/sh\ncat '{}'\n", sample_file().display());
+        std::fs::write(&kopia, script).expect("writable tempdir");
+        std::fs::set_permissions(&kopia, std::fs::Permissions::from_mode(0o755))
+            .expect("writable tempdir");
+        let cache_duration = Duration::from_millis(200);
+        let cache = new_cache(
+            KopiaCommand::new(kopia.to_string_lossy()),
+            None,
+            cache_duration,
+        );
+
+        let first = cache.get_fresh().expect("kopia succeeds");
+        let cached = cache.get_fresh().expect("cached");
+        assert!(Arc::ptr_eq(&first, &cached));
+
+        std::thread::sleep(cache_duration);
+        let refreshed = cache.get_fresh().expect("kopia succeeds");
+        assert!(!Arc::ptr_eq(&first, &refreshed));
+        let cached = cache.get().expect("cached");
+        assert!(Arc::ptr_eq(&refreshed, &cached));
+    }
+}
@@ -0,0 +1,135 @@
+//! Async variants of the `kopia` command constructors, using [`tokio::process`]
+
+use crate::{Error, InvalidSourceReport, KopiaSnapshots, ParseOptions, kopia};
+use std::process::Stdio;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::AsyncReadExt as _;
+use tokio_util::io::SyncIoBridge;
+
+impl KopiaSnapshots {
+    /// Executes kopia command to retrieve snapshots and parses the output, without blocking
+    /// the async runtime.
+    ///
+    /// The process is killed if it does not complete within the `timeout`, or if the
+    /// returned future is dropped.
+    ///
+    /// The output is parsed while streaming on the blocking thread pool (see
+    /// [`tokio::task::spawn_blocking`]). Snapshots with an invalid source are skipped (and
+    /// reported).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`Self::new_from_command_with_report`]
+    pub async fn new_from_command_async(
+        kopia: impl Into<kopia::KopiaCommand>,
+        timeout: Duration,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        Self::run_command_async(&kopia.into(), timeout, ParseOptions::default()).await
+    }
+
+    /// Executes kopia command to retrieve snapshots and parses the output without blocking
+    /// the async runtime, with the options (see [`Self::new_from_reader_with_options`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`Self::new_from_command_with_report`]
+    pub async fn new_from_command_with_options_async(
+        kopia: impl Into<kopia::KopiaCommand>,
+        timeout: Duration,
+        options: ParseOptions,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        Self::run_command_async(&kopia.into(), timeout, options).await
+    }
+
+    async fn run_command_async(
+        kopia: &kopia::KopiaCommand,
+        timeout: Duration,
+        options: ParseOptions,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        let audit = kopia.start_audit();
+        let mut outcome = None;
+        let result = Self::run_command_async_inner(kopia, timeout, options, &mut outcome).await;
+        if let (Some(audit), Some(outcome)) = (audit, outcome) {
+            audit.finish_logged(outcome);
+        }
+        result
+    }
+
+    async fn run_command_async_inner(
+        kopia: &kopia::KopiaCommand,
+        timeout: Duration,
+        options: ParseOptions,
+        outcome: &mut Option<kopia::AuditOutcome>,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        let mut child = tokio::process::Command::from(kopia.snapshot_list())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdout_pipe = child
+            .stdout
+            .take()
+            .ok_or_else(|| std::io::Error::other("Failed to capture stdout"))?;
+        let mut stderr_pipe = child
+            .stderr
+            .take()
+            .ok_or_else(|| std::io::Error::other("Failed to capture stderr"))?;
+        let (stdout_pipe, stdout_bytes) =
+            kopia::CountingReader::new(SyncIoBridge::new(stdout_pipe));
+
+        // Parse JSON directly from the stdout stream on the blocking thread pool
+        // This avoids buffering the entire JSON in memory, and parsing on the async runtime
+        let parse = tokio::task::spawn_blocking(move || {
+            let collector = kopia::ReportCollector::default();
+            Self::parse_reader(stdout_pipe, options, collector.invalid_source_fn())
+                .map(|this| (this, collector.finish()))
+        });
+
+        let mut stderr_buffer = Vec::new();
+        let completed = tokio::time::timeout(timeout, async {
+            tokio::join!(
+                child.wait(),
+                stderr_pipe.read_to_end(&mut stderr_buffer),
+                parse,
+            )
+        })
+        .await;
+
+        let Ok((status, stderr_read, parse_result)) = completed else {
+            // Timeout exceeded, kill the process (ending the parse at the closed stdout)
+            let _ = child.kill().await;
+            *outcome = Some(kopia::AuditOutcome {
+                exit_code: None,
+                timed_out: true,
+                stdout_bytes: stdout_bytes.load(Ordering::Relaxed),
+                stderr: stderr_buffer.clone(),
+            });
+
+            // Output read before the timeout is kept in the buffer
+            let stderr = Some(kopia.stderr_text(&stderr_buffer));
+            return Err(Error::Timeout { timeout, stderr });
+        };
+        let status = status?;
+        stderr_read?;
+        *outcome = Some(kopia::AuditOutcome {
+            exit_code: status.code(),
+            timed_out: false,
+            stdout_bytes: stdout_bytes.load(Ordering::Relaxed),
+            stderr: stderr_buffer.clone(),
+        });
+
+        let stderr = kopia.stderr_text(&stderr_buffer);
+        if !status.success() {
+            return Err(Error::CommandFailed {
+                exit_code: status.code(),
+                stderr,
+            });
+        }
+
+        // Return the parse result, which may contain JSON parsing errors
+        let (this, invalid_sources) = parse_result.map_err(std::io::Error::other)??;
+        Ok((this.with_kopia_stderr(stderr), invalid_sources))
+    }
+}
@@ -0,0 +1,326 @@
+//! Changes between two snapshot listings, e.g. saved `kopia snapshot list --json` outputs
+//! before and after a retention policy change
+//!
+//! Compares each source: added and removed sources, the snapshot counts, the size of the
+//! latest snapshot, the retained snapshots of each retention class, and the errors of
+//! snapshots only in the new listing.
+
+use crate::{KopiaSnapshots, Snapshot, SourceStr};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Changed sources between two snapshot listings, in sorted order
+#[derive(Debug, Serialize)]
+pub struct SnapshotsDiff {
+    sources: Vec<SourceDiff>,
+}
+
+/// Changes of a single source
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceDiff {
+    /// Source of the snapshots
+    pub source: SourceStr,
+    /// Whether the source was added, removed or changed
+    pub change: SourceChange,
+    /// Number of snapshots in the old listing
+    pub old_snapshot_count: usize,
+    /// Number of snapshots in the new listing
+    pub new_snapshot_count: usize,
+    /// Change in size of the latest snapshot in bytes, zero unless in both listings
+    pub size_change_bytes: i128,
+    /// Changed number of retained snapshots of each retention class (e.g. `daily`)
+    pub retention_changes: Vec<RetentionChange>,
+    /// Total errors of the snapshots only in the new listing
+    pub new_errors: u64,
+}
+
+/// Kind of change of a [`SourceDiff`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SourceChange {
+    /// Only in the new listing
+    Added,
+    /// Only in the old listing
+    Removed,
+    /// In both listings
+    Changed,
+}
+
+/// Changed number of retained snapshots of a retention class
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct RetentionChange {
+    /// Retention class, e.g. `daily`
+    pub class: String,
+    /// Number of snapshots retained in the old listing
+    pub old: u64,
+    /// Number of snapshots retained in the new listing
+    pub new: u64,
+}
+
+impl SnapshotsDiff {
+    /// Compares the `old` and `new` listings, omitting unchanged sources
+    #[must_use]
+    pub fn new(old: &KopiaSnapshots, new: &KopiaSnapshots) -> Self {
+        let old_class_counts = old.get_retention_class_counts();
+        let new_class_counts = new.get_retention_class_counts();
+        let sources: BTreeSet<&SourceStr> = old.sources().chain(new.sources()).collect();
+        let sources = sources
+            .into_iter()
+            .filter_map(|source| {
+                let old_snapshots = old.snapshots_for(source);
+                let new_snapshots = new.snapshots_for(source);
+                let change = match (old_snapshots, new_snapshots) {
+                    (None, Some(_)) => SourceChange::Added,
+                    (Some(_), None) => SourceChange::Removed,
+                    _ => SourceChange::Changed,
+                };
+                let old_snapshots = old_snapshots.unwrap_or_default();
+                let new_snapshots = new_snapshots.unwrap_or_default();
+
+                let latest_size = |ks: &KopiaSnapshots, snapshots: &[Snapshot]| {
+                    let latest = ks.latest_policy.select_latest(snapshots)?;
+                    Some(i128::from(latest.stats.total_size))
+                };
+                let size_change_bytes = latest_size(new, new_snapshots)
+                    .zip(latest_size(old, old_snapshots))
+                    .map_or(0, |(new_size, old_size)| new_size - old_size);
+
+                let empty = BTreeMap::new();
+                let old_classes = old_class_counts.get(source).unwrap_or(&empty);
+                let new_classes = new_class_counts.get(source).unwrap_or(&empty);
+                let classes: BTreeSet<&String> =
+                    old_classes.keys().chain(new_classes.keys()).collect();
+                let retention_changes = classes
+                    .into_iter()
+                    .filter_map(|class| {
+                        let old = old_classes.get(class).copied().unwrap_or(0);
+                        let new = new_classes.get(class).copied().unwrap_or(0);
+                        (old != new).then(|| RetentionChange {
+                            class: class.clone(),
+                            old,
+                            new,
+                        })
+                    })
+                    .collect();
+
+                let old_ids: BTreeSet<&str> = old_snapshots
+                    .iter()
+                    .map(|snapshot| snapshot.id.as_str())
+                    .collect();
+                let new_errors = new_snapshots
+                    .iter()
+                    .filter(|snapshot| !old_ids.contains(snapshot.id.as_str()))
+                    .map(|snapshot| snapshot.stats.error_count)
+                    .fold(0, u64::saturating_add);
+
+                let diff = SourceDiff {
+                    source: source.clone(),
+                    change,
+                    old_snapshot_count: old_snapshots.len(),
+                    new_snapshot_count: new_snapshots.len(),
+                    size_change_bytes,
+                    retention_changes,
+                    new_errors,
+                };
+                (!diff.is_unchanged()).then_some(diff)
+            })
+            .collect();
+        Self { sources }
+    }
+
+    /// Returns the changed sources
+    #[must_use]
+    pub fn sources(&self) -> &[SourceDiff] {
+        &self.sources
+    }
+
+    /// Returns `true` if no source changed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+}
+
+impl SourceDiff {
+    fn is_unchanged(&self) -> bool {
+        let Self {
+            source: _,
+            change,
+            old_snapshot_count,
+            new_snapshot_count,
+            size_change_bytes,
+            retention_changes,
+            new_errors,
+        } = self;
+        *change == SourceChange::Changed
+            && old_snapshot_count == new_snapshot_count
+            && *size_change_bytes == 0
+            && retention_changes.is_empty()
+            && *new_errors == 0
+    }
+}
+
+/// Human-readable report, listing each changed source (`+` added, `-` removed, `~` changed)
+/// followed by its retention changes
+impl fmt::Display for SnapshotsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { sources } = self;
+        for diff in sources {
+            let SourceDiff {
+                source,
+                change,
+                old_snapshot_count,
+                new_snapshot_count,
+                size_change_bytes,
+                retention_changes,
+                new_errors,
+            } = diff;
+            let source = source.as_str();
+            match change {
+                SourceChange::Added => write!(f, "+ {source}: {new_snapshot_count} snapshots")?,
+                SourceChange::Removed => write!(f, "- {source}: {old_snapshot_count} snapshots")?,
+                SourceChange::Changed => write!(
+                    f,
+                    "~ {source}: {old_snapshot_count} -> {new_snapshot_count} snapshots"
+                )?,
+            }
+            if *size_change_bytes != 0 {
+                write!(f, ", latest size {size_change_bytes:+} bytes")?;
+            }
+            if *new_errors > 0 {
+                write!(f, ", {new_errors} new errors")?;
+            }
+            writeln!(f)?;
+            for RetentionChange { class, old, new } in retention_changes {
+                writeln!(f, "    {class}: {old} -> {new}")?;
+            }
+        }
+        write!(f, "{} sources changed", sources.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RetentionChange, SnapshotsDiff, SourceChange};
+    use crate::{
+        AssertContains as _, KopiaSnapshots,
+        test_util::{multi_map, test_snapshot},
+    };
+
+    fn listings() -> (KopiaSnapshots, KopiaSnapshots) {
+        let (old, _sources) = multi_map(vec![
+            (
+                "alice",
+                "hostA",
+                "/data",
+                vec![
+                    test_snapshot("1", 1000, &["daily-2"]),
+                    test_snapshot("2", 1500, &["latest-1", "daily-1"]),
+                ],
+            ),
+            ("bob", "hostB", "/old", vec![test_snapshot("3", 10, &[])]),
+            ("carol", "hostC", "/same", vec![test_snapshot("4", 10, &[])]),
+        ]);
+        let mut failed = test_snapshot("6", 2000, &["latest-1", "daily-1"]);
+        failed.stats.error_count = 3;
+        let (new, _sources) = multi_map(vec![
+            (
+                "alice",
+                "hostA",
+                "/data",
+                vec![test_snapshot("2", 1500, &["latest-2", "daily-2"]), failed],
+            ),
+            ("carol", "hostC", "/same", vec![test_snapshot("4", 10, &[])]),
+            ("dave", "hostD", "/new", vec![test_snapshot("5", 10, &[])]),
+        ]);
+        (old, new)
+    }
+
+    #[test]
+    fn diff_sources() {
+        let (old, new) = listings();
+        let diff = SnapshotsDiff::new(&old, &new);
+
+        let changes: Vec<_> = diff
+            .sources()
+            .iter()
+            .map(|diff| (diff.source.as_str(), diff.change))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("alice@hostA:/data", SourceChange::Changed),
+                ("bob@hostB:/old", SourceChange::Removed),
+                ("dave@hostD:/new", SourceChange::Added),
+            ]
+        );
+        let alice = &diff.sources()[0];
+        assert_eq!(alice.size_change_bytes, 500);
+        assert_eq!(alice.new_errors, 3);
+        assert_eq!(
+            alice.retention_changes,
+            [RetentionChange {
+                class: "latest".to_string(),
+                old: 1,
+                new: 2,
+            }]
+        );
+
+        diff.to_string().assert_contains_lines(&[
+            "~ alice@hostA:/data: 2 -> 2 snapshots, latest size +500 bytes, 3 new errors",
+            "    latest: 1 -> 2",
+            "- bob@hostB:/old: 1 snapshots",
+            "+ dave@hostD:/new: 1 snapshots",
+            "3 sources changed",
+        ]);
+        assert!(SnapshotsDiff::new(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn diff_retention() {
+        let (old, _sources) = multi_map(vec![(
+            "alice",
+            "hostA",
+            "/data",
+            vec![
+                test_snapshot("1", 1000, &["daily-2", "weekly-1"]),
+                test_snapshot("2", 1000, &["latest-1", "daily-1"]),
+            ],
+        )]);
+        let (new, _sources) = multi_map(vec![(
+            "alice",
+            "hostA",
+            "/data",
+            vec![
+                test_snapshot("1", 1000, &["daily-2"]),
+                test_snapshot("2", 1000, &["latest-1", "daily-1", "monthly-1"]),
+            ],
+        )]);
+
+        let diff = SnapshotsDiff::new(&old, &new);
+        diff.to_string().assert_contains_lines(&[
+            "~ alice@hostA:/data: 2 -> 2 snapshots",
+            "    monthly: 0 -> 1",
+            "    weekly: 1 -> 0",
+            "1 sources changed",
+        ]);
+        let json = serde_json::to_value(&diff).expect("serializable");
+        assert_eq!(
+            json,
+            serde_json::json!({"sources": [{
+                "source": "alice@hostA:/data",
+                "change": "changed",
+                "oldSnapshotCount": 2,
+                "newSnapshotCount": 2,
+                "sizeChangeBytes": 0,
+                "retentionChanges": [
+                    {"class": "monthly", "old": 0, "new": 1},
+                    {"class": "weekly", "old": 1, "new": 0},
+                ],
+                "newErrors": 0,
+            }]})
+        );
+    }
+}
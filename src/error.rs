@@ -0,0 +1,101 @@
+use crate::SourceStrError;
+use std::{fmt, path::PathBuf, time::Duration};
+
+/// Error fetching or parsing snapshots for [`KopiaSnapshots`](crate::KopiaSnapshots)
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Failed to run the `kopia` command, or to read the snapshots
+    Io(std::io::Error),
+    /// The `kopia` command exited unsuccessfully
+    CommandFailed {
+        /// Exit code, if the command was not terminated by a signal
+        exit_code: Option<i32>,
+        /// Standard error output of the command
+        stderr: String,
+    },
+    /// The `kopia` command did not complete within the timeout, and was killed
+    Timeout {
+        /// Timeout for the command
+        timeout: Duration,
+        /// Standard error output of the command, if it could be captured
+        stderr: Option<String>,
+    },
+    /// The snapshots are not valid JSON, or not in the expected format
+    Json(serde_json::Error),
+    /// A snapshot source is invalid, and the `invalid_source_fn` returned the error
+    InvalidSource(SourceStrError),
+    /// Failed to read the snapshots file at `path`
+    File {
+        /// Path of the snapshots file
+        path: PathBuf,
+        /// Cause of the failure
+        source: Box<Error>,
+    },
+    /// Failed to append to the audit log of `kopia` invocations at `path`
+    AuditLog {
+        /// Path of the audit log
+        path: PathBuf,
+        /// Cause of the failure
+        source: std::io::Error,
+    },
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => e.source(),
+            Self::Json(e) => e.source(),
+            Self::InvalidSource(e) => e.source(),
+            Self::File { source, .. } => Some(source),
+            Self::AuditLog { source, .. } => Some(source),
+            Self::CommandFailed { .. } | Self::Timeout { .. } => None,
+        }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Json(e) => write!(f, "{e}"),
+            Self::InvalidSource(e) => write!(f, "{e}"),
+            Self::CommandFailed { exit_code, stderr } => {
+                let exit_code = exit_code.unwrap_or(-1);
+                write!(
+                    f,
+                    "kopia command failed with exit code: {exit_code}\nstderr: {stderr}"
+                )
+            }
+            Self::Timeout { timeout, stderr } => {
+                let seconds = timeout.as_secs_f64();
+                writeln!(f, "kopia command timeout after {seconds} seconds")?;
+                match stderr {
+                    Some(stderr) => write!(f, "stderr: {stderr}"),
+                    None => write!(f, "<stderr is unknown>"),
+                }
+            }
+            Self::File { path, .. } => {
+                write!(f, "failed to read snapshots file {}", path.display())
+            }
+            Self::AuditLog { path, .. } => {
+                write!(f, "failed to write kopia audit log {}", path.display())
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+impl From<SourceStrError> for Error {
+    fn from(value: SourceStrError) -> Self {
+        Self::InvalidSource(value)
+    }
+}
@@ -0,0 +1,246 @@
+//! Fetching snapshots from `kopia` (or a snapshots file), shared by the exporter commands
+
+use crate::health::{ExpectedInterval, HealthThresholds};
+#[cfg(feature = "history")]
+use crate::history::HistoryDb;
+use crate::kopia::KopiaCommand;
+use crate::metrics::ExporterStats;
+#[cfg(feature = "otel")]
+use crate::trace;
+use crate::{BuildInfo, Error, InvalidSourceReport, KopiaSnapshots, ParseOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "history")]
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Settings for fetching snapshots from kopia
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    /// Command line running `kopia`
+    pub kopia: KopiaCommand,
+    /// Snapshots file read instead of running `kopia`
+    pub snapshots_file: Option<PathBuf>,
+    /// Timeout of each `kopia` invocation
+    pub kopia_timeout: Duration,
+    /// Options for parsing the `kopia` output
+    pub parse_options: ParseOptions,
+    /// Limit of sources, see [`KopiaSnapshots::with_max_sources`]
+    pub max_sources: Option<usize>,
+    /// Repository label, see [`KopiaSnapshots::with_repo_id`]
+    pub repo_id: Option<String>,
+    /// Adds the per-source rollups, see [`KopiaSnapshots::with_source_rollups`]
+    pub source_rollups: bool,
+    /// Repository quota, see [`KopiaSnapshots::with_repo_quota_bytes`]
+    pub repo_quota_bytes: Option<u64>,
+    /// Expected count of each retention class, see [`KopiaSnapshots::with_expected_retention`]
+    pub expected_retention: Vec<(String, u32)>,
+    /// Expected snapshot intervals, see [`KopiaSnapshots::with_expected_interval`]
+    pub expected_intervals: Vec<ExpectedInterval>,
+    /// Database recording the history of each fetch
+    #[cfg(feature = "history")]
+    pub history_db: Option<Arc<Mutex<HistoryDb>>>,
+    /// Days of history included in the metrics
+    #[cfg(feature = "history")]
+    pub history_window_days: u32,
+    /// Thresholds of the health metrics, see [`KopiaSnapshots::with_health_thresholds`]
+    pub health_thresholds: Option<HealthThresholds>,
+    /// Statistics of the exporter, updated by each fetch
+    pub stats: Arc<ExporterStats>,
+}
+impl FetchConfig {
+    /// Returns the config without recording the fetches in the audit log or history database
+    #[must_use]
+    pub fn without_recording(&self) -> Self {
+        Self {
+            kopia: self.kopia.clone().without_audit_log(),
+            #[cfg(feature = "history")]
+            history_db: None,
+            ..self.clone()
+        }
+    }
+
+    /// Fetches the snapshots, logging data errors, and applies the configured transforms
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if running `kopia` or reading the snapshots file fails
+    pub fn fetch(&self) -> eyre::Result<KopiaSnapshots> {
+        #[cfg(feature = "otel")]
+        let mut span = trace::Span::enter("fetch");
+        let (fetched_at, result) = match &self.snapshots_file {
+            // modified before reading, so a concurrent write is read again later
+            Some(path) => (
+                file_modified(path).unwrap_or_else(jiff::Timestamp::now),
+                self.read_snapshots_file(path),
+            ),
+            None => (jiff::Timestamp::now(), self.run_kopia()),
+        };
+        let (snapshots, invalid_sources) = result.inspect_err(|e| {
+            self.stats.record_fetch_error(e);
+            #[cfg(feature = "otel")]
+            span.set_error(e);
+        })?;
+        if let Some(stderr) = snapshots.kopia_stderr() {
+            eprintln!("Warning: kopia succeeded with output on stderr: {stderr}");
+        }
+        // log data errors but otherwise ignore
+        for e in invalid_sources {
+            eprintln!("{:?}", eyre::eyre!(e));
+        }
+        let malformed = snapshots.malformed_snapshots();
+        if let Some(first_error) = malformed.first_error() {
+            eprintln!(
+                "skipped {} malformed snapshots, first: {first_error}",
+                malformed.count()
+            );
+        }
+        let replacements = snapshots.utf8_replacements();
+        if replacements > 0 {
+            eprintln!("replaced {replacements} invalid UTF-8 sequences in the kopia output");
+        }
+        let snapshots = snapshots
+            .with_fetched_at(fetched_at)
+            .with_custom_metric(|_| Some(BuildInfo::current().to_metric()));
+        let snapshots = self.stats.register(snapshots);
+        let snapshots = match self.max_sources {
+            Some(max_sources) => snapshots.with_max_sources(max_sources),
+            None => snapshots,
+        };
+        let snapshots = match &self.repo_id {
+            Some(repo_id) => snapshots.with_repo_id(repo_id),
+            None => snapshots,
+        };
+        let snapshots = if self.source_rollups {
+            snapshots.with_source_rollups()
+        } else {
+            snapshots
+        };
+        let snapshots = match self.repo_quota_bytes {
+            Some(quota_bytes) => snapshots.with_repo_quota_bytes(quota_bytes),
+            None => snapshots,
+        };
+        let snapshots = self
+            .expected_retention
+            .iter()
+            .fold(snapshots, |snapshots, (class, count)| {
+                snapshots.with_expected_retention(class, *count)
+            });
+        let snapshots = self
+            .expected_intervals
+            .iter()
+            .cloned()
+            .fold(snapshots, KopiaSnapshots::with_expected_interval);
+        #[cfg(feature = "history")]
+        let snapshots = match &self.history_db {
+            Some(history_db) => {
+                record_history(history_db, snapshots, fetched_at, self.history_window_days)
+            }
+            None => snapshots,
+        };
+        Ok(match &self.health_thresholds {
+            Some(thresholds) => snapshots.with_health_thresholds(thresholds.clone()),
+            None => snapshots,
+        })
+    }
+
+    fn run_kopia(&self) -> Result<(KopiaSnapshots, InvalidSourceReport), Error> {
+        let kopia_start = Instant::now();
+        let result = KopiaSnapshots::new_from_command_with_options(
+            self.kopia.clone(),
+            self.kopia_timeout,
+            self.parse_options,
+        );
+        // including failures, e.g. the duration until timing out
+        self.stats
+            .record_kopia_command(ExporterStats::SNAPSHOT_LIST, kopia_start.elapsed());
+        if let Some(exit_code) = kopia_exit_code(&result) {
+            self.stats.record_fetch_exit_code(exit_code);
+        }
+        result
+    }
+
+    fn read_snapshots_file(
+        &self,
+        path: &Path,
+    ) -> Result<(KopiaSnapshots, InvalidSourceReport), Error> {
+        let result = KopiaSnapshots::new_from_path_with_options(path, self.parse_options);
+        self.stats.record_snapshots_file_read(result.is_ok());
+        result
+    }
+}
+
+/// Records the snapshots in the history database, and adds the recorded history
+///
+/// Failures are logged, keeping the snapshots without history.
+#[cfg(feature = "history")]
+fn record_history(
+    history_db: &Mutex<HistoryDb>,
+    snapshots: KopiaSnapshots,
+    observed_at: jiff::Timestamp,
+    window_days: u32,
+) -> KopiaSnapshots {
+    let mut history_db = history_db.lock().unwrap_or_else(PoisonError::into_inner);
+    let history = history_db
+        .record(&snapshots, observed_at)
+        .and_then(|_rows| history_db.load());
+    match history {
+        Ok(history) => snapshots.with_history(history.with_window_days(window_days)),
+        Err(e) => {
+            eprintln!("Error recording snapshot history: {e}");
+            snapshots
+        }
+    }
+}
+
+/// Returns the modification time of the file, if available
+fn file_modified(path: &Path) -> Option<jiff::Timestamp> {
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    modified.ok()?.try_into().ok()
+}
+
+/// Returns the exit code of the `kopia` command with the result, or `None` if it did not run
+///
+/// Commands without an exit code (killed by a signal, or after the timeout) are reported as -1.
+fn kopia_exit_code<T>(result: &Result<T, Error>) -> Option<i32> {
+    match result {
+        // parse errors of the output, after kopia succeeded
+        Ok(_) | Err(Error::Json(_) | Error::InvalidSource(_)) => Some(0),
+        Err(Error::CommandFailed { exit_code, .. }) => Some(exit_code.unwrap_or(-1)),
+        Err(Error::Timeout { .. }) => Some(-1),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::kopia_exit_code;
+    use crate::Error;
+    use std::time::Duration;
+
+    #[test]
+    fn kopia_exit_codes() {
+        let exit_code = |error| kopia_exit_code::<()>(&Err(error));
+
+        assert_eq!(kopia_exit_code(&Ok(())), Some(0));
+        let timeout = Error::Timeout {
+            timeout: Duration::from_secs(1),
+            stderr: None,
+        };
+        assert_eq!(exit_code(timeout), Some(-1));
+        let failed = Error::CommandFailed {
+            exit_code: Some(1),
+            stderr: String::new(),
+        };
+        assert_eq!(exit_code(failed), Some(1));
+        let killed = Error::CommandFailed {
+            exit_code: None,
+            stderr: String::new(),
+        };
+        assert_eq!(exit_code(killed), Some(-1));
+        let json = serde_json::from_str::<u64>("[").expect_err("invalid JSON");
+        assert_eq!(exit_code(Error::Json(json)), Some(0));
+        let not_found = Error::Io(std::io::ErrorKind::NotFound.into());
+        assert_eq!(exit_code(not_found), None);
+    }
+}
@@ -0,0 +1,389 @@
+//! Backup health evaluation against configured thresholds
+//!
+//! Thresholds are checked against the latest snapshot of each source (see
+//! [`LatestSnapshotPolicy`](crate::LatestSnapshotPolicy)). Each threshold has a
+//! critical and an optional warning level. Sources can be silenced during a
+//! [`SilenceWindow`], e.g. for planned maintenance.
+
+use crate::{KopiaSnapshots, SourceStr};
+use std::fmt;
+
+pub use self::expected_interval::ExpectedInterval;
+pub use self::silence::{SilenceSchedule, SilenceWindow};
+pub use self::tracker::{HealthTracker, HealthTransition};
+
+mod alerts;
+mod expected_interval;
+mod nagios;
+mod silence;
+mod summary;
+mod tracker;
+
+/// Health status, ordered from best to worst
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HealthStatus {
+    /// All thresholds satisfied
+    Ok,
+    /// A warning threshold is exceeded
+    Warning,
+    /// A critical threshold is exceeded
+    Critical,
+    /// No snapshots available to evaluate
+    Unknown,
+}
+impl HealthStatus {
+    /// Returns the upper-case name, e.g. `"WARNING"`
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::Warning => "WARNING",
+            Self::Critical => "CRITICAL",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Limits for the latest snapshot of each source
+///
+/// Unset thresholds are not checked.
+#[derive(Clone, Debug, Default)]
+pub struct HealthThresholds {
+    /// Critical if the latest snapshot is older than this
+    pub max_age: Option<jiff::SignedDuration>,
+    /// Warning if the latest snapshot is older than this
+    pub warn_max_age: Option<jiff::SignedDuration>,
+    /// Critical if the latest snapshot has more errors than this
+    pub max_errors: Option<u64>,
+    /// Warning if the latest snapshot has more errors than this
+    pub warn_max_errors: Option<u64>,
+    /// Critical if the latest snapshot is smaller than this (in bytes)
+    pub min_size: Option<u64>,
+    /// Warning if the latest snapshot is smaller than this (in bytes)
+    pub warn_min_size: Option<u64>,
+    /// Windows during which matching sources are considered healthy
+    pub silences: Vec<SilenceWindow>,
+}
+
+/// Health of a single source
+#[derive(Clone, Debug)]
+pub struct SourceHealth {
+    /// Source of the snapshots
+    pub source: SourceStr,
+    /// Worst status of all checked thresholds
+    pub status: HealthStatus,
+    /// Descriptions of the exceeded thresholds
+    pub problems: Vec<String>,
+    /// Age of the latest snapshot in seconds
+    pub age_seconds: Option<i64>,
+    /// Error count of the latest snapshot
+    pub errors: Option<u64>,
+    /// Total size of the latest snapshot in bytes
+    pub size_bytes: Option<u64>,
+    /// Whether a [`SilenceWindow`] is active, suppressing all problems
+    pub silenced: bool,
+}
+impl SourceHealth {
+    /// Returns `true` unless the status is critical (or unknown)
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.status <= HealthStatus::Warning
+    }
+}
+
+/// Health of all sources
+#[derive(Clone, Debug)]
+pub struct HealthReport {
+    /// Health of each source, ordered by source
+    pub sources: Vec<SourceHealth>,
+    /// Thresholds used for the evaluation
+    pub thresholds: HealthThresholds,
+}
+impl HealthReport {
+    /// Returns the worst status of all sources, or [`HealthStatus::Unknown`] if there are no sources
+    #[must_use]
+    pub fn status(&self) -> HealthStatus {
+        self.sources
+            .iter()
+            .map(|source| source.status)
+            .max()
+            .unwrap_or(HealthStatus::Unknown)
+    }
+
+    /// Returns `true` if there are sources, and all are healthy
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        !self.sources.is_empty() && self.sources.iter().all(SourceHealth::is_healthy)
+    }
+
+    /// Formats the overall status and the unhealthy sources as JSON
+    ///
+    /// e.g. `{"healthy":false,"status":"CRITICAL","failing":[{"source":"...","status":"CRITICAL","problems":["errors 3 > 0"]}]}`
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let failing: Vec<_> = self
+            .sources
+            .iter()
+            .filter(|source| !source.is_healthy())
+            .map(|source| {
+                serde_json::json!({
+                    "source": source.source.as_str(),
+                    "status": source.status.name(),
+                    "problems": source.problems,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "healthy": self.is_healthy(),
+            "status": self.status().name(),
+            "failing": failing,
+        })
+        .to_string()
+    }
+}
+
+impl KopiaSnapshots {
+    /// Evaluates the latest snapshot of each source against the thresholds
+    #[must_use]
+    pub fn evaluate_health(
+        &self,
+        now: jiff::Timestamp,
+        thresholds: &HealthThresholds,
+    ) -> HealthReport {
+        let sources = self
+            .snapshots_map
+            .iter()
+            .map(|(source, snapshots)| {
+                let latest = self.latest_policy.select_latest(snapshots);
+                let age_seconds = latest
+                    .and_then(|snapshot| snapshot.end_time)
+                    .map(|end_time| now.as_second() - end_time.as_second());
+                let errors = latest.map(|snapshot| snapshot.stats.error_count);
+                let size_bytes = latest.map(|snapshot| snapshot.stats.total_size);
+
+                let silenced = thresholds
+                    .silences
+                    .iter()
+                    .any(|silence| silence.matches(source.as_str()) && silence.is_active(now));
+                let mut health = SourceHealth {
+                    source: source.clone(),
+                    status: HealthStatus::Ok,
+                    problems: vec![],
+                    age_seconds,
+                    errors,
+                    size_bytes,
+                    silenced,
+                };
+                if !silenced {
+                    health.check(thresholds);
+                }
+                health
+            })
+            .collect();
+        HealthReport {
+            sources,
+            thresholds: thresholds.clone(),
+        }
+    }
+}
+
+impl SourceHealth {
+    fn check(&mut self, thresholds: &HealthThresholds) {
+        let HealthThresholds {
+            max_age,
+            warn_max_age,
+            max_errors,
+            warn_max_errors,
+            min_size,
+            warn_min_size,
+            silences: _,
+        } = thresholds;
+
+        if max_age.is_some() || warn_max_age.is_some() {
+            match self.age_seconds {
+                Some(age_seconds) => {
+                    let age = jiff::SignedDuration::from_secs(age_seconds);
+                    self.check_limit(*max_age, *warn_max_age, |limit| {
+                        (age > limit).then(|| format!("age {age:#} > {limit:#}"))
+                    });
+                }
+                None => self.add_problem(HealthStatus::Critical, "no snapshot".to_string()),
+            }
+        }
+        if let Some(errors) = self.errors {
+            self.check_limit(*max_errors, *warn_max_errors, |limit| {
+                (errors > limit).then(|| format!("errors {errors} > {limit}"))
+            });
+        }
+        if let Some(size_bytes) = self.size_bytes {
+            self.check_limit(*min_size, *warn_min_size, |limit| {
+                (size_bytes < limit).then(|| format!("size {size_bytes} B < {limit} B"))
+            });
+        }
+    }
+
+    /// Adds a problem for the first violated limit, `check_fn` describes the violation
+    fn check_limit<T>(
+        &mut self,
+        critical_limit: Option<T>,
+        warning_limit: Option<T>,
+        check_fn: impl Fn(T) -> Option<String>,
+    ) {
+        if let Some(problem) = critical_limit.and_then(&check_fn) {
+            self.add_problem(HealthStatus::Critical, problem);
+        } else if let Some(problem) = warning_limit.and_then(&check_fn) {
+            self.add_problem(HealthStatus::Warning, problem);
+        }
+    }
+
+    fn add_problem(&mut self, status: HealthStatus, problem: String) {
+        self.status = self.status.max(status);
+        self.problems.push(problem);
+    }
+}
+
+/// Parses a duration such as `26h`, `1d 12h`, `90m`, or ISO 8601 `PT26H`
+///
+/// Days are treated as 24 hours.
+///
+/// # Errors
+///
+/// Returns an error if the duration is invalid
+pub fn parse_duration(s: &str) -> Result<jiff::SignedDuration, String> {
+    let span: jiff::Span = s
+        .parse()
+        .map_err(|e| format!("invalid duration {s:?}: {e}"))?;
+    span.to_duration(jiff::SpanRelativeTo::days_are_24_hours())
+        .map_err(|e| format!("invalid duration {s:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HealthStatus, HealthThresholds, parse_duration};
+    use crate::test_util::{multi_map, test_snapshot};
+
+    fn now() -> jiff::Timestamp {
+        // test snapshots end at 2025-08-14T00:01:00Z
+        "2025-08-14T12:01:00Z".parse().expect("valid timestamp")
+    }
+
+    #[test]
+    fn durations() {
+        let hours = |h| jiff::SignedDuration::from_hours(h);
+        assert_eq!(parse_duration("26h"), Ok(hours(26)));
+        assert_eq!(parse_duration("1d 2h"), Ok(hours(26)));
+        assert_eq!(parse_duration("PT26H"), Ok(hours(26)));
+        let err = parse_duration("soon").expect_err("invalid");
+        assert!(err.contains("invalid duration"), "{err}");
+    }
+
+    #[test]
+    fn thresholds() {
+        let mut failed = test_snapshot("2", 5000, &[]);
+        failed.stats.error_count = 3;
+        let (map, _sources) = multi_map(vec![
+            ("alice", "host", "/ok", vec![test_snapshot("1", 5000, &[])]),
+            ("bob", "host", "/failed", vec![failed]),
+            ("carol", "host", "/small", vec![test_snapshot("3", 10, &[])]),
+        ]);
+
+        let thresholds = HealthThresholds {
+            max_age: Some(jiff::SignedDuration::from_hours(26)),
+            warn_max_age: Some(jiff::SignedDuration::from_hours(6)),
+            max_errors: Some(0),
+            min_size: Some(100),
+            ..HealthThresholds::default()
+        };
+        let report = map.evaluate_health(now(), &thresholds);
+        let statuses: Vec<_> = report
+            .sources
+            .iter()
+            .map(|source| {
+                (
+                    source.source.as_str(),
+                    source.status,
+                    source.problems.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (
+                    "alice@host:/ok",
+                    HealthStatus::Warning,
+                    vec!["age 12h > 6h".to_string()]
+                ),
+                (
+                    "bob@host:/failed",
+                    HealthStatus::Critical,
+                    vec!["age 12h > 6h".to_string(), "errors 3 > 0".to_string()]
+                ),
+                (
+                    "carol@host:/small",
+                    HealthStatus::Critical,
+                    vec!["age 12h > 6h".to_string(), "size 10 B < 100 B".to_string()]
+                ),
+            ]
+        );
+        assert_eq!(report.status(), HealthStatus::Critical);
+        assert!(!report.is_healthy());
+        assert!(report.sources[0].is_healthy());
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).expect("valid JSON");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "healthy": false,
+                "status": "CRITICAL",
+                "failing": [
+                    {
+                        "source": "bob@host:/failed",
+                        "status": "CRITICAL",
+                        "problems": ["age 12h > 6h", "errors 3 > 0"],
+                    },
+                    {
+                        "source": "carol@host:/small",
+                        "status": "CRITICAL",
+                        "problems": ["age 12h > 6h", "size 10 B < 100 B"],
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn silenced() {
+        let mut failed = test_snapshot("2", 5000, &[]);
+        failed.stats.error_count = 3;
+        let (map, _sources) = multi_map(vec![("bob", "host", "/failed", vec![failed])]);
+
+        let mut thresholds = HealthThresholds {
+            max_errors: Some(0),
+            silences: vec!["bob@host:/failed=11:00-13:00".parse().expect("valid")],
+            ..HealthThresholds::default()
+        };
+        let report = map.evaluate_health(now(), &thresholds);
+        assert_eq!(report.status(), HealthStatus::Ok);
+        assert!(report.sources[0].silenced);
+        assert!(report.sources[0].problems.is_empty());
+
+        thresholds.silences = vec!["*=13:00-14:00".parse().expect("valid")];
+        let report = map.evaluate_health(now(), &thresholds);
+        assert_eq!(report.status(), HealthStatus::Critical);
+        assert!(!report.sources[0].silenced);
+    }
+
+    #[test]
+    fn no_sources_unknown() {
+        let (map, _sources) = multi_map(vec![]);
+        let report = map.evaluate_health(now(), &HealthThresholds::default());
+        assert_eq!(report.status(), HealthStatus::Unknown);
+        assert!(!report.is_healthy());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::health::HealthThresholds;
+use crate::metrics::Metrics;
+use std::fmt::Write as _;
+
+/// Single Prometheus alerting rule
+struct AlertRule {
+    alert: String,
+    expr: String,
+    severity: &'static str,
+    summary: String,
+}
+impl AlertRule {
+    /// Adds the critical and warning rules for a threshold, if set
+    fn pair<T: Copy>(
+        rules: &mut Vec<Self>,
+        alert: &str,
+        (critical, warning): (Option<T>, Option<T>),
+        expr: impl Fn(T) -> String,
+        summary: impl Fn(T) -> String,
+    ) {
+        let levels = [(critical, "critical", ""), (warning, "warning", "Warning")];
+        for (limit, severity, suffix) in levels {
+            if let Some(limit) = limit {
+                rules.push(Self {
+                    alert: format!("{alert}{suffix}"),
+                    expr: expr(limit),
+                    severity,
+                    summary: summary(limit),
+                });
+            }
+        }
+    }
+}
+
+impl HealthThresholds {
+    /// Renders Prometheus alerting rules (YAML) checking the exported metrics against
+    /// the thresholds
+    ///
+    /// Unset thresholds produce no rule. An additional rule alerts when no snapshots are
+    /// exported at all.
+    #[must_use]
+    pub fn prometheus_alert_rules(&self) -> String {
+        const SOURCE: &str = "{{ $labels.source }}";
+        let age = Metrics::<()>::kopia_snapshot_age_seconds.name();
+        let errors = Metrics::<()>::kopia_snapshot_errors_total.name();
+        let size = Metrics::<()>::kopia_snapshot_size_bytes_total.name();
+
+        let mut rules = vec![];
+        AlertRule::pair(
+            &mut rules,
+            "KopiaBackupTooOld",
+            (self.max_age, self.warn_max_age),
+            |limit| format!("{age} > {}", limit.as_secs()),
+            |limit| format!("Latest Kopia snapshot of {SOURCE} is older than {limit:#}"),
+        );
+        AlertRule::pair(
+            &mut rules,
+            "KopiaBackupErrors",
+            (self.max_errors, self.warn_max_errors),
+            |limit| format!("{errors} > {limit}"),
+            |limit| format!("Latest Kopia snapshot of {SOURCE} has more than {limit} errors"),
+        );
+        AlertRule::pair(
+            &mut rules,
+            "KopiaBackupTooSmall",
+            (self.min_size, self.warn_min_size),
+            |limit| format!("{size} < {limit}"),
+            |limit| format!("Latest Kopia snapshot of {SOURCE} is smaller than {limit} bytes"),
+        );
+        rules.push(AlertRule {
+            alert: "KopiaSnapshotsMissing".to_string(),
+            expr: format!("absent({age})"),
+            severity: "critical",
+            summary: "No Kopia snapshots are exported".to_string(),
+        });
+
+        let mut output = String::from("groups:\n  - name: kopia-exporter\n    rules:\n");
+        for AlertRule {
+            alert,
+            expr,
+            severity,
+            summary,
+        } in rules
+        {
+            writeln!(output, "      - alert: {alert}").expect("infallible");
+            writeln!(output, "        expr: {expr}").expect("infallible");
+            writeln!(output, "        for: 5m").expect("infallible");
+            writeln!(output, "        labels:").expect("infallible");
+            writeln!(output, "          severity: {severity}").expect("infallible");
+            writeln!(output, "        annotations:").expect("infallible");
+            writeln!(output, "          summary: \"{summary}\"").expect("infallible");
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::health::HealthThresholds;
+
+    #[test]
+    fn alert_rules() {
+        let thresholds = HealthThresholds {
+            max_age: Some(jiff::SignedDuration::from_hours(26)),
+            warn_max_errors: Some(0),
+            ..HealthThresholds::default()
+        };
+        insta::assert_snapshot!(thresholds.prometheus_alert_rules(), @r#"
+        groups:
+          - name: kopia-exporter
+            rules:
+              - alert: KopiaBackupTooOld
+                expr: kopia_snapshot_age_seconds > 93600
+                for: 5m
+                labels:
+                  severity: critical
+                annotations:
+                  summary: "Latest Kopia snapshot of {{ $labels.source }} is older than 26h"
+              - alert: KopiaBackupErrorsWarning
+                expr: kopia_snapshot_errors_total > 0
+                for: 5m
+                labels:
+                  severity: warning
+                annotations:
+                  summary: "Latest Kopia snapshot of {{ $labels.source }} has more than 0 errors"
+              - alert: KopiaSnapshotsMissing
+                expr: absent(kopia_snapshot_age_seconds)
+                for: 5m
+                labels:
+                  severity: critical
+                annotations:
+                  summary: "No Kopia snapshots are exported"
+        "#);
+    }
+}
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+/// Expected interval between snapshots of matching sources, for the
+/// [schedule drift](crate::KopiaSnapshots::kopia_snapshot_schedule_drift_seconds) metric
+///
+/// Parsed from `SOURCE=DURATION`, where `SOURCE` is a source (`user@host:/path`) or `*`
+/// for all sources, and `DURATION` is as in [`parse_duration`](super::parse_duration),
+/// e.g. `*=1h` or `alice@host:/data=1d`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpectedInterval {
+    /// Source with the expected interval, or `None` for all sources
+    pub source: Option<String>,
+    /// Expected interval between the starts of consecutive snapshots
+    pub interval: jiff::SignedDuration,
+}
+
+impl ExpectedInterval {
+    /// Returns the interval for the source, preferring the last interval naming the source
+    /// over the last one for all sources
+    pub(crate) fn find(intervals: &[Self], source: &str) -> Option<jiff::SignedDuration> {
+        let exact = intervals
+            .iter()
+            .rfind(|expected| expected.source.as_deref() == Some(source));
+        exact
+            .or_else(|| intervals.iter().rfind(|expected| expected.source.is_none()))
+            .map(|expected| expected.interval)
+    }
+}
+
+impl FromStr for ExpectedInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((source, interval)) = s.rsplit_once('=') else {
+            return Err(format!(
+                "invalid expected interval {s:?}, expected SOURCE=DURATION (SOURCE may be \"*\")"
+            ));
+        };
+        let source = match source.trim() {
+            "*" => None,
+            source => Some(source.to_string()),
+        };
+        let interval = super::parse_duration(interval.trim())?;
+        if !interval.is_positive() {
+            return Err(format!("invalid expected interval {s:?}: must be positive"));
+        }
+        Ok(Self { source, interval })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExpectedInterval;
+
+    #[test]
+    fn parse_and_find() {
+        let hours = |h| jiff::SignedDuration::from_hours(h);
+        let intervals: Vec<ExpectedInterval> = ["alice@host:/data=1d", "*=1h", "*=2h"]
+            .into_iter()
+            .map(|s| s.parse().expect("valid expected interval"))
+            .collect();
+        assert_eq!(intervals[0].source.as_deref(), Some("alice@host:/data"));
+        assert_eq!(intervals[1].source, None);
+
+        assert_eq!(
+            ExpectedInterval::find(&intervals, "alice@host:/data"),
+            Some(hours(24))
+        );
+        assert_eq!(
+            ExpectedInterval::find(&intervals, "bob@host:/data"),
+            Some(hours(2))
+        );
+        assert_eq!(
+            ExpectedInterval::find(&intervals[..1], "bob@host:/data"),
+            None
+        );
+
+        for invalid in ["1h", "*=soon", "*=0s", "*=-1h"] {
+            assert!(invalid.parse::<ExpectedInterval>().is_err(), "{invalid}");
+        }
+    }
+}
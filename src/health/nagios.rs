@@ -0,0 +1,130 @@
+use crate::health::{HealthReport, HealthStatus};
+use std::fmt::Write as _;
+
+impl HealthStatus {
+    /// Returns the Nagios plugin exit code (0 OK, 1 WARNING, 2 CRITICAL, 3 UNKNOWN)
+    #[must_use]
+    pub fn nagios_exit_code(self) -> u8 {
+        match self {
+            Self::Ok => 0,
+            Self::Warning => 1,
+            Self::Critical => 2,
+            Self::Unknown => 3,
+        }
+    }
+}
+
+impl HealthReport {
+    /// Formats the report as a single Nagios plugin output line, with perfdata
+    ///
+    /// e.g. `KOPIA CRITICAL - user@host:/path: errors 3 > 0 | 'user@host:/path errors'=3;;0 ...`
+    #[must_use]
+    pub fn to_nagios(&self) -> String {
+        let status = self.status();
+        let mut output = format!("KOPIA {status} - ");
+
+        let problems: Vec<String> = self
+            .sources
+            .iter()
+            .filter(|source| !source.problems.is_empty())
+            .map(|source| format!("{}: {}", source.source.as_str(), source.problems.join(", ")))
+            .collect();
+        if self.sources.is_empty() {
+            output.push_str("no snapshots found");
+        } else if problems.is_empty() {
+            let count = self.sources.len();
+            let plural = if count == 1 { "" } else { "s" };
+            write!(output, "{count} source{plural} healthy").expect("infallible");
+        } else {
+            output.push_str(&problems.join("; "));
+        }
+
+        let perfdata = self.nagios_perfdata();
+        if !perfdata.is_empty() {
+            write!(output, " | {}", perfdata.join(" ")).expect("infallible");
+        }
+        output
+    }
+
+    fn nagios_perfdata(&self) -> Vec<String> {
+        let thresholds = &self.thresholds;
+        let limit = |value: Option<String>| value.unwrap_or_default();
+        let mut perfdata = vec![];
+        for source in &self.sources {
+            // single quotes in labels are escaped by doubling
+            let source_label = source.source.as_str().replace('\'', "''");
+            if let Some(age_seconds) = source.age_seconds {
+                perfdata.push(format!(
+                    "'{source_label} age'={age_seconds}s;{};{}",
+                    limit(thresholds.warn_max_age.map(|d| d.as_secs().to_string())),
+                    limit(thresholds.max_age.map(|d| d.as_secs().to_string())),
+                ));
+            }
+            if let Some(errors) = source.errors {
+                perfdata.push(format!(
+                    "'{source_label} errors'={errors};{};{}",
+                    limit(thresholds.warn_max_errors.map(|n| n.to_string())),
+                    limit(thresholds.max_errors.map(|n| n.to_string())),
+                ));
+            }
+            if let Some(size_bytes) = source.size_bytes {
+                // `N:` range alerts when the value is below N
+                perfdata.push(format!(
+                    "'{source_label} size'={size_bytes}B;{};{}",
+                    limit(thresholds.warn_min_size.map(|n| format!("{n}:"))),
+                    limit(thresholds.min_size.map(|n| format!("{n}:"))),
+                ));
+            }
+        }
+        perfdata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::health::HealthThresholds;
+    use crate::test_util::{multi_map, single_map, test_snapshot};
+
+    fn now() -> jiff::Timestamp {
+        "2025-08-14T01:01:00Z".parse().expect("valid timestamp")
+    }
+
+    #[test]
+    fn nagios_ok() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &[])]);
+        let thresholds = HealthThresholds {
+            max_age: Some(jiff::SignedDuration::from_hours(26)),
+            max_errors: Some(0),
+            warn_min_size: Some(500),
+            ..HealthThresholds::default()
+        };
+        let report = map.evaluate_health(now(), &thresholds);
+        insta::assert_snapshot!(report.to_nagios(), @"KOPIA OK - 1 source healthy | 'user_name@host:/path age'=3600s;;93600 'user_name@host:/path errors'=0;;0 'user_name@host:/path size'=1000B;500:;");
+        assert_eq!(report.status().nagios_exit_code(), 0);
+    }
+
+    #[test]
+    fn nagios_critical() {
+        let mut failed = test_snapshot("2", 1000, &[]);
+        failed.stats.error_count = 3;
+        let (map, _sources) = multi_map(vec![
+            ("alice", "host", "/ok", vec![test_snapshot("1", 1000, &[])]),
+            ("bob", "host", "/failed", vec![failed]),
+        ]);
+        let thresholds = HealthThresholds {
+            max_errors: Some(0),
+            ..HealthThresholds::default()
+        };
+        let report = map.evaluate_health(now(), &thresholds);
+        insta::assert_snapshot!(report.to_nagios(), @"KOPIA CRITICAL - bob@host:/failed: errors 3 > 0 | 'alice@host:/ok age'=3600s;; 'alice@host:/ok errors'=0;;0 'alice@host:/ok size'=1000B;; 'bob@host:/failed age'=3600s;; 'bob@host:/failed errors'=3;;0 'bob@host:/failed size'=1000B;;");
+        assert_eq!(report.status().nagios_exit_code(), 2);
+    }
+
+    #[test]
+    fn nagios_unknown() {
+        let (map, _sources) = multi_map(vec![]);
+        let report = map.evaluate_health(now(), &HealthThresholds::default());
+        assert_eq!(report.to_nagios(), "KOPIA UNKNOWN - no snapshots found");
+        assert_eq!(report.status().nagios_exit_code(), 3);
+    }
+}
@@ -0,0 +1,206 @@
+use jiff::civil::{Time, Weekday};
+use std::str::FromStr;
+
+/// Period during which health problems of matching sources are ignored
+///
+/// Parsed from `SOURCE=WINDOW`, where `SOURCE` is a source (`user@host:/path`) or `*`
+/// for all sources, and `WINDOW` is either:
+/// - an absolute range `START/END` of RFC 3339 timestamps, e.g.
+///   `2025-08-14T20:00:00Z/2025-08-15T06:00:00Z`
+/// - a recurring daily range in UTC `[DAYS ]HH:MM-HH:MM`, where `DAYS` is `daily`
+///   (default) or a comma-separated list such as `sat,sun`, e.g. `sun 01:00-05:00`.
+///   Ranges crossing midnight (`22:00-02:00`) belong to the day they start on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SilenceWindow {
+    /// Source to silence, or `None` for all sources
+    pub source: Option<String>,
+    /// When the silence is active
+    pub schedule: SilenceSchedule,
+}
+
+/// Active period of a [`SilenceWindow`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SilenceSchedule {
+    /// Single range, from `start` (inclusive) to `end` (exclusive)
+    Absolute {
+        /// Start of the range
+        start: jiff::Timestamp,
+        /// End of the range
+        end: jiff::Timestamp,
+    },
+    /// Range repeating on the specified days (in UTC)
+    Recurring {
+        /// Days the range starts on (empty for every day)
+        weekdays: Vec<Weekday>,
+        /// Time of day the range starts (inclusive)
+        start: Time,
+        /// Time of day the range ends (exclusive)
+        end: Time,
+    },
+}
+
+impl SilenceWindow {
+    /// Returns `true` if the window applies to the source
+    #[must_use]
+    pub fn matches(&self, source: &str) -> bool {
+        self.source
+            .as_deref()
+            .is_none_or(|silenced| silenced == source)
+    }
+
+    /// Returns `true` if the window is active at the specified time
+    #[must_use]
+    pub fn is_active(&self, now: jiff::Timestamp) -> bool {
+        match &self.schedule {
+            SilenceSchedule::Absolute { start, end } => (*start..*end).contains(&now),
+            SilenceSchedule::Recurring {
+                weekdays,
+                start,
+                end,
+            } => {
+                let now = now.to_zoned(jiff::tz::TimeZone::UTC).datetime();
+                let time = now.time();
+                let is_day = |weekday: Weekday| weekdays.is_empty() || weekdays.contains(&weekday);
+                if start <= end {
+                    (*start..*end).contains(&time) && is_day(now.weekday())
+                } else if time >= *start {
+                    is_day(now.weekday())
+                } else {
+                    time < *end && is_day(now.weekday().previous())
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for SilenceWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((source, window)) = s.rsplit_once('=') else {
+            return Err(format!(
+                "invalid silence {s:?}, expected SOURCE=WINDOW (SOURCE may be \"*\")"
+            ));
+        };
+        let source = match source.trim() {
+            "*" => None,
+            source => Some(source.to_string()),
+        };
+        let schedule = window
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid silence {s:?}: {e}"))?;
+        Ok(Self { source, schedule })
+    }
+}
+
+impl FromStr for SilenceSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((start, end)) = s.split_once('/') {
+            let parse = |timestamp: &str| {
+                timestamp
+                    .parse::<jiff::Timestamp>()
+                    .map_err(|e| format!("invalid timestamp {timestamp:?}: {e}"))
+            };
+            let (start, end) = (parse(start)?, parse(end)?);
+            if start >= end {
+                return Err(format!("start {start} is not before end {end}"));
+            }
+            return Ok(Self::Absolute { start, end });
+        }
+
+        let (days, range) = s.rsplit_once(' ').unwrap_or(("daily", s));
+        let weekdays = match days.trim() {
+            "daily" => vec![],
+            days => days
+                .split(',')
+                .map(|day| parse_weekday(day.trim()))
+                .collect::<Result<_, _>>()?,
+        };
+        let Some((start, end)) = range.split_once('-') else {
+            return Err(format!(
+                "invalid time range {range:?}, expected HH:MM-HH:MM"
+            ));
+        };
+        let parse = |time: &str| {
+            time.parse::<Time>()
+                .map_err(|e| format!("invalid time {time:?}: {e}"))
+        };
+        Ok(Self::Recurring {
+            weekdays,
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+fn parse_weekday(day: &str) -> Result<Weekday, String> {
+    Ok(match day.to_ascii_lowercase().as_str() {
+        "mon" => Weekday::Monday,
+        "tue" => Weekday::Tuesday,
+        "wed" => Weekday::Wednesday,
+        "thu" => Weekday::Thursday,
+        "fri" => Weekday::Friday,
+        "sat" => Weekday::Saturday,
+        "sun" => Weekday::Sunday,
+        _ => return Err(format!("invalid day {day:?}, expected daily or mon..sun")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SilenceSchedule, SilenceWindow};
+
+    fn at(timestamp: &str) -> jiff::Timestamp {
+        timestamp.parse().expect("valid timestamp")
+    }
+
+    #[test]
+    fn absolute() {
+        let silence: SilenceWindow = "alice@host:/ok=2025-08-14T20:00:00Z/2025-08-15T06:00:00Z"
+            .parse()
+            .expect("valid");
+        assert_eq!(silence.source.as_deref(), Some("alice@host:/ok"));
+        assert!(silence.matches("alice@host:/ok"));
+        assert!(!silence.matches("bob@host:/ok"));
+        assert!(!silence.is_active(at("2025-08-14T19:59:59Z")));
+        assert!(silence.is_active(at("2025-08-14T20:00:00Z")));
+        assert!(!silence.is_active(at("2025-08-15T06:00:00Z")));
+    }
+
+    #[test]
+    fn recurring() {
+        // 2025-08-17 is a Sunday
+        let silence: SilenceWindow = "*=sun 22:00-02:00".parse().expect("valid");
+        assert!(silence.matches("anything"));
+        assert!(!silence.is_active(at("2025-08-17T21:59:00Z")));
+        assert!(silence.is_active(at("2025-08-17T23:00:00Z")));
+        assert!(silence.is_active(at("2025-08-18T01:00:00Z")));
+        assert!(!silence.is_active(at("2025-08-18T23:00:00Z")));
+
+        let daily: SilenceWindow = "*=01:00-05:00".parse().expect("valid");
+        assert!(matches!(
+            &daily.schedule,
+            SilenceSchedule::Recurring { weekdays, .. } if weekdays.is_empty()
+        ));
+        assert!(daily.is_active(at("2025-08-18T04:59:00Z")));
+    }
+
+    #[test]
+    fn invalid() {
+        for (input, expected) in [
+            ("sun 01:00-05:00", "expected SOURCE=WINDOW"),
+            ("*=someday 01:00-05:00", "invalid day"),
+            ("*=01:00", "expected HH:MM-HH:MM"),
+            (
+                "*=2025-08-15T00:00:00Z/2025-08-14T00:00:00Z",
+                "is not before",
+            ),
+        ] {
+            let err = input.parse::<SilenceWindow>().expect_err(input);
+            assert!(err.contains(expected), "{input}: {err}");
+        }
+    }
+}
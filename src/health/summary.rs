@@ -0,0 +1,92 @@
+use crate::health::HealthReport;
+use std::fmt::Write as _;
+
+impl HealthReport {
+    /// Formats the report as a human-readable summary, one line per source
+    ///
+    /// e.g.
+    /// ```text
+    /// CRITICAL: 1 of 2 sources unhealthy
+    ///   [OK] alice@host:/ok: age 1h, 0 errors, 1000 B
+    ///   [CRITICAL] bob@host:/failed: errors 3 > 0
+    /// ```
+    #[must_use]
+    pub fn to_summary(&self) -> String {
+        let status = self.status();
+        let count = self.sources.len();
+        let unhealthy = self
+            .sources
+            .iter()
+            .filter(|source| !source.is_healthy())
+            .count();
+        let plural = if count == 1 { "" } else { "s" };
+
+        let mut output = if count == 0 {
+            format!("{status}: no snapshots found")
+        } else if unhealthy == 0 {
+            format!("{status}: {count} source{plural} healthy")
+        } else {
+            format!("{status}: {unhealthy} of {count} source{plural} unhealthy")
+        };
+        for source in &self.sources {
+            write!(
+                output,
+                "\n  [{}] {}: ",
+                source.status,
+                source.source.as_str()
+            )
+            .expect("infallible");
+            if source.problems.is_empty() {
+                let mut details = vec![];
+                if let Some(age_seconds) = source.age_seconds {
+                    let age = jiff::SignedDuration::from_secs(age_seconds);
+                    details.push(format!("age {age:#}"));
+                }
+                if let Some(errors) = source.errors {
+                    details.push(format!("{errors} errors"));
+                }
+                if let Some(size_bytes) = source.size_bytes {
+                    details.push(format!("{size_bytes} B"));
+                }
+                if source.silenced {
+                    details.push("silenced".to_string());
+                }
+                output.push_str(&details.join(", "));
+            } else {
+                output.push_str(&source.problems.join(", "));
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::health::HealthThresholds;
+    use crate::test_util::{multi_map, test_snapshot};
+
+    #[test]
+    fn summary() {
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+        let mut failed = test_snapshot("2", 1000, &[]);
+        failed.stats.error_count = 3;
+        let (map, _sources) = multi_map(vec![
+            ("alice", "host", "/ok", vec![test_snapshot("1", 1000, &[])]),
+            ("bob", "host", "/failed", vec![failed]),
+        ]);
+        let thresholds = HealthThresholds {
+            max_errors: Some(0),
+            ..HealthThresholds::default()
+        };
+        let report = map.evaluate_health(now, &thresholds);
+        insta::assert_snapshot!(report.to_summary(), @r"
+        CRITICAL: 1 of 2 sources unhealthy
+          [OK] alice@host:/ok: age 1h, 0 errors, 1000 B
+          [CRITICAL] bob@host:/failed: errors 3 > 0
+        ");
+
+        let (map, _sources) = multi_map(vec![]);
+        let report = map.evaluate_health(now, &thresholds);
+        assert_eq!(report.to_summary(), "UNKNOWN: no snapshots found");
+    }
+}
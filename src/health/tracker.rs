@@ -0,0 +1,170 @@
+use crate::SourceStr;
+use crate::health::{HealthReport, HealthStatus};
+use std::collections::BTreeMap;
+
+/// Change of a source between healthy and unhealthy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthTransition {
+    /// Source of the snapshots
+    pub source: SourceStr,
+    /// New health of the source
+    pub healthy: bool,
+    /// Status of the source when the transition was reported
+    pub status: HealthStatus,
+    /// Descriptions of the exceeded thresholds (empty when healthy)
+    pub problems: Vec<String>,
+}
+
+impl HealthTransition {
+    /// Short notification title, e.g. `Kopia backup CRITICAL` or `Kopia backup recovered`
+    #[must_use]
+    pub fn title(&self) -> String {
+        if self.healthy {
+            "Kopia backup recovered".to_string()
+        } else {
+            format!("Kopia backup {}", self.status)
+        }
+    }
+
+    /// Notification message naming the source and problems, e.g. `user@host:/path (errors 3 > 0)`
+    #[must_use]
+    pub fn message(&self) -> String {
+        let source = self.source.as_str();
+        if self.problems.is_empty() {
+            source.to_string()
+        } else {
+            format!("{source} ({})", self.problems.join(", "))
+        }
+    }
+}
+
+/// Tracks the health of each source across evaluations, reporting transitions with
+/// hysteresis
+///
+/// Sources start out as healthy, so sources which are unhealthy from the start are
+/// reported once unhealthy for the required number of evaluations.
+#[derive(Clone, Debug)]
+pub struct HealthTracker {
+    unhealthy_after: u32,
+    healthy_after: u32,
+    sources: BTreeMap<SourceStr, TrackedSource>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TrackedSource {
+    /// Last reported health
+    healthy: bool,
+    /// Number of consecutive evaluations disagreeing with the reported health
+    pending: u32,
+}
+
+impl HealthTracker {
+    /// Creates a tracker which reports a source as unhealthy once unhealthy in
+    /// `unhealthy_after` consecutive evaluations, and as recovered once healthy in
+    /// `healthy_after` consecutive evaluations (values below 1 are treated as 1)
+    #[must_use]
+    pub fn new(unhealthy_after: u32, healthy_after: u32) -> Self {
+        Self {
+            unhealthy_after: unhealthy_after.max(1),
+            healthy_after: healthy_after.max(1),
+            sources: BTreeMap::new(),
+        }
+    }
+
+    /// Records the report, returning the transitions which passed the hysteresis
+    ///
+    /// Sources missing from the report are forgotten.
+    pub fn update(&mut self, report: &HealthReport) -> Vec<HealthTransition> {
+        let mut sources = BTreeMap::new();
+        let mut transitions = vec![];
+        for source_health in &report.sources {
+            let mut tracked = self
+                .sources
+                .remove(&source_health.source)
+                .unwrap_or(TrackedSource {
+                    healthy: true,
+                    pending: 0,
+                });
+            let healthy = source_health.is_healthy();
+            if healthy == tracked.healthy {
+                tracked.pending = 0;
+            } else {
+                tracked.pending += 1;
+                let required = if healthy {
+                    self.healthy_after
+                } else {
+                    self.unhealthy_after
+                };
+                if tracked.pending >= required {
+                    tracked = TrackedSource {
+                        healthy,
+                        pending: 0,
+                    };
+                    transitions.push(HealthTransition {
+                        source: source_health.source.clone(),
+                        healthy,
+                        status: source_health.status,
+                        problems: source_health.problems.clone(),
+                    });
+                }
+            }
+            sources.insert(source_health.source.clone(), tracked);
+        }
+        self.sources = sources;
+        transitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HealthTracker;
+    use crate::health::{HealthReport, HealthStatus, HealthThresholds, SourceHealth};
+    use crate::test_util::single_map;
+
+    fn report(status: HealthStatus) -> HealthReport {
+        let (_map, source) = single_map(vec![]);
+        HealthReport {
+            sources: vec![SourceHealth {
+                source,
+                status,
+                problems: vec![],
+                age_seconds: None,
+                errors: None,
+                size_bytes: None,
+                silenced: false,
+            }],
+            thresholds: HealthThresholds::default(),
+        }
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut tracker = HealthTracker::new(2, 3);
+        // returns the reported health, if changed
+        let healthy = |tracker: &mut HealthTracker, status| {
+            let transitions = tracker.update(&report(status));
+            assert!(transitions.len() <= 1, "{transitions:?}");
+            transitions.first().map(|transition| transition.healthy)
+        };
+
+        assert_eq!(healthy(&mut tracker, HealthStatus::Ok), None);
+        assert_eq!(healthy(&mut tracker, HealthStatus::Critical), None);
+        // flapping resets the count
+        assert_eq!(healthy(&mut tracker, HealthStatus::Warning), None);
+        assert_eq!(healthy(&mut tracker, HealthStatus::Critical), None);
+        assert_eq!(healthy(&mut tracker, HealthStatus::Critical), Some(false));
+        assert_eq!(healthy(&mut tracker, HealthStatus::Critical), None);
+        assert_eq!(healthy(&mut tracker, HealthStatus::Ok), None);
+        assert_eq!(healthy(&mut tracker, HealthStatus::Ok), None);
+        assert_eq!(healthy(&mut tracker, HealthStatus::Ok), Some(true));
+    }
+
+    #[test]
+    fn unhealthy_from_start() {
+        let mut tracker = HealthTracker::new(0, 0);
+        let transitions = tracker.update(&report(HealthStatus::Unknown));
+        assert_eq!(transitions.len(), 1);
+        assert!(!transitions[0].healthy);
+        assert_eq!(transitions[0].status, HealthStatus::Unknown);
+    }
+}
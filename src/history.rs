@@ -0,0 +1,106 @@
+//! Snapshot history beyond the retention of the repository
+//!
+//! Pruned snapshots are no longer listed by `kopia`, erasing the history needed for trends
+//! over longer periods. With the `history` feature, a `HistoryDb` records the snapshots
+//! observed on each refresh in `SQLite`, and loads them as a [`SnapshotHistory`] for
+//! [`KopiaSnapshots::with_history`](crate::KopiaSnapshots::with_history).
+//!
+//! The `kopia_history_*` metrics report trends over the last
+//! [window of days](SnapshotHistory::with_window_days) of the history.
+
+use crate::{SourceMap, SourceStr};
+
+#[cfg(feature = "history")]
+pub use self::db::HistoryDb;
+
+#[cfg(feature = "history")]
+mod db;
+
+/// Snapshot as last observed, possibly pruned since
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoricSnapshot {
+    /// Snapshot ID
+    pub id: String,
+    /// Start time of the snapshot, if valid
+    pub start_time: Option<jiff::Timestamp>,
+    /// End time of the snapshot, if valid
+    pub end_time: Option<jiff::Timestamp>,
+    /// Total size of the snapshot in bytes
+    pub total_size: u64,
+    /// Number of errors of the snapshot
+    pub error_count: u64,
+    /// Last time the snapshot was observed
+    pub last_observed_at: jiff::Timestamp,
+}
+
+/// Every observed snapshot of each source, ordered by end time
+#[derive(Clone, Debug)]
+pub struct SnapshotHistory {
+    snapshots_map: SourceMap<Vec<HistoricSnapshot>>,
+    window_days: u32,
+}
+impl SnapshotHistory {
+    /// Default number of days of the window of the trend metrics
+    pub const DEFAULT_WINDOW_DAYS: u32 = 30;
+
+    /// Creates the history from the snapshots of each source, in any order
+    #[must_use]
+    pub fn new(snapshots_map: SourceMap<Vec<HistoricSnapshot>>) -> Self {
+        let snapshots_map = snapshots_map
+            .into_iter()
+            .map(|(source, mut snapshots)| {
+                snapshots.sort_by(|a, b| (a.end_time, &a.id).cmp(&(b.end_time, &b.id)));
+                (source, snapshots)
+            })
+            .collect();
+        Self {
+            snapshots_map,
+            window_days: Self::DEFAULT_WINDOW_DAYS,
+        }
+    }
+
+    /// Sets the number of days (ending now) the trend metrics are computed over
+    #[must_use]
+    pub fn with_window_days(mut self, window_days: u32) -> Self {
+        self.window_days = window_days;
+        self
+    }
+
+    /// Returns the number of days the trend metrics are computed over
+    #[must_use]
+    pub fn window_days(&self) -> u32 {
+        self.window_days
+    }
+
+    /// Returns the snapshots of the source, ordered by end time
+    #[must_use]
+    pub fn snapshots_for(&self, source: &SourceStr) -> Option<&[HistoricSnapshot]> {
+        self.snapshots_map.get(source).map(Vec::as_slice)
+    }
+
+    /// Iterates the snapshots of each source
+    pub fn iter(&self) -> impl Iterator<Item = (&SourceStr, &[HistoricSnapshot])> {
+        self.snapshots_map
+            .iter()
+            .map(|(source, snapshots)| (source, snapshots.as_slice()))
+    }
+
+    /// Returns the start of the window ending at `now`
+    pub(crate) fn window_start(&self, now: jiff::Timestamp) -> jiff::Timestamp {
+        let window = jiff::SignedDuration::from_hours(24 * i64::from(self.window_days));
+        now.checked_sub(window).unwrap_or(jiff::Timestamp::MIN)
+    }
+
+    /// Iterates the snapshots of the source ending within the window ending at `now`
+    pub(crate) fn iter_window<'a>(
+        &self,
+        snapshots: &'a [HistoricSnapshot],
+        now: jiff::Timestamp,
+    ) -> impl Iterator<Item = (jiff::Timestamp, &'a HistoricSnapshot)> {
+        let start = self.window_start(now);
+        snapshots.iter().filter_map(move |snapshot| {
+            let end_time = snapshot.end_time?;
+            (start < end_time && end_time <= now).then_some((end_time, snapshot))
+        })
+    }
+}
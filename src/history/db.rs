@@ -0,0 +1,219 @@
+use super::{HistoricSnapshot, SnapshotHistory};
+use crate::{KopiaSnapshots, SourceMap, SourceStr};
+use rusqlite::{Connection, params};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    source TEXT NOT NULL,
+    snapshot_id TEXT NOT NULL,
+    start_time INTEGER,
+    end_time INTEGER,
+    total_size INTEGER NOT NULL,
+    error_count INTEGER NOT NULL,
+    last_observed_at INTEGER NOT NULL,
+    UNIQUE (source, snapshot_id)
+);
+";
+
+/// `SQLite` database of the snapshots observed on each refresh
+///
+/// The `snapshots` table holds one row per snapshot, updated to the latest observation by
+/// each [`record`](Self::record), with times in seconds since the Unix epoch.
+#[derive(Debug)]
+pub struct HistoryDb {
+    connection: Connection,
+}
+impl HistoryDb {
+    /// Opens the database at the path, creating it if missing
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or initialized
+    pub fn open(path: impl AsRef<std::path::Path>) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Opens a new in-memory database, discarded once dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be initialized
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection })
+    }
+
+    /// Inserts or updates the row of each snapshot observed at `observed_at`, returning the
+    /// number of snapshots
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the database fails
+    pub fn record(
+        &mut self,
+        snapshots: &KopiaSnapshots,
+        observed_at: jiff::Timestamp,
+    ) -> rusqlite::Result<usize> {
+        let transaction = self.connection.transaction()?;
+        let mut count = 0;
+        {
+            let mut upsert = transaction.prepare_cached(
+                "INSERT INTO snapshots (last_observed_at, source, snapshot_id, start_time, \
+                 end_time, total_size, error_count) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) \
+                 ON CONFLICT (source, snapshot_id) DO UPDATE SET \
+                 last_observed_at = excluded.last_observed_at, \
+                 start_time = excluded.start_time, end_time = excluded.end_time, \
+                 total_size = excluded.total_size, error_count = excluded.error_count",
+            )?;
+            for (source, snapshots) in &snapshots.snapshots_map {
+                for snapshot in snapshots {
+                    upsert.execute(params![
+                        observed_at.as_second(),
+                        source.as_str(),
+                        snapshot.id,
+                        snapshot.start_time.map(jiff::Timestamp::as_second),
+                        snapshot.end_time.map(jiff::Timestamp::as_second),
+                        to_sql_integer(snapshot.stats.total_size),
+                        to_sql_integer(snapshot.stats.error_count),
+                    ])?;
+                    count += 1;
+                }
+            }
+        }
+        transaction.commit()?;
+        Ok(count)
+    }
+
+    /// Loads every recorded snapshot, with the values of its last observation
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the database fails
+    pub fn load(&self) -> rusqlite::Result<SnapshotHistory> {
+        let mut select = self.connection.prepare_cached(
+            "SELECT source, snapshot_id, start_time, end_time, total_size, error_count, \
+             last_observed_at FROM snapshots",
+        )?;
+        let rows = select.query_map([], |row| {
+            let source: String = row.get(0)?;
+            let snapshot = HistoricSnapshot {
+                id: row.get(1)?,
+                start_time: row
+                    .get::<_, Option<i64>>(2)?
+                    .map(|seconds| to_timestamp(2, seconds))
+                    .transpose()?,
+                end_time: row
+                    .get::<_, Option<i64>>(3)?
+                    .map(|seconds| to_timestamp(3, seconds))
+                    .transpose()?,
+                total_size: from_sql_integer(row.get(4)?),
+                error_count: from_sql_integer(row.get(5)?),
+                last_observed_at: to_timestamp(6, row.get(6)?)?,
+            };
+            Ok((source, snapshot))
+        })?;
+        let mut snapshots_map = SourceMap::new();
+        for row in rows {
+            let (source, snapshot) = row?;
+            snapshots_map
+                .entry(SourceStr::from_rendered(source))
+                .or_insert_with(Vec::new)
+                .push(snapshot);
+        }
+        Ok(SnapshotHistory::new(snapshots_map))
+    }
+}
+
+/// Converts to an `SQLite` integer (signed), saturating
+fn to_sql_integer(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+/// Converts from an `SQLite` integer, with zero for negative values
+fn from_sql_integer(value: i64) -> u64 {
+    u64::try_from(value).unwrap_or(0)
+}
+
+fn to_timestamp(column: usize, seconds: i64) -> rusqlite::Result<jiff::Timestamp> {
+    jiff::Timestamp::from_second(seconds).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Integer, e.into())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HistoryDb;
+    use crate::test_util::{single_map, test_snapshot};
+
+    #[test]
+    fn record_and_load() {
+        let mut db = HistoryDb::open_in_memory().expect("in-memory database");
+        let first: jiff::Timestamp = "2025-08-14T00:05:00Z".parse().expect("valid timestamp");
+        let second: jiff::Timestamp = "2025-08-15T00:05:00Z".parse().expect("valid timestamp");
+
+        let mut pruned = test_snapshot("1", 1000, &[]);
+        pruned.end_time = "2025-08-13T00:01:00Z".to_string();
+        let (map, source) = single_map(vec![pruned, test_snapshot("2", 2000, &[])]);
+        assert_eq!(db.record(&map, first).expect("recorded"), 2);
+
+        let mut failed = test_snapshot("2", 2500, &[]);
+        failed.stats.error_count = 3;
+        let (map, _source) = single_map(vec![failed]);
+        assert_eq!(db.record(&map, second).expect("recorded"), 1);
+
+        let history = db.load().expect("loaded");
+        let snapshots = history.snapshots_for(&source).expect("recorded source");
+        let summary: Vec<_> = snapshots
+            .iter()
+            .map(|snapshot| {
+                (
+                    snapshot.id.as_str(),
+                    snapshot.total_size,
+                    snapshot.error_count,
+                    snapshot.last_observed_at,
+                )
+            })
+            .collect();
+        assert_eq!(summary, [("1", 1000, 0, first), ("2", 2500, 3, second)]);
+        assert_eq!(
+            snapshots[0].end_time,
+            Some("2025-08-13T00:01:00Z".parse().expect("valid timestamp"))
+        );
+        assert_eq!(history.iter().count(), 1);
+    }
+
+    #[test]
+    fn record_same_listing() {
+        let mut db = HistoryDb::open_in_memory().expect("in-memory database");
+        let first: jiff::Timestamp = "2025-08-14T00:05:00Z".parse().expect("valid timestamp");
+        let second: jiff::Timestamp = "2025-08-14T00:05:30Z".parse().expect("valid timestamp");
+        let row_count = |db: &HistoryDb| {
+            db.connection
+                .query_row("SELECT COUNT(*) FROM snapshots", [], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .expect("counted")
+        };
+
+        let (map, source) = single_map(vec![
+            test_snapshot("1", 1000, &[]),
+            test_snapshot("2", 2000, &[]),
+        ]);
+        assert_eq!(db.record(&map, first).expect("recorded"), 2);
+        assert_eq!(row_count(&db), 2);
+        assert_eq!(db.record(&map, second).expect("recorded"), 2);
+        assert_eq!(row_count(&db), 2);
+
+        let history = db.load().expect("loaded");
+        let snapshots = history.snapshots_for(&source).expect("recorded source");
+        assert!(
+            snapshots
+                .iter()
+                .all(|snapshot| snapshot.last_observed_at == second)
+        );
+    }
+}
@@ -3,38 +3,128 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+pub use self::aggregate::ParseOptions;
+pub(crate) use self::aggregate::{Compaction, FoldedSnapshots, compact};
+pub use self::audit::AuditRecord;
+pub(crate) use self::audit::{AuditOutcome, CountingReader};
+pub use self::builder::SnapshotJsonBuilder;
+pub use self::command::KopiaCommand;
+pub(crate) use self::duplicates::SeenSnapshotIds;
+pub(crate) use self::invalid_sources::ReportCollector;
+pub use self::invalid_sources::{InvalidSourceReport, InvalidSources};
+pub use self::latest_policy::LatestSnapshotPolicy;
+pub(crate) use self::malformed::MalformedSnapshot;
+pub use self::malformed::MalformedSnapshots;
+pub use self::ndjson::SnapshotsNdjsonReader;
+pub use self::retention_reason::RetentionReason;
 pub use self::source_map::SourceMap;
 pub use self::source_str::{Error as SourceStrError, SourceStr};
+pub(crate) use self::stream::for_each_snapshot;
+pub(crate) use self::timestamp::{
+    TimestampIssue, TimestampIssueCounts, classify_timestamp, parse_timestamp,
+};
+pub(crate) use self::utf8::LossyUtf8Reader;
 use crate::KopiaSnapshots;
 
+mod aggregate;
+mod audit;
+mod builder;
+mod command;
+mod duplicates;
+mod invalid_sources;
+mod latest_policy;
+mod malformed;
+mod ndjson;
+mod retention_reason;
 mod source_map;
 mod source_str;
-
+mod stream;
+mod timestamp;
+mod utf8;
+
+/// Snapshot as listed by `kopia snapshot list --json`
+///
+/// Tolerates changes between `kopia` versions: unknown fields are ignored, and missing
+/// counters default to zero. Snapshots which fail to parse anyway are skipped by the
+/// constructors of [`KopiaSnapshots`], see
+/// [`KopiaSnapshots::malformed_snapshots`](crate::KopiaSnapshots::malformed_snapshots).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[expect(missing_docs)] // no need to document all fields
 pub struct SnapshotJson {
     pub id: String,
     pub source: Source,
+    #[serde(default)]
     pub description: String,
     pub start_time: String,
     pub end_time: String,
+    #[serde(default)]
     pub stats: Stats,
-    pub root_entry: RootEntry,
+    /// Root directory entry, absent (or `null`) for some incomplete snapshots
+    #[serde(default)]
+    pub root_entry: Option<RootEntry>,
+    /// Retention policy rules keeping the snapshot, absent for snapshots no rule retains
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub retention_reason: Vec<String>,
+    /// Reason the snapshot is incomplete (e.g. `"checkpoint"`), absent for complete snapshots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incomplete: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[expect(missing_docs)] // no need to document all fields
 pub struct Snapshot {
     pub id: String,
     pub source: Source,
     pub description: String,
-    pub start_time: String,
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub start_time: Option<jiff::Timestamp>,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub end_time: Option<jiff::Timestamp>,
     pub stats: Stats,
-    pub root_entry: RootEntry,
-    pub retention_reason: Vec<String>,
+    /// Root directory entry, absent for some incomplete snapshots
+    pub root_entry: Option<RootEntry>,
+    /// Modification time of the root entry, parsed from [`RootEntry::mtime`]
+    #[serde(skip)]
+    pub root_mtime: Option<jiff::Timestamp>,
+    pub retention_reason: Vec<RetentionReason>,
+    /// Reason the snapshot is incomplete (e.g. `"checkpoint"`), absent for complete snapshots
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incomplete: Option<String>,
+    /// Timestamps as reported by `kopia`, for reporting values which failed to parse
+    #[serde(skip)]
+    pub raw_times: RawTimes,
+}
+
+impl Snapshot {
+    /// Returns the issues of the start and end times, which are then absent
+    pub(crate) fn timestamp_issues(&self) -> impl Iterator<Item = TimestampIssue> + '_ {
+        let RawTimes {
+            start_time,
+            end_time,
+        } = &self.raw_times;
+        [start_time, end_time]
+            .into_iter()
+            .filter_map(|raw| classify_timestamp(raw).err())
+    }
+
+    /// Returns the number of files which failed, from the root entry (zero if absent)
+    #[must_use]
+    pub fn failed_files(&self) -> u64 {
+        self.root_entry
+            .as_ref()
+            .map_or(0, |root_entry| root_entry.summ.num_failed)
+    }
+}
+
+/// Unparsed timestamps of a [`Snapshot`]
+#[derive(Debug, Clone, Default)]
+pub struct RawTimes {
+    /// Raw [`Snapshot::start_time`]
+    pub start_time: String,
+    /// Raw [`Snapshot::end_time`]
+    pub end_time: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -46,24 +136,24 @@ pub struct Source {
     pub path: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 #[expect(missing_docs)] // no need to document all fields
 pub struct Stats {
     pub total_size: u64,
     pub excluded_total_size: u64,
-    pub file_count: u32,
-    pub cached_files: u32,
-    pub non_cached_files: u32,
-    pub dir_count: u32,
-    pub excluded_file_count: u32,
-    pub excluded_dir_count: u32,
-    pub ignored_error_count: u32,
-    pub error_count: u32,
+    pub file_count: u64,
+    pub cached_files: u64,
+    pub non_cached_files: u64,
+    pub dir_count: u64,
+    pub excluded_file_count: u64,
+    pub excluded_dir_count: u64,
+    pub ignored_error_count: u64,
+    pub error_count: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 #[expect(missing_docs)] // no need to document all fields
 pub struct RootEntry {
     pub name: String,
@@ -75,16 +165,28 @@ pub struct RootEntry {
     pub summ: Summary,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 #[expect(missing_docs)] // no need to document all fields
 pub struct Summary {
     pub size: u64,
-    pub files: u32,
-    pub symlinks: u32,
-    pub dirs: u32,
+    pub files: u64,
+    pub symlinks: u64,
+    pub dirs: u64,
     pub max_time: String,
-    pub num_failed: u32,
+    pub num_failed: u64,
+}
+
+/// Serializes the timestamp in RFC 3339 format (avoids the `jiff/serde` feature)
+#[expect(clippy::ref_option)] // signature required by `serialize_with`
+pub(crate) fn serialize_timestamp<S: serde::Serializer>(
+    timestamp: &Option<jiff::Timestamp>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match timestamp {
+        Some(timestamp) => serializer.collect_str(timestamp),
+        None => serializer.serialize_none(),
+    }
 }
 
 impl From<SnapshotJson> for Snapshot {
@@ -98,16 +200,28 @@ impl From<SnapshotJson> for Snapshot {
             stats,
             root_entry,
             retention_reason,
+            incomplete,
         } = value;
         Self {
             id,
             source,
             description,
-            start_time,
-            end_time: end_time.parse().ok(),
+            start_time: parse_timestamp(&start_time),
+            end_time: parse_timestamp(&end_time),
             stats,
+            root_mtime: root_entry
+                .as_ref()
+                .and_then(|root_entry| parse_timestamp(&root_entry.mtime)),
             root_entry,
-            retention_reason,
+            retention_reason: retention_reason
+                .into_iter()
+                .map(RetentionReason::new)
+                .collect(),
+            incomplete,
+            raw_times: RawTimes {
+                start_time,
+                end_time,
+            },
         }
     }
 }
@@ -115,102 +229,112 @@ impl From<SnapshotJson> for Snapshot {
 impl KopiaSnapshots {
     /// Returns the number of snapshots for each [`Snapshot::retention_reason`]
     #[must_use]
-    pub fn get_retention_counts(&self) -> SourceMap<BTreeMap<String, u32>> {
+    pub fn get_retention_counts(&self) -> SourceMap<BTreeMap<String, u64>> {
         self.snapshots_map
             .iter()
             .map(|(source, snapshots)| {
-                let mut reason_counts = BTreeMap::<String, u32>::new();
+                let mut reason_counts = self
+                    .folded
+                    .get(source)
+                    .map(|folded| folded.retention_counts.clone())
+                    .unwrap_or_default();
                 for snapshot in snapshots {
                     for reason in &snapshot.retention_reason {
-                        *reason_counts.entry(reason.clone()).or_insert(0) += 1;
+                        *reason_counts.entry(reason.as_str().to_owned()).or_insert(0) += 1;
                     }
                 }
                 (source.clone(), reason_counts)
             })
             .collect()
     }
+
+    /// Returns the number of snapshots for each [`RetentionReason::class`] (e.g. `daily`)
+    #[must_use]
+    pub fn get_retention_class_counts(&self) -> SourceMap<BTreeMap<String, u64>> {
+        self.get_retention_counts()
+            .into_iter()
+            .map(|(source, reason_counts)| {
+                let mut class_counts = BTreeMap::new();
+                for (reason, count) in reason_counts {
+                    let class = RetentionReason::new(reason).class().to_owned();
+                    *class_counts.entry(class).or_insert(0) += count;
+                }
+                (source, class_counts)
+            })
+            .collect()
+    }
 }
 
-#[cfg(test)]
-pub(crate) mod test_util {
-    use super::*;
+/// Builders for realistic snapshot fixtures, for tests of this and downstream crates
+///
+/// All snapshots end at `2025-08-14T00:01:00Z` unless modified, with zero errors.
+#[cfg(any(test, feature = "testkit"))]
+pub mod test_util {
+    use super::{KopiaSnapshots, SnapshotJson, Source, SourceStr};
 
+    /// Source of the snapshots built by [`test_snapshot`], `user_name@host:/path`
+    #[must_use]
+    pub fn test_source() -> Source {
+        Source {
+            host: "host".to_string(),
+            user_name: "user_name".to_string(),
+            path: "/path".to_string(),
+        }
+    }
+
+    /// Constructs a [`SourceStr`] without validation
     #[track_caller]
+    #[must_use]
     pub fn source_str(s: &str) -> SourceStr {
         SourceStr::new_unchecked(s.to_string())
     }
 
+    /// Parses the snapshots, returning the [`SourceStr`] of [`test_source`]
+    ///
+    /// # Panics
+    /// Panics if any snapshot has an invalid source
+    #[must_use]
     pub fn single_map(snapshots: Vec<SnapshotJson>) -> (KopiaSnapshots, SourceStr) {
-        let source = Source {
-            host: "host".to_string(),
-            user_name: "user_name".to_string(),
-            path: "/path".to_string(),
-        }
-        .render()
-        .expect("valid source");
+        let source = test_source().render().expect("valid source");
 
-        let map =
-            KopiaSnapshots::new_from_snapshots(snapshots, |_| Ok(())).expect("valid snapshots");
+        let (map, invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(snapshots);
+        invalid_sources.into_result().expect("valid snapshots");
 
         (map, source)
     }
 
+    /// Builds a complete snapshot of [`test_source`] with the specified size and retention
+    /// reasons
+    #[must_use]
     pub fn test_snapshot(id: &str, total_size: u64, retention_reasons: &[&str]) -> SnapshotJson {
-        test_snapshot_with_source(
-            id,
-            total_size,
-            retention_reasons,
-            Source {
-                host: "host".to_string(),
-                user_name: "user_name".to_string(),
-                path: "/path".to_string(),
-            },
-        )
+        test_snapshot_with_source(id, total_size, retention_reasons, test_source())
     }
 
+    /// Builds a complete snapshot of the source with the specified size and retention
+    /// reasons
+    #[must_use]
     pub fn test_snapshot_with_source(
         id: &str,
         total_size: u64,
         retention_reasons: &[&str],
         source: Source,
     ) -> SnapshotJson {
-        SnapshotJson {
-            id: id.to_string(),
-            source,
-            description: "".to_string(),
-            start_time: "2025-08-14T00:00:00Z".to_string(),
-            end_time: "2025-08-14T00:01:00Z".to_string(),
-            stats: Stats {
-                total_size,
-                excluded_total_size: 0,
-                file_count: 10,
-                cached_files: 5,
-                non_cached_files: 5,
-                dir_count: 2,
-                excluded_file_count: 0,
-                excluded_dir_count: 0,
-                ignored_error_count: 0,
-                error_count: 0,
-            },
-            root_entry: RootEntry {
-                name: "test".to_string(),
-                entry_type: "d".to_string(),
-                mode: "0755".to_string(),
-                mtime: "2025-08-14T00:00:00Z".to_string(),
-                obj: format!("obj{id}"),
-                summ: Summary {
-                    size: total_size,
-                    files: 10,
-                    symlinks: 0,
-                    dirs: 2,
-                    max_time: "2025-08-14T00:00:00Z".to_string(),
-                    num_failed: 0,
-                },
-            },
-            retention_reason: retention_reasons.iter().map(ToString::to_string).collect(),
-        }
+        SnapshotJson::builder()
+            .id(id)
+            .source(source)
+            .total_size(total_size)
+            .file_count(10)
+            .dir_count(2)
+            .retention_reasons(retention_reasons)
+            .build()
     }
 
+    /// Parses the snapshots of each `(user_name, host, path, snapshots)`, overriding the
+    /// source of the snapshots, and returns the [`SourceStr`] of each
+    ///
+    /// # Panics
+    /// Panics if any source is invalid
+    #[must_use]
     pub fn multi_map(
         sources_snapshots: Vec<(&str, &str, &str, Vec<SnapshotJson>)>,
     ) -> (KopiaSnapshots, Vec<SourceStr>) {
@@ -232,8 +356,8 @@ pub(crate) mod test_util {
             }
         }
 
-        let map =
-            KopiaSnapshots::new_from_snapshots(all_snapshots, |_| Ok(())).expect("valid snapshots");
+        let (map, invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(all_snapshots);
+        invalid_sources.into_result().expect("valid snapshots");
 
         (map, sources)
     }
@@ -242,7 +366,7 @@ pub(crate) mod test_util {
 #[cfg(test)]
 mod tests {
     use crate::{
-        KopiaSnapshots,
+        KopiaSnapshots, ParseOptions,
         test_util::{single_map, source_str, test_snapshot},
     };
 
@@ -286,15 +410,26 @@ mod tests {
             }
         ]"#;
 
-        let snapshots = KopiaSnapshots::new_parse_json(json, |e| eyre::bail!(e))
-            .expect("valid JSON")
+        let (map, invalid_sources) =
+            KopiaSnapshots::new_from_reader_with_report(json.as_bytes()).expect("valid JSON");
+        assert!(invalid_sources.is_empty());
+        let snapshots = map
             .into_inner_map()
             .into_expect_only(&source_str("user@test:/test"))
             .expect("single source");
         assert_eq!(snapshots.len(), 1);
         assert_eq!(snapshots[0].id, "test123");
         assert_eq!(snapshots[0].stats.total_size, 1000);
-        assert_eq!(snapshots[0].retention_reason, vec!["latest-1", "daily-1"]);
+        let reasons: Vec<_> = snapshots[0]
+            .retention_reason
+            .iter()
+            .map(|reason| (reason.class(), reason.slot()))
+            .collect();
+        assert_eq!(reasons, [("latest", Some(1)), ("daily", Some(1))]);
+        let expected_time: jiff::Timestamp = "2025-08-14T00:00:00Z".parse().expect("valid");
+        assert_eq!(snapshots[0].start_time, Some(expected_time));
+        assert_eq!(snapshots[0].root_mtime, Some(expected_time));
+        assert_eq!(snapshots[0].raw_times.end_time, "2025-08-14T00:01:00Z");
     }
 
     #[test]
@@ -346,13 +481,173 @@ mod tests {
         assert_eq!(counts.get("daily-2"), Some(&1));
     }
 
+    #[test]
+    fn merge_combines_sources_deterministically() {
+        let snapshot = |id: &str, end_time: &str| {
+            let mut snapshot = test_snapshot(id, 1000, &["daily"]);
+            snapshot.end_time = end_time.to_string();
+            snapshot
+        };
+        let (first, source) = single_map(vec![
+            snapshot("a", "2025-08-14T00:01:00Z"),
+            snapshot("c", "2025-08-14T00:03:00Z"),
+        ]);
+        let (second, _) = single_map(vec![
+            snapshot("b", "2025-08-14T00:02:00Z"),
+            snapshot("d", "2025-08-14T00:03:00Z"),
+        ]);
+
+        let ids = |map: KopiaSnapshots| {
+            map.into_inner_map()
+                .into_expect_only(&source)
+                .expect("single")
+                .into_iter()
+                .map(|snapshot| snapshot.id)
+                .collect::<Vec<_>>()
+        };
+        let merged = first.clone().merge(second.clone());
+        let counts = merged
+            .get_retention_counts()
+            .into_expect_only(&source)
+            .expect("single");
+        assert_eq!(counts.get("daily"), Some(&4));
+        assert_eq!(ids(merged), ["a", "b", "c", "d"]);
+        assert_eq!(
+            ids(second.clone().merge(first.clone())),
+            ["a", "b", "c", "d"]
+        );
+        assert_eq!(
+            ids([second, first].into_iter().collect()),
+            ["a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn borrowing_accessors() {
+        let mut invalid = test_snapshot("2", 1000, &["daily-1"]);
+        invalid.source.host = "bad:host".to_string();
+        let (map, invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(vec![
+            test_snapshot("1", 1000, &["daily-1"]),
+            invalid,
+        ]);
+        assert_eq!(invalid_sources.errors().len(), 1);
+        let source = source_str("user_name@host:/path");
+
+        assert_eq!(map.sources().collect::<Vec<_>>(), [&source]);
+        let ids: Vec<_> = map
+            .snapshots_for(&source)
+            .expect("present")
+            .iter()
+            .map(|snapshot| snapshot.id.as_str())
+            .collect();
+        assert_eq!(ids, ["1"]);
+        assert!(map.snapshots_for(&source_str("other@host:/path")).is_none());
+
+        let invalid_sources = map.invalid_sources();
+        assert_eq!(invalid_sources.user_names().count(), 0);
+        assert_eq!(
+            invalid_sources.hosts().collect::<Vec<_>>(),
+            [("bad:host", 1)]
+        );
+    }
+
+    #[test]
+    fn invalid_source_report() {
+        let mut invalid = test_snapshot("2", 1000, &["daily-1"]);
+        invalid.source.user_name = "bad@user".to_string();
+        let snapshots = vec![test_snapshot("1", 1000, &["daily-1"]), invalid];
+
+        let (map, report) = KopiaSnapshots::new_from_snapshots_with_report(snapshots.clone());
+        assert_eq!(map.sources().count(), 1);
+        assert_eq!(report.errors().len(), 1);
+        let error = report.into_result().expect_err("invalid source");
+        assert!(error.to_string().contains("bad@user"), "{error}");
+
+        #[expect(deprecated)]
+        let rejected = KopiaSnapshots::new_from_snapshots(snapshots, Err);
+        assert!(rejected.is_err());
+    }
+
+    #[test]
+    fn truncated_sample_data() {
+        // regression seeds for `fuzz/fuzz_targets/snapshot_parser.rs`: errors, never panics
+        let sample_data = include_str!("sample_kopia-snapshot-list.json").as_bytes();
+        for len in (0..sample_data.len()).step_by(61) {
+            let truncated = &sample_data[..len];
+            assert!(KopiaSnapshots::new_from_reader_with_report(truncated).is_err());
+            let aggregated = KopiaSnapshots::new_from_reader_with_options(
+                truncated,
+                ParseOptions::aggregate_only(crate::LatestSnapshotPolicy::Newest),
+            );
+            assert!(aggregated.is_err());
+        }
+    }
+
+    #[test]
+    fn tolerates_missing_and_unknown_fields() {
+        let json = r#"[
+            {"id":"minimal","source":{"host":"host","userName":"user_name","path":"/path"},
+             "startTime":"2025-08-14T00:00:00Z","endTime":"2025-08-14T00:01:00Z"},
+            {"id":"partial","source":{"host":"host","userName":"user_name","path":"/path"},
+             "startTime":"2025-08-14T01:00:00Z","endTime":"2025-08-14T01:01:00Z",
+             "stats":{"totalSize":1000,"futureCounter":7},"rootEntry":null,
+             "incomplete":"checkpoint","futureField":{"nested":[1,2]}}
+        ]"#;
+        let (map, invalid_sources) =
+            KopiaSnapshots::new_from_reader_with_report(json.as_bytes()).expect("tolerated");
+        assert!(invalid_sources.is_empty());
+        assert!(map.malformed_snapshots().is_empty());
+
+        let source = source_str("user_name@host:/path");
+        let snapshots = map.snapshots_for(&source).expect("present");
+        assert_eq!(snapshots.len(), 2);
+        let (minimal, partial) = (&snapshots[0], &snapshots[1]);
+        assert_eq!(minimal.description, "");
+        assert_eq!(minimal.stats.total_size, 0);
+        assert!(minimal.root_entry.is_none());
+        assert_eq!(partial.stats.total_size, 1000);
+        assert_eq!(partial.stats.error_count, 0);
+        assert!(partial.root_entry.is_none());
+        assert_eq!(partial.failed_files(), 0);
+        assert_eq!(partial.incomplete.as_deref(), Some("checkpoint"));
+    }
+
+    #[test]
+    fn counts_beyond_u32() {
+        let json = r#"[
+            {"id":"huge","source":{"host":"host","userName":"user_name","path":"/path"},
+             "startTime":"2025-08-14T00:00:00Z","endTime":"2025-08-14T00:01:00Z",
+             "stats":{"fileCount":5000000000,"dirCount":4294967296,"errorCount":4294967297},
+             "rootEntry":{"summ":{"files":5000000000,"numFailed":4294967298}}}
+        ]"#;
+        let (map, _) = KopiaSnapshots::new_from_reader_with_report(json.as_bytes()).expect("valid");
+        assert!(map.malformed_snapshots().is_empty());
+
+        let source = source_str("user_name@host:/path");
+        let snapshot = &map.snapshots_for(&source).expect("present")[0];
+        assert_eq!(snapshot.stats.file_count, 5_000_000_000);
+        assert_eq!(snapshot.stats.dir_count, 4_294_967_296);
+        assert_eq!(snapshot.failed_files(), 4_294_967_298);
+
+        let now: jiff::Timestamp = "2025-08-14T01:00:00Z".parse().expect("valid timestamp");
+        let metrics = map.generate_all_metrics(now);
+        for expected in [
+            r#"kopia_snapshot_errors_total{source="user_name@host:/path"} 4294967297"#,
+            r#"kopia_snapshot_failed_files_total{source="user_name@host:/path"} 4294967298"#,
+        ] {
+            assert!(metrics.lines().any(|line| line == expected), "{metrics}");
+        }
+    }
+
     #[test]
     fn parse_sample_data() {
         let sample_data = include_str!("sample_kopia-snapshot-list.json");
         let source = source_str("kopia-system@milton:/persist-home");
 
-        let map = KopiaSnapshots::new_parse_json(sample_data, |e| eyre::bail!(e))
-            .expect("valid snapshot JSON");
+        let (map, invalid_sources) =
+            KopiaSnapshots::new_from_reader_with_report(sample_data.as_bytes())
+                .expect("valid snapshot JSON");
+        assert!(invalid_sources.is_empty());
 
         {
             // inspect parsed snapshots (for single source)
@@ -366,10 +661,14 @@ mod tests {
 
             let latest = snapshots.last().expect("nonempty");
             assert_eq!(latest.id, "c5be996d125abae92340f3a658443b24");
-            assert_eq!(latest.start_time, "2025-08-14T00:00:04.04475167Z");
+            assert_eq!(latest.raw_times.start_time, "2025-08-14T00:00:04.04475167Z");
+            assert_eq!(
+                latest.start_time,
+                "2025-08-14T00:00:04.04475167Z".parse().ok()
+            );
             assert_eq!(latest.stats.total_size, 42_154_950_324);
             assert_eq!(latest.stats.error_count, 0);
-            assert_eq!(latest.root_entry.summ.num_failed, 0);
+            assert_eq!(latest.failed_files(), 0);
         }
 
         let retention_counts = map.get_retention_counts();
@@ -0,0 +1,211 @@
+use super::{SeenSnapshotIds, TimestampIssueCounts};
+use crate::{LatestSnapshotPolicy, Snapshot};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+
+/// Options for parsing a snapshot listing, e.g. for
+/// [`KopiaSnapshots::new_from_reader_with_options`](crate::KopiaSnapshots::new_from_reader_with_options)
+///
+/// With `max_snapshots_per_source`, only the oldest plus the newest N snapshots accepted by
+/// the `latest_policy` are kept for each source. All other snapshots only contribute to the
+/// counts (total, by retention reason, and parse errors), so memory use is proportional to
+/// the number of sources rather than the number of snapshots. Listings of all snapshots
+/// (e.g. [`KopiaSnapshots::snapshots_ndjson_reader`](crate::KopiaSnapshots::snapshots_ndjson_reader))
+/// only include the kept snapshots.
+///
+/// Only the IDs of the kept snapshots are remembered, so a snapshot listed again after
+/// being folded into the counts is counted again, rather than skipped as a
+/// [duplicate](crate::KopiaSnapshots::duplicate_snapshots).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Policy selecting the latest snapshot of each source
+    pub latest_policy: LatestSnapshotPolicy,
+    /// Number of newest snapshots to keep for each source (besides the oldest), if limited
+    pub max_snapshots_per_source: Option<NonZeroUsize>,
+}
+impl ParseOptions {
+    /// Options of aggregate-only mode, keeping only the snapshots needed for the metrics:
+    /// the oldest, and the latest and previous according to the `latest_policy`
+    #[must_use]
+    pub fn aggregate_only(latest_policy: LatestSnapshotPolicy) -> Self {
+        Self {
+            latest_policy,
+            max_snapshots_per_source: Some(Compaction::AGGREGATE_KEEP_NEWEST),
+        }
+    }
+
+    pub(crate) fn compaction(self) -> Option<Compaction> {
+        let Self {
+            latest_policy,
+            max_snapshots_per_source,
+        } = self;
+        max_snapshots_per_source.map(|keep_newest| Compaction {
+            policy: latest_policy,
+            keep_newest,
+        })
+    }
+}
+
+/// Snapshots kept for each source while parsing, the rest are folded into the counts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Compaction {
+    /// Policy selecting the newest snapshots to keep
+    pub policy: LatestSnapshotPolicy,
+    /// Number of newest snapshots to keep, in addition to the oldest
+    pub keep_newest: NonZeroUsize,
+}
+impl Compaction {
+    /// Snapshots kept in aggregate-only mode (latest and previous)
+    pub const AGGREGATE_KEEP_NEWEST: NonZeroUsize = NonZeroUsize::new(2).expect("nonzero");
+}
+
+/// Totals of the snapshots dropped by [`compact`], for count metrics
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FoldedSnapshots {
+    /// Number of dropped snapshots
+    pub count: u64,
+    /// Number of dropped snapshots for each retention reason
+    pub retention_counts: BTreeMap<String, u64>,
+    /// Number of dropped snapshots with an unparseable end time
+    pub timestamp_parse_errors: u64,
+    /// Issues of the start and end times of the dropped snapshots
+    #[serde(default)]
+    pub timestamp_issues: TimestampIssueCounts,
+}
+impl FoldedSnapshots {
+    fn fold(&mut self, snapshot: &Snapshot) {
+        self.count += 1;
+        for reason in &snapshot.retention_reason {
+            *self
+                .retention_counts
+                .entry(reason.as_str().to_owned())
+                .or_insert(0) += 1;
+        }
+        if snapshot.end_time.is_none() {
+            self.timestamp_parse_errors += 1;
+        }
+        for issue in snapshot.timestamp_issues() {
+            self.timestamp_issues.record(issue);
+        }
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        let Self {
+            count,
+            retention_counts,
+            timestamp_parse_errors,
+            timestamp_issues,
+        } = other;
+        self.count += count;
+        for (reason, count) in retention_counts {
+            *self.retention_counts.entry(reason).or_insert(0) += count;
+        }
+        self.timestamp_parse_errors += timestamp_parse_errors;
+        self.timestamp_issues.merge(timestamp_issues);
+    }
+}
+
+/// Drops the snapshots which are not kept by the compaction, folding them into `folded` and
+/// forgetting their IDs from `seen_ids`
+///
+/// Keeps the oldest snapshot (for the oldest age), and the newest snapshots accepted by the
+/// policy (e.g. latest and previous in aggregate-only mode).
+pub(crate) fn compact(
+    snapshots: &mut Vec<Snapshot>,
+    compaction: Compaction,
+    folded: &mut FoldedSnapshots,
+    seen_ids: &mut SeenSnapshotIds,
+) {
+    let Compaction {
+        policy,
+        keep_newest,
+    } = compaction;
+    let keep_newest = keep_newest.get();
+    if snapshots.len() <= keep_newest.saturating_add(1) {
+        return;
+    }
+    let mut newest_remaining = keep_newest;
+    let mut keep = vec![false; snapshots.len()];
+    keep[0] = true;
+    for (index, snapshot) in snapshots.iter().enumerate().skip(1).rev() {
+        if newest_remaining == 0 {
+            break;
+        }
+        if policy.accepts(snapshot) {
+            keep[index] = true;
+            newest_remaining -= 1;
+        }
+    }
+    let mut keep = keep.into_iter();
+    snapshots.retain(|snapshot| {
+        let keep = keep.next().unwrap_or(true);
+        if !keep {
+            folded.fold(snapshot);
+            seen_ids.forget(&snapshot.id);
+        }
+        keep
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compaction, FoldedSnapshots, ParseOptions, SeenSnapshotIds, compact};
+    use crate::{LatestSnapshotPolicy, Snapshot, test_util::test_snapshot};
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn keeps_oldest_and_newest_accepted() {
+        let mut snapshots: Vec<Snapshot> = (1..=5)
+            .map(|id| test_snapshot(&id.to_string(), id * 100, &["daily"]))
+            .map(Snapshot::from)
+            .collect();
+        snapshots[4].incomplete = Some("checkpoint".to_string());
+        let mut seen_ids = SeenSnapshotIds::default();
+        for snapshot in &snapshots {
+            seen_ids.insert(&snapshot.id);
+        }
+
+        let mut folded = FoldedSnapshots::default();
+        compact(
+            &mut snapshots,
+            ParseOptions::aggregate_only(LatestSnapshotPolicy::NewestComplete)
+                .compaction()
+                .expect("limited"),
+            &mut folded,
+            &mut seen_ids,
+        );
+        let ids: Vec<_> = snapshots.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["1", "3", "4"]);
+        assert_eq!(folded.count, 2);
+        assert_eq!(folded.retention_counts["daily"], 2);
+
+        // only the IDs of the kept snapshots are held
+        assert!(!seen_ids.insert("3"));
+        assert!(seen_ids.insert("2"));
+        assert!(seen_ids.insert("5"));
+    }
+
+    #[test]
+    fn keeps_newest_limit() {
+        let mut snapshots: Vec<Snapshot> = (1..=5)
+            .map(|id| test_snapshot(&id.to_string(), id * 100, &["daily"]))
+            .map(Snapshot::from)
+            .collect();
+        let limit = |keep_newest| Compaction {
+            policy: LatestSnapshotPolicy::Newest,
+            keep_newest: NonZeroUsize::new(keep_newest).expect("nonzero"),
+        };
+
+        let mut folded = FoldedSnapshots::default();
+        let mut seen_ids = SeenSnapshotIds::default();
+        compact(&mut snapshots, limit(4), &mut folded, &mut seen_ids);
+        assert_eq!(snapshots.len(), 5);
+        assert_eq!(folded.count, 0);
+
+        compact(&mut snapshots, limit(1), &mut folded, &mut seen_ids);
+        let ids: Vec<_> = snapshots.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["1", "5"]);
+        assert_eq!(folded.count, 3);
+    }
+}
@@ -0,0 +1,202 @@
+use super::KopiaCommand;
+use crate::Error;
+use serde::Serialize;
+use std::io::{Read, Write as _};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Record of one `kopia` invocation, written as a line of the audit log (see
+/// [`KopiaCommand::with_audit_log`])
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditRecord {
+    /// Program and arguments, including the `nice` and `ionice` wrappers
+    pub argv: Vec<String>,
+    /// Names of the environment variables set for the process (values are omitted)
+    pub env_keys: Vec<String>,
+    /// Time the process was started (RFC 3339)
+    pub start_time: String,
+    /// Time the process exited, or was killed (RFC 3339)
+    pub end_time: String,
+    /// Exit code, if the process exited (not killed by a signal)
+    pub exit_code: Option<i32>,
+    /// Whether the process was killed after the timeout
+    pub timed_out: bool,
+    /// Number of bytes read from standard output
+    pub stdout_bytes: u64,
+    /// Number of bytes read from standard error
+    pub stderr_bytes: u64,
+    /// Standard error output, truncated to [`AuditRecord::STDERR_LIMIT`] bytes
+    pub stderr: String,
+    /// Whether `stderr` was truncated
+    pub stderr_truncated: bool,
+}
+impl AuditRecord {
+    /// Maximum length of the recorded standard error output, in bytes
+    pub const STDERR_LIMIT: usize = 4096;
+}
+
+/// Invocation in progress, recorded to the audit log when finished
+pub(crate) struct PendingAudit {
+    path: PathBuf,
+    argv: Vec<String>,
+    env_keys: Vec<String>,
+    start_time: jiff::Timestamp,
+}
+
+/// Result of an invocation, for the audit log
+pub(crate) struct AuditOutcome {
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub stdout_bytes: u64,
+    pub stderr: Vec<u8>,
+}
+
+impl KopiaCommand {
+    /// Starts recording an invocation of the snapshot list command, if an audit log is set
+    pub(crate) fn start_audit(&self) -> Option<PendingAudit> {
+        let path = self.audit_log()?.to_owned();
+        let command = self.snapshot_list();
+        let argv = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let env_keys = command
+            .get_envs()
+            .map(|(key, _)| key.to_string_lossy().into_owned())
+            .collect();
+        Some(PendingAudit {
+            path,
+            argv,
+            env_keys,
+            start_time: jiff::Timestamp::now(),
+        })
+    }
+}
+
+impl PendingAudit {
+    /// Appends the record of the finished invocation to the audit log
+    pub(crate) fn finish(self, outcome: AuditOutcome) -> Result<(), Error> {
+        let Self {
+            path,
+            argv,
+            env_keys,
+            start_time,
+        } = self;
+        let AuditOutcome {
+            exit_code,
+            timed_out,
+            stdout_bytes,
+            stderr,
+        } = outcome;
+        let stderr_truncated = stderr.len() > AuditRecord::STDERR_LIMIT;
+        let record = AuditRecord {
+            argv,
+            env_keys,
+            start_time: start_time.to_string(),
+            end_time: jiff::Timestamp::now().to_string(),
+            exit_code,
+            timed_out,
+            stdout_bytes,
+            stderr_bytes: stderr.len() as u64,
+            stderr: String::from_utf8_lossy(&stderr[..stderr.len().min(AuditRecord::STDERR_LIMIT)])
+                .into_owned(),
+            stderr_truncated,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        // single write, so concurrent invocations append whole lines
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(|source| Error::AuditLog { path, source })
+    }
+
+    /// Appends the record like [`Self::finish`], logging a failure to standard error
+    /// instead of failing the invocation of `kopia`
+    pub(crate) fn finish_logged(self, outcome: AuditOutcome) {
+        if let Err(e) = self.finish(outcome) {
+            match std::error::Error::source(&e) {
+                Some(source) => eprintln!("Error: {e}: {source}"),
+                None => eprintln!("Error: {e}"),
+            }
+        }
+    }
+}
+
+/// Reader counting the bytes read, e.g. from the standard output of `kopia`
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+impl<R> CountingReader<R> {
+    /// Returns the reader and the shared count
+    pub(crate) fn new(inner: R) -> (Self, Arc<AtomicU64>) {
+        let count = Arc::new(AtomicU64::new(0));
+        let reader = Self {
+            inner,
+            count: Arc::clone(&count),
+        };
+        (reader, count)
+    }
+}
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count.fetch_add(len as u64, Ordering::Relaxed);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditOutcome;
+    use crate::kopia::KopiaCommand;
+
+    #[test]
+    fn appends_records() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("audit.jsonl");
+        let command = KopiaCommand::new("kopia")
+            .with_nice(5)
+            .with_env("KOPIA_PASSWORD", "secret")
+            .with_audit_log(&path);
+        assert!(KopiaCommand::new("kopia").start_audit().is_none());
+
+        for stderr in [&b"warning\n"[..], &[b'x'; 5000]] {
+            let pending = command.start_audit().expect("audit log set");
+            pending
+                .finish(AuditOutcome {
+                    exit_code: Some(0),
+                    timed_out: false,
+                    stdout_bytes: 123,
+                    stderr: stderr.to_vec(),
+                })
+                .expect("writable");
+        }
+
+        let log = std::fs::read_to_string(&path).expect("log written");
+        assert!(!log.contains("secret"), "{log}");
+        let records: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("valid JSON line"))
+            .collect();
+        assert_eq!(records.len(), 2);
+        let first = &records[0];
+        assert_eq!(
+            first["argv"],
+            serde_json::json!(["nice", "-n", "5", "kopia", "snapshot", "list", "--json"])
+        );
+        assert_eq!(first["env_keys"], serde_json::json!(["KOPIA_PASSWORD"]));
+        assert_eq!(first["exit_code"], 0);
+        assert_eq!(first["stdout_bytes"], 123);
+        assert_eq!(first["stderr"], "warning\n");
+        assert_eq!(first["stderr_truncated"], false);
+        let second = &records[1];
+        assert_eq!(second["stderr_bytes"], 5000);
+        assert_eq!(second["stderr"].as_str().map(str::len), Some(4096));
+        assert_eq!(second["stderr_truncated"], true);
+    }
+}
@@ -0,0 +1,224 @@
+use super::{RootEntry, SnapshotJson, Source, Stats, Summary};
+
+impl SnapshotJson {
+    /// Returns a builder for a complete snapshot, for constructing snapshot data
+    /// programmatically (e.g. in tests, or to synthesize a snapshot listing)
+    ///
+    /// ```
+    /// use kopia_exporter::{KopiaSnapshots, SnapshotJson, kopia::Source};
+    ///
+    /// let snapshot = SnapshotJson::builder()
+    ///     .id("abc123")
+    ///     .source(Source {
+    ///         host: "host".to_string(),
+    ///         user_name: "alice".to_string(),
+    ///         path: "/home".to_string(),
+    ///     })
+    ///     .end_time("2025-08-14T00:01:00Z".parse().expect("valid timestamp"))
+    ///     .total_size(1024)
+    ///     .retention_reasons(&["latest-1", "daily-1"])
+    ///     .build();
+    /// assert_eq!(snapshot.stats.total_size, 1024);
+    ///
+    /// let (snapshots, invalid_sources) =
+    ///     KopiaSnapshots::new_from_snapshots_with_report(vec![snapshot]);
+    /// assert!(invalid_sources.is_empty());
+    /// assert_eq!(snapshots.sources().count(), 1);
+    /// ```
+    pub fn builder() -> SnapshotJsonBuilder {
+        SnapshotJsonBuilder::default()
+    }
+}
+
+/// Builder for a [`SnapshotJson`], see [`SnapshotJson::builder`]
+///
+/// Defaults to a complete snapshot of `user_name@host:/path` with no content, starting
+/// at `2025-08-14T00:00:00Z` and ending one minute later, with zero errors.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct SnapshotJsonBuilder(SnapshotJson);
+impl Default for SnapshotJsonBuilder {
+    fn default() -> Self {
+        Self(SnapshotJson {
+            id: String::new(),
+            source: Source {
+                host: "host".to_string(),
+                user_name: "user_name".to_string(),
+                path: "/path".to_string(),
+            },
+            description: String::new(),
+            start_time: "2025-08-14T00:00:00Z".to_string(),
+            end_time: "2025-08-14T00:01:00Z".to_string(),
+            stats: Stats {
+                total_size: 0,
+                excluded_total_size: 0,
+                file_count: 0,
+                cached_files: 0,
+                non_cached_files: 0,
+                dir_count: 0,
+                excluded_file_count: 0,
+                excluded_dir_count: 0,
+                ignored_error_count: 0,
+                error_count: 0,
+            },
+            root_entry: Some(RootEntry {
+                name: String::new(),
+                entry_type: "d".to_string(),
+                mode: "0755".to_string(),
+                mtime: "2025-08-14T00:00:00Z".to_string(),
+                obj: String::new(),
+                summ: Summary {
+                    size: 0,
+                    files: 0,
+                    symlinks: 0,
+                    dirs: 0,
+                    max_time: "2025-08-14T00:00:00Z".to_string(),
+                    num_failed: 0,
+                },
+            }),
+            retention_reason: vec![],
+            incomplete: None,
+        })
+    }
+}
+impl SnapshotJsonBuilder {
+    /// Sets the snapshot ID, and the root object ID derived from it
+    pub fn id(mut self, id: &str) -> Self {
+        let Self(snapshot) = &mut self;
+        snapshot.id = id.to_string();
+        if let Some(root_entry) = &mut snapshot.root_entry {
+            root_entry.obj = format!("obj{id}");
+        }
+        self
+    }
+
+    /// Sets the source
+    pub fn source(mut self, source: Source) -> Self {
+        self.0.source = source;
+        self
+    }
+
+    /// Sets the description
+    pub fn description(mut self, description: &str) -> Self {
+        self.0.description = description.to_string();
+        self
+    }
+
+    /// Sets the start time
+    pub fn start_time(mut self, start_time: jiff::Timestamp) -> Self {
+        self.0.start_time = start_time.to_string();
+        self
+    }
+
+    /// Sets the end time
+    pub fn end_time(mut self, end_time: jiff::Timestamp) -> Self {
+        self.0.end_time = end_time.to_string();
+        self
+    }
+
+    /// Sets the total size, of both the snapshot and the root entry
+    pub fn total_size(mut self, total_size: u64) -> Self {
+        let Self(snapshot) = &mut self;
+        snapshot.stats.total_size = total_size;
+        self.summary_mut().size = total_size;
+        self
+    }
+
+    /// Sets the total size of the excluded files
+    pub fn excluded_total_size(mut self, excluded_total_size: u64) -> Self {
+        self.0.stats.excluded_total_size = excluded_total_size;
+        self
+    }
+
+    /// Sets the file count, of both the snapshot and the root entry (split evenly between
+    /// cached and non-cached files)
+    pub fn file_count(mut self, file_count: u64) -> Self {
+        let Self(snapshot) = &mut self;
+        snapshot.stats.file_count = file_count;
+        snapshot.stats.cached_files = file_count / 2;
+        snapshot.stats.non_cached_files = file_count - file_count / 2;
+        self.summary_mut().files = file_count;
+        self
+    }
+
+    /// Sets the directory count, of both the snapshot and the root entry
+    pub fn dir_count(mut self, dir_count: u64) -> Self {
+        let Self(snapshot) = &mut self;
+        snapshot.stats.dir_count = dir_count;
+        self.summary_mut().dirs = dir_count;
+        self
+    }
+
+    /// Sets the error count
+    pub fn error_count(mut self, error_count: u64) -> Self {
+        self.0.stats.error_count = error_count;
+        self
+    }
+
+    /// Sets the ignored error count
+    pub fn ignored_error_count(mut self, ignored_error_count: u64) -> Self {
+        self.0.stats.ignored_error_count = ignored_error_count;
+        self
+    }
+
+    /// Sets the count of files which failed, in the root entry
+    pub fn failed_files(mut self, num_failed: u64) -> Self {
+        self.summary_mut().num_failed = num_failed;
+        self
+    }
+
+    /// Sets the retention reasons (e.g. `"latest-1"`, `"daily-2"`)
+    pub fn retention_reasons(mut self, retention_reasons: &[&str]) -> Self {
+        self.0.retention_reason = retention_reasons.iter().map(ToString::to_string).collect();
+        self
+    }
+
+    /// Marks the snapshot incomplete for the reason (e.g. `"checkpoint"`)
+    pub fn incomplete(mut self, reason: &str) -> Self {
+        self.0.incomplete = Some(reason.to_string());
+        self
+    }
+
+    /// Removes the root entry, as for some incomplete snapshots
+    pub fn without_root_entry(mut self) -> Self {
+        self.0.root_entry = None;
+        self
+    }
+
+    /// Returns the summary of the root entry, adding a default root entry if removed
+    fn summary_mut(&mut self) -> &mut Summary {
+        let Self(snapshot) = self;
+        &mut snapshot
+            .root_entry
+            .get_or_insert_with(RootEntry::default)
+            .summ
+    }
+
+    /// Returns the built snapshot
+    #[must_use]
+    pub fn build(self) -> SnapshotJson {
+        let Self(snapshot) = self;
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SnapshotJson, kopia::Snapshot};
+
+    #[test]
+    fn parsed_fields() {
+        let end_time: jiff::Timestamp = "2025-09-01T12:00:00Z".parse().expect("valid timestamp");
+        let snapshot: Snapshot = SnapshotJson::builder()
+            .end_time(end_time)
+            .error_count(2)
+            .failed_files(3)
+            .incomplete("checkpoint")
+            .build()
+            .into();
+        assert_eq!(snapshot.end_time, Some(end_time));
+        assert_eq!(snapshot.stats.error_count, 2);
+        assert_eq!(snapshot.failed_files(), 3);
+        assert_eq!(snapshot.incomplete.as_deref(), Some("checkpoint"));
+    }
+}
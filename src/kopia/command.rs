@@ -0,0 +1,333 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Command line used to run `kopia`, optionally at a lower CPU and I/O priority
+///
+/// Priorities are lowered by wrapping the command in `nice` and `ionice` (which must be
+/// in `PATH`), so a metrics refresh does not compete with a running backup.
+#[derive(Clone, PartialEq, Eq)]
+pub struct KopiaCommand {
+    kopia_bin: String,
+    nice: Option<i8>,
+    ionice_idle: bool,
+    env: Vec<(String, String)>,
+    audit_log: Option<PathBuf>,
+    stderr_limit: usize,
+}
+impl KopiaCommand {
+    /// Default for [`Self::with_stderr_limit`], in bytes
+    pub const DEFAULT_STDERR_LIMIT: usize = 4096;
+
+    /// Creates a command running the specified `kopia` binary
+    #[must_use]
+    pub fn new(kopia_bin: impl Into<String>) -> Self {
+        Self {
+            kopia_bin: kopia_bin.into(),
+            nice: None,
+            ionice_idle: false,
+            env: vec![],
+            audit_log: None,
+            stderr_limit: Self::DEFAULT_STDERR_LIMIT,
+        }
+    }
+
+    /// Runs `kopia` with the specified niceness adjustment (see `nice -n`)
+    #[must_use]
+    pub fn with_nice(mut self, adjustment: i8) -> Self {
+        self.nice = Some(adjustment);
+        self
+    }
+
+    /// Runs `kopia` in the idle I/O scheduling class (see `ionice -c 3`)
+    #[must_use]
+    pub fn with_ionice_idle(mut self) -> Self {
+        self.ionice_idle = true;
+        self
+    }
+
+    /// Sets an environment variable for the `kopia` process (e.g. `KOPIA_LOG_DIR`),
+    /// overriding the inherited environment and any previous value for the `key`
+    #[must_use]
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Appends a JSON line to the file for each invocation of `kopia`, see
+    /// [`AuditRecord`](super::AuditRecord)
+    ///
+    /// Failing to write the record is logged to standard error as
+    /// [`Error::AuditLog`](crate::Error::AuditLog), without failing the invocation.
+    #[must_use]
+    pub fn with_audit_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audit_log = Some(path.into());
+        self
+    }
+
+    /// Stops appending to the audit log set by [`Self::with_audit_log`], e.g. for a trial run
+    #[must_use]
+    pub fn without_audit_log(mut self) -> Self {
+        self.audit_log = None;
+        self
+    }
+
+    /// Truncates the standard error output of `kopia` to `bytes`, marked with `(truncated)`,
+    /// in errors and [`KopiaSnapshots::kopia_stderr`](crate::KopiaSnapshots::kopia_stderr)
+    #[must_use]
+    pub fn with_stderr_limit(mut self, bytes: usize) -> Self {
+        self.stderr_limit = bytes;
+        self
+    }
+
+    /// Converts the standard error output to text, truncated to the limit
+    pub(crate) fn stderr_text(&self, stderr: &[u8]) -> String {
+        let text = String::from_utf8_lossy(stderr);
+        if text.len() <= self.stderr_limit {
+            return text.into_owned();
+        }
+        let end = text.floor_char_boundary(self.stderr_limit);
+        format!("{} (truncated)", &text[..end])
+    }
+
+    /// Returns the path of the audit log, if set
+    #[must_use]
+    pub fn audit_log(&self) -> Option<&Path> {
+        self.audit_log.as_deref()
+    }
+
+    /// Resolves each program run (`ionice`, `nice` and the `kopia` binary) to its path,
+    /// searching `PATH` for names without a `/`, to detect a missing program before running
+    /// the command
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`NotFound`](std::io::ErrorKind::NotFound) error naming the first program
+    /// which is not an executable file
+    pub fn resolve_programs(&self) -> std::io::Result<Vec<PathBuf>> {
+        let Self {
+            kopia_bin,
+            nice,
+            ionice_idle,
+            env: _,
+            audit_log: _,
+            stderr_limit: _,
+        } = self;
+        let ionice = ionice_idle.then_some("ionice");
+        let nice = nice.map(|_| "nice");
+        [ionice, nice, Some(kopia_bin.as_str())]
+            .into_iter()
+            .flatten()
+            .map(|program| {
+                let candidates: Vec<PathBuf> = if program.contains('/') {
+                    vec![PathBuf::from(program)]
+                } else {
+                    std::env::var_os("PATH")
+                        .map(|path| {
+                            std::env::split_paths(&path)
+                                .map(|dir| dir.join(program))
+                                .collect()
+                        })
+                        .unwrap_or_default()
+                };
+                candidates
+                    .into_iter()
+                    .find(|candidate| is_executable(candidate))
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            format!("program not found or not executable: {program:?}"),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the command listing all snapshots as JSON
+    pub(crate) fn snapshot_list(&self) -> Command {
+        // each wrapper execs the next program, so killing the child kills `kopia`
+        let program = self.programs();
+        let (bin, wrapper_args) = program.split_first().expect("nonempty");
+        let mut command = Command::new(bin);
+        // join the current trace, for `kopia` builds (or wrappers) reading the W3C context
+        // from the environment, overridden by any configured `TRACEPARENT`
+        #[cfg(feature = "otel")]
+        if let Some(context) = crate::trace::current() {
+            command.env("TRACEPARENT", context.traceparent());
+        }
+        command
+            .args(wrapper_args)
+            .args(["snapshot", "list", "--json"])
+            .envs(self.env.iter().map(|(key, value)| (key, value)));
+        command
+    }
+
+    /// Returns the wrapper programs (with their arguments) followed by the `kopia` binary
+    fn programs(&self) -> Vec<String> {
+        let Self {
+            kopia_bin,
+            nice,
+            ionice_idle,
+            env: _,
+            audit_log: _,
+            stderr_limit: _,
+        } = self;
+        let mut program: Vec<String> = vec![];
+        if *ionice_idle {
+            program.extend(["ionice".to_owned(), "-c".to_owned(), "3".to_owned()]);
+        }
+        if let Some(adjustment) = nice {
+            program.extend(["nice".to_owned(), "-n".to_owned(), adjustment.to_string()]);
+        }
+        program.push(kopia_bin.clone());
+        program
+    }
+}
+
+/// Omits the environment values, which may contain secrets (e.g. `KOPIA_PASSWORD`)
+impl fmt::Debug for KopiaCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            kopia_bin,
+            nice,
+            ionice_idle,
+            env,
+            audit_log,
+            stderr_limit,
+        } = self;
+        let env_keys: Vec<&str> = env.iter().map(|(key, _)| key.as_str()).collect();
+        f.debug_struct("KopiaCommand")
+            .field("kopia_bin", kopia_bin)
+            .field("nice", nice)
+            .field("ionice_idle", ionice_idle)
+            .field("env_keys", &env_keys)
+            .field("audit_log", audit_log)
+            .field("stderr_limit", stderr_limit)
+            .finish()
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt as _;
+
+    std::fs::metadata(path).is_ok_and(|metadata| {
+        #[cfg(unix)]
+        let executable = metadata.permissions().mode() & 0o111 != 0;
+        #[cfg(not(unix))]
+        let executable = true;
+        metadata.is_file() && executable
+    })
+}
+impl From<&str> for KopiaCommand {
+    fn from(kopia_bin: &str) -> Self {
+        Self::new(kopia_bin)
+    }
+}
+impl From<String> for KopiaCommand {
+    fn from(kopia_bin: String) -> Self {
+        Self::new(kopia_bin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KopiaCommand;
+
+    fn command_line(command: &KopiaCommand) -> Vec<String> {
+        let command = command.snapshot_list();
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn plain() {
+        let command = KopiaCommand::new("kopia");
+        assert_eq!(
+            command_line(&command),
+            ["kopia", "snapshot", "list", "--json"]
+        );
+    }
+
+    #[test]
+    fn nice_and_ionice() {
+        let command = KopiaCommand::new("/bin/kopia")
+            .with_nice(10)
+            .with_ionice_idle();
+        assert_eq!(
+            command_line(&command),
+            [
+                "ionice",
+                "-c",
+                "3",
+                "nice",
+                "-n",
+                "10",
+                "/bin/kopia",
+                "snapshot",
+                "list",
+                "--json"
+            ]
+        );
+    }
+
+    #[test]
+    fn env() {
+        let command = KopiaCommand::new("kopia")
+            .with_env("KOPIA_CHECK_FOR_UPDATES", "false")
+            .with_env("KOPIA_PASSWORD", "secret")
+            .with_env("KOPIA_CHECK_FOR_UPDATES", "true");
+        let snapshot_list = command.snapshot_list();
+        let envs: Vec<_> = snapshot_list.get_envs().collect();
+        assert_eq!(
+            envs,
+            [
+                ("KOPIA_CHECK_FOR_UPDATES".as_ref(), Some("true".as_ref())),
+                ("KOPIA_PASSWORD".as_ref(), Some("secret".as_ref())),
+            ]
+        );
+
+        let debug = format!("{command:?}");
+        assert!(debug.contains("KOPIA_PASSWORD"), "{debug}");
+        assert!(!debug.contains("secret"), "{debug}");
+    }
+
+    #[test]
+    fn stderr_truncated() {
+        let command = KopiaCommand::new("kopia").with_stderr_limit(8);
+        assert_eq!(command.stderr_text(b"warning\n"), "warning\n");
+        assert_eq!(
+            command.stderr_text(b"ERROR something failed"),
+            "ERROR so (truncated)"
+        );
+        // within the limit of a character
+        assert_eq!(
+            command.stderr_text("ERROR\u{1F980} crab".as_bytes()),
+            "ERROR (truncated)"
+        );
+        assert_eq!(
+            command.stderr_text(b"\xff\xfe"),
+            "\u{FFFD}\u{FFFD}",
+            "lossy UTF-8"
+        );
+    }
+
+    #[test]
+    fn resolve_programs() {
+        let resolved = KopiaCommand::new("sh")
+            .with_nice(10)
+            .resolve_programs()
+            .expect("found in PATH");
+        assert_eq!(resolved.len(), 2);
+        assert!(resolved[0].ends_with("nice"), "{resolved:?}");
+        assert!(resolved[1].ends_with("sh"), "{resolved:?}");
+
+        let error = KopiaCommand::new("/nonexistent/kopia")
+            .resolve_programs()
+            .expect_err("missing");
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("/nonexistent/kopia"), "{error}");
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::{BTreeMap, HashSet};
+
+/// IDs of the snapshots inserted into [`KopiaSnapshots`](crate::KopiaSnapshots), for skipping
+/// snapshots listed more than once (e.g. fetching the same repository twice)
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SeenSnapshotIds {
+    ids: HashSet<String>,
+    /// Number of skipped duplicates for each ID
+    duplicates: BTreeMap<String, u64>,
+}
+impl SeenSnapshotIds {
+    /// Starts with the previously skipped duplicates (e.g. deserialized)
+    pub fn with_duplicates(duplicates: BTreeMap<String, u64>) -> Self {
+        Self {
+            ids: HashSet::new(),
+            duplicates,
+        }
+    }
+
+    /// Returns `true` if the ID was not seen before, otherwise counts a duplicate
+    pub fn insert(&mut self, id: &str) -> bool {
+        if self.ids.contains(id) {
+            let count = self.duplicates.entry(id.to_owned()).or_default();
+            *count = count.saturating_add(1);
+            false
+        } else {
+            self.ids.insert(id.to_owned());
+            true
+        }
+    }
+
+    /// Forgets the ID of a snapshot folded away by compaction, so the IDs held do not grow
+    /// with the number of folded snapshots
+    ///
+    /// A later duplicate of the snapshot is no longer detected.
+    pub fn forget(&mut self, id: &str) {
+        self.ids.remove(id);
+    }
+
+    /// Adds the IDs and duplicates of `other`, without counting the IDs seen by both
+    ///
+    /// Call [`Self::insert`] for the snapshots of `other` first, to count (and skip) those.
+    pub fn merge(&mut self, other: Self) {
+        let Self { ids, duplicates } = other;
+        self.ids.extend(ids);
+        for (id, count) in duplicates {
+            let total = self.duplicates.entry(id).or_default();
+            *total = total.saturating_add(count);
+        }
+    }
+
+    /// Returns the number of skipped duplicates for each ID
+    pub fn duplicates(&self) -> &BTreeMap<String, u64> {
+        &self.duplicates
+    }
+
+    /// Returns the total number of skipped duplicates
+    pub fn duplicate_count(&self) -> u64 {
+        self.duplicates
+            .values()
+            .fold(0, |total, &count| total.saturating_add(count))
+    }
+}
@@ -0,0 +1,120 @@
+use crate::SourceStrError;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Counts of snapshots skipped due to an invalid source, by the invalid component
+///
+/// See [`KopiaSnapshots::invalid_sources`](crate::KopiaSnapshots::invalid_sources)
+#[derive(Clone, Copy, Debug)]
+pub struct InvalidSources<'a> {
+    user_names: &'a BTreeMap<String, u64>,
+    hosts: &'a BTreeMap<String, u64>,
+    paths: &'a BTreeMap<String, u64>,
+}
+impl<'a> InvalidSources<'a> {
+    pub(crate) fn new(
+        user_names: &'a BTreeMap<String, u64>,
+        hosts: &'a BTreeMap<String, u64>,
+        paths: &'a BTreeMap<String, u64>,
+    ) -> Self {
+        Self {
+            user_names,
+            hosts,
+            paths,
+        }
+    }
+
+    /// Iterates the invalid user names, with the number of snapshots for each
+    pub fn user_names(&self) -> impl Iterator<Item = (&'a str, u64)> + use<'a> {
+        self.user_names
+            .iter()
+            .map(|(user_name, count)| (user_name.as_str(), *count))
+    }
+
+    /// Iterates the invalid hosts, with the number of snapshots for each
+    pub fn hosts(&self) -> impl Iterator<Item = (&'a str, u64)> + use<'a> {
+        self.hosts
+            .iter()
+            .map(|(host, count)| (host.as_str(), *count))
+    }
+
+    /// Iterates the invalid paths, with the number of snapshots for each
+    pub fn paths(&self) -> impl Iterator<Item = (&'a str, u64)> + use<'a> {
+        self.paths
+            .iter()
+            .map(|(path, count)| (path.as_str(), *count))
+    }
+
+    /// Returns `true` if no snapshots were skipped
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.user_names.is_empty() && self.hosts.is_empty() && self.paths.is_empty()
+    }
+}
+
+/// Snapshots skipped while parsing due to an invalid source, in the order encountered
+///
+/// Returned by the `*_with_report` constructors of
+/// [`KopiaSnapshots`](crate::KopiaSnapshots), which skip invalid sources rather than failing.
+/// The counts are also available from
+/// [`KopiaSnapshots::invalid_sources`](crate::KopiaSnapshots::invalid_sources).
+#[derive(Debug, Default)]
+pub struct InvalidSourceReport(Vec<SourceStrError>);
+impl InvalidSourceReport {
+    /// Returns the errors for each skipped snapshot
+    #[must_use]
+    pub fn errors(&self) -> &[SourceStrError] {
+        let Self(errors) = self;
+        errors
+    }
+
+    /// Returns `true` if no snapshots were skipped
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        let Self(errors) = self;
+        errors.is_empty()
+    }
+
+    /// Returns an error for the first skipped snapshot, if any
+    ///
+    /// # Errors
+    /// Returns the first error, for callers requiring all sources to be valid
+    pub fn into_result(self) -> Result<(), SourceStrError> {
+        let Self(errors) = self;
+        errors.into_iter().next().map_or(Ok(()), Err)
+    }
+}
+impl IntoIterator for InvalidSourceReport {
+    type Item = SourceStrError;
+    type IntoIter = std::vec::IntoIter<SourceStrError>;
+    fn into_iter(self) -> Self::IntoIter {
+        let Self(errors) = self;
+        errors.into_iter()
+    }
+}
+
+/// Collects the invalid source errors into an [`InvalidSourceReport`], from the
+/// `invalid_source_fn` of the constructors (possibly on another thread)
+#[derive(Clone, Default)]
+pub(crate) struct ReportCollector(Arc<Mutex<Vec<SourceStrError>>>);
+impl ReportCollector {
+    /// Returns the `invalid_source_fn` recording each error, and skipping the snapshot
+    pub fn invalid_source_fn(
+        &self,
+    ) -> impl Fn(SourceStrError) -> Result<(), SourceStrError> + Send + 'static {
+        let Self(errors) = self.clone();
+        move |error| {
+            errors
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(error);
+            Ok(())
+        }
+    }
+
+    pub fn finish(self) -> InvalidSourceReport {
+        let Self(errors) = self;
+        let errors = std::mem::take(&mut *errors.lock().unwrap_or_else(PoisonError::into_inner));
+        InvalidSourceReport(errors)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::Snapshot;
+
+/// Policy for selecting which snapshot counts as the "latest" for each source
+///
+/// Used by all metrics describing the latest snapshot (age, size, errors, etc.)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LatestSnapshotPolicy {
+    /// Newest snapshot, regardless of completion or errors
+    #[default]
+    Newest,
+    /// Newest snapshot which completed (not marked incomplete, with a valid end time)
+    NewestComplete,
+    /// Newest complete snapshot with zero errors and zero failed files
+    NewestWithoutErrors,
+}
+impl LatestSnapshotPolicy {
+    const ALL: &[Self] = &[
+        Self::Newest,
+        Self::NewestComplete,
+        Self::NewestWithoutErrors,
+    ];
+
+    /// Returns the name used for parsing and display
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::NewestComplete => "newest-complete",
+            Self::NewestWithoutErrors => "newest-without-errors",
+        }
+    }
+
+    /// Returns `true` if the snapshot is eligible to be the "latest" snapshot
+    #[must_use]
+    pub fn accepts(self, snapshot: &Snapshot) -> bool {
+        let is_complete = || snapshot.incomplete.is_none() && snapshot.end_time.is_some();
+        let is_without_errors = || snapshot.stats.error_count == 0 && snapshot.failed_files() == 0;
+        match self {
+            Self::Newest => true,
+            Self::NewestComplete => is_complete(),
+            Self::NewestWithoutErrors => is_complete() && is_without_errors(),
+        }
+    }
+
+    /// Iterates the accepted snapshots, newest first
+    pub fn iter_newest_first(self, snapshots: &[Snapshot]) -> impl Iterator<Item = &Snapshot> {
+        snapshots
+            .iter()
+            .rev()
+            .filter(move |snapshot| self.accepts(snapshot))
+    }
+
+    /// Returns the "latest" snapshot, according to the policy
+    #[must_use]
+    pub fn select_latest(self, snapshots: &[Snapshot]) -> Option<&Snapshot> {
+        self.iter_newest_first(snapshots).next()
+    }
+}
+impl std::fmt::Display for LatestSnapshotPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+impl std::str::FromStr for LatestSnapshotPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| {
+                let expected: Vec<_> = Self::ALL.iter().map(|policy| policy.name()).collect();
+                format!(
+                    "invalid latest snapshot policy {s:?}, expected one of: {}",
+                    expected.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LatestSnapshotPolicy;
+    use crate::{Snapshot, test_util::test_snapshot};
+
+    fn snapshots() -> Vec<Snapshot> {
+        let good = test_snapshot("good", 1000, &[]);
+        let mut failed = test_snapshot("failed", 2000, &[]);
+        failed.stats.error_count = 2;
+        let mut incomplete = test_snapshot("incomplete", 3000, &[]);
+        incomplete.incomplete = Some("checkpoint".to_string());
+        vec![good, failed, incomplete]
+            .into_iter()
+            .map(Snapshot::from)
+            .collect()
+    }
+
+    #[test]
+    fn select_latest() {
+        let snapshots = snapshots();
+        for (policy, expected_id) in [
+            (LatestSnapshotPolicy::Newest, "incomplete"),
+            (LatestSnapshotPolicy::NewestComplete, "failed"),
+            (LatestSnapshotPolicy::NewestWithoutErrors, "good"),
+        ] {
+            let latest = policy.select_latest(&snapshots).expect("nonempty");
+            assert_eq!(latest.id, expected_id, "policy {policy}");
+        }
+    }
+
+    #[test]
+    fn select_latest_none_accepted() {
+        let snapshots: Vec<_> = snapshots().into_iter().skip(1).collect();
+        let latest = LatestSnapshotPolicy::NewestWithoutErrors.select_latest(&snapshots);
+        assert!(latest.is_none());
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        for policy in LatestSnapshotPolicy::ALL {
+            let parsed: LatestSnapshotPolicy = policy.to_string().parse().expect("valid");
+            assert_eq!(parsed, *policy);
+        }
+        let err = "oldest"
+            .parse::<LatestSnapshotPolicy>()
+            .expect_err("invalid");
+        assert!(err.contains("newest-complete"), "{err}");
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Element of the snapshot list which is valid JSON, but not a [`SnapshotJson`](super::SnapshotJson)
+#[derive(Debug)]
+pub(crate) struct MalformedSnapshot {
+    /// Index in the snapshot list
+    pub index: usize,
+    pub error: serde_json::Error,
+}
+impl fmt::Display for MalformedSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { index, error } = self;
+        write!(f, "snapshot {index}: {error}")
+    }
+}
+
+/// Count of snapshots skipped while parsing because they did not match the expected
+/// structure (e.g. a field of the wrong type), with the first error
+///
+/// See [`KopiaSnapshots::malformed_snapshots`](crate::KopiaSnapshots::malformed_snapshots)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MalformedSnapshots {
+    count: u64,
+    first_error: Option<String>,
+}
+impl MalformedSnapshots {
+    pub(crate) fn record(&mut self, malformed: &MalformedSnapshot) {
+        self.count = self.count.saturating_add(1);
+        self.first_error
+            .get_or_insert_with(|| malformed.to_string());
+    }
+
+    pub(crate) fn merge(&mut self, other: Self) {
+        let Self { count, first_error } = other;
+        self.count = self.count.saturating_add(count);
+        self.first_error = self.first_error.take().or(first_error);
+    }
+
+    /// Returns the number of skipped snapshots
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the error of the first skipped snapshot, e.g.
+    /// `"snapshot 3: invalid type: string \"12\", expected u64 at line 1 column 140"`
+    #[must_use]
+    pub fn first_error(&self) -> Option<&str> {
+        self.first_error.as_deref()
+    }
+
+    /// Returns `true` if no snapshots were skipped
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{KopiaSnapshots, Snapshot};
+use std::io;
+
+/// Reader streaming all snapshots as newline-delimited JSON
+///
+/// Serializes one snapshot at a time, so the output is never buffered in full.
+pub struct SnapshotsNdjsonReader<'a> {
+    snapshots: Box<dyn Iterator<Item = &'a Snapshot> + 'a>,
+    line: Vec<u8>,
+    position: usize,
+}
+
+impl io::Read for SnapshotsNdjsonReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.line.len() {
+            let Some(snapshot) = self.snapshots.next() else {
+                return Ok(0);
+            };
+            self.line.clear();
+            self.position = 0;
+            serde_json::to_writer(&mut self.line, snapshot).map_err(io::Error::other)?;
+            self.line.push(b'\n');
+        }
+        let remaining = &self.line[self.position..];
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl KopiaSnapshots {
+    /// Returns a reader streaming all snapshots as newline-delimited JSON (one object per line),
+    /// ordered by source then by time
+    #[must_use]
+    pub fn snapshots_ndjson_reader(&self) -> SnapshotsNdjsonReader<'_> {
+        SnapshotsNdjsonReader {
+            snapshots: Box::new(
+                self.snapshots_map
+                    .iter()
+                    .flat_map(|(_, snapshots)| snapshots),
+            ),
+            line: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{multi_map, test_snapshot};
+    use std::io::Read as _;
+
+    #[test]
+    fn stream_lines() {
+        let (map, _sources) = multi_map(vec![
+            (
+                "alice",
+                "host",
+                "/a",
+                vec![test_snapshot("a1", 100, &[]), test_snapshot("a2", 200, &[])],
+            ),
+            ("bob", "host", "/b", vec![test_snapshot("b1", 300, &[])]),
+        ]);
+
+        let mut output = String::new();
+        map.snapshots_ndjson_reader()
+            .read_to_string(&mut output)
+            .expect("serializable");
+
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("valid JSON line"))
+            .collect();
+        let ids: Vec<_> = lines.iter().map(|line| line["id"].as_str()).collect();
+        assert_eq!(ids, [Some("a1"), Some("a2"), Some("b1")]);
+        assert_eq!(lines[2]["source"]["userName"], "bob");
+        assert_eq!(lines[2]["stats"]["totalSize"], 300);
+        assert_eq!(lines[2]["endTime"], "2025-08-14T00:01:00Z");
+        assert!(output.ends_with('\n'));
+    }
+
+    #[test]
+    fn small_reads() {
+        let (map, _sources) = multi_map(vec![(
+            "alice",
+            "host",
+            "/a",
+            vec![test_snapshot("a1", 100, &[])],
+        )]);
+
+        let mut expected = String::new();
+        map.snapshots_ndjson_reader()
+            .read_to_string(&mut expected)
+            .expect("serializable");
+
+        let mut reader = map.snapshots_ndjson_reader();
+        let mut output = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            let len = reader.read(&mut buf).expect("serializable");
+            if len == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(String::from_utf8(output).expect("UTF-8"), expected);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+/// Reason `kopia` retains a snapshot, e.g. `daily-3` for the third newest daily snapshot
+///
+/// The raw string is preserved, with the class (e.g. `daily`) and slot (e.g. `3`) parsed
+/// from the `CLASS-SLOT` form when possible.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RetentionReason {
+    raw: String,
+    /// Length of the class prefix and the slot number, if in the `CLASS-SLOT` form
+    class_slot: Option<(usize, u32)>,
+}
+impl RetentionReason {
+    /// Parses the raw retention reason, which may be in any form
+    #[must_use]
+    pub fn new(raw: String) -> Self {
+        let class_slot = raw.rsplit_once('-').and_then(|(class, slot)| {
+            let slot = slot.parse().ok()?;
+            (!class.is_empty()).then_some((class.len(), slot))
+        });
+        Self { raw, class_slot }
+    }
+
+    /// Returns the raw retention reason reported by `kopia`
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Returns the retention class (e.g. `latest`, `daily`), or the raw reason if not in
+    /// the `CLASS-SLOT` form
+    #[must_use]
+    pub fn class(&self) -> &str {
+        match self.class_slot {
+            Some((class_len, _)) => &self.raw[..class_len],
+            None => &self.raw,
+        }
+    }
+
+    /// Returns the slot within the class (e.g. `3` for `daily-3`), if present
+    #[must_use]
+    pub fn slot(&self) -> Option<u32> {
+        self.class_slot.map(|(_, slot)| slot)
+    }
+}
+impl From<String> for RetentionReason {
+    fn from(raw: String) -> Self {
+        Self::new(raw)
+    }
+}
+impl fmt::Display for RetentionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+impl serde::Serialize for RetentionReason {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetentionReason;
+
+    #[test]
+    fn class_and_slot() {
+        for (raw, class, slot) in [
+            ("daily-3", "daily", Some(3)),
+            ("latest-10", "latest", Some(10)),
+            ("annual-1", "annual", Some(1)),
+            ("custom-name", "custom-name", None),
+            ("daily", "daily", None),
+            ("-1", "-1", None),
+        ] {
+            let reason = RetentionReason::new(raw.to_string());
+            assert_eq!(reason.as_str(), raw);
+            assert_eq!(reason.class(), class, "{raw}");
+            assert_eq!(reason.slot(), slot, "{raw}");
+        }
+    }
+}
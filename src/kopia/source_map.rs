@@ -2,7 +2,8 @@ use crate::SourceStr;
 use std::collections::BTreeMap;
 
 /// Map from [`SourceStr`] to the desired data elements
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
 pub struct SourceMap<T>(BTreeMap<SourceStr, T>);
 impl<T> SourceMap<T> {
     /// Creates an empty map
@@ -18,6 +19,17 @@ impl<T> SourceMap<T> {
         let Self(inner) = self;
         inner.entry(key)
     }
+    /// Returns a reference to the value for the source, if present
+    #[must_use]
+    pub fn get(&self, source: &SourceStr) -> Option<&T> {
+        let Self(inner) = self;
+        inner.get(source)
+    }
+    /// Removes and returns the value for the source, if present
+    pub fn remove(&mut self, source: &SourceStr) -> Option<T> {
+        let Self(inner) = self;
+        inner.remove(source)
+    }
     /// Returns a single value if it is the only value
     ///
     /// # Errors
@@ -42,6 +54,12 @@ impl<T> SourceMap<T> {
         let Self(inner) = self;
         inner.iter()
     }
+    /// Returns the number of sources
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let Self(inner) = self;
+        inner.len()
+    }
     /// Returns `true` if the map is empty
     #[must_use]
     pub fn is_empty(&self) -> bool {
@@ -57,6 +75,13 @@ impl<T> SourceMap<T> {
         }
     }
 }
+impl<T> IntoIterator for SourceMap<T> {
+    type Item = (SourceStr, T);
+    type IntoIter = std::collections::btree_map::IntoIter<SourceStr, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
 impl<'a, T> IntoIterator for &'a SourceMap<T> {
     type Item = (&'a SourceStr, &'a T);
     type IntoIter = std::collections::btree_map::Iter<'a, SourceStr, T>;
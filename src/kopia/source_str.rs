@@ -3,9 +3,13 @@ use crate::Source;
 impl Source {
     /// Converts from the JSON/typed [`Source`] to a flat string [`SourceStr`]
     ///
+    /// The `path` may contain `@` and `:` (e.g. a Windows drive letter), as the first `@`
+    /// and the following `:` delimit the components.
+    ///
     /// # Errors
     /// Returns an error if the `user_name` or `host` contain invalid characters that would
-    /// make the flat string representation ambiguous
+    /// make the flat string representation ambiguous, or the `path` contains control
+    /// characters (e.g. a newline) that would corrupt line-based outputs
     pub fn render(&self) -> Result<SourceStr, Error> {
         let Self {
             host,
@@ -40,6 +44,13 @@ impl Source {
             }
         }
 
+        if let Some(invalid_char) = path.chars().find(|c| c.is_control()) {
+            return make_err(ErrorKind::InvalidPath {
+                path: path.clone(),
+                invalid_char,
+            });
+        }
+
         let rendered = format!("{user_name}@{host}:{path}");
         Ok(SourceStr(rendered))
     }
@@ -53,6 +64,44 @@ impl SourceStr {
     pub fn new_unchecked(value: String) -> Self {
         Self(value)
     }
+    /// Constructs from a previously rendered string (not validated as a [`Source`])
+    #[cfg(feature = "history")]
+    pub(crate) fn from_rendered(value: String) -> Self {
+        Self(value)
+    }
+    /// Returns the rendered source string
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        let Self(text) = self;
+        text
+    }
+    /// Returns the bucket for sources exceeding the configured limit
+    ///
+    /// Does not collide with rendered sources, which always contain `@` and `:`
+    #[must_use]
+    pub fn overflow() -> Self {
+        Self("_overflow".to_string())
+    }
+    /// Returns the rollup across all sources, see [`KopiaSnapshots::with_source_rollups`]
+    ///
+    /// Does not collide with rendered sources, which always contain `@` and `:`
+    ///
+    /// [`KopiaSnapshots::with_source_rollups`]: crate::KopiaSnapshots::with_source_rollups
+    #[must_use]
+    pub fn all() -> Self {
+        Self("_all".to_string())
+    }
+}
+impl serde::Serialize for SourceStr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+impl<'de> serde::Deserialize<'de> for SourceStr {
+    /// Accepts any string, as previously serialized (not validated as a [`Source`])
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
 }
 impl std::fmt::Debug for SourceStr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -75,7 +124,7 @@ impl Error {
     pub fn invalid_user_name(&self) -> Option<&str> {
         match &self.kind {
             ErrorKind::InvalidUserName { user_name, .. } => Some(user_name),
-            ErrorKind::InvalidHost { .. } => None,
+            ErrorKind::InvalidHost { .. } | ErrorKind::InvalidPath { .. } => None,
         }
     }
 
@@ -84,12 +133,22 @@ impl Error {
     pub fn invalid_host(&self) -> Option<&str> {
         match &self.kind {
             ErrorKind::InvalidHost { host, .. } => Some(host),
-            ErrorKind::InvalidUserName { .. } => None,
+            ErrorKind::InvalidUserName { .. } | ErrorKind::InvalidPath { .. } => None,
+        }
+    }
+
+    /// Returns the invalid path if this is an `InvalidPath` error
+    #[must_use]
+    pub fn invalid_path(&self) -> Option<&str> {
+        match &self.kind {
+            ErrorKind::InvalidPath { path, .. } => Some(path),
+            ErrorKind::InvalidUserName { .. } | ErrorKind::InvalidHost { .. } => None,
         }
     }
 }
 
 #[derive(Debug)]
+#[expect(clippy::enum_variant_names)] // matching the `invalid_*` accessors
 enum ErrorKind {
     InvalidUserName {
         user_name: String,
@@ -99,11 +158,17 @@ enum ErrorKind {
         host: String,
         invalid_char: char,
     },
+    InvalidPath {
+        path: String,
+        invalid_char: char,
+    },
 }
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self.kind {
-            ErrorKind::InvalidUserName { .. } | ErrorKind::InvalidHost { .. } => None,
+            ErrorKind::InvalidUserName { .. }
+            | ErrorKind::InvalidHost { .. }
+            | ErrorKind::InvalidPath { .. } => None,
         }
     }
 }
@@ -123,6 +188,9 @@ impl std::fmt::Display for Error {
             ErrorKind::InvalidHost { host, invalid_char } => {
                 write!(f, "invalid char {invalid_char:?} in host {host:?}")
             }
+            ErrorKind::InvalidPath { path, invalid_char } => {
+                write!(f, "invalid char {invalid_char:?} in path {path:?}")
+            }
         }?;
         write!(f, " in {value_source:?}")
     }
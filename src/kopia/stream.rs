@@ -0,0 +1,294 @@
+use super::MalformedSnapshot;
+use crate::{Error, SnapshotJson};
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use std::fmt;
+
+/// Deserializes a listing of snapshots, passing each snapshot to `snapshot_fn` as soon as it
+/// is parsed, so the full list is never held in memory
+///
+/// The listing is a sequence of JSON values, each of which is either an array of snapshots
+/// (as printed by `kopia snapshot list --json`), an object wrapping the array in its
+/// `snapshots` field, or a single snapshot (one per line for newline-delimited JSON).
+///
+/// Elements which are valid JSON but not a snapshot are passed as [`MalformedSnapshot`],
+/// without failing the remaining elements. Stops at the first error returned by `snapshot_fn`.
+///
+/// # Errors
+///
+/// Returns an error if the input is empty or not valid JSON, a value is neither an array nor
+/// an object, or `snapshot_fn` returns an error
+pub(crate) fn for_each_snapshot(
+    reader: impl std::io::Read,
+    snapshot_fn: impl FnMut(Result<SnapshotJson, MalformedSnapshot>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut seed = ForEachSnapshot {
+        snapshot_fn,
+        index: 0,
+        error: None,
+    };
+    let mut reader = std::io::BufReader::new(reader);
+    loop {
+        // a fresh deserializer for each top-level value, as objects and arrays end without
+        // reading ahead (unlike `serde_json::StreamDeserializer`, which takes no seed)
+        let mut deserializer = serde_json::Deserializer::from_reader(&mut reader);
+        let result = (&mut seed).deserialize(&mut deserializer);
+        // prefer the error from `snapshot_fn` over the resulting (generic) JSON error
+        if let Some(error) = seed.error {
+            return Err(error);
+        }
+        result?;
+        if !skip_whitespace(&mut reader)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Consumes leading whitespace, returning `true` if more input follows
+fn skip_whitespace(reader: &mut impl std::io::BufRead) -> std::io::Result<bool> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        let whitespace = buf
+            .iter()
+            .take_while(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+            .count();
+        let more = whitespace < buf.len();
+        reader.consume(whitespace);
+        if more {
+            return Ok(true);
+        }
+    }
+}
+
+struct ForEachSnapshot<F> {
+    snapshot_fn: F,
+    /// Index of the next element, counted across all top-level values
+    index: usize,
+    error: Option<Error>,
+}
+
+impl<F> ForEachSnapshot<F>
+where
+    F: FnMut(Result<SnapshotJson, MalformedSnapshot>) -> Result<(), Error>,
+{
+    fn element<E: de::Error>(
+        &mut self,
+        snapshot: Result<SnapshotJson, serde_json::Error>,
+    ) -> Result<(), E> {
+        let index = self.index;
+        self.index += 1;
+        if let Err(error) =
+            (self.snapshot_fn)(snapshot.map_err(|error| MalformedSnapshot { index, error }))
+        {
+            self.error = Some(error);
+            return Err(E::custom("aborted by snapshot callback"));
+        }
+        Ok(())
+    }
+}
+
+impl<'de, F> DeserializeSeed<'de> for &mut ForEachSnapshot<F>
+where
+    F: FnMut(Result<SnapshotJson, MalformedSnapshot>) -> Result<(), Error>,
+{
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, F> Visitor<'de> for &mut ForEachSnapshot<F>
+where
+    F: FnMut(Result<SnapshotJson, MalformedSnapshot>) -> Result<(), Error>,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "an array of snapshots, an object with a `snapshots` array, or a snapshot"
+        )
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        // buffer each element, so a malformed snapshot does not fail the whole list
+        while let Some(element) = seq.next_element::<Box<serde_json::value::RawValue>>()? {
+            self.element(serde_json::from_str(element.get()))?;
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        // buffer the fields, until the `snapshots` field shows the object is a wrapper
+        let mut fields = serde_json::Map::new();
+        let mut wrapper = false;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == SNAPSHOTS_FIELD {
+                map.next_value_seed(SnapshotArray(&mut *self))?;
+                wrapper = true;
+                fields.clear();
+            } else if wrapper {
+                map.next_value::<de::IgnoredAny>()?;
+            } else {
+                fields.insert(key, map.next_value()?);
+            }
+        }
+        if !wrapper {
+            self.element(serde_json::from_value(serde_json::Value::Object(fields)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Field of the object wrapping the array of snapshots
+const SNAPSHOTS_FIELD: &str = "snapshots";
+
+/// Array of snapshots in the `snapshots` field of a wrapper object
+struct SnapshotArray<'a, F>(&'a mut ForEachSnapshot<F>);
+
+impl<'de, F> DeserializeSeed<'de> for SnapshotArray<'_, F>
+where
+    F: FnMut(Result<SnapshotJson, MalformedSnapshot>) -> Result<(), Error>,
+{
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::for_each_snapshot;
+    use crate::{Error, test_util::test_snapshot};
+
+    #[test]
+    fn streams_elements() {
+        let snapshots = vec![test_snapshot("1", 100, &[]), test_snapshot("2", 200, &[])];
+        let json = serde_json::to_string(&snapshots).expect("serializable");
+
+        let mut ids = vec![];
+        for_each_snapshot(json.as_bytes(), |snapshot| {
+            ids.push(snapshot.expect("valid").id);
+            Ok(())
+        })
+        .expect("valid");
+        assert_eq!(ids, ["1", "2"]);
+    }
+
+    #[test]
+    fn callback_error_stops() {
+        let snapshots = vec![test_snapshot("1", 100, &[]), test_snapshot("2", 200, &[])];
+        let json = serde_json::to_string(&snapshots).expect("serializable");
+
+        let mut count = 0;
+        let err = for_each_snapshot(json.as_bytes(), |_| {
+            count += 1;
+            Err(Error::Io(std::io::Error::other("stop")))
+        })
+        .expect_err("callback error");
+        assert_eq!(err.to_string(), "stop");
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn malformed_elements_skipped() {
+        let mut wrong_type = serde_json::to_value(test_snapshot("2", 200, &[])).expect("valid");
+        wrong_type["stats"]["totalSize"] = serde_json::json!("large");
+        let json = serde_json::to_string(&serde_json::json!([
+            test_snapshot("1", 100, &[]),
+            1,
+            wrong_type,
+            test_snapshot("3", 300, &[]),
+        ]))
+        .expect("serializable");
+
+        let mut ids = vec![];
+        let mut malformed = vec![];
+        for_each_snapshot(json.as_bytes(), |snapshot| {
+            match snapshot {
+                Ok(snapshot) => ids.push(snapshot.id),
+                Err(e) => malformed.push(e.to_string()),
+            }
+            Ok(())
+        })
+        .expect("valid JSON array");
+        assert_eq!(ids, ["1", "3"]);
+        assert_eq!(malformed.len(), 2);
+        assert!(
+            malformed[0].starts_with("snapshot 1: invalid type"),
+            "{malformed:?}"
+        );
+        assert!(
+            malformed[1].starts_with(r#"snapshot 2: invalid type: string "large""#),
+            "{malformed:?}"
+        );
+    }
+
+    #[test]
+    fn detects_shapes() {
+        let snapshots = [test_snapshot("1", 100, &[]), test_snapshot("2", 200, &[])];
+        let array = serde_json::to_string(&snapshots).expect("serializable");
+        let ndjson = snapshots
+            .iter()
+            .map(|snapshot| serde_json::to_string(snapshot).expect("serializable") + "\n")
+            .collect::<String>();
+        let wrapped = serde_json::to_string(&serde_json::json!({
+            "version": 2,
+            "snapshots": snapshots,
+            "ignored": [1],
+        }))
+        .expect("serializable");
+        let pretty = serde_json::to_string_pretty(&snapshots).expect("serializable");
+
+        for json in [array, ndjson, wrapped, pretty] {
+            let mut ids = vec![];
+            for_each_snapshot(json.as_bytes(), |snapshot| {
+                ids.push(snapshot.expect("valid").id);
+                Ok(())
+            })
+            .expect("valid");
+            assert_eq!(ids, ["1", "2"], "{json}");
+        }
+    }
+
+    #[test]
+    fn ndjson_malformed_lines_skipped() {
+        let json = [
+            serde_json::to_string(&test_snapshot("1", 100, &[])).expect("serializable"),
+            r#"{"id":"missing-fields"}"#.to_owned(),
+            serde_json::to_string(&test_snapshot("3", 300, &[])).expect("serializable"),
+        ]
+        .join("\r\n");
+
+        let mut ids = vec![];
+        let mut malformed = vec![];
+        for_each_snapshot(json.as_bytes(), |snapshot| {
+            match snapshot {
+                Ok(snapshot) => ids.push(snapshot.id),
+                Err(e) => malformed.push(e.to_string()),
+            }
+            Ok(())
+        })
+        .expect("valid NDJSON");
+        assert_eq!(ids, ["1", "3"]);
+        assert_eq!(malformed.len(), 1);
+        assert!(
+            malformed[0].starts_with("snapshot 1: missing field"),
+            "{malformed:?}"
+        );
+    }
+
+    #[test]
+    fn invalid_json() {
+        for json in ["", "  \n", "[1", "[] trailing", "1", r#""text""#, "[]\n{"] {
+            assert!(
+                for_each_snapshot(json.as_bytes(), |_| Ok(())).is_err(),
+                "{json}"
+            );
+        }
+    }
+}
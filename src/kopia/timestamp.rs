@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+/// Maximum fractional second digits accepted by [`jiff::Timestamp`] (nanoseconds)
+const MAX_FRACTION_DIGITS: usize = 9;
+
+/// Problem with a timestamp reported by `kopia`, which is then treated as absent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TimestampIssue {
+    /// Empty, e.g. an omitted field
+    Missing,
+    /// Not an RFC 3339 timestamp
+    Unparseable,
+    /// Before the Unix epoch, e.g. the Go zero time `0001-01-01T00:00:00Z` of an unset field
+    OutOfRange,
+}
+
+/// Parses a timestamp reported by `kopia`, in RFC 3339 form with any UTC offset
+///
+/// Accepts `Z` or a numeric offset (e.g. `+02:00`, depending on the platform), fractional
+/// seconds of any precision (truncated to nanoseconds), and surrounding whitespace. All
+/// snapshot times are parsed here, so every metric agrees on which timestamps are valid.
+pub(crate) fn parse_timestamp(raw: &str) -> Option<jiff::Timestamp> {
+    classify_timestamp(raw).ok()
+}
+
+/// Parses the timestamp as [`parse_timestamp`], or returns the issue preventing it
+pub(crate) fn classify_timestamp(raw: &str) -> Result<jiff::Timestamp, TimestampIssue> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err(TimestampIssue::Missing);
+    }
+    let timestamp: jiff::Timestamp = raw
+        .parse()
+        .ok()
+        .or_else(|| truncate_fraction(raw)?.parse().ok())
+        .ok_or(TimestampIssue::Unparseable)?;
+    if timestamp < jiff::Timestamp::UNIX_EPOCH {
+        return Err(TimestampIssue::OutOfRange);
+    }
+    Ok(timestamp)
+}
+
+/// Number of timestamps with each [`TimestampIssue`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TimestampIssueCounts {
+    pub missing: u64,
+    pub unparseable: u64,
+    pub out_of_range: u64,
+}
+impl TimestampIssueCounts {
+    pub fn record(&mut self, issue: TimestampIssue) {
+        let count = match issue {
+            TimestampIssue::Missing => &mut self.missing,
+            TimestampIssue::Unparseable => &mut self.unparseable,
+            TimestampIssue::OutOfRange => &mut self.out_of_range,
+        };
+        *count = count.saturating_add(1);
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        let Self {
+            missing,
+            unparseable,
+            out_of_range,
+        } = other;
+        self.missing = self.missing.saturating_add(missing);
+        self.unparseable = self.unparseable.saturating_add(unparseable);
+        self.out_of_range = self.out_of_range.saturating_add(out_of_range);
+    }
+}
+
+/// Drops the fractional second digits beyond nanoseconds, if any
+fn truncate_fraction(raw: &str) -> Option<String> {
+    let fraction_start = raw.find(['.', ','])? + 1;
+    let digits = raw[fraction_start..]
+        .bytes()
+        .take_while(u8::is_ascii_digit)
+        .count();
+    (digits > MAX_FRACTION_DIGITS).then(|| {
+        let kept_end = fraction_start + MAX_FRACTION_DIGITS;
+        let rest_start = fraction_start + digits;
+        format!("{}{}", &raw[..kept_end], &raw[rest_start..])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimestampIssue, TimestampIssueCounts, classify_timestamp, parse_timestamp};
+
+    #[test]
+    fn offsets_and_fractions() {
+        let expected: jiff::Timestamp = "2025-08-14T00:00:00.5Z".parse().expect("valid timestamp");
+        for raw in [
+            "2025-08-14T00:00:00.5Z",
+            "2025-08-14T00:00:00.500000000Z",
+            "2025-08-14T02:00:00.5+02:00",
+            "2025-08-13T19:30:00.5-04:30",
+            "2025-08-14T02:00:00.5+0200",
+            "2025-08-14T00:00:00.5-00:00",
+            "2025-08-14 02:00:00.5+02:00",
+            "2025-08-14T02:00:00,5+02:00",
+            "2025-08-14T02:00:00.5000000000001+02:00",
+            " 2025-08-14T00:00:00.5Z\n",
+        ] {
+            assert_eq!(parse_timestamp(raw), Some(expected), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn excess_fraction_truncated() {
+        let expected: jiff::Timestamp = "2025-08-14T00:00:00.123456789Z"
+            .parse()
+            .expect("valid timestamp");
+        assert_eq!(
+            parse_timestamp("2025-08-14T00:00:00.12345678987654321Z"),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        for (raw, expected) in [
+            ("", TimestampIssue::Missing),
+            (" \n", TimestampIssue::Missing),
+            ("not a time", TimestampIssue::Unparseable),
+            // local time, without an offset
+            ("2025-08-14T00:00:00", TimestampIssue::Unparseable),
+            ("2025-08-14T00:00:00.Z", TimestampIssue::Unparseable),
+            ("2025-13-14T00:00:00Z", TimestampIssue::Unparseable),
+            ("0001-01-01T00:00:00Z", TimestampIssue::OutOfRange),
+            ("1969-12-31T23:59:59Z", TimestampIssue::OutOfRange),
+        ] {
+            assert_eq!(classify_timestamp(raw), Err(expected), "{raw:?}");
+            assert_eq!(parse_timestamp(raw), None, "{raw:?}");
+        }
+        assert_eq!(
+            classify_timestamp("1970-01-01T00:00:00Z"),
+            Ok(jiff::Timestamp::UNIX_EPOCH)
+        );
+    }
+
+    #[test]
+    fn issue_counts() {
+        let mut counts = TimestampIssueCounts::default();
+        counts.record(TimestampIssue::Missing);
+        counts.record(TimestampIssue::OutOfRange);
+        counts.merge(counts);
+        counts.record(TimestampIssue::Unparseable);
+        assert_eq!(
+            counts,
+            TimestampIssueCounts {
+                missing: 2,
+                unparseable: 1,
+                out_of_range: 2,
+            }
+        );
+    }
+}
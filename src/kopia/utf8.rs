@@ -0,0 +1,146 @@
+use std::io;
+
+/// Reader replacing invalid UTF-8 sequences with U+FFFD (as [`String::from_utf8_lossy`]),
+/// so a single mangled path does not fail the whole snapshot list
+pub(crate) struct LossyUtf8Reader<R> {
+    inner: R,
+    /// Input not yet transcoded, e.g. a sequence split by the end of the last read
+    input: Vec<u8>,
+    /// Transcoded output not yet read
+    output: Vec<u8>,
+    position: usize,
+    replacements: u64,
+}
+
+impl<R: io::Read> LossyUtf8Reader<R> {
+    const CHUNK_LEN: usize = 8 * 1024;
+    const REPLACEMENT: &str = "\u{FFFD}";
+
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            input: Vec::new(),
+            output: Vec::new(),
+            position: 0,
+            replacements: 0,
+        }
+    }
+
+    /// Returns the number of invalid sequences replaced so far
+    pub fn replacements(&self) -> u64 {
+        self.replacements
+    }
+
+    /// Moves the input to the output, keeping an incomplete sequence at the end unless `eof`
+    fn transcode(&mut self, eof: bool) {
+        let Self {
+            input,
+            output,
+            replacements,
+            ..
+        } = self;
+        let mut rest = input.as_slice();
+        while !rest.is_empty() {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    output.extend_from_slice(valid.as_bytes());
+                    rest = &[];
+                }
+                Err(error) => {
+                    let (valid, invalid) = rest.split_at(error.valid_up_to());
+                    output.extend_from_slice(valid);
+                    let invalid_len = match error.error_len() {
+                        Some(len) => len,
+                        None if eof => invalid.len(),
+                        None => {
+                            rest = invalid;
+                            break;
+                        }
+                    };
+                    output.extend_from_slice(Self::REPLACEMENT.as_bytes());
+                    *replacements = replacements.saturating_add(1);
+                    rest = &invalid[invalid_len..];
+                }
+            }
+        }
+        let consumed = input.len() - rest.len();
+        input.drain(..consumed);
+    }
+}
+
+impl<R: io::Read> io::Read for LossyUtf8Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.output.len() {
+            self.output.clear();
+            self.position = 0;
+            let len = self.input.len();
+            self.input.resize(len + Self::CHUNK_LEN, 0);
+            let read = match self.inner.read(&mut self.input[len..]) {
+                Ok(read) => read,
+                Err(error) => {
+                    self.input.truncate(len);
+                    return Err(error);
+                }
+            };
+            self.input.truncate(len + read);
+            if read == 0 && self.input.is_empty() {
+                return Ok(0);
+            }
+            self.transcode(read == 0);
+        }
+        let remaining = &self.output[self.position..];
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LossyUtf8Reader;
+    use std::io::Read as _;
+
+    /// Reader returning at most `len` bytes per read, to split sequences between reads
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        len: usize,
+    }
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = self.len.min(buf.len()).min(self.bytes.len());
+            let (head, tail) = self.bytes.split_at(len);
+            buf[..len].copy_from_slice(head);
+            self.bytes = tail;
+            Ok(len)
+        }
+    }
+
+    fn transcode(bytes: &[u8], len: usize) -> (String, u64) {
+        let mut reader = LossyUtf8Reader::new(Trickle { bytes, len });
+        let mut output = String::new();
+        reader.read_to_string(&mut output).expect("valid UTF-8");
+        (output, reader.replacements())
+    }
+
+    #[test]
+    fn valid_unchanged() {
+        let text = "plain, ünïcode and 🦀 emoji";
+        for len in [1, 2, 3, 1024] {
+            assert_eq!(
+                transcode(text.as_bytes(), len),
+                (text.to_owned(), 0),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_replaced() {
+        let bytes = b"/home/\xffuser/caf\xc3\xa9/\xe2\x82/end\xf0\x9f";
+        let expected = String::from_utf8_lossy(bytes).into_owned();
+        for len in [1, 2, 3, 1024] {
+            assert_eq!(transcode(bytes, len), (expected.clone(), 3), "{len}");
+        }
+    }
+}
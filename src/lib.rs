@@ -21,92 +21,310 @@
 //!     - The oldest snapshots should be pruned according to retention policy
 // //! - [Pruning health](Metrics::PRUNING_HEALTH)
 // //!     - Verify that pruning operations complete successfully and maintain expected retention
+//! - [Backup health](Metrics::BACKUP_HEALTH)
+//!     - Summarize the above into a single healthy/unhealthy status, using configured thresholds
 //! - [Data quality](Metrics::DATA_QUALITY)
 //!     - Verify that kopia data is valid to be interpreted for metrics generation
+//! - [Exporter status](Metrics::EXPORTER_STATUS)
+//!     - Judge the staleness of the exported data
 //!
 //! ## Metrics
 //!
 //! All available Prometheus metrics are defined in the [`metrics`] module.
 //! Each metric is documented in its own module with category and help text.
+//!
+//! The rendered output is deterministic: identical snapshots and `now` render byte-identical
+//! output, with metric families in a fixed order, samples ordered by source (then by any other
+//! label value), and labels in a fixed order within each metric. Diffing scrapes or caching
+//! them by content (e.g. an `ETag`) can rely on this.
+//!
+//! With the `prometheus-client` feature, [`KopiaSnapshots`] implements the `prometheus_client`
+//! `Collector` trait, to embed the metrics in an existing registry.
+//!
+//! ## Features
+//!
+//! - `cli` (default): the `kopia-exporter` binary, including the HTTP server, and the `fetch`
+//!   and `cache` modules it serves the snapshots with
+//! - `push`: the `push` module (enabled by `cli`)
+//! - `prometheus-client`: the `Collector` implementation above
+//! - `tokio`: async variants of the `kopia` command constructors (e.g.
+//!   `KopiaSnapshots::new_from_command_async`)
+//! - `history`: the `HistoryDb` recording snapshots in `SQLite` (see [`history`])
+//! - `otel`: the [`trace`] spans of fetching (running `kopia` and parsing), for export to an
+//!   OpenTelemetry collector (see `kopia-exporter serve --otlp-traces-url`)
+//! - `testkit`: the `test_util` snapshot fixture builders and the [`testkit`] harness running
+//!   the `kopia-exporter` binary, for downstream tests
+//!
+//! Library consumers only needing the parsing and metrics can disable the default features.
 
 pub use crate::assert_contains::AssertContains;
+pub use crate::build_info::BuildInfo;
+pub use crate::error::Error;
 pub use crate::kopia::*;
 pub use crate::metrics::Metrics;
-use eyre::{Result, eyre};
 use std::time::Duration;
 
+#[cfg(feature = "cli")]
+pub mod cache;
+pub mod diff;
+#[cfg(feature = "cli")]
+pub mod fetch;
+pub mod health;
+pub mod history;
 pub mod kopia;
 pub mod metrics;
+#[cfg(feature = "push")]
+pub mod push;
+pub mod status;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+#[cfg(feature = "otel")]
+pub mod trace;
+pub mod validate;
 
 mod assert_contains;
+mod build_info;
+#[cfg(feature = "tokio")]
+mod command_async;
+mod error;
+mod serialize;
 
 /// Parsed snapshots list from `kopia`
+///
+/// Multiple sets can be combined using [`Self::merge`], or by collecting an iterator.
 #[derive(Clone, Debug)]
 pub struct KopiaSnapshots {
     snapshots_map: SourceMap<Vec<Snapshot>>,
-    invalid_user_names: std::collections::BTreeMap<String, u32>,
-    invalid_hosts: std::collections::BTreeMap<String, u32>,
+    invalid_user_names: std::collections::BTreeMap<String, u64>,
+    invalid_hosts: std::collections::BTreeMap<String, u64>,
+    invalid_paths: std::collections::BTreeMap<String, u64>,
+    malformed: kopia::MalformedSnapshots,
+    seen_ids: kopia::SeenSnapshotIds,
+    /// Number of invalid UTF-8 sequences replaced in the `kopia` output
+    utf8_replacements: u64,
+    /// Standard error output of a successful `kopia` command, if not empty
+    kopia_stderr: Option<String>,
+    latest_policy: LatestSnapshotPolicy,
+    sources_truncated: Option<u32>,
+    health_thresholds: Option<health::HealthThresholds>,
+    fetched_at: Option<jiff::Timestamp>,
+    /// Value of the `repo_id` label added to every sample
+    repo_id: Option<String>,
+    /// Whether to add `source="_all"` rollup samples to the key per-source metrics
+    source_rollups: bool,
+    /// Storage quota of the repository, for the time-to-full estimate
+    repo_quota_bytes: Option<u64>,
+    /// Expected number of retained snapshots of each retention class (e.g. `daily`)
+    expected_retention: std::collections::BTreeMap<String, u32>,
+    /// Expected interval between snapshots, for the schedule drift
+    expected_intervals: Vec<health::ExpectedInterval>,
+    /// Snapshots observed earlier, including those pruned since
+    history: Option<history::SnapshotHistory>,
+    /// Snapshots kept while parsing, in aggregate-only mode or with a per-source limit
+    compaction: Option<kopia::Compaction>,
+    folded: SourceMap<kopia::FoldedSnapshots>,
+    custom_metrics: metrics::CustomMetricFns,
 }
 
 impl KopiaSnapshots {
+    /// Creates a new `KopiaSnapshots` from a vector of parsed snapshots, skipping (and
+    /// reporting) snapshots with an invalid source.
+    #[must_use]
+    pub fn new_from_snapshots_with_report(
+        snapshots: Vec<SnapshotJson>,
+    ) -> (Self, InvalidSourceReport) {
+        let collector = kopia::ReportCollector::default();
+        let invalid_source_fn = collector.invalid_source_fn();
+        let mut this = Self::empty();
+        for snapshot in snapshots {
+            if let Err(e) = this.try_insert_snapshot(snapshot) {
+                // NOTE: recording the error never fails
+                let _ = invalid_source_fn(e);
+            }
+        }
+        (this, collector.finish())
+    }
+
     /// Creates a new `KopiaSnapshots` from a vector of parsed snapshots.
     ///
     /// # Errors
     ///
     /// Returns an error if `invalid_source_fn` returns an error
+    #[deprecated(
+        note = "use `new_from_snapshots_with_report`, which reports invalid sources instead of calling `invalid_source_fn`"
+    )]
     pub fn new_from_snapshots(
         snapshots: Vec<SnapshotJson>,
-        invalid_source_fn: impl Fn(SourceStrError) -> eyre::Result<()>,
-    ) -> Result<Self> {
-        // organize by [`SourceStr`]
-        let mut snapshots_map = SourceMap::new();
-        let mut invalid_user_names = std::collections::BTreeMap::new();
-        let mut invalid_hosts = std::collections::BTreeMap::new();
-
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError>,
+    ) -> Result<Self, Error> {
+        let mut this = Self::empty();
         for snapshot in snapshots {
-            let source_str = match snapshot.source.render() {
-                Ok(s) => s,
-                Err(e) => {
-                    // Track the invalid source
-                    if let Some(invalid_user) = e.invalid_user_name() {
-                        *invalid_user_names
-                            .entry(invalid_user.to_string())
-                            .or_insert(0) += 1;
-                    }
-                    if let Some(invalid_host) = e.invalid_host() {
-                        *invalid_hosts.entry(invalid_host.to_string()).or_insert(0) += 1;
-                    }
-
-                    // Call the callback for backward compatibility
-                    invalid_source_fn(e)?;
-                    continue;
-                }
-            };
-            let list: &mut Vec<Snapshot> = snapshots_map.entry(source_str).or_default();
-            list.push(snapshot.into());
+            this.insert_snapshot(snapshot, &invalid_source_fn)?;
         }
-        Ok(Self {
-            snapshots_map,
-            invalid_user_names,
-            invalid_hosts,
-        })
+        Ok(this)
     }
 
     /// Parses JSON from a reader (streaming).
     ///
-    /// This is the primary implementation that streams JSON parsing,
-    /// avoiding buffering the entire input in memory.
+    /// This is the primary implementation that streams JSON parsing, organizing each
+    /// snapshot as soon as it is parsed, avoiding buffering the entire input (or the
+    /// full list of parsed snapshots) in memory.
+    ///
+    /// Accepts the JSON array printed by `kopia snapshot list --json`, newline-delimited JSON
+    /// (one snapshot per line), or an object wrapping the array in its `snapshots` field.
     ///
     /// # Errors
     ///
     /// Returns an error if the JSON content cannot be parsed as snapshot data, or
     /// `invalid_source_fn` returns an error
+    #[deprecated(
+        note = "use `new_from_reader_with_report`, which reports invalid sources instead of calling `invalid_source_fn`"
+    )]
     pub fn new_from_reader(
         reader: impl std::io::Read,
-        invalid_source_fn: impl Fn(SourceStrError) -> eyre::Result<()>,
-    ) -> Result<Self> {
-        let snapshots: Vec<SnapshotJson> = serde_json::from_reader(reader)?;
-        Self::new_from_snapshots(snapshots, invalid_source_fn)
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError>,
+    ) -> Result<Self, Error> {
+        Self::parse_reader(reader, ParseOptions::default(), invalid_source_fn)
+    }
+
+    /// Parses JSON from a reader (streaming), skipping (and reporting) snapshots with an
+    /// invalid source.
+    ///
+    /// See [`Self::new_from_reader`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON content cannot be parsed as snapshot data
+    pub fn new_from_reader_with_report(
+        reader: impl std::io::Read,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        let collector = kopia::ReportCollector::default();
+        let this = Self::parse_reader(
+            reader,
+            ParseOptions::default(),
+            collector.invalid_source_fn(),
+        )?;
+        Ok((this, collector.finish()))
+    }
+
+    /// Parses JSON from a reader (streaming) with the options (e.g. aggregate-only mode),
+    /// skipping (and reporting) snapshots with an invalid source.
+    ///
+    /// See [`Self::new_from_reader`] and [`ParseOptions`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON content cannot be parsed as snapshot data
+    pub fn new_from_reader_with_options(
+        reader: impl std::io::Read,
+        options: ParseOptions,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        let collector = kopia::ReportCollector::default();
+        let this = Self::parse_reader(reader, options, collector.invalid_source_fn())?;
+        Ok((this, collector.finish()))
+    }
+
+    fn parse_reader(
+        reader: impl std::io::Read,
+        options: ParseOptions,
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError>,
+    ) -> Result<Self, Error> {
+        let mut this = Self::empty();
+        this.latest_policy = options.latest_policy;
+        this.compaction = options.compaction();
+        let mut reader = kopia::LossyUtf8Reader::new(reader);
+        kopia::for_each_snapshot(&mut reader, |snapshot| match snapshot {
+            Ok(snapshot) => this.insert_snapshot(snapshot, &invalid_source_fn),
+            Err(malformed) => {
+                this.malformed.record(&malformed);
+                Ok(())
+            }
+        })?;
+        this.utf8_replacements = reader.replacements();
+        Ok(this)
+    }
+
+    fn empty() -> Self {
+        Self {
+            snapshots_map: SourceMap::new(),
+            invalid_user_names: std::collections::BTreeMap::new(),
+            invalid_hosts: std::collections::BTreeMap::new(),
+            invalid_paths: std::collections::BTreeMap::new(),
+            malformed: kopia::MalformedSnapshots::default(),
+            seen_ids: kopia::SeenSnapshotIds::default(),
+            utf8_replacements: 0,
+            kopia_stderr: None,
+            latest_policy: LatestSnapshotPolicy::default(),
+            sources_truncated: None,
+            health_thresholds: None,
+            custom_metrics: metrics::CustomMetricFns::default(),
+            fetched_at: None,
+            repo_id: None,
+            source_rollups: false,
+            repo_quota_bytes: None,
+            expected_retention: std::collections::BTreeMap::new(),
+            expected_intervals: Vec::new(),
+            history: None,
+            compaction: None,
+            folded: SourceMap::new(),
+        }
+    }
+
+    /// Organizes the snapshot by [`SourceStr`], tracking invalid sources
+    fn insert_snapshot(
+        &mut self,
+        snapshot: SnapshotJson,
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError>,
+    ) -> Result<(), Error> {
+        self.try_insert_snapshot(snapshot)
+            .or_else(|e| invalid_source_fn(e).map_err(Error::InvalidSource))
+    }
+
+    /// Organizes the snapshot by [`SourceStr`], or counts and returns the invalid source
+    ///
+    /// Skips (and counts) snapshots with an ID seen before.
+    fn try_insert_snapshot(&mut self, snapshot: SnapshotJson) -> Result<(), SourceStrError> {
+        if !self.seen_ids.insert(&snapshot.id) {
+            return Ok(());
+        }
+        let source_str = match snapshot.source.render() {
+            Ok(s) => s,
+            Err(e) => {
+                // Track the invalid source
+                if let Some(invalid_user) = e.invalid_user_name() {
+                    *self
+                        .invalid_user_names
+                        .entry(invalid_user.to_string())
+                        .or_insert(0) += 1;
+                }
+                if let Some(invalid_host) = e.invalid_host() {
+                    *self
+                        .invalid_hosts
+                        .entry(invalid_host.to_string())
+                        .or_insert(0) += 1;
+                }
+                if let Some(invalid_path) = e.invalid_path() {
+                    *self
+                        .invalid_paths
+                        .entry(invalid_path.to_string())
+                        .or_insert(0) += 1;
+                }
+
+                return Err(e);
+            }
+        };
+        let folded = self.compaction.map(|compaction| {
+            (
+                compaction,
+                self.folded.entry(source_str.clone()).or_default(),
+            )
+        });
+        let list: &mut Vec<Snapshot> = self.snapshots_map.entry(source_str).or_default();
+        list.push(snapshot.into());
+        if let Some((compaction, folded)) = folded {
+            kopia::compact(list, compaction, folded, &mut self.seen_ids);
+        }
+        Ok(())
     }
 
     /// Parses JSON content from a string.
@@ -118,36 +336,197 @@ impl KopiaSnapshots {
     ///
     /// Returns an error if the JSON content cannot be parsed as snapshot data, or
     /// `invalid_source_fn` returns an error
+    #[deprecated(
+        note = "use `new_from_reader_with_report`, which reports invalid sources instead of calling `invalid_source_fn`"
+    )]
     pub fn new_parse_json(
         json_content: &str,
-        invalid_source_fn: impl Fn(SourceStrError) -> eyre::Result<()>,
-    ) -> Result<Self> {
-        Self::new_from_reader(std::io::Cursor::new(json_content), invalid_source_fn)
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError>,
+    ) -> Result<Self, Error> {
+        Self::parse_reader(
+            json_content.as_bytes(),
+            ParseOptions::default(),
+            invalid_source_fn,
+        )
+    }
+
+    /// Parses JSON from a file, streaming its content, skipping (and reporting) snapshots
+    /// with an invalid source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or read, or the JSON content cannot be
+    /// parsed as snapshot data. Errors include the path of the file.
+    pub fn new_from_path_with_report(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        let collector = kopia::ReportCollector::default();
+        let this = Self::parse_path(
+            path.as_ref(),
+            ParseOptions::default(),
+            collector.invalid_source_fn(),
+        )?;
+        Ok((this, collector.finish()))
+    }
+
+    /// Parses JSON from a file, streaming its content, with the options (see
+    /// [`Self::new_from_reader_with_options`]), skipping (and reporting) snapshots with an
+    /// invalid source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or read, or the JSON content cannot be
+    /// parsed as snapshot data. Errors include the path of the file.
+    pub fn new_from_path_with_options(
+        path: impl AsRef<std::path::Path>,
+        options: ParseOptions,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        let collector = kopia::ReportCollector::default();
+        let this = Self::parse_path(path.as_ref(), options, collector.invalid_source_fn())?;
+        Ok((this, collector.finish()))
+    }
+
+    fn parse_path(
+        path: &std::path::Path,
+        options: ParseOptions,
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError>,
+    ) -> Result<Self, Error> {
+        std::fs::File::open(path)
+            .map_err(Error::from)
+            .and_then(|file| Self::parse_reader(file, options, invalid_source_fn))
+            .map_err(|source| Error::File {
+                path: path.to_owned(),
+                source: Box::new(source),
+            })
     }
 
     /// Executes kopia command to retrieve snapshots and parses the output.
     ///
+    /// The `kopia` binary path may be passed directly, or as a [`kopia::KopiaCommand`]
+    /// to adjust the process priority.
+    ///
     /// # Errors
     ///
     /// Returns an error if:
-    /// - The kopia command fails to execute
-    /// - The command returns a non-zero exit code
-    /// - The command execution exceeds the specified timeout
-    /// - The output cannot be parsed as UTF-8
-    /// - The JSON output cannot be parsed as snapshot data
-    /// - `invalid_source_fn` returns an error
+    /// - The kopia command fails to execute ([`Error::Io`])
+    /// - The command returns a non-zero exit code ([`Error::CommandFailed`])
+    /// - The command execution exceeds the specified timeout ([`Error::Timeout`])
+    /// - The output cannot be parsed as UTF-8, or as snapshot data ([`Error::Json`])
+    /// - `invalid_source_fn` returns an error ([`Error::InvalidSource`])
+    #[deprecated(
+        note = "use `new_from_command_with_report`, which reports invalid sources instead of calling `invalid_source_fn`"
+    )]
     pub fn new_from_command(
-        kopia_bin: &str,
+        kopia: impl Into<kopia::KopiaCommand>,
+        timeout: Duration,
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError> + Send + 'static,
+    ) -> Result<Self, Error> {
+        Self::run_command(
+            &kopia.into(),
+            timeout,
+            ParseOptions::default(),
+            invalid_source_fn,
+        )
+    }
+
+    /// Executes kopia command to retrieve snapshots and parses the output, skipping (and
+    /// reporting) snapshots with an invalid source.
+    ///
+    /// See [`Self::new_from_command`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`Self::new_from_command`], except for
+    /// invalid sources
+    pub fn new_from_command_with_report(
+        kopia: impl Into<kopia::KopiaCommand>,
+        timeout: Duration,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        let collector = kopia::ReportCollector::default();
+        let this = Self::run_command(
+            &kopia.into(),
+            timeout,
+            ParseOptions::default(),
+            collector.invalid_source_fn(),
+        )?;
+        Ok((this, collector.finish()))
+    }
+
+    /// Executes kopia command to retrieve snapshots and parses the output, with the options
+    /// (see [`Self::new_from_reader_with_options`]), skipping (and reporting) snapshots with
+    /// an invalid source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`Self::new_from_command_with_report`]
+    pub fn new_from_command_with_options(
+        kopia: impl Into<kopia::KopiaCommand>,
+        timeout: Duration,
+        options: ParseOptions,
+    ) -> Result<(Self, InvalidSourceReport), Error> {
+        let collector = kopia::ReportCollector::default();
+        let this = Self::run_command(
+            &kopia.into(),
+            timeout,
+            options,
+            collector.invalid_source_fn(),
+        )?;
+        Ok((this, collector.finish()))
+    }
+
+    fn run_command(
+        kopia: &kopia::KopiaCommand,
         timeout: Duration,
-        invalid_source_fn: impl Fn(SourceStrError) -> eyre::Result<()> + Send + 'static,
-    ) -> Result<Self> {
+        options: ParseOptions,
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError> + Send + 'static,
+    ) -> Result<Self, Error> {
+        let audit = kopia.start_audit();
+        let mut outcome = None;
+        #[cfg(feature = "otel")]
+        let mut span = trace::Span::enter("kopia");
+        let result =
+            Self::run_command_inner(kopia, timeout, options, invalid_source_fn, &mut outcome);
+        #[cfg(feature = "otel")]
+        Self::record_span(&mut span, outcome.as_ref(), &result);
+        if let (Some(audit), Some(outcome)) = (audit, outcome) {
+            audit.finish_logged(outcome);
+        }
+        result
+    }
+
+    #[cfg(feature = "otel")]
+    fn record_span(
+        span: &mut trace::Span,
+        outcome: Option<&kopia::AuditOutcome>,
+        result: &Result<Self, Error>,
+    ) {
+        if let Some(outcome) = outcome {
+            if let Some(exit_code) = outcome.exit_code {
+                span.set_attribute("process.exit_code", i64::from(exit_code));
+            }
+            let stdout_bytes = i64::try_from(outcome.stdout_bytes).unwrap_or(i64::MAX);
+            span.set_attribute("kopia.stdout_bytes", stdout_bytes);
+        }
+        if let Err(e) = result {
+            span.set_error(e);
+        }
+    }
+
+    fn run_command_inner(
+        kopia: &kopia::KopiaCommand,
+        timeout: Duration,
+        options: ParseOptions,
+        invalid_source_fn: impl Fn(SourceStrError) -> Result<(), SourceStrError> + Send + 'static,
+        outcome: &mut Option<kopia::AuditOutcome>,
+    ) -> Result<Self, Error> {
         use std::io::Read;
-        use std::process::{Command, Stdio};
+        use std::process::Stdio;
+        use std::sync::atomic::Ordering;
         use std::sync::mpsc;
         use std::time::Instant;
 
-        let mut child = Command::new(kopia_bin)
-            .args(["snapshot", "list", "--json"])
+        let mut child = kopia
+            .snapshot_list()
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()?;
@@ -156,17 +535,22 @@ impl KopiaSnapshots {
         let stdout_pipe = child
             .stdout
             .take()
-            .ok_or_else(|| eyre!("Failed to capture stdout"))?;
+            .ok_or_else(|| std::io::Error::other("Failed to capture stdout"))?;
         let stderr_pipe = child
             .stderr
             .take()
-            .ok_or_else(|| eyre!("Failed to capture stderr"))?;
+            .ok_or_else(|| std::io::Error::other("Failed to capture stderr"))?;
+        let (stdout_pipe, stdout_bytes) = kopia::CountingReader::new(stdout_pipe);
 
         // Spawn thread to parse JSON directly from stdout stream
         // This avoids buffering the entire JSON in memory before parsing
         let (result_tx, result_rx) = mpsc::channel();
+        #[cfg(feature = "otel")]
+        let parent = trace::current();
         std::thread::spawn(move || {
-            let result = Self::new_from_reader(stdout_pipe, invalid_source_fn);
+            #[cfg(feature = "otel")]
+            let _span = trace::Span::enter_with_parent("parse", parent);
+            let result = Self::parse_reader(stdout_pipe, options, invalid_source_fn);
             let _ = result_tx.send(result);
         });
 
@@ -186,24 +570,29 @@ impl KopiaSnapshots {
         loop {
             if let Some(status) = child.try_wait()? {
                 // Process completed - get results from threads
-                let parse_result = result_rx
-                    .recv()
-                    .map_err(|_| eyre!("Failed to receive parse result from thread"))?;
+                let parse_result = result_rx.recv().map_err(|_| {
+                    std::io::Error::other("Failed to receive parse result from thread")
+                })?;
                 let stderr_buffer = stderr_rx
                     .recv()
-                    .map_err(|_| eyre!("Failed to receive stderr from thread"))?;
+                    .map_err(|_| std::io::Error::other("Failed to receive stderr from thread"))?;
+                *outcome = Some(kopia::AuditOutcome {
+                    exit_code: status.code(),
+                    timed_out: false,
+                    stdout_bytes: stdout_bytes.load(Ordering::Relaxed),
+                    stderr: stderr_buffer.clone(),
+                });
 
+                let stderr = kopia.stderr_text(&stderr_buffer);
                 if !status.success() {
-                    let stderr = String::from_utf8_lossy(&stderr_buffer);
-                    return Err(eyre!(
-                        "kopia command failed with exit code: {}\nstderr: {}",
-                        status.code().unwrap_or(-1),
-                        stderr
-                    ));
+                    return Err(Error::CommandFailed {
+                        exit_code: status.code(),
+                        stderr,
+                    });
                 }
 
                 // Return the parse result, which may contain JSON parsing errors
-                return parse_result;
+                return parse_result.map(|this| this.with_kopia_stderr(stderr));
             }
 
             // Check timeout
@@ -212,26 +601,347 @@ impl KopiaSnapshots {
                 let _ = child.kill();
                 let _ = child.wait();
 
-                let seconds = timeout.as_secs_f64();
-
                 // Try to get whatever output the threads have captured
-                let Ok(stderr_buffer) = stderr_rx.recv() else {
-                    return Err(eyre!(
-                        "kopia command timeout after {seconds} seconds\n<stderr is unknown>",
-                    ));
-                };
-                let stderr = String::from_utf8_lossy(&stderr_buffer);
+                let stderr_buffer = stderr_rx.recv().ok();
+                *outcome = Some(kopia::AuditOutcome {
+                    exit_code: None,
+                    timed_out: true,
+                    stdout_bytes: stdout_bytes.load(Ordering::Relaxed),
+                    stderr: stderr_buffer.clone().unwrap_or_default(),
+                });
+                let stderr = stderr_buffer.map(|buffer| kopia.stderr_text(&buffer));
 
                 // Note: We can't easily get partial stdout since it's being consumed by the parser
-                return Err(eyre!(
-                    "kopia command timeout after {seconds} seconds\nstderr: {stderr}",
-                ));
+                return Err(Error::Timeout { timeout, stderr });
             }
             // Sleep briefly before checking again
             std::thread::sleep(poll_interval);
         }
     }
 
+    /// Sets the policy for selecting the "latest" snapshot of each source
+    #[must_use]
+    pub fn with_latest_policy(mut self, latest_policy: LatestSnapshotPolicy) -> Self {
+        self.latest_policy = latest_policy;
+        self
+    }
+
+    /// Returns the policy for selecting the "latest" snapshot of each source
+    #[must_use]
+    pub fn latest_policy(&self) -> LatestSnapshotPolicy {
+        self.latest_policy
+    }
+
+    /// Sets the thresholds for the [backup health](Metrics::BACKUP_HEALTH) metrics
+    #[must_use]
+    pub fn with_health_thresholds(mut self, health_thresholds: health::HealthThresholds) -> Self {
+        self.health_thresholds = Some(health_thresholds);
+        self
+    }
+
+    /// Sets the time the snapshots were fetched, for the
+    /// [data age](Self::kopia_exporter_data_age_seconds) and
+    /// [last successful fetch](Self::kopia_exporter_last_successful_fetch_timestamp) metrics
+    #[must_use]
+    pub fn with_fetched_at(mut self, fetched_at: jiff::Timestamp) -> Self {
+        self.fetched_at = Some(fetched_at);
+        self
+    }
+
+    /// Adds the `repo_id` label with the repository ID or name to every sample of every
+    /// metric, so metrics of sources with the same name in different repositories (e.g.
+    /// from multiple exporters) do not collide
+    #[must_use]
+    pub fn with_repo_id(mut self, repo_id: impl Into<String>) -> Self {
+        self.repo_id = Some(repo_id.into());
+        self
+    }
+
+    /// Adds rollup samples with `source="_all"` to the key per-source metrics: the sum of the
+    /// [latest sizes](Self::kopia_snapshot_size_bytes_total),
+    /// [snapshot counts](Self::kopia_snapshots_total) and
+    /// [latest errors](Self::kopia_snapshot_errors_total), and the maximum
+    /// [latest age](Self::kopia_snapshot_age_seconds) across all sources
+    ///
+    /// Queries aggregating over every `source` must then exclude `source="_all"`.
+    #[must_use]
+    pub fn with_source_rollups(mut self) -> Self {
+        self.source_rollups = true;
+        self
+    }
+
+    /// Sets the storage quota of the repository in bytes, for the
+    /// [time-to-full estimate](Self::kopia_repository_estimated_days_until_full)
+    #[must_use]
+    pub fn with_repo_quota_bytes(mut self, repo_quota_bytes: u64) -> Self {
+        self.repo_quota_bytes = Some(repo_quota_bytes);
+        self
+    }
+
+    /// Sets the expected number of retained snapshots of the retention class (e.g. `daily`,
+    /// `weekly`), for the [retention deficit](Self::kopia_retention_deficit) metric
+    ///
+    /// Verifies pruning without reading the retention policies from `kopia`.
+    #[must_use]
+    pub fn with_expected_retention(mut self, class: impl Into<String>, count: u32) -> Self {
+        self.expected_retention.insert(class.into(), count);
+        self
+    }
+
+    /// Adds the expected interval between snapshots of matching sources, for the
+    /// [schedule drift](Self::kopia_snapshot_schedule_drift_seconds) metric
+    ///
+    /// An interval naming the source takes precedence over one for all sources.
+    #[must_use]
+    pub fn with_expected_interval(mut self, expected: health::ExpectedInterval) -> Self {
+        self.expected_intervals.push(expected);
+        self
+    }
+
+    /// Sets the history of snapshots observed earlier (e.g. loaded from the `HistoryDb` of the
+    /// `history` feature), spanning beyond the retention of the repository
+    #[must_use]
+    pub fn with_history(mut self, history: history::SnapshotHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Returns the `repo_id` label value, if set
+    #[must_use]
+    pub fn repo_id(&self) -> Option<&str> {
+        self.repo_id.as_deref()
+    }
+
+    /// Returns the time the snapshots were fetched, if known
+    #[must_use]
+    pub fn fetched_at(&self) -> Option<jiff::Timestamp> {
+        self.fetched_at
+    }
+
+    /// Returns the configured health thresholds, if any
+    #[must_use]
+    pub fn health_thresholds(&self) -> Option<&health::HealthThresholds> {
+        self.health_thresholds.as_ref()
+    }
+
+    /// Limits the number of distinct sources, aggregating snapshots of all remaining
+    /// sources into the single [`SourceStr::overflow`] bucket.
+    ///
+    /// Sources are retained in sorted order, so the set of retained sources is stable
+    /// between fetches. The number of aggregated sources is reported by
+    /// [`Self::kopia_sources_truncated_total`].
+    #[must_use]
+    pub fn with_max_sources(mut self, max_sources: usize) -> Self {
+        if self.snapshots_map.len() <= max_sources {
+            self.sources_truncated = Some(0);
+            return self;
+        }
+
+        let mut truncated = 0;
+        let mut overflow = Vec::new();
+        let mut overflow_folded = kopia::FoldedSnapshots::default();
+        let mut snapshots_map = SourceMap::new();
+        for (index, (source, snapshots)) in self.snapshots_map.into_iter().enumerate() {
+            if index < max_sources {
+                snapshots_map.entry(source).or_insert(snapshots);
+            } else {
+                truncated += 1;
+                overflow.extend(snapshots);
+                if let Some(folded) = self.folded.remove(&source) {
+                    overflow_folded.merge(folded);
+                }
+            }
+        }
+        // keep "latest" semantics meaningful for the mixed sources
+        overflow.sort_by_key(|snapshot| snapshot.end_time);
+        if let Some(compaction) = self.compaction {
+            kopia::compact(
+                &mut overflow,
+                compaction,
+                &mut overflow_folded,
+                &mut self.seen_ids,
+            );
+            self.folded
+                .entry(SourceStr::overflow())
+                .or_default()
+                .merge(overflow_folded);
+        }
+        snapshots_map
+            .entry(SourceStr::overflow())
+            .or_insert(overflow);
+
+        self.snapshots_map = snapshots_map;
+        self.sources_truncated = Some(truncated);
+        self
+    }
+
+    /// Combines the snapshots of both sets, e.g. fetched from multiple repositories.
+    ///
+    /// Snapshots of a source present in both sets are combined into a single list, ordered
+    /// by end time then ID, so the result does not depend on the order of merging. Settings
+    /// (latest policy, health thresholds, repo ID, custom metrics) are kept from `self` if set, and
+    /// the fetch time is the earliest of both.
+    ///
+    /// Snapshots of `other` with an ID already in `self` are skipped (see
+    /// [`Self::duplicate_snapshots`]), unless already folded into the counts of
+    /// aggregate-only mode.
+    #[must_use]
+    pub fn merge(mut self, other: Self) -> Self {
+        use std::collections::btree_map::Entry;
+
+        let Self {
+            snapshots_map,
+            invalid_user_names,
+            invalid_hosts,
+            invalid_paths,
+            malformed,
+            seen_ids,
+            utf8_replacements,
+            kopia_stderr,
+            latest_policy: _,
+            sources_truncated,
+            health_thresholds,
+            fetched_at,
+            repo_id,
+            source_rollups,
+            repo_quota_bytes,
+            expected_retention,
+            expected_intervals,
+            history,
+            compaction,
+            folded,
+            custom_metrics,
+        } = other;
+
+        for (name, count) in invalid_user_names {
+            *self.invalid_user_names.entry(name).or_insert(0) += count;
+        }
+        for (host, count) in invalid_hosts {
+            *self.invalid_hosts.entry(host).or_insert(0) += count;
+        }
+        for (path, count) in invalid_paths {
+            *self.invalid_paths.entry(path).or_insert(0) += count;
+        }
+        self.malformed.merge(malformed);
+        for (source, folded) in folded {
+            self.folded.entry(source).or_default().merge(folded);
+        }
+        self.compaction = self.compaction.or(compaction);
+        let mut combined_sources = vec![];
+        for (source, mut snapshots) in snapshots_map {
+            snapshots.retain(|snapshot| self.seen_ids.insert(&snapshot.id));
+            if snapshots.is_empty() {
+                continue;
+            }
+            match self.snapshots_map.entry(source.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(snapshots);
+                }
+                Entry::Occupied(mut entry) => {
+                    let list = entry.get_mut();
+                    list.extend(snapshots);
+                    list.sort_by(|a, b| (a.end_time, &a.id).cmp(&(b.end_time, &b.id)));
+                    combined_sources.push(source);
+                }
+            }
+        }
+        self.seen_ids.merge(seen_ids);
+        // after merging the IDs, so the IDs of the folded snapshots are forgotten
+        if let Some(compaction) = self.compaction {
+            for source in combined_sources {
+                let list = self.snapshots_map.entry(source.clone()).or_default();
+                let folded = self.folded.entry(source).or_default();
+                kopia::compact(list, compaction, folded, &mut self.seen_ids);
+            }
+        }
+        self.utf8_replacements = self.utf8_replacements.saturating_add(utf8_replacements);
+        self.kopia_stderr = match (self.kopia_stderr, kopia_stderr) {
+            (Some(a), Some(b)) => Some(format!("{a}\n{b}")),
+            (a, b) => a.or(b),
+        };
+        self.sources_truncated = match (self.sources_truncated, sources_truncated) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        self.health_thresholds = self.health_thresholds.or(health_thresholds);
+        self.repo_id = self.repo_id.or(repo_id);
+        self.source_rollups |= source_rollups;
+        self.repo_quota_bytes = self.repo_quota_bytes.or(repo_quota_bytes);
+        if self.expected_retention.is_empty() {
+            self.expected_retention = expected_retention;
+        }
+        if self.expected_intervals.is_empty() {
+            self.expected_intervals = expected_intervals;
+        }
+        self.history = self.history.or(history);
+        if self.custom_metrics.is_empty() {
+            self.custom_metrics = custom_metrics;
+        }
+        self.fetched_at = match (self.fetched_at, fetched_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self
+    }
+
+    /// Iterates the sources with snapshots, in sorted order
+    pub fn sources(&self) -> impl Iterator<Item = &SourceStr> {
+        self.snapshots_map.iter().map(|(source, _)| source)
+    }
+
+    /// Returns the snapshots of the source, if present
+    ///
+    /// In aggregate-only mode, only the kept snapshots are returned.
+    #[must_use]
+    pub fn snapshots_for(&self, source: &SourceStr) -> Option<&[Snapshot]> {
+        self.snapshots_map.get(source).map(Vec::as_slice)
+    }
+
+    /// Returns the counts of snapshots skipped due to an invalid source
+    #[must_use]
+    pub fn invalid_sources(&self) -> InvalidSources<'_> {
+        InvalidSources::new(
+            &self.invalid_user_names,
+            &self.invalid_hosts,
+            &self.invalid_paths,
+        )
+    }
+
+    /// Returns the count of snapshots skipped because they did not match the expected
+    /// structure (see [`SnapshotJson`]), rather than failing the whole list
+    #[must_use]
+    pub fn malformed_snapshots(&self) -> &kopia::MalformedSnapshots {
+        &self.malformed
+    }
+
+    /// Returns the number of snapshots skipped for repeating the ID of another snapshot
+    #[must_use]
+    pub fn duplicate_snapshots(&self) -> u64 {
+        self.seen_ids.duplicate_count()
+    }
+
+    /// Returns the number of invalid UTF-8 sequences in the `kopia` output (e.g. in paths
+    /// mangled by the locale), each replaced by U+FFFD while parsing
+    #[must_use]
+    pub fn utf8_replacements(&self) -> u64 {
+        self.utf8_replacements
+    }
+
+    /// Returns the standard error output of the successful `kopia` command (e.g. warnings),
+    /// truncated to the [`KopiaCommand::with_stderr_limit`](kopia::KopiaCommand::with_stderr_limit)
+    ///
+    /// Only present for the `new_from_command` constructors, if `kopia` wrote to stderr.
+    #[must_use]
+    pub fn kopia_stderr(&self) -> Option<&str> {
+        self.kopia_stderr.as_deref()
+    }
+
+    /// Sets the standard error output of the `kopia` command, ignoring whitespace-only output
+    pub(crate) fn with_kopia_stderr(mut self, stderr: String) -> Self {
+        self.kopia_stderr = (!stderr.trim().is_empty()).then_some(stderr);
+        self
+    }
+
     /// Returns the inner [`SourceMap`]
     #[must_use]
     pub fn into_inner_map(self) -> SourceMap<Vec<Snapshot>> {
@@ -239,3 +949,12 @@ impl KopiaSnapshots {
         snapshots_map
     }
 }
+
+impl FromIterator<KopiaSnapshots> for KopiaSnapshots {
+    /// Merges all sets, see [`KopiaSnapshots::merge`]
+    fn from_iter<I: IntoIterator<Item = KopiaSnapshots>>(iter: I) -> Self {
+        iter.into_iter()
+            .reduce(Self::merge)
+            .unwrap_or_else(Self::empty)
+    }
+}
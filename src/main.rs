@@ -5,26 +5,297 @@
 
 use base64::prelude::*;
 use clap::Parser;
-use kopia_exporter::KopiaSnapshots;
+use eyre::WrapErr as _;
+#[cfg(feature = "history")]
+use kopia_exporter::history::{HistoryDb, SnapshotHistory};
+#[cfg(feature = "otel")]
+use kopia_exporter::trace;
+use kopia_exporter::{
+    BuildInfo, KopiaSnapshots, LatestSnapshotPolicy, ParseOptions,
+    cache::{FetchedSnapshots, SnapshotCache},
+    diff::SnapshotsDiff,
+    fetch::FetchConfig,
+    health::{self, ExpectedInterval, HealthThresholds, SilenceWindow},
+    kopia::KopiaCommand,
+    metrics::{ExporterStats, MetricCategory, MetricsBuilder, StatsdFlavor},
+    push::{
+        NotifyConfig, PushConfig, PushTarget, StatsdTarget, TextfileTarget, gotify::GotifyTarget,
+        home_assistant::HomeAssistantTopics, mqtt::MqttTarget, ntfy::NtfyTarget,
+    },
+    validate::ValidationReport,
+};
+use std::fmt;
+use std::io::Write as _;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs as _};
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tiny_http::{Header, Method, Response, Server};
 
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0   success
+  1   other error
+  2   invalid command line
+  69  kopia unavailable (binary not found, or listing snapshots failed), only for print and
+      serve --dry-run; serve keeps running and reports kopia failures in the metrics
+  75  failed to bind the HTTP port
+  78  invalid configuration (e.g. unreadable credentials file, invalid bind address)
+The check command exits with the Nagios status (0 OK, 1 WARNING, 2 CRITICAL, 3 UNKNOWN).";
+
+/// Prometheus metrics exporter for Kopia backup repositories
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_long_help = EXIT_CODES_HELP)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Kopia binary path
-    #[arg(short, long, default_value = "kopia")]
+    #[arg(short, long, default_value = "kopia", global = true)]
     kopia_bin: String,
 
-    /// Server bind address
+    /// Niceness adjustment for the kopia process (runs kopia via `nice -n`)
+    #[arg(long, allow_hyphen_values = true, value_parser = clap::value_parser!(i8).range(-20..=19), global = true)]
+    kopia_nice: Option<i8>,
+
+    /// Run the kopia process in the idle I/O scheduling class (runs kopia via `ionice -c 3`),
+    /// so refreshing metrics does not compete with a running backup for I/O
+    #[arg(long, global = true)]
+    kopia_ionice_idle: bool,
+
+    /// Environment variable for the kopia process, e.g. `KOPIA_CHECK_FOR_UPDATES=false`
+    /// (may be repeated, overrides --kopia-env-file)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env_var, global = true)]
+    kopia_env: Vec<(String, String)>,
+
+    /// File of `KEY=VALUE` lines with environment variables for the kopia process, ignoring
+    /// blank lines and lines starting with `#`
+    #[arg(long, global = true)]
+    kopia_env_file: Option<std::path::PathBuf>,
+
+    /// Append a JSON line to this file for each kopia invocation, recording the command
+    /// line, start and end time, exit code, output sizes and (truncated) stderr
+    #[arg(long, global = true)]
+    kopia_audit_log: Option<std::path::PathBuf>,
+
+    /// Maximum length in bytes of the kopia stderr included in errors and warnings, longer
+    /// output is truncated and marked with "(truncated)"
+    #[arg(long, value_name = "BYTES", default_value_t = KopiaCommand::DEFAULT_STDERR_LIMIT, global = true)]
+    kopia_stderr_limit: usize,
+
+    /// Timeout in seconds for kopia command execution
+    #[arg(short = 't', long, default_value = "15.0", global = true)]
+    timeout: f64,
+
+    /// Read the snapshots from this file (the output of `kopia snapshot list --json`, e.g.
+    /// written by a cron job) instead of running kopia, reloading it within a second of being
+    /// modified or replaced (rather than after --cache-seconds)
+    ///
+    /// The data age metrics count from the modification time of the file. A failed reload
+    /// keeps serving the previous snapshots, see `kopia_exporter_snapshots_file_reload_failed`.
+    #[arg(long, global = true)]
+    snapshots_file: Option<std::path::PathBuf>,
+
+    /// Policy for which snapshot counts as "latest" for the latest-snapshot metrics
+    /// (newest, newest-complete, newest-without-errors)
+    #[arg(long, default_value = "newest", global = true)]
+    latest_policy: LatestSnapshotPolicy,
+
+    /// Maximum number of distinct sources to emit, extra sources are aggregated
+    /// into source="_overflow"
+    #[arg(long, global = true)]
+    max_sources: Option<usize>,
+
+    /// Repository ID or name, added as the `repo_id` label to every metric, so metrics of
+    /// exporters for different repositories with the same sources do not collide
+    #[arg(long, global = true)]
+    repo_id: Option<String>,
+
+    /// Add rollup series across all sources with source="_all" to the key metrics (sum of
+    /// latest size, snapshot count and latest errors, maximum latest age)
+    #[arg(long, global = true)]
+    source_rollups: bool,
+
+    /// Storage quota of the repository in bytes, to estimate the days until full from the
+    /// growth of the snapshot sizes (`kopia_repository_estimated_days_until_full`)
+    #[arg(long, global = true)]
+    repo_quota_bytes: Option<u64>,
+
+    /// Expected number of retained snapshots of a retention class, e.g. `daily=7`, reported
+    /// by `kopia_retention_deficit` when fewer are retained (may be repeated)
+    #[arg(long, value_name = "CLASS=COUNT", value_parser = parse_expected_retention, global = true)]
+    expected_retention: Vec<(String, u32)>,
+
+    /// Expected interval between snapshots of a source (or "*" for all sources), e.g.
+    /// "*=1h", reported as `kopia_snapshot_schedule_drift_seconds` (may be repeated)
+    #[arg(
+        long = "expected-interval",
+        value_name = "SOURCE=DURATION",
+        global = true
+    )]
+    expected_intervals: Vec<ExpectedInterval>,
+
+    /// `SQLite` database to record every observed snapshot in on each fetch, keeping the
+    /// history of pruned snapshots (e.g. for the size trends of the status page and the
+    /// `kopia_history_*` trend metrics)
+    #[cfg(feature = "history")]
+    #[arg(long, value_name = "PATH", global = true)]
+    history_db: Option<std::path::PathBuf>,
+
+    /// Days of history (ending now) for the `kopia_history_*` trend metrics, with --history-db
+    #[cfg(feature = "history")]
+    #[arg(
+        long,
+        value_name = "DAYS",
+        default_value_t = SnapshotHistory::DEFAULT_WINDOW_DAYS,
+        global = true
+    )]
+    history_window_days: u32,
+
+    /// Keep only the snapshots needed for metrics while parsing (oldest, latest and
+    /// previous per source), counting the rest. Reduces memory use for large repositories,
+    /// but `/snapshots.ndjson` lists only the kept snapshots
+    #[arg(long, global = true)]
+    aggregate_only: bool,
+
+    /// Keep only the oldest plus the newest N snapshots of each source while parsing,
+    /// counting the rest, like --aggregate-only with more previous snapshots
+    #[arg(
+        long,
+        value_name = "N",
+        global = true,
+        conflicts_with = "aggregate_only"
+    )]
+    max_snapshots_per_source: Option<std::num::NonZeroUsize>,
+
+    #[command(flatten)]
+    thresholds: ThresholdArgs,
+
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+impl Args {
+    /// Parses the arguments, rejecting `serve` options given before another command
+    fn try_parse_checked_from(
+        itr: impl IntoIterator<Item = impl Into<std::ffi::OsString> + Clone>,
+    ) -> Result<Self, clap::Error> {
+        use clap::{CommandFactory as _, FromArgMatches as _};
+
+        let matches = Self::command().try_get_matches_from(itr)?;
+        let args = Self::from_arg_matches(&matches)?;
+        if let Some((command, _)) = matches.subcommand()
+            && let Some(group_id) = <ServeArgs as clap::Args>::group_id()
+            && let Some(mut ids) = matches.get_many::<clap::Id>(group_id.as_str())
+            && let Some(id) = ids.find(|id| {
+                matches.value_source(id.as_str()) == Some(clap::parser::ValueSource::CommandLine)
+            })
+        {
+            let option = id.as_str().replace('_', "-");
+            return Err(Self::command().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!("the option '--{option}' is not accepted before the '{command}' command"),
+            ));
+        }
+        Ok(args)
+    }
+
+    /// Returns the settings for fetching the snapshots
+    fn fetch_config(&self) -> eyre::Result<FetchConfig> {
+        let mut kopia = KopiaCommand::new(self.kopia_bin.clone());
+        if let Some(adjustment) = self.kopia_nice {
+            kopia = kopia.with_nice(adjustment);
+        }
+        if self.kopia_ionice_idle {
+            kopia = kopia.with_ionice_idle();
+        }
+        let env_file = self
+            .kopia_env_file
+            .as_deref()
+            .map(read_env_file)
+            .transpose()?
+            .unwrap_or_default();
+        for (key, value) in env_file.into_iter().chain(self.kopia_env.iter().cloned()) {
+            kopia = kopia.with_env(key, value);
+        }
+        if let Some(path) = &self.kopia_audit_log {
+            kopia = kopia.with_audit_log(path);
+        }
+        kopia = kopia.with_stderr_limit(self.kopia_stderr_limit);
+        #[cfg(feature = "history")]
+        let history_db = self
+            .history_db
+            .as_deref()
+            .map(|path| {
+                HistoryDb::open(path)
+                    .wrap_err_with(|| format!("Failed to open history database {}", path.display()))
+            })
+            .transpose()?
+            .map(|db| Arc::new(std::sync::Mutex::new(db)));
+        Ok(FetchConfig {
+            kopia,
+            snapshots_file: self.snapshots_file.clone(),
+            kopia_timeout: Duration::from_secs_f64(self.timeout),
+            parse_options: if self.aggregate_only {
+                ParseOptions::aggregate_only(self.latest_policy)
+            } else {
+                ParseOptions {
+                    latest_policy: self.latest_policy,
+                    max_snapshots_per_source: self.max_snapshots_per_source,
+                }
+            },
+            max_sources: self.max_sources,
+            repo_id: self.repo_id.clone(),
+            source_rollups: self.source_rollups,
+            repo_quota_bytes: self.repo_quota_bytes,
+            expected_retention: self.expected_retention.clone(),
+            expected_intervals: self.expected_intervals.clone(),
+            #[cfg(feature = "history")]
+            history_db,
+            #[cfg(feature = "history")]
+            history_window_days: self.history_window_days,
+            health_thresholds: self.thresholds.to_thresholds(),
+            stats: Arc::default(),
+        })
+    }
+}
+
+// Options of the `serve` command, also accepted without a command (not a doc comment, which
+// would replace the `about` of the flattening command)
+#[derive(clap::Args, Debug)]
+#[expect(clippy::struct_excessive_bools)] // independent command-line flags
+struct ServeArgs {
+    /// Server bind address, as IP:PORT or HOST:PORT (resolved at startup)
     #[arg(short, long, default_value = "127.0.0.1:9090")]
     bind: String,
 
     /// Cache duration in seconds (0 to disable)
+    ///
+    /// Once expired, cached data is still served while refreshing in the background.
+    /// The `kopia_exporter_cache_{hits,misses}_total` and `kopia_exporter_data_age_seconds`
+    /// metrics show the effect of this setting (and `kopia_exporter_cached_failures_total`
+    /// of --error-cache-seconds).
     #[arg(short, long, default_value = "30")]
     cache_seconds: u64,
 
-    /// Maximum number of bind retry attempts (0 = no retries, just 1 attempt)
+    /// Duration in seconds to cache kopia failures (0 to disable), before running kopia again
+    #[arg(long, default_value = "5")]
+    error_cache_seconds: u64,
+
+    /// Append the fetch time as a timestamp to every sample served on `/metrics`, to
+    /// attribute cached data to when it was fetched rather than to the scrape time
+    #[arg(long)]
+    sample_timestamps: bool,
+
+    /// Fetch snapshots once at startup before serving requests, with this timeout in seconds
+    /// (instead of --timeout), so the first scrape is served from the cache
+    #[arg(long)]
+    warm_up_timeout: Option<f64>,
+
+    /// Maximum number of bind retry attempts while the port is in use (0 = no retries, just 1
+    /// attempt)
+    ///
+    /// Also retries while no local interface has the address (e.g. a VPN interface coming
+    /// up), but not on permission denied.
     #[arg(short = 'r', long, default_value = "5")]
     max_bind_retries: u32,
 
@@ -40,9 +311,435 @@ struct Args {
     #[arg(long)]
     auth_credentials_file: Option<String>,
 
-    /// Timeout in seconds for kopia command execution
-    #[arg(short = 't', long, default_value = "15.0")]
-    timeout: f64,
+    /// InfluxDB/VictoriaMetrics write URL to periodically push metrics in line protocol
+    /// (e.g. `http://influxdb:8086/api/v2/write?org=home&bucket=kopia`)
+    #[arg(long)]
+    influx_url: Option<String>,
+
+    /// Path to file containing the Influx API token, sent as `Authorization: Token <token>`
+    #[arg(long, requires = "influx_url")]
+    influx_token_file: Option<String>,
+
+    /// Prometheus remote write URL to periodically push all metrics
+    /// (e.g. `http://prometheus:9090/api/v1/write`)
+    #[arg(long)]
+    remote_write_url: Option<String>,
+
+    /// Path to file containing username:password for remote write basic auth
+    #[arg(long, requires = "remote_write_url")]
+    remote_write_credentials_file: Option<String>,
+
+    /// Prometheus Pushgateway base URL to periodically push all metrics
+    /// (e.g. `http://pushgateway:9091`)
+    #[arg(long)]
+    pushgateway_url: Option<String>,
+
+    /// Pushgateway job label
+    #[arg(long, default_value = "kopia_exporter", requires = "pushgateway_url")]
+    pushgateway_job: String,
+
+    /// Pushgateway instance label (omitted by default)
+    #[arg(long, requires = "pushgateway_url")]
+    pushgateway_instance: Option<String>,
+
+    /// Path to periodically (and atomically) write the metrics for the `node_exporter`
+    /// textfile collector (e.g. `/var/lib/node_exporter/kopia.prom`)
+    #[arg(long)]
+    textfile_output: Option<std::path::PathBuf>,
+
+    /// MQTT broker address (host:port) to periodically publish per-source backup freshness
+    /// and size, with Home Assistant discovery
+    #[arg(long)]
+    mqtt_addr: Option<String>,
+
+    /// Path to file containing username:password for the MQTT broker
+    #[arg(long, requires = "mqtt_addr")]
+    mqtt_credentials_file: Option<String>,
+
+    /// Topic prefix for the MQTT state topics
+    #[arg(long, default_value = "kopia_exporter", requires = "mqtt_addr")]
+    mqtt_topic_prefix: String,
+
+    /// Home Assistant MQTT discovery prefix
+    #[arg(long, default_value = "homeassistant", requires = "mqtt_addr")]
+    mqtt_discovery_prefix: String,
+
+    /// statsd server address (host:port) to periodically send the key gauges over UDP
+    #[arg(long)]
+    statsd_addr: Option<String>,
+
+    /// statsd line format (statsd, dogstatsd), dogstatsd sends labels as tags
+    #[arg(long, default_value = "dogstatsd")]
+    statsd_flavor: StatsdFlavor,
+
+    /// Webhook URL (http:// or https://) to POST a JSON notification when a source changes
+    /// between healthy and unhealthy (e.g. a Discord or Slack webhook)
+    #[arg(long)]
+    webhook_url: Option<String>,
+
+    /// Number of consecutive push intervals a source must be unhealthy before notifying
+    #[arg(long, default_value = "1")]
+    notify_unhealthy_after: u32,
+
+    /// Number of consecutive push intervals a source must be healthy before notifying
+    /// about the recovery
+    #[arg(long, default_value = "1")]
+    notify_healthy_after: u32,
+
+    /// Heartbeat URL (http:// or https://) to ping each push interval, only while all sources
+    /// are healthy (e.g. healthchecks.io), so a missing ping alerts even if the exporter dies
+    #[arg(long)]
+    heartbeat_url: Option<String>,
+
+    /// ntfy server URL (http:// or https://, e.g. `https://ntfy.sh`) to publish notifications
+    /// about health changes, requires --ntfy-topic
+    #[arg(long, requires = "ntfy_topic")]
+    ntfy_server: Option<String>,
+
+    /// ntfy topic for notifications
+    #[arg(long, requires = "ntfy_server")]
+    ntfy_topic: Option<String>,
+
+    /// Path to a file containing the ntfy access token
+    #[arg(long, requires = "ntfy_server")]
+    ntfy_token_file: Option<String>,
+
+    /// Gotify server URL (http:// or https://) to send notifications about health changes,
+    /// requires --gotify-token-file
+    #[arg(long, requires = "gotify_token_file")]
+    gotify_server: Option<String>,
+
+    /// Path to a file containing the Gotify application token
+    #[arg(long, requires = "gotify_server")]
+    gotify_token_file: Option<String>,
+
+    /// OTLP/HTTP traces URL (http://) to export spans of fetching and responding, in
+    /// batches as JSON (e.g. `http://otel-collector:4318/v1/traces`)
+    #[cfg(feature = "otel")]
+    #[arg(long)]
+    otlp_traces_url: Option<String>,
+
+    /// Interval in seconds between metrics pushes (and textfile writes)
+    ///
+    /// Pushes share the snapshots cached for --cache-seconds with the HTTP endpoints.
+    #[arg(long, visible_alias = "interval", default_value = "60")]
+    push_interval: u64,
+
+    /// Disable the HTTP server, only push metrics to the configured outputs
+    #[arg(long)]
+    no_http: bool,
+
+    /// Check the configuration, the kopia binary and one fetch of the snapshots, print a
+    /// summary, then exit without binding the port or pushing metrics
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Write the process ID to this file once the port is bound (or on startup with
+    /// --no-http), removing it on SIGTERM or SIGINT
+    #[arg(long)]
+    pid_file: Option<std::path::PathBuf>,
+
+    /// Serve `POST /-/quit` to shut down gracefully (as on SIGTERM), e.g. where signals
+    /// cannot be sent across container boundaries. Requires basic auth
+    #[arg(long)]
+    enable_quit: bool,
+}
+
+impl ServeArgs {
+    /// Returns the destinations of the periodic pushes
+    fn push_config(&self) -> eyre::Result<PushConfig> {
+        let influx = self
+            .influx_url
+            .as_deref()
+            .map(|url| {
+                let target = PushTarget::parse(url)?;
+                let Some(token_file) = &self.influx_token_file else {
+                    return Ok(target);
+                };
+                let token = std::fs::read_to_string(token_file).map_err(|e| {
+                    eyre::eyre!("Failed to read influx token file '{token_file}': {e}")
+                })?;
+                Ok::<_, eyre::Report>(
+                    target.with_header("Authorization", format!("Token {}", token.trim())),
+                )
+            })
+            .transpose()?;
+        let remote_write = self
+            .remote_write_url
+            .as_deref()
+            .map(|url| {
+                let target = PushTarget::parse(url)?
+                    .with_header("Content-Encoding", "snappy")
+                    .with_header("X-Prometheus-Remote-Write-Version", "0.1.0");
+                let Some(file_path) = &self.remote_write_credentials_file else {
+                    return Ok(target);
+                };
+                let content = std::fs::read_to_string(file_path).map_err(|e| {
+                    eyre::eyre!("Failed to read remote write credentials file '{file_path}': {e}")
+                })?;
+                let credentials = content.trim();
+                if !credentials.contains(':') {
+                    return Err(eyre::eyre!(
+                        "Remote write credentials file must contain 'username:password'"
+                    ));
+                }
+                let encoded = BASE64_STANDARD.encode(credentials);
+                Ok(target.with_header("Authorization", format!("Basic {encoded}")))
+            })
+            .transpose()?;
+        let pushgateway = self
+            .pushgateway_url
+            .as_deref()
+            .map(|url| {
+                PushTarget::pushgateway(
+                    url,
+                    &self.pushgateway_job,
+                    self.pushgateway_instance.as_deref(),
+                )
+            })
+            .transpose()?;
+        let textfile = self.textfile_output.as_ref().map(TextfileTarget::new);
+        let mqtt = self
+            .mqtt_addr
+            .as_deref()
+            .map(|addr| {
+                let target = MqttTarget::new(addr, "kopia-exporter");
+                let target = match &self.mqtt_credentials_file {
+                    Some(file_path) => {
+                        let content = std::fs::read_to_string(file_path).map_err(|e| {
+                            eyre::eyre!("Failed to read MQTT credentials file '{file_path}': {e}")
+                        })?;
+                        let Some((username, password)) = content.trim().split_once(':') else {
+                            return Err(eyre::eyre!(
+                                "MQTT credentials file must contain 'username:password'"
+                            ));
+                        };
+                        target.with_credentials(username.to_string(), password.to_string())
+                    }
+                    None => target,
+                };
+                let topics = HomeAssistantTopics {
+                    state_prefix: self.mqtt_topic_prefix.clone(),
+                    discovery_prefix: self.mqtt_discovery_prefix.clone(),
+                };
+                Ok((target, topics))
+            })
+            .transpose()?;
+        let statsd = self
+            .statsd_addr
+            .as_deref()
+            .map(StatsdTarget::connect)
+            .transpose()?
+            .map(|target| (target, self.statsd_flavor));
+        Ok(PushConfig {
+            interval: Duration::from_secs(self.push_interval),
+            influx,
+            remote_write,
+            pushgateway,
+            textfile,
+            mqtt,
+            statsd,
+            notify: self.notify_config()?,
+        })
+    }
+
+    /// Returns the notifications about the backup health
+    fn notify_config(&self) -> eyre::Result<NotifyConfig> {
+        let webhook = self
+            .webhook_url
+            .as_deref()
+            .map(PushTarget::parse)
+            .transpose()?;
+        let heartbeat = self
+            .heartbeat_url
+            .as_deref()
+            .map(PushTarget::parse)
+            .transpose()?;
+        let read_token = |token_file: &str, service: &str| {
+            std::fs::read_to_string(token_file)
+                .map(|token| token.trim().to_string())
+                .map_err(|e| eyre::eyre!("Failed to read {service} token file '{token_file}': {e}"))
+        };
+        let ntfy = match (&self.ntfy_server, &self.ntfy_topic) {
+            (Some(server), Some(topic)) => {
+                let target = NtfyTarget::new(server, topic)?;
+                Some(match &self.ntfy_token_file {
+                    Some(token_file) => target.with_token(&read_token(token_file, "ntfy")?),
+                    None => target,
+                })
+            }
+            _ => None,
+        };
+        let gotify = match (&self.gotify_server, &self.gotify_token_file) {
+            (Some(server), Some(token_file)) => Some(GotifyTarget::new(
+                server,
+                &read_token(token_file, "Gotify")?,
+            )?),
+            _ => None,
+        };
+        Ok(NotifyConfig {
+            unhealthy_after: self.notify_unhealthy_after,
+            healthy_after: self.notify_healthy_after,
+            webhook,
+            heartbeat,
+            ntfy,
+            gotify,
+        })
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Serve metrics over HTTP and push to the configured outputs (default command)
+    Serve(Box<ServeArgs>),
+    /// Fetch snapshots once, then print the metrics to stdout
+    Print {
+        /// Output format
+        #[arg(long, default_value = "prometheus")]
+        format: PrintFormat,
+    },
+    /// Check backup health against the thresholds once, then exit
+    Check {
+        /// Output format
+        ///
+        /// Both formats exit with 0 (ok), 1 (warning), 2 (critical) or 3 (unknown, e.g. no
+        /// snapshots or failed to fetch)
+        #[arg(long, default_value = "human")]
+        format: CheckFormat,
+    },
+    /// Parse a saved `kopia snapshot list --json` output, and report data-quality findings
+    /// (invalid sources, bad timestamps, incomplete snapshots, duplicate IDs) for each source
+    ///
+    /// Exits with 1 if there are any findings.
+    Validate {
+        /// Path to the saved JSON output
+        file: std::path::PathBuf,
+    },
+    /// Compare two saved `kopia snapshot list --json` outputs, and print the changes of each
+    /// source (added and removed sources, latest size, retention, errors of new snapshots)
+    ///
+    /// Exits with 1 if any source changed, like `diff`.
+    Diff {
+        /// Path to the old JSON output
+        old: std::path::PathBuf,
+        /// Path to the new JSON output
+        new: std::path::PathBuf,
+        /// Output format
+        #[arg(long, default_value = "human")]
+        format: DiffFormat,
+    },
+    /// Print the version, git commit, build time, target and enabled features
+    Version,
+    /// Generate configuration for other tools
+    Generate {
+        #[command(subcommand)]
+        target: GenerateTarget,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum PrintFormat {
+    /// Prometheus text exposition format, as served on `/metrics`
+    Prometheus,
+    /// JSON array of samples, as served on `/metrics.json`
+    Json,
+    /// `InfluxDB` line protocol, as served on `/metrics.influx`
+    Influx,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum DiffFormat {
+    /// Each changed source followed by its retention changes
+    Human,
+    /// JSON object with the changes of each changed source
+    Json,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum GenerateTarget {
+    /// Prometheus alerting rules (YAML) for the thresholds, e.g. `generate alerts --max-age 26h`
+    Alerts,
+    /// Man page (roff) for the command line, printed to stdout
+    Man {
+        /// Write a man page for each command to this directory instead (e.g.
+        /// `kopia-exporter.1`, `kopia-exporter-serve.1`)
+        #[arg(long)]
+        out_dir: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum CheckFormat {
+    /// Summary line followed by the status of each source
+    Human,
+    /// Single status line with perfdata
+    Nagios,
+}
+
+// Backup health thresholds, checked against the latest snapshot of each source (not a doc
+// comment, see `ServeArgs`)
+#[derive(clap::Args, Debug)]
+struct ThresholdArgs {
+    /// Critical if the latest snapshot is older than this (e.g. 26h, 1d 12h)
+    #[arg(long, value_parser = health::parse_duration, global = true)]
+    max_age: Option<jiff::SignedDuration>,
+
+    /// Warning if the latest snapshot is older than this (e.g. 25h)
+    #[arg(long, value_parser = health::parse_duration, global = true)]
+    warn_max_age: Option<jiff::SignedDuration>,
+
+    /// Critical if the latest snapshot has more errors than this
+    #[arg(long, global = true)]
+    max_errors: Option<u64>,
+
+    /// Warning if the latest snapshot has more errors than this
+    #[arg(long, global = true)]
+    warn_max_errors: Option<u64>,
+
+    /// Critical if the latest snapshot is smaller than this many bytes
+    #[arg(long, global = true)]
+    min_size: Option<u64>,
+
+    /// Warning if the latest snapshot is smaller than this many bytes
+    #[arg(long, global = true)]
+    warn_min_size: Option<u64>,
+
+    /// Silence health problems of a source (or "*" for all) during a window, e.g.
+    /// "*=sun 01:00-05:00" (UTC, recurring) or
+    /// "user@host:/path=2025-08-14T20:00:00Z/2025-08-15T06:00:00Z" (absolute)
+    ///
+    /// Metrics are still exported while silenced. May be specified multiple times.
+    #[arg(long = "silence", global = true)]
+    silences: Vec<SilenceWindow>,
+}
+impl ThresholdArgs {
+    /// Returns the configured thresholds, or `None` if no thresholds are set
+    fn to_thresholds(&self) -> Option<HealthThresholds> {
+        let Self {
+            max_age,
+            warn_max_age,
+            max_errors,
+            warn_max_errors,
+            min_size,
+            warn_min_size,
+            ref silences,
+        } = *self;
+        let any_set = max_age.is_some()
+            || warn_max_age.is_some()
+            || max_errors.is_some()
+            || warn_max_errors.is_some()
+            || min_size.is_some()
+            || warn_min_size.is_some();
+        any_set.then_some(HealthThresholds {
+            max_age,
+            warn_max_age,
+            max_errors,
+            warn_max_errors,
+            min_size,
+            warn_min_size,
+            silences: silences.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -52,7 +749,7 @@ struct BasicAuthConfig {
 }
 
 impl BasicAuthConfig {
-    fn from_args(args: &Args) -> eyre::Result<Option<Self>> {
+    fn from_args(args: &ServeArgs) -> eyre::Result<Option<Self>> {
         match (
             &args.auth_username,
             &args.auth_password,
@@ -106,20 +803,6 @@ impl BasicAuthConfig {
     }
 }
 
-#[derive(Debug, Clone)]
-struct TimedSnapshots {
-    snapshots: KopiaSnapshots,
-    created_at: Instant,
-}
-impl TimedSnapshots {
-    fn now(snapshots: KopiaSnapshots) -> Self {
-        Self {
-            snapshots,
-            created_at: Instant::now(),
-        }
-    }
-}
-
 fn send_unauthorized_response(request: tiny_http::Request) {
     let header = Header::from_bytes(
         &b"WWW-Authenticate"[..],
@@ -132,90 +815,511 @@ fn send_unauthorized_response(request: tiny_http::Request) {
     let _ = request.respond(response);
 }
 
+/// Parses a `KEY=VALUE` environment variable for `kopia`
+fn parse_env_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() && !key.contains(char::is_whitespace) => {
+            Ok((key.to_owned(), value.to_owned()))
+        }
+        _ => Err(format!("expected KEY=VALUE, found {s:?}")),
+    }
+}
+
+fn parse_expected_retention(s: &str) -> Result<(String, u32), String> {
+    match s.split_once('=') {
+        Some((class, count)) if !class.is_empty() && !class.contains(char::is_whitespace) => {
+            let count = count
+                .parse()
+                .map_err(|e| format!("invalid count {count:?}: {e}"))?;
+            Ok((class.to_owned(), count))
+        }
+        _ => Err(format!("expected CLASS=COUNT, found {s:?}")),
+    }
+}
+
+fn read_env_file(path: &std::path::Path) -> eyre::Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| eyre::eyre!("Failed to read kopia env file {}: {e}", path.display()))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(index, line)| {
+            parse_env_var(line.trim())
+                .map_err(|e| eyre::eyre!("{}:{}: {e}", path.display(), index + 1))
+        })
+        .collect()
+}
+
+/// Endpoints serving data derived from the (cached) snapshots
+#[derive(Clone, Copy, Debug)]
+enum SnapshotsEndpoint {
+    Prometheus,
+    /// Prometheus metrics of the source in the rest of the path (percent-encoded)
+    SourcePrometheus,
+    Json,
+    Influx,
+    SnapshotsNdjson,
+    Health,
+    Status,
+}
+impl SnapshotsEndpoint {
+    const ALL: [Self; 7] = [
+        Self::Prometheus,
+        Self::SourcePrometheus,
+        Self::Json,
+        Self::Influx,
+        Self::SnapshotsNdjson,
+        Self::Health,
+        Self::Status,
+    ];
+
+    const SOURCE_PATH_PREFIX: &str = "/metrics/source/";
+
+    fn from_url(url: &str) -> Option<Self> {
+        let path = url_path(url);
+        if path
+            .strip_prefix(Self::SOURCE_PATH_PREFIX)
+            .is_some_and(|source| !source.is_empty())
+        {
+            return Some(Self::SourcePrometheus);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|endpoint| endpoint.path() == path)
+    }
+    /// Returns the path, or the route of paths with parameters
+    fn path(self) -> &'static str {
+        match self {
+            Self::Prometheus => "/metrics",
+            Self::SourcePrometheus => "/metrics/source/{source}",
+            Self::Json => "/metrics.json",
+            Self::Influx => "/metrics.influx",
+            Self::SnapshotsNdjson => "/snapshots.ndjson",
+            Self::Health => "/health",
+            Self::Status => "/status",
+        }
+    }
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Prometheus | Self::SourcePrometheus | Self::Influx => "text/plain; charset=utf-8",
+            Self::Json | Self::Health => "application/json",
+            Self::SnapshotsNdjson => "application/x-ndjson",
+            Self::Status => "text/html; charset=utf-8",
+        }
+    }
+    /// Responds to the request, returning the status code
+    fn respond(
+        self,
+        request: tiny_http::Request,
+        fetched: &FetchedSnapshots,
+        now: jiff::Timestamp,
+    ) -> u16 {
+        let FetchedSnapshots {
+            snapshots,
+            prometheus,
+            timestamp,
+        } = fetched;
+        let header = Header::from_bytes(&b"Content-Type"[..], self.content_type().as_bytes())
+            .expect("Invalid header");
+        let output = match self {
+            Self::Prometheus => {
+                let Some(selection) = metrics_selection(request.url()) else {
+                    return respond_streaming(request, header, |writer| {
+                        prometheus.render_to(snapshots, now, writer)
+                    });
+                };
+                render(|| selection.with_timestamp(*timestamp).render(snapshots, now))
+            }
+            Self::SourcePrometheus => {
+                let source = url_path(request.url())
+                    .strip_prefix(Self::SOURCE_PATH_PREFIX)
+                    .and_then(percent_decode)
+                    .and_then(|source| {
+                        snapshots
+                            .sources()
+                            .find(|known| known.as_str() == source)
+                            .cloned()
+                    });
+                let Some(source) = source else {
+                    let response = Response::from_string("Unknown source").with_status_code(404);
+                    let _ = request.respond(response);
+                    return 404;
+                };
+                let selection = metrics_selection(request.url()).unwrap_or_default();
+                let selection = selection.with_source(source).with_timestamp(*timestamp);
+                render(|| selection.render(snapshots, now))
+            }
+            Self::Json => render(|| snapshots.generate_all_metrics_json(now)),
+            Self::Influx => render(|| snapshots.generate_all_metrics_influx(now)),
+            Self::SnapshotsNdjson => {
+                // stream with chunked encoding, without buffering the full output
+                let reader = snapshots.snapshots_ndjson_reader();
+                let response = Response::new(200.into(), vec![header], reader, None, None);
+                let _ = request.respond(response);
+                return 200;
+            }
+            Self::Health => {
+                // unconfigured thresholds only require sources to be present
+                let default_thresholds = HealthThresholds::default();
+                let thresholds = snapshots.health_thresholds().unwrap_or(&default_thresholds);
+                let report = render(|| snapshots.evaluate_health(now, thresholds));
+                let status_code = if report.is_healthy() { 200 } else { 503 };
+                let response = Response::from_string(report.to_json())
+                    .with_header(header)
+                    .with_status_code(status_code);
+                let _ = request.respond(response);
+                return status_code;
+            }
+            Self::Status => {
+                let default_thresholds = HealthThresholds::default();
+                let thresholds = snapshots.health_thresholds().unwrap_or(&default_thresholds);
+                render(|| snapshots.generate_status_html(now, thresholds))
+            }
+        };
+        let response = Response::from_string(output).with_header(header);
+        let _ = request.respond(response);
+        200
+    }
+}
+
+/// Renders a response body, traced as the `render` span with the `otel` feature
+fn render<T>(render_fn: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "otel")]
+    let _span = trace::Span::enter("render");
+    render_fn()
+}
+
+/// Decodes the `%XX` escapes of the URL path, if the result is valid UTF-8
+fn percent_decode(path: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            let hex = std::str::from_utf8(hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            decoded.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Returns the path of the URL, without the query
+fn url_path(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(path, _query)| path)
+}
+
+/// Responds with the output written by `write_fn` using chunked encoding, without buffering
+/// the full output, returning the status code
+fn respond_streaming(
+    request: tiny_http::Request,
+    header: Header,
+    write_fn: impl FnOnce(&mut std::io::PipeWriter) -> std::io::Result<()> + Send,
+) -> u16 {
+    let (reader, mut writer) = match std::io::pipe() {
+        Ok(pipe) => pipe,
+        Err(e) => {
+            eprintln!("Error creating response pipe: {e}");
+            let response = Response::from_string("Internal Server Error").with_status_code(500);
+            let _ = request.respond(response);
+            return 500;
+        }
+    };
+    #[cfg(feature = "otel")]
+    let parent = trace::current();
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            #[cfg(feature = "otel")]
+            let _span = trace::Span::enter_with_parent("render", parent);
+            // write errors only occur when the client disconnects (closing the reader)
+            let _ = write_fn(&mut writer);
+        });
+        let response = Response::new(200.into(), vec![header], reader, None, None);
+        let _ = request.respond(response);
+    });
+    200
+}
+
+/// Parses the `collect[]` query parameters selecting metric names or categories, if any
+fn metrics_selection(url: &str) -> Option<MetricsBuilder> {
+    let (_path, query) = url.split_once('?')?;
+    let selected: Vec<&str> = query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .filter(|(key, _)| *key == "collect[]" || key.eq_ignore_ascii_case("collect%5B%5D"))
+        .map(|(_, value)| value)
+        .collect();
+    if selected.is_empty() {
+        return None;
+    }
+    let builder = selected
+        .into_iter()
+        .fold(MetricsBuilder::new(), |builder, name| {
+            let category = MetricCategory::ALL
+                .iter()
+                .find(|category| category.name().eq_ignore_ascii_case(name));
+            match category {
+                Some(category) => builder.with_category(*category),
+                None => builder.with(name),
+            }
+        });
+    Some(builder)
+}
+
+/// Exports the recorded spans to the OTLP/HTTP collector in batches, in the background
+#[cfg(feature = "otel")]
+fn start_trace_export(target: PushTarget) -> eyre::Result<()> {
+    const BATCH_DELAY: Duration = Duration::from_secs(5);
+    const MAX_QUEUED_SPANS: usize = 2048;
+
+    let (span_tx, span_rx) = std::sync::mpsc::sync_channel(MAX_QUEUED_SPANS);
+    trace::set_sink(move |span| {
+        // drop spans while the collector is behind, rather than delaying responses
+        let _ = span_tx.try_send(span);
+    })?;
+    std::thread::spawn(move || {
+        while let Ok(first) = span_rx.recv() {
+            std::thread::sleep(BATCH_DELAY);
+            let spans: Vec<_> = std::iter::once(first).chain(span_rx.try_iter()).collect();
+            let body =
+                trace::encode_otlp_json(&spans, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            if let Err(e) = target.send(
+                "POST",
+                "application/json",
+                body.as_bytes(),
+                PushConfig::TIMEOUT,
+            ) {
+                eprintln!("Error exporting {} trace spans: {e}", spans.len());
+            }
+        }
+    });
+    Ok(())
+}
+
+fn push_loop(cache: &SnapshotCache, push_config: &PushConfig) {
+    let mut tracker = push_config.notify.tracker();
+    loop {
+        match cache.get_fresh() {
+            Ok(fetched) => {
+                push_config.push(&fetched.snapshots, jiff::Timestamp::now(), &mut tracker);
+            }
+            Err(e) => eprintln!("Error fetching snapshots for push: {e}"),
+        }
+        std::thread::sleep(push_config.interval);
+    }
+}
+
+/// Path of the endpoint shutting down the server, if enabled by `--enable-quit`
+const QUIT_PATH: &str = "/-/quit";
+
+/// Serves requests until a quit request (if enabled) is accepted
 #[expect(clippy::needless_pass_by_value)] // Server is consumed by incoming_requests()
 fn serve_requests(
     server: Server,
-    kopia_bin: &str,
-    cache_duration: Duration,
-    kopia_timeout: Duration,
+    cache: &SnapshotCache,
     auth: Option<BasicAuthConfig>,
+    enable_quit: bool,
 ) {
-    let mut cache: Option<TimedSnapshots> = None;
+    let stats = &cache.fetch_config().stats;
     for request in server.incoming_requests() {
-        // Check authentication if configured
-        if let Some(ref auth_config) = auth
-            && !auth_config.validate_request(&request)
-        {
-            send_unauthorized_response(request);
-            continue;
+        let start = Instant::now();
+        let endpoint = SnapshotsEndpoint::from_url(request.url());
+        // bounded, as any other path is a single label value
+        let path = match (endpoint, url_path(request.url())) {
+            (Some(endpoint), _) => endpoint.path(),
+            (None, "/") => "/",
+            (None, QUIT_PATH) => QUIT_PATH,
+            (None, _) => "other",
+        };
+        let quit = enable_quit && path == QUIT_PATH && *request.method() == Method::Post;
+        #[cfg(feature = "otel")]
+        let mut span = trace::Span::enter("respond");
+        #[cfg(feature = "otel")]
+        span.set_attribute("http.route", path);
+
+        let status_code = catch_request_panic(stats, || {
+            handle_request(request, endpoint, cache, auth.as_ref(), enable_quit, start)
+        });
+        stats.record_http_request(path, status_code, start.elapsed());
+        #[cfg(feature = "otel")]
+        span.set_attribute("http.response.status_code", status_code);
+        if quit && status_code == 200 {
+            println!("Received quit request, shutting down");
+            break;
         }
+    }
+}
 
-        match (request.method(), request.url()) {
-            (&Method::Get, "/metrics") => {
-                // 1. Check if cached value is available (clear if expired)
-                if let Some(cached) = &cache
-                    && cached.created_at.elapsed() >= cache_duration
-                {
-                    cache = None; // Clear expired cache
-                }
+/// Runs the request handler, returning its status code
+///
+/// A panic is counted and the handler's request dropped while unwinding (which responds 500),
+/// rather than ending the serving thread and leaving the process running without serving.
+fn catch_request_panic(stats: &ExporterStats, handle: impl FnOnce() -> u16) -> u16 {
+    // shared state stays consistent, as the locks ignore poisoning
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(handle)).unwrap_or_else(|_| {
+        stats.record_internal_panic();
+        500
+    })
+}
 
-                // 2. Get snapshots (from cache or fresh fetch)
-                let current = cache.take().map_or_else(
-                    || {
-                        KopiaSnapshots::new_from_command(
-                            kopia_bin,
-                            kopia_timeout,
-                            |e: kopia_exporter::kopia::SourceStrError| {
-                                // log data errors but otherwise ignore
-                                eprintln!("{:?}", eyre::eyre!(e));
-                                Ok(())
-                            },
-                        )
-                        .map(TimedSnapshots::now)
-                    },
-                    Ok,
-                );
-
-                // 3. Serve the result
-                match &current {
-                    Ok(TimedSnapshots { snapshots, .. }) => {
-                        let now = jiff::Timestamp::now();
-                        let metrics_output = snapshots.generate_all_metrics(now);
-                        let header = Header::from_bytes(
-                            &b"Content-Type"[..],
-                            &b"text/plain; charset=utf-8"[..],
-                        )
-                        .expect("Invalid header");
-                        let response = Response::from_string(metrics_output).with_header(header);
-                        let _ = request.respond(response);
-                    }
-                    Err(e) => {
-                        eprintln!("Error fetching snapshots: {e}");
-                        let error_response =
-                            Response::from_string("Error fetching metrics").with_status_code(500);
-                        let _ = request.respond(error_response);
-                    }
-                }
+/// Responds to the request, returning the status code
+fn handle_request(
+    request: tiny_http::Request,
+    endpoint: Option<SnapshotsEndpoint>,
+    cache: &SnapshotCache,
+    auth: Option<&BasicAuthConfig>,
+    enable_quit: bool,
+    start: Instant,
+) -> u16 {
+    // Check authentication if configured
+    if let Some(auth_config) = auth
+        && !auth_config.validate_request(&request)
+    {
+        send_unauthorized_response(request);
+        return 401;
+    }
 
-                // 4. Store result in cache (if successful and cache enabled)
-                if let Ok(current) = current
-                    && !cache_duration.is_zero()
-                {
-                    cache = Some(current);
+    match (request.method(), endpoint, request.url()) {
+        (&Method::Get, Some(endpoint), _) => {
+            let status_code = match cache.get() {
+                Ok(snapshots) => endpoint.respond(request, &snapshots, jiff::Timestamp::now()),
+                Err(e) => {
+                    eprintln!("Error fetching snapshots: {e}");
+                    let error_response =
+                        Response::from_string("Error fetching metrics").with_status_code(500);
+                    let _ = request.respond(error_response);
+                    500
                 }
+            };
+            if matches!(endpoint, SnapshotsEndpoint::Prometheus) {
+                cache.fetch_config().stats.record_scrape(start.elapsed());
             }
-            (&Method::Get, "/") => {
-                let html = include_str!("index.html");
-                let header =
-                    Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..])
-                        .expect("Invalid header");
-                let response = Response::from_string(html).with_header(header);
-                let _ = request.respond(response);
+            status_code
+        }
+        (&Method::Get, None, "/") => {
+            let html = include_str!("index.html");
+            let header = Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..])
+                .expect("Invalid header");
+            let response = Response::from_string(html).with_header(header);
+            let _ = request.respond(response);
+            200
+        }
+        (&Method::Post, None, QUIT_PATH) if enable_quit => {
+            let _ = request.respond(Response::from_string("Shutting down"));
+            200
+        }
+        _ => {
+            let response = Response::from_string("Not Found").with_status_code(404);
+            let _ = request.respond(response);
+            404
+        }
+    }
+}
+
+/// Category of a run-level failure, determining the exit code
+///
+/// Lets restart policies tell configuration errors (which a restart will not fix) from
+/// transient failures, see [`EXIT_CODES_HELP`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    /// Invalid configuration
+    Config,
+    /// Failed to bind the HTTP port
+    Bind,
+    /// The kopia binary is missing, or listing the snapshots failed
+    ///
+    /// Only returned by the one-shot `print` and `serve --dry-run`, since `serve` keeps running
+    /// and reports failures to fetch in the metrics instead.
+    Kopia,
+}
+impl Failure {
+    /// Exit code from `sysexits.h`
+    fn exit_code(self) -> u8 {
+        match self {
+            Self::Config => 78, // EX_CONFIG
+            Self::Bind => 75,   // EX_TEMPFAIL
+            Self::Kopia => 69,  // EX_UNAVAILABLE
+        }
+    }
+
+    /// Returns the exit code for the error, categorized by [`WrapErr::wrap_err`](eyre::WrapErr::wrap_err)
+    fn exit_code_for(report: &eyre::Report) -> ExitCode {
+        report
+            .downcast_ref::<Self>()
+            .map_or(ExitCode::FAILURE, |failure| {
+                ExitCode::from(failure.exit_code())
+            })
+    }
+}
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config => write!(f, "Invalid configuration"),
+            Self::Bind => write!(f, "Failed to start the HTTP server"),
+            Self::Kopia => write!(f, "Kopia unavailable"),
+        }
+    }
+}
+
+/// Process ID file, removed when dropped
+struct PidFile {
+    path: std::path::PathBuf,
+}
+impl PidFile {
+    fn create(path: &std::path::Path) -> eyre::Result<Self> {
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .map_err(|e| eyre::eyre!("Failed to write PID file {}: {e}", path.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+        })
+    }
+
+    /// Removes the file and exits on the first SIGTERM or SIGINT, or removes the file once
+    /// the returned guard is [released](PidFileGuard::release)
+    fn remove_on_signal(self) -> eyre::Result<PidFileGuard> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+
+        let mut signals = signal_hook::iterator::Signals::new([SIGTERM, SIGINT])?;
+        let handle = signals.handle();
+        let thread = std::thread::spawn(move || {
+            let signal = signals.forever().next();
+            if let Some(signal) = signal {
+                println!("Received signal {signal}, shutting down");
             }
-            _ => {
-                let response = Response::from_string("Not Found").with_status_code(404);
-                let _ = request.respond(response);
+            drop(self);
+            if signal.is_some() {
+                std::process::exit(0);
             }
+        });
+        Ok(PidFileGuard { handle, thread })
+    }
+}
+/// Signal handling thread of a [`PidFile`]
+struct PidFileGuard {
+    handle: signal_hook::iterator::Handle,
+    thread: std::thread::JoinHandle<()>,
+}
+impl PidFileGuard {
+    /// Stops handling signals and removes the file, when shutting down otherwise
+    fn release(self) {
+        let Self { handle, thread } = self;
+        handle.close();
+        let _ = thread.join();
+    }
+}
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            eprintln!("Failed to remove PID file {}: {e}", self.path.display());
         }
     }
 }
@@ -224,31 +1328,76 @@ fn calculate_delay_seconds(attempt: u32) -> u64 {
     (1u64 << (attempt - 1)).min(16) // 1, 2, 4, 8, 16, 16, 16... seconds (capped at 16)
 }
 
-fn start_server_with_retry(bind_addr: &str, max_retries: u32) -> eyre::Result<Server> {
+/// Resolves the `--bind` address, which may name a host (e.g. `localhost:9090`)
+fn resolve_bind_addr(bind_addr: &str) -> eyre::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = bind_addr
+        .to_socket_addrs()
+        .map_err(|e| eyre::eyre!("Invalid bind address {bind_addr:?}, expected HOST:PORT: {e}"))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(eyre::eyre!(
+            "Invalid bind address {bind_addr:?}: resolved to no addresses"
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Describes the bind error, and whether retrying may succeed (e.g. once another process
+/// releases the port, or the network interface of the address comes up)
+fn describe_bind_error(error: &std::io::Error, addrs: &[SocketAddr]) -> (String, bool) {
+    use std::io::ErrorKind;
+    match error.kind() {
+        ErrorKind::AddrInUse => (format!("address already in use ({error})"), true),
+        ErrorKind::AddrNotAvailable => (
+            format!("cannot assign the address, no local interface has it (yet) ({error})"),
+            true,
+        ),
+        ErrorKind::PermissionDenied if addrs.iter().any(|addr| addr.port() < 1024) => (
+            format!(
+                "permission denied, ports below 1024 require privileges (e.g. CAP_NET_BIND_SERVICE) ({error})"
+            ),
+            false,
+        ),
+        ErrorKind::PermissionDenied => (format!("permission denied ({error})"), false),
+        _ => (error.to_string(), true),
+    }
+}
+
+fn start_server_with_retry(
+    bind_addr: &str,
+    addrs: &[SocketAddr],
+    max_retries: u32,
+) -> eyre::Result<Server> {
     let mut attempt = 1;
     let mut retries_remaining = max_retries;
 
     loop {
         // 1. First attempt (or retry attempt)
-        match Server::http(bind_addr) {
-            Ok(server) => {
+        match TcpListener::bind(addrs) {
+            Ok(listener) => {
                 if attempt > 1 {
                     println!("Successfully bound to {bind_addr} on attempt {attempt}");
                 }
-                return Ok(server);
+                return Server::from_listener(listener, None)
+                    .map_err(|e| eyre::eyre!("Failed to start server on {bind_addr}: {e}"));
             }
             Err(e) => {
-                // 2. If fails, check retries remaining
+                let (reason, retryable) = describe_bind_error(&e, addrs);
+                // 2. If fails permanently, return error without retrying
+                if !retryable {
+                    return Err(eyre::eyre!("Failed to bind to {bind_addr}: {reason}"));
+                }
+                // 3. If fails, check retries remaining
                 if retries_remaining == 0 {
-                    // 4. If exhausted, return error
+                    // 5. If exhausted, return error
                     return Err(eyre::eyre!(
-                        "Failed to bind to {bind_addr} after {attempt} attempts: {e}"
+                        "Failed to bind to {bind_addr} after {attempt} attempts: {reason}"
                     ));
                 }
 
-                // 3. If allowed, delay and continue
+                // 4. If allowed, delay and continue
                 let delay_secs = calculate_delay_seconds(attempt);
-                eprintln!("Bind attempt {attempt} failed: {e}. Retrying in {delay_secs}s...");
+                eprintln!("Bind attempt {attempt} failed: {reason}. Retrying in {delay_secs}s...");
                 std::thread::sleep(Duration::from_secs(delay_secs));
 
                 attempt += 1;
@@ -258,31 +1407,289 @@ fn start_server_with_retry(bind_addr: &str, max_retries: u32) -> eyre::Result<Se
     }
 }
 
-fn main() -> eyre::Result<()> {
-    let args = Args::parse();
+fn run_check(
+    fetch_config: &FetchConfig,
+    thresholds: &HealthThresholds,
+    format: CheckFormat,
+) -> ExitCode {
+    match fetch_config.fetch() {
+        Ok(snapshots) => {
+            let report = snapshots.evaluate_health(jiff::Timestamp::now(), thresholds);
+            match format {
+                CheckFormat::Human => println!("{}", report.to_summary()),
+                CheckFormat::Nagios => println!("{}", report.to_nagios()),
+            }
+            ExitCode::from(report.status().nagios_exit_code())
+        }
+        Err(e) => {
+            let status = health::HealthStatus::Unknown;
+            match format {
+                CheckFormat::Human => eprintln!("{status}: failed to fetch snapshots: {e}"),
+                CheckFormat::Nagios => println!("KOPIA {status} - failed to fetch snapshots: {e}"),
+            }
+            ExitCode::from(status.nagios_exit_code())
+        }
+    }
+}
+
+fn run_print(fetch_config: &FetchConfig, format: PrintFormat) -> eyre::Result<()> {
+    let snapshots = fetch_config.fetch().wrap_err(Failure::Kopia)?;
+    let now = jiff::Timestamp::now();
+    let mut stdout = std::io::stdout().lock();
+    match format {
+        PrintFormat::Prometheus => snapshots.generate_all_metrics_to(now, &mut stdout)?,
+        PrintFormat::Json => write!(stdout, "{}", snapshots.generate_all_metrics_json(now))?,
+        PrintFormat::Influx => write!(stdout, "{}", snapshots.generate_all_metrics_influx(now))?,
+    }
+    writeln!(stdout)?;
+    Ok(())
+}
+
+fn run_validate(file: &std::path::Path) -> eyre::Result<ExitCode> {
+    let (snapshots, invalid_sources) = KopiaSnapshots::new_from_path_with_report(file)?;
+    let report = ValidationReport::new(&snapshots, invalid_sources);
+    println!("{report}");
+    Ok(if report.finding_count() == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn run_diff(
+    old: &std::path::Path,
+    new: &std::path::Path,
+    latest_policy: LatestSnapshotPolicy,
+    format: DiffFormat,
+) -> eyre::Result<ExitCode> {
+    let read = |path: &std::path::Path| -> eyre::Result<KopiaSnapshots> {
+        let (snapshots, invalid_sources) = KopiaSnapshots::new_from_path_with_report(path)?;
+        for e in invalid_sources {
+            eprintln!("{}: {:?}", path.display(), eyre::eyre!(e));
+        }
+        Ok(snapshots.with_latest_policy(latest_policy))
+    };
+    let diff = SnapshotsDiff::new(&read(old)?, &read(new)?);
+    match format {
+        DiffFormat::Human => println!("{diff}"),
+        DiffFormat::Json => println!("{}", serde_json::to_string(&diff)?),
+    }
+    Ok(if diff.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Checks that an output is configured, since `--no-http` disables serving metrics
+fn check_outputs(args: &ServeArgs, push_config: &PushConfig) -> eyre::Result<()> {
+    if args.no_http && push_config.is_empty() {
+        return Err(eyre::eyre!(
+            "No outputs configured: --no-http requires at least one push output (e.g. --textfile-output)"
+        ))
+        .wrap_err(Failure::Config);
+    }
+    Ok(())
+}
+
+/// Checks that serving would start, without binding the port or recording the fetch
+fn dry_run(
+    fetch_config: &FetchConfig,
+    args: &ServeArgs,
+    bind_addrs: &[SocketAddr],
+) -> eyre::Result<()> {
+    if let Some(path) = &fetch_config.snapshots_file {
+        println!("Reading snapshots from {}", path.display());
+    } else {
+        let programs = fetch_config
+            .kopia
+            .resolve_programs()
+            .wrap_err(Failure::Kopia)?;
+        for program in &programs {
+            println!("Found {}", program.display());
+        }
+    }
+
+    let start = Instant::now();
+    let snapshots = fetch_config
+        .without_recording()
+        .fetch()
+        .wrap_err(Failure::Kopia)?;
+    let snapshot_count: usize = snapshots
+        .sources()
+        .filter_map(|source| snapshots.snapshots_for(source))
+        .map(<[_]>::len)
+        .sum();
+    println!(
+        "Fetched {snapshot_count} snapshots of {} sources in {:.1?}",
+        snapshots.sources().count(),
+        start.elapsed()
+    );
 
-    let auth = BasicAuthConfig::from_args(&args)?;
+    if args.no_http {
+        println!("Would push metrics without an HTTP server");
+    } else {
+        let resolved: Vec<String> = bind_addrs.iter().map(ToString::to_string).collect();
+        println!(
+            "Would serve metrics on {} ({})",
+            args.bind,
+            resolved.join(", ")
+        );
+    }
+    println!("Dry run OK");
+    Ok(())
+}
+
+fn run_serve(fetch_config: FetchConfig, args: &ServeArgs) -> eyre::Result<()> {
+    let auth = BasicAuthConfig::from_args(args).wrap_err(Failure::Config)?;
+    let push_config = args.push_config().wrap_err(Failure::Config)?;
+    #[cfg(feature = "otel")]
+    let otlp_traces = args
+        .otlp_traces_url
+        .as_deref()
+        .map(PushTarget::parse)
+        .transpose()
+        .wrap_err(Failure::Config)?;
     if auth.is_some() {
         println!("Basic authentication enabled");
+    } else if args.enable_quit {
+        return Err(eyre::eyre!(
+            "--enable-quit requires basic authentication (e.g. --auth-credentials-file)"
+        ))
+        .wrap_err(Failure::Config);
+    }
+    // fail early on an invalid address, rather than retrying to bind it
+    let bind_addrs = if args.no_http {
+        vec![]
+    } else {
+        resolve_bind_addr(&args.bind).wrap_err(Failure::Config)?
+    };
+    check_outputs(args, &push_config)?;
+
+    if args.dry_run {
+        return dry_run(&fetch_config, args, &bind_addrs);
+    }
+    #[cfg(feature = "otel")]
+    if let Some(target) = otlp_traces {
+        start_trace_export(target)?;
+    }
+
+    let cache = SnapshotCache::new(
+        fetch_config,
+        Duration::from_secs(args.cache_seconds),
+        Duration::from_secs(args.error_cache_seconds),
+        args.sample_timestamps,
+    );
+    if let Some(path) = cache.fetch_config().snapshots_file.clone() {
+        let cache = cache.clone();
+        std::thread::spawn(move || cache.watch_snapshots_file(&path));
+    }
+
+    if args.no_http {
+        println!("Starting Kopia Exporter without HTTP server");
+        let _pid_file = args
+            .pid_file
+            .as_deref()
+            .map(|path| {
+                PidFile::create(path)
+                    .wrap_err(Failure::Config)?
+                    .remove_on_signal()
+            })
+            .transpose()?;
+        push_loop(&cache, &push_config);
+        return Ok(());
     }
 
     println!("Starting Kopia Exporter on {}", args.bind);
 
-    let server = start_server_with_retry(&args.bind, args.max_bind_retries)?;
+    let server = start_server_with_retry(&args.bind, &bind_addrs, args.max_bind_retries)
+        .wrap_err(Failure::Bind)?;
+    let pid_file = args
+        .pid_file
+        .as_deref()
+        .map(|path| {
+            PidFile::create(path)
+                .wrap_err(Failure::Config)?
+                .remove_on_signal()
+        })
+        .transpose()?;
 
-    let cache_duration = Duration::from_secs(args.cache_seconds);
-    let kopia_timeout = Duration::from_secs_f64(args.timeout);
-    serve_requests(server, &args.kopia_bin, cache_duration, kopia_timeout, auth);
+    if let Some(timeout) = args.warm_up_timeout {
+        println!("Warming up the cache");
+        cache.warm_up(Duration::from_secs_f64(timeout));
+    }
+    // after warming up, to share the snapshots rather than fetching them twice
+    if !push_config.is_empty() {
+        let cache = cache.clone();
+        std::thread::spawn(move || push_loop(&cache, &push_config));
+    }
+    serve_requests(server, &cache, auth, args.enable_quit);
 
+    if let Some(pid_file) = pid_file {
+        pid_file.release();
+    }
     Ok(())
 }
 
+fn main() -> ExitCode {
+    let args = Args::try_parse_checked_from(std::env::args_os()).unwrap_or_else(|e| e.exit());
+    run(&args).unwrap_or_else(|report| {
+        eprintln!("Error: {report:?}");
+        Failure::exit_code_for(&report)
+    })
+}
+
+fn run(args: &Args) -> eyre::Result<ExitCode> {
+    let fetch_config = args.fetch_config().wrap_err(Failure::Config)?;
+
+    match &args.command {
+        // bare invocation serves, for compatibility
+        None => run_serve(fetch_config, &args.serve)?,
+        Some(Command::Serve(serve_args)) => run_serve(fetch_config, serve_args)?,
+        Some(Command::Print { format }) => run_print(&fetch_config, *format)?,
+        Some(Command::Check { format }) => {
+            let thresholds = args.thresholds.to_thresholds().unwrap_or_default();
+            return Ok(run_check(&fetch_config, &thresholds, *format));
+        }
+        Some(Command::Validate { file }) => return run_validate(file),
+        Some(Command::Diff { old, new, format }) => {
+            return run_diff(old, new, args.latest_policy, *format);
+        }
+        Some(Command::Version) => println!("{}", BuildInfo::current()),
+        Some(Command::Generate {
+            target: GenerateTarget::Alerts,
+        }) => {
+            let thresholds = args
+                .thresholds
+                .to_thresholds()
+                .ok_or_else(|| {
+                    eyre::eyre!(
+                        "No thresholds configured: specify at least one (e.g. --max-age 26h)"
+                    )
+                })
+                .wrap_err(Failure::Config)?;
+            print!("{}", thresholds.prometheus_alert_rules());
+        }
+        Some(Command::Generate {
+            target: GenerateTarget::Man { out_dir },
+        }) => {
+            use clap::CommandFactory as _;
+
+            let command = Args::command();
+            match out_dir {
+                Some(out_dir) => clap_mangen::generate_to(command, out_dir)?,
+                None => clap_mangen::Man::new(command).render(&mut std::io::stdout().lock())?,
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
 #[cfg(test)]
 mod tests {
     #![expect(clippy::unwrap_used)] // tests can unwrap
 
     use super::*;
-    use std::net::TcpListener;
 
     #[test]
     fn start_server_with_retry_success_first_attempt() {
@@ -290,17 +1697,20 @@ mod tests {
         let addr = listener.local_addr().unwrap();
         drop(listener);
 
-        let result = start_server_with_retry(&addr.to_string(), 3);
+        let result = start_server_with_retry(&addr.to_string(), &[addr], 3);
         assert!(result.is_ok());
     }
 
     #[test]
     fn start_server_with_retry_no_retries() {
-        let result = start_server_with_retry("127.0.0.1:99999", 0);
-        assert!(result.is_err());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let result = start_server_with_retry(&addr.to_string(), &[addr], 0);
         let err_msg = result.err().unwrap().to_string();
-        assert!(err_msg.contains("Failed to bind to 127.0.0.1:99999"));
+        assert!(err_msg.contains(&format!("Failed to bind to {addr}")));
         assert!(err_msg.contains("after 1 attempts")); // 0 retries = 1 attempt only
+        assert!(err_msg.contains("address already in use"), "{err_msg}");
     }
 
     #[test]
@@ -308,13 +1718,171 @@ mod tests {
         let listener = TcpListener::bind("127.0.0.1:0").unwrap();
         let addr = listener.local_addr().unwrap();
 
-        let result = start_server_with_retry(&addr.to_string(), 2);
+        let result = start_server_with_retry(&addr.to_string(), &[addr], 2);
         assert!(result.is_err());
         let err_msg = result.err().unwrap().to_string();
         assert!(err_msg.contains("Failed to bind to"));
         assert!(err_msg.contains("after 3 attempts")); // 1 initial + 2 retries = 3 attempts
     }
 
+    #[test]
+    fn resolve_bind_addr_hostname() {
+        let addrs = resolve_bind_addr("localhost:9090").unwrap();
+        assert!(!addrs.is_empty());
+        assert!(
+            addrs.iter().all(|addr| addr.ip().is_loopback()),
+            "{addrs:?}"
+        );
+        assert!(addrs.iter().all(|addr| addr.port() == 9090), "{addrs:?}");
+
+        for invalid in ["127.0.0.1:99999", "127.0.0.1", "localhost", ":9090"] {
+            let err_msg = resolve_bind_addr(invalid).unwrap_err().to_string();
+            assert!(err_msg.contains("Invalid bind address"), "{err_msg}");
+        }
+    }
+
+    #[test]
+    fn describe_bind_errors() {
+        use std::io::{Error, ErrorKind};
+        let addr = |port| SocketAddr::from(([127, 0, 0, 1], port));
+        let describe = |kind, port| describe_bind_error(&Error::from(kind), &[addr(port)]);
+
+        let (reason, retryable) = describe(ErrorKind::AddrInUse, 9090);
+        assert!(reason.starts_with("address already in use"), "{reason}");
+        assert!(retryable);
+
+        let (reason, retryable) = describe(ErrorKind::AddrNotAvailable, 9090);
+        assert!(reason.starts_with("cannot assign the address"), "{reason}");
+        assert!(retryable);
+
+        let (reason, retryable) = describe(ErrorKind::PermissionDenied, 80);
+        assert!(reason.contains("ports below 1024"), "{reason}");
+        assert!(!retryable);
+
+        let (reason, retryable) = describe(ErrorKind::PermissionDenied, 9090);
+        assert!(reason.starts_with("permission denied ("), "{reason}");
+        assert!(!retryable);
+    }
+
+    #[test]
+    #[expect(clippy::panic)] // testing panic isolation
+    fn request_panic_isolated() {
+        use kopia_exporter::AssertContains as _;
+
+        let stats = Arc::new(ExporterStats::default());
+        assert_eq!(catch_request_panic(&stats, || 404), 404);
+        assert_eq!(catch_request_panic(&stats, || panic!("handler bug")), 500);
+        let (snapshots, _) = KopiaSnapshots::new_from_reader_with_report(&b"[]"[..]).unwrap();
+        stats
+            .register(snapshots)
+            .generate_all_metrics(jiff::Timestamp::now())
+            .assert_contains_lines(&["kopia_exporter_internal_panics_total 1"]);
+    }
+
+    #[test]
+    fn source_endpoint_paths() {
+        assert_eq!(
+            percent_decode("kopia%40nas%3A%2Fdata%20dir").as_deref(),
+            Some("kopia@nas:/data dir")
+        );
+        assert_eq!(
+            percent_decode("kopia@nas:/data").as_deref(),
+            Some("kopia@nas:/data")
+        );
+        assert_eq!(percent_decode("%C3%A9").as_deref(), Some("é"));
+        for invalid in ["%", "%2", "%zz", "%+1", "%C3"] {
+            assert_eq!(percent_decode(invalid), None, "{invalid:?}");
+        }
+
+        assert!(matches!(
+            SnapshotsEndpoint::from_url("/metrics/source/kopia%40nas%3A%2Fdata?collect[]=x"),
+            Some(SnapshotsEndpoint::SourcePrometheus)
+        ));
+        assert!(SnapshotsEndpoint::from_url("/metrics/source/").is_none());
+    }
+
+    #[test]
+    fn kopia_env() {
+        assert_eq!(
+            parse_env_var("KOPIA_LOG_DIR=/var/log/kopia=1"),
+            Ok(("KOPIA_LOG_DIR".to_owned(), "/var/log/kopia=1".to_owned()))
+        );
+        assert_eq!(
+            parse_env_var("EMPTY="),
+            Ok(("EMPTY".to_owned(), String::new()))
+        );
+        assert!(parse_env_var("=value").is_err());
+        assert!(parse_env_var("KOPIA_LOG_DIR").is_err());
+        assert!(parse_env_var("KOPIA_LOG_DIR = /tmp").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kopia.env");
+        std::fs::write(&path, "# comment\n\nA=1\n  B=2 \n").unwrap();
+        assert_eq!(
+            read_env_file(&path).unwrap(),
+            [
+                ("A".to_owned(), "1".to_owned()),
+                ("B".to_owned(), "2".to_owned())
+            ]
+        );
+        std::fs::write(&path, "A=1\nB\n").unwrap();
+        let error = read_env_file(&path).unwrap_err().to_string();
+        assert!(
+            error.ends_with("kopia.env:2: expected KEY=VALUE, found \"B\""),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn expected_retention() {
+        assert_eq!(
+            parse_expected_retention("daily=7"),
+            Ok(("daily".to_owned(), 7))
+        );
+        assert!(parse_expected_retention("=7").is_err());
+        assert!(parse_expected_retention("daily").is_err());
+        assert!(parse_expected_retention("daily=-1").is_err());
+        assert!(parse_expected_retention("daily=seven").is_err());
+    }
+
+    #[test]
+    fn failure_exit_codes() {
+        let error = || eyre::eyre!("Address already in use");
+        let code = |report: eyre::Report| format!("{:?}", Failure::exit_code_for(&report));
+
+        assert_eq!(code(error()), format!("{:?}", ExitCode::FAILURE));
+        let report = Err::<(), _>(error()).wrap_err(Failure::Bind).unwrap_err();
+        assert_eq!(code(report), format!("{:?}", ExitCode::from(75)));
+        let report = Err::<(), _>(error()).wrap_err(Failure::Config).unwrap_err();
+        assert_eq!(code(report), format!("{:?}", ExitCode::from(78)));
+    }
+
+    #[test]
+    fn serve_options_with_commands() {
+        let args = Args::try_parse_checked_from(["kopia-exporter", "--bind", "0.0.0.0:1"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.serve.bind, "0.0.0.0:1");
+
+        let args = Args::try_parse_checked_from(["kopia-exporter", "serve", "--bind", "0.0.0.0:2"])
+            .unwrap();
+        assert!(
+            matches!(&args.command, Some(Command::Serve(serve_args)) if serve_args.bind == "0.0.0.0:2"),
+            "{:?}",
+            args.command
+        );
+
+        let args =
+            Args::try_parse_checked_from(["kopia-exporter", "--kopia-bin", "/bin/kopia", "check"])
+                .unwrap();
+        assert_eq!(args.kopia_bin, "/bin/kopia");
+
+        let error =
+            Args::try_parse_checked_from(["kopia-exporter", "--bind", "0.0.0.0:1", "check"])
+                .unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+        assert!(error.to_string().contains("'--bind'"), "{error}");
+    }
+
     #[test]
     fn delay_calculation_and_cap() {
         // Test exponential backoff sequence
@@ -1,10 +1,21 @@
 //! Defines metrics attached to [`KopiaSnapshots`]
 
 use crate::{KopiaSnapshots, define_metric_categories};
-use std::fmt::Display;
 
-use self::metrics_framework::DisplayMetric;
-pub use self::metrics_framework::{AttachMetricLabel as _, MetricLabel, MetricType, Metrics};
+pub use self::builder::MetricsBuilder;
+pub use self::custom::CustomMetric;
+pub(crate) use self::custom::CustomMetricFns;
+pub use self::exporter_stats::ExporterStats;
+#[cfg(feature = "push")]
+pub(crate) use self::format_remote_write::write_varint;
+pub use self::format_statsd::StatsdFlavor;
+pub use self::metrics_framework::{
+    AttachMetricLabel as _, MetricCategory, MetricFamily, MetricLabel, MetricType, Metrics,
+    SampleValue, SampleVisitor,
+};
+use self::metrics_framework::{DisplayMetric, PrometheusText, WithLabel};
+pub use self::prerendered::PrerenderedMetrics;
+use self::rollup::Rollup;
 
 mod metrics_framework;
 
@@ -13,18 +24,40 @@ define_metric_categories! {
     NEW_SNAPSHOT_HEALTH: impl KopiaSnapshots {
         /// Age of newest snapshot in seconds
         ///
-        /// Returns metrics showing the age in seconds of the most recent snapshot for each source.
+        /// Returns metrics showing the age in seconds of the most recent snapshot for each source,
+        /// and the maximum as `source="_all"` with [`Self::with_source_rollups`].
         /// Only present if snapshots list is not empty.
-        pub fn kopia_snapshot_age_seconds<Gauge>(&self, now: jiff::Timestamp) -> Option<impl Display> {
-            SnapshotAgeSeconds::new(self, now, <[crate::Snapshot]>::last)
+        pub fn kopia_snapshot_age_seconds<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
+            let policy = self.latest_policy;
+            SnapshotAgeSeconds::new(self, now, |snapshots| policy.select_latest(snapshots))
+                .map(|metric| Rollup::Max.wrap(self, metric))
         }
         /// Unix timestamp of last successful snapshot
         ///
         /// Generates Prometheus metrics for the last successful snapshot timestamp.
         /// Only present if snapshots list is not empty.
-        pub fn kopia_snapshot_last_success_timestamp<Gauge>(&self) -> Option<impl Display> {
+        pub fn kopia_snapshot_last_success_timestamp<Gauge>(&self) -> Option<impl MetricFamily> {
             SnapshotLastSuccessTimestamp::new(self)
         }
+        /// Latest snapshot interval minus the expected interval in seconds
+        ///
+        /// Returns metrics showing how much longer (positive) or shorter (negative) than the
+        /// [expected interval](Self::with_expected_interval) the interval between the starts of
+        /// the latest two snapshots was, for each source. Signals a slipping schedule before the
+        /// snapshot age exceeds a hard threshold. Only present for sources with an expected
+        /// interval and more than one snapshot.
+        pub fn kopia_snapshot_schedule_drift_seconds<Gauge>(&self) -> Option<impl MetricFamily> {
+            ScheduleDriftSeconds::new(self)
+        }
+        /// Days without snapshots in the history window
+        ///
+        /// Returns metrics counting the days (24 hour periods ending now) of the
+        /// [history](Self::with_history) window without a snapshot ending, for each source.
+        /// Only days since the first recorded snapshot of the source are counted. Only present
+        /// if a history is set.
+        pub fn kopia_history_days_without_snapshots<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
+            HistoryDaysWithoutSnapshots::new(self, now)
+        }
     }
 }
 define_metric_categories! {
@@ -32,18 +65,28 @@ define_metric_categories! {
     BACKUP_COMPLETION_STATUS: impl KopiaSnapshots {
         /// Total errors in latest snapshot
         ///
-        /// Returns metrics showing the total number of errors in the most recent snapshot.
+        /// Returns metrics showing the total number of errors in the most recent snapshot,
+        /// and the sum as `source="_all"` with [`Self::with_source_rollups`].
         /// Only present if snapshots list is not empty.
-        pub fn kopia_snapshot_errors_total<Gauge>(&self) -> Option<impl Display> {
+        pub fn kopia_snapshot_errors_total<Gauge>(&self) -> Option<impl MetricFamily> {
             last_snapshots::MetricLastSnapshots::new(self, |v| v.stats.error_count)
+                .map(|metric| Rollup::Sum.wrap(self, metric))
         }
         /// Ignored errors in latest snapshot
         ///
         /// Returns a string containing Prometheus-formatted metrics showing the total
         /// number of ignored errors in the most recent snapshot. Only present if snapshots list is not empty.
-        pub fn kopia_snapshot_errors_ignored_total<Gauge>(&self) -> Option<impl Display> {
+        pub fn kopia_snapshot_errors_ignored_total<Gauge>(&self) -> Option<impl MetricFamily> {
             last_snapshots::MetricLastSnapshots::new(self, |v| v.stats.ignored_error_count)
         }
+        /// Ratio of snapshots without errors in the history window
+        ///
+        /// Returns metrics showing the fraction of the snapshots ending within the
+        /// [history](Self::with_history) window that completed without errors, for each source.
+        /// Only present for sources with snapshots in the window.
+        pub fn kopia_history_success_ratio<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
+            HistorySuccessRatio::new(self, now)
+        }
     }
 }
 define_metric_categories! {
@@ -53,8 +96,8 @@ define_metric_categories! {
         ///
         /// Returns metrics showing the number of failed files in the most recent snapshot.
         /// Only present if snapshots list is not empty.
-        pub fn kopia_snapshot_failed_files_total<Gauge>(&self) -> Option<impl Display> {
-            last_snapshots::MetricLastSnapshots::new(self, |v| v.root_entry.summ.num_failed)
+        pub fn kopia_snapshot_failed_files_total<Gauge>(&self) -> Option<impl MetricFamily> {
+            last_snapshots::MetricLastSnapshots::new(self, crate::Snapshot::failed_files)
         }
     }
 }
@@ -63,17 +106,47 @@ define_metric_categories! {
     REMAINING_SPACE: impl KopiaSnapshots {
         /// Total size of latest snapshot in bytes
         ///
-        /// Returns metrics showing the total size in bytes of the most recent snapshot.
+        /// Returns metrics showing the total size in bytes of the most recent snapshot,
+        /// and the sum as `source="_all"` with [`Self::with_source_rollups`].
         /// Only present if snapshots list is not empty.
-        pub fn kopia_snapshot_size_bytes_total<Gauge>(&self) -> Option<impl Display> {
+        pub fn kopia_snapshot_size_bytes_total<Gauge>(&self) -> Option<impl MetricFamily> {
             last_snapshots::MetricLastSnapshots::new(self, |v| v.stats.total_size)
+                .map(|metric| Rollup::Sum.wrap(self, metric))
         }
         /// Change in size from previous snapshot
         ///
         /// Returns metrics showing the change in bytes from the previous snapshot.
         /// Only present if snapshots list has more than one snapshot.
-        pub fn kopia_snapshot_size_bytes_change<Gauge>(&self) -> Option<impl Display> {
-            SnapshotSizeByteChanges::new(self)
+        pub fn kopia_snapshot_size_bytes_change<Gauge>(&self) -> Option<impl MetricFamily> {
+            SnapshotSizeByteChanges::new(self, |v| v.stats.total_size)
+        }
+        /// Change in excluded size from previous snapshot
+        ///
+        /// Returns metrics showing the change in bytes of the excluded files from the previous
+        /// snapshot. A jump signals an ignore rule newly excluding data that was backed up.
+        /// Only present if snapshots list has more than one snapshot.
+        pub fn kopia_snapshot_excluded_size_change_bytes<Gauge>(&self) -> Option<impl MetricFamily> {
+            use kopia_snapshot_size_bytes_change::SnapshotSizeByteChanges;
+            SnapshotSizeByteChanges::new(self, |v| v.stats.excluded_total_size)
+        }
+        /// Estimated days until the repository quota is full
+        ///
+        /// Returns a single metric estimating the days until the total size of the latest
+        /// snapshots (before deduplication and compression) reaches the
+        /// [configured quota](Self::with_repo_quota_bytes), growing at the least squares rate
+        /// of each source's retained snapshots. Zero if already reached. Only present if a
+        /// quota is configured and the total size is growing.
+        pub fn kopia_repository_estimated_days_until_full<Gauge>(&self) -> Option<impl MetricFamily> {
+            EstimatedDaysUntilFull::new(self)
+        }
+        /// Growth of the snapshot size in bytes per day over the history window
+        ///
+        /// Returns metrics showing the least squares rate of change of the total size of the
+        /// snapshots ending within the [history](Self::with_history) window, for each source,
+        /// including snapshots pruned since. Only present for sources with at least two
+        /// snapshots in the window.
+        pub fn kopia_history_size_growth_bytes_per_day<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
+            HistorySizeGrowth::new(self, now)
         }
     }
 }
@@ -84,25 +157,68 @@ define_metric_categories! {
         ///
         /// Returns metrics showing the count of snapshots for each retention reason
         /// (e.g., "latest-1", "daily-7", etc.).
-        pub fn kopia_snapshots_by_retention<Gauge>(&self) -> impl Display {
+        pub fn kopia_snapshots_by_retention<Gauge>(&self) -> impl MetricFamily {
             let always = SnapshotsByRetention::new(self);
             (always,)
         }
         /// Total number of snapshots
         ///
-        /// Returns metrics showing the total count of all snapshots in the repository.
-        pub fn kopia_snapshots_total<Gauge>(&self) -> impl Display {
-            let always = SnapshotsTotal::new(self);
+        /// Returns metrics showing the total count of all snapshots in the repository,
+        /// and the sum as `source="_all"` with [`Self::with_source_rollups`].
+        pub fn kopia_snapshots_total<Gauge>(&self) -> impl MetricFamily {
+            let always = Rollup::Sum.wrap(self, SnapshotsTotal::new(self));
+            (always,)
+        }
+        /// Number of sources with snapshots
+        ///
+        /// Returns a single metric counting the sources, zero for an empty repository.
+        pub fn kopia_sources_total<Gauge>(&self) -> impl MetricFamily {
+            let always = SourcesTotal::new(self);
+            (always,)
+        }
+        /// Whether kopia listed no snapshots at all (1 empty, 0 otherwise)
+        ///
+        /// Returns a single metric distinguishing an empty repository, which has no per-source
+        /// metrics, from missing metrics (e.g. failing to fetch the snapshots).
+        pub fn kopia_repository_empty<Gauge>(&self) -> impl MetricFamily {
+            let always = RepositoryEmpty::new(self);
             (always,)
         }
         /// Age of oldest retained snapshot in seconds
         ///
         /// Returns metrics showing the age in seconds of the oldest retained snapshot for each source.
         /// Only present if snapshots list is not empty.
-        pub fn kopia_snapshot_oldest_age_seconds<Gauge>(&self, now: jiff::Timestamp) -> Option<impl Display> {
+        pub fn kopia_snapshot_oldest_age_seconds<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
             use kopia_snapshot_age_seconds::SnapshotAgeSeconds;
             SnapshotAgeSeconds::new(self, now, <[crate::Snapshot]>::first)
         }
+        /// Missing retained snapshots by retention class
+        ///
+        /// Returns metrics showing how many retained snapshots of each class are missing compared
+        /// to the [expected retention](Self::with_expected_retention), zero if enough are retained.
+        /// Only present if an expected retention is configured and snapshots list is not empty.
+        pub fn kopia_retention_deficit<Gauge>(&self) -> Option<impl MetricFamily> {
+            RetentionDeficit::new(self)
+        }
+    }
+}
+define_metric_categories! {
+    /// Backup health
+    BACKUP_HEALTH: impl KopiaSnapshots {
+        /// Whether the latest snapshot satisfies the health thresholds (1 healthy, 0 unhealthy)
+        ///
+        /// Returns metrics showing whether each source has no critical threshold violations.
+        /// Only present if health thresholds are configured.
+        pub fn kopia_backup_healthy<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
+            BackupHealthy::new(self, now)
+        }
+        /// Whether all sources are healthy (1 healthy, 0 unhealthy or no sources)
+        ///
+        /// Returns a single metric summarizing [`Self::kopia_backup_healthy`] for all sources.
+        /// Only present if health thresholds are configured.
+        pub fn kopia_backup_healthy_all<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
+            BackupHealthyAll::new(self, now)
+        }
     }
 }
 define_metric_categories! {
@@ -111,75 +227,368 @@ define_metric_categories! {
         /// Number of snapshots with unparseable sources
         ///
         /// Returns metrics showing the count of snapshots with unparseable sources
-        /// (invalid usernames or hostnames).
+        /// (invalid usernames, hostnames, or paths).
         /// Only present if there are parsing errors.
-        pub fn kopia_snapshot_parse_errors_source<Gauge>(&self) -> Option<impl Display> {
+        pub fn kopia_snapshot_parse_errors_source<Gauge>(&self) -> Option<impl MetricFamily> {
             SnapshotParseErrorsSource::new(self)
         }
         /// Number of snapshots with unparseable timestamps
         ///
         /// Returns metrics showing the count of snapshots with unparseable timestamps.
         /// Only present if there are parsing errors.
-        pub fn kopia_snapshot_parse_errors_timestamp_total<Gauge>(&self) -> Option<impl Display> {
+        pub fn kopia_snapshot_parse_errors_timestamp_total<Gauge>(&self) -> Option<impl MetricFamily> {
             ParseErrorCountsTimestamp::new(self)
         }
+        /// Number of snapshots skipped for not matching the expected JSON structure
+        ///
+        /// Returns a metric showing the count of snapshots which failed to parse (e.g. a field
+        /// of the wrong type), skipped instead of failing the whole snapshot list.
+        /// Only present if there are parsing errors.
+        pub fn kopia_snapshot_parse_errors_malformed_total<Gauge>(&self) -> Option<impl MetricFamily> {
+            SnapshotParseErrorsMalformed::new(self)
+        }
+        /// Number of invalid UTF-8 sequences in the snapshots list
+        ///
+        /// Returns a metric showing the count of invalid UTF-8 sequences (e.g. paths mangled
+        /// by the locale), which are replaced by U+FFFD instead of failing the whole list.
+        /// Only present if there are parsing errors.
+        pub fn kopia_snapshot_parse_errors_utf8_total<Gauge>(&self) -> Option<impl MetricFamily> {
+            SnapshotParseErrorsUtf8::new(self)
+        }
+        /// Number of snapshots skipped for repeating the ID of another snapshot
+        ///
+        /// Returns a metric showing the count of duplicate snapshots (e.g. from overlapping
+        /// listings), which are skipped so they do not inflate the other counts.
+        /// Only present if there are duplicates.
+        pub fn kopia_snapshot_duplicates_total<Gauge>(&self) -> Option<impl MetricFamily> {
+            SnapshotDuplicates::new(self)
+        }
+        /// Number of data quality issues in the snapshots list, by kind
+        ///
+        /// Returns metrics summarizing the issues of the other data quality metrics, plus
+        /// missing, unparseable, and out-of-range (before 1970) snapshot start/end times,
+        /// which are treated as absent. Every kind is present, zero if there are no issues.
+        pub fn kopia_data_quality_issues_total<Gauge>(&self) -> impl MetricFamily {
+            let always = DataQualityIssues::new(self);
+            (always,)
+        }
+        /// Number of sources aggregated into the overflow bucket
+        ///
+        /// Returns metrics showing how many sources exceeded the configured source limit
+        /// and were aggregated into `source="_overflow"`.
+        /// Only present if a source limit is configured.
+        pub fn kopia_sources_truncated_total<Gauge>(&self) -> Option<impl MetricFamily> {
+            SourcesTruncatedTotal::new(self)
+        }
+    }
+}
+define_metric_categories! {
+    /// Exporter status
+    EXPORTER_STATUS: impl KopiaSnapshots {
+        /// Age of the exported snapshots data in seconds
+        ///
+        /// Returns a metric showing how long ago the snapshots were fetched from kopia,
+        /// which grows while cached data is served. Only present if the fetch time is known.
+        pub fn kopia_exporter_data_age_seconds<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
+            DataAgeSeconds::new(self, now)
+        }
+        /// Unix timestamp of the last successful fetch of the snapshots from kopia
+        ///
+        /// Returns a metric showing when the exported snapshots were fetched, which stays
+        /// unchanged while fetches fail and stale data is served. Only present if the fetch
+        /// time is known.
+        pub fn kopia_exporter_last_successful_fetch_timestamp<Gauge>(&self) -> Option<impl MetricFamily> {
+            LastSuccessfulFetchTimestamp::new(self)
+        }
     }
 }
 
+impl MetricCategory {
+    /// All categories, in order of definition
+    pub const ALL: &'static [Self] = &[
+        Self::NEW_SNAPSHOT_HEALTH,
+        Self::BACKUP_COMPLETION_STATUS,
+        Self::DATA_INTEGRITY_VERIFICATION,
+        Self::REMAINING_SPACE,
+        Self::PRUNED_SNAPSHOTS,
+        Self::BACKUP_HEALTH,
+        Self::DATA_QUALITY,
+        Self::EXPORTER_STATUS,
+    ];
+}
+
 // Helpers
+mod builder;
+mod custom;
+mod exporter_stats;
+mod format_influx;
+mod format_json;
+#[cfg(feature = "prometheus-client")]
+mod format_prometheus_client;
+mod format_remote_write;
+mod format_statsd;
 mod last_snapshots;
+mod prerendered;
+mod rollup;
+
+/// Constructs a metric family depending on the current time
+type NowMetricFn =
+    for<'a> fn(&'a KopiaSnapshots, jiff::Timestamp) -> Option<Box<dyn MetricFamily + 'a>>;
+
+/// Present metric family, or the constructor of a metric depending on the current time
+enum FamilyEntry<'a> {
+    Fixed(Box<dyn MetricFamily + 'a>),
+    Now(NowMetricFn),
+    /// Index of the [custom metric](KopiaSnapshots::with_custom_metric), computed for
+    /// each render
+    Custom(usize),
+}
+
+fn boxed<'a>(metric: Option<impl MetricFamily + 'a>) -> Option<Box<dyn MetricFamily + 'a>> {
+    metric.map(|metric| Box::new(metric) as Box<dyn MetricFamily + 'a>)
+}
 
 impl KopiaSnapshots {
     /// Generates all Prometheus metrics for the `/metrics` endpoint.
     ///
     /// Combines all available metrics into a single response suitable for
     /// Prometheus scraping.
+    ///
+    /// The output is byte-identical for identical snapshots and `now`, see the
+    /// [crate docs](crate#metrics).
     #[must_use]
     pub fn generate_all_metrics(&self, now: jiff::Timestamp) -> String {
-        struct Accumulator(String);
-        impl Accumulator {
-            fn new() -> Self {
-                Self(String::new())
+        self.render_prometheus(now, None)
+    }
+
+    /// Generates all Prometheus metrics, with every sample timestamped `timestamp`.
+    ///
+    /// Attributes the samples to when the snapshots were fetched (e.g. [`Self::fetched_at`])
+    /// rather than to the scrape time, when serving cached snapshots.
+    #[must_use]
+    pub fn generate_all_metrics_timestamped(
+        &self,
+        now: jiff::Timestamp,
+        timestamp: jiff::Timestamp,
+    ) -> String {
+        self.render_prometheus(now, Some(timestamp))
+    }
+
+    /// Writes all Prometheus metrics of [`Self::generate_all_metrics`] to the `writer`,
+    /// one metric family at a time.
+    ///
+    /// Avoids buffering the full output, which is large for repositories with many sources.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the `writer` fails
+    pub fn generate_all_metrics_to(
+        &self,
+        now: jiff::Timestamp,
+        writer: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        self.write_prometheus(now, None, writer)
+    }
+
+    fn render_prometheus(
+        &self,
+        now: jiff::Timestamp,
+        timestamp: Option<jiff::Timestamp>,
+    ) -> String {
+        let mut output = Vec::new();
+        self.write_prometheus(now, timestamp, &mut output)
+            .expect("infallible");
+        String::from_utf8(output).expect("metrics are valid UTF-8")
+    }
+
+    fn write_prometheus(
+        &self,
+        now: jiff::Timestamp,
+        timestamp: Option<jiff::Timestamp>,
+        writer: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        let timestamp_millis = timestamp.map(jiff::Timestamp::as_millisecond);
+        for (index, metric) in self.all_metric_families(now).into_iter().enumerate() {
+            if index > 0 {
+                writer.write_all(b"\n")?;
             }
-            fn push(mut self, metric: Option<impl Display>) -> Self {
-                use std::fmt::Write as _;
-                if let Some(m) = metric {
-                    let Self(output) = &mut self;
-                    if !output.is_empty() {
-                        output.push('\n');
-                    }
-                    write!(output, "{m}").expect("infallible");
+            let metric = PrometheusText {
+                family: &*metric,
+                timestamp_millis,
+            };
+            write!(writer, "{metric}")?;
+        }
+        Ok(())
+    }
+
+    /// Renders the metrics of [`Self::generate_all_metrics`] which do not depend on the
+    /// current time, to cheaply render the full output for each request.
+    ///
+    /// If `timestamp` is specified, every sample is timestamped as in
+    /// [`Self::generate_all_metrics_timestamped`].
+    #[must_use]
+    pub fn prerender_metrics(&self, timestamp: Option<jiff::Timestamp>) -> PrerenderedMetrics {
+        PrerenderedMetrics::new(self, timestamp)
+    }
+
+    /// Generates all metrics as a JSON array, for the `/metrics.json` endpoint.
+    ///
+    /// Contains the same values as [`Self::generate_all_metrics`], with one object
+    /// per sample listing the metric `name`, `help`, `type`, `labels`, and `value`.
+    #[must_use]
+    pub fn generate_all_metrics_json(&self, now: jiff::Timestamp) -> String {
+        format_json::render(&self.all_metric_families(now))
+    }
+
+    /// Generates all metrics in the Influx line protocol, with every line timestamped `now`.
+    #[must_use]
+    pub fn generate_all_metrics_influx(&self, now: jiff::Timestamp) -> String {
+        format_influx::render(&self.all_metric_families(now), now)
+    }
+
+    /// Generates all metrics as a Prometheus remote write `WriteRequest` protobuf message.
+    ///
+    /// Every sample is timestamped `now`. The message must be snappy-compressed before
+    /// sending, see `push::snappy` (with the `push` feature).
+    #[must_use]
+    pub fn generate_remote_write(&self, now: jiff::Timestamp) -> Vec<u8> {
+        format_remote_write::render(&self.all_metric_families(now), now)
+    }
+
+    /// Generates `StatsD` gauge lines for the key metrics: latest snapshot age, size, and errors.
+    ///
+    /// Returns one line per sample, to be sent over UDP.
+    #[must_use]
+    pub fn generate_statsd_lines(&self, now: jiff::Timestamp, flavor: StatsdFlavor) -> Vec<String> {
+        let mut metrics: Vec<Box<dyn MetricFamily + '_>> = Vec::new();
+        if let Some(metric) = self.kopia_snapshot_age_seconds(now) {
+            metrics.push(Box::new(metric));
+        }
+        if let Some(metric) = self.kopia_snapshot_size_bytes_total() {
+            metrics.push(Box::new(metric));
+        }
+        if let Some(metric) = self.kopia_snapshot_errors_total() {
+            metrics.push(Box::new(metric));
+        }
+        let metrics: Vec<_> = metrics
+            .into_iter()
+            .map(|metric| self.with_repo_label(metric))
+            .collect();
+        format_statsd::render(&metrics, flavor)
+    }
+
+    /// Returns all present metrics, in the order of [`Self::generate_all_metrics`]
+    fn all_metric_families(&self, now: jiff::Timestamp) -> Vec<Box<dyn MetricFamily + '_>> {
+        self.metric_family_entries()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                FamilyEntry::Fixed(metric) => Some(metric),
+                FamilyEntry::Now(metric_fn) => self.now_metric(metric_fn, now),
+                FamilyEntry::Custom(index) => self.custom_metric(index),
+            })
+            .collect()
+    }
+
+    /// Constructs the metric depending on the current time, if present
+    fn now_metric(
+        &self,
+        metric_fn: NowMetricFn,
+        now: jiff::Timestamp,
+    ) -> Option<Box<dyn MetricFamily + '_>> {
+        metric_fn(self, now).map(|metric| self.with_repo_label(metric))
+    }
+
+    /// Adds the [`Self::with_repo_id`] label to the samples of the metric, if set
+    fn with_repo_label<'a>(
+        &'a self,
+        metric: Box<dyn MetricFamily + 'a>,
+    ) -> Box<dyn MetricFamily + 'a> {
+        match &self.repo_id {
+            Some(repo_id) => Box::new(WithLabel {
+                family: metric,
+                label: ("repo_id", repo_id),
+            }),
+            None => metric,
+        }
+    }
+
+    /// Returns all metrics, in the order of [`Self::generate_all_metrics`]
+    ///
+    /// Metrics depending on the current time are only constructed on demand.
+    fn metric_family_entries(&self) -> Vec<FamilyEntry<'_>> {
+        struct Accumulator<'a>(Vec<FamilyEntry<'a>>);
+        impl<'a> Accumulator<'a> {
+            fn push(mut self, metric: Option<impl MetricFamily + 'a>) -> Self {
+                if let Some(m) = boxed(metric) {
+                    let Self(metrics) = &mut self;
+                    metrics.push(FamilyEntry::Fixed(m));
                 }
                 self
             }
-            fn finish(self) -> String {
-                let Self(output) = self;
-                output
+            fn push_now(mut self, metric_fn: NowMetricFn) -> Self {
+                let Self(metrics) = &mut self;
+                metrics.push(FamilyEntry::Now(metric_fn));
+                self
+            }
+            fn push_custom(mut self, count: usize) -> Self {
+                let Self(metrics) = &mut self;
+                metrics.extend((0..count).map(FamilyEntry::Custom));
+                self
+            }
+            fn finish(self) -> Vec<FamilyEntry<'a>> {
+                let Self(metrics) = self;
+                metrics
             }
         }
 
-        Accumulator::new()
+        Accumulator(Vec::new())
             .push(Some(self.kopia_snapshots_by_retention()))
             .push(self.kopia_snapshot_size_bytes_total())
-            .push(self.kopia_snapshot_age_seconds(now))
-            .push(self.kopia_snapshot_oldest_age_seconds(now))
+            .push_now(|ks, now| boxed(ks.kopia_snapshot_age_seconds(now)))
+            .push_now(|ks, now| boxed(ks.kopia_snapshot_oldest_age_seconds(now)))
+            .push(self.kopia_retention_deficit())
             .push(self.kopia_snapshot_parse_errors_timestamp_total())
             .push(self.kopia_snapshot_parse_errors_source())
+            .push(self.kopia_snapshot_parse_errors_malformed_total())
+            .push(self.kopia_snapshot_parse_errors_utf8_total())
+            .push(self.kopia_snapshot_duplicates_total())
+            .push(Some(self.kopia_data_quality_issues_total()))
             .push(self.kopia_snapshot_last_success_timestamp())
+            .push(self.kopia_snapshot_schedule_drift_seconds())
+            .push_now(|ks, now| boxed(ks.kopia_history_days_without_snapshots(now)))
             .push(self.kopia_snapshot_errors_total())
             .push(self.kopia_snapshot_errors_ignored_total())
+            .push_now(|ks, now| boxed(ks.kopia_history_success_ratio(now)))
             .push(self.kopia_snapshot_failed_files_total())
             .push(self.kopia_snapshot_size_bytes_change())
+            .push(self.kopia_snapshot_excluded_size_change_bytes())
+            .push(self.kopia_repository_estimated_days_until_full())
+            .push_now(|ks, now| boxed(ks.kopia_history_size_growth_bytes_per_day(now)))
             .push(Some(self.kopia_snapshots_total()))
+            .push(Some(self.kopia_sources_total()))
+            .push(Some(self.kopia_repository_empty()))
+            .push(self.kopia_sources_truncated_total())
+            .push_now(|ks, now| boxed(ks.kopia_backup_healthy(now)))
+            .push_now(|ks, now| boxed(ks.kopia_backup_healthy_all(now)))
+            .push_now(|ks, now| boxed(ks.kopia_exporter_data_age_seconds(now)))
+            .push(self.kopia_exporter_last_successful_fetch_timestamp())
+            .push_custom(self.custom_metrics.len())
             .finish()
+            .into_iter()
+            .map(|entry| match entry {
+                FamilyEntry::Fixed(metric) => FamilyEntry::Fixed(self.with_repo_label(metric)),
+                entry @ (FamilyEntry::Now(_) | FamilyEntry::Custom(_)) => entry,
+            })
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{CustomMetric, MetricType, StatsdFlavor};
     use crate::{
         AssertContains as _, KopiaSnapshots,
-        test_util::{single_map, test_snapshot},
+        test_util::{multi_map, single_map, test_snapshot},
     };
 
     #[test]
@@ -189,22 +598,325 @@ mod tests {
         let now = jiff::Timestamp::now();
 
         let (map, _source) = single_map(snapshots);
-        map.generate_all_metrics(now).assert_contains_lines(&[
-            "# TYPE kopia_snapshots_by_retention gauge",
-            "# TYPE kopia_snapshot_size_bytes_total gauge",
-            "# TYPE kopia_snapshot_age_seconds gauge",
-            "# TYPE kopia_snapshot_oldest_age_seconds gauge",
-            "# TYPE kopia_snapshot_errors_total gauge",
-            "# TYPE kopia_snapshot_failed_files_total gauge",
+        map.generate_all_metrics(now)
+            .assert_contains_lines(&[
+                "# TYPE kopia_snapshots_by_retention gauge",
+                "# TYPE kopia_snapshot_size_bytes_total gauge",
+                "# TYPE kopia_snapshot_age_seconds gauge",
+                "# TYPE kopia_snapshot_oldest_age_seconds gauge",
+                "# TYPE kopia_snapshot_errors_total gauge",
+                "# TYPE kopia_snapshot_failed_files_total gauge",
+                "# TYPE kopia_snapshots_total gauge",
+            ])
+            // valid sources and timestamps
+            .assert_not_contains_lines(&[
+                "# TYPE kopia_snapshot_parse_errors_source gauge",
+                "# TYPE kopia_snapshot_parse_errors_timestamp_total gauge",
+            ]);
+    }
+
+    #[test]
+    fn generate_all_metrics_to_writer() {
+        let (map, _sources) = multi_map(vec![
+            (
+                "alice",
+                "host",
+                "/a",
+                vec![test_snapshot("a1", 100, &["daily-1"])],
+            ),
+            (
+                "bob",
+                "host",
+                "/b",
+                vec![test_snapshot("b1", 300, &["latest-1"])],
+            ),
+        ]);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let mut output = Vec::new();
+        map.generate_all_metrics_to(now, &mut output)
+            .expect("infallible");
+        assert_eq!(output, map.generate_all_metrics(now).into_bytes());
+    }
+
+    #[test]
+    fn generate_all_metrics_timestamped() {
+        let snapshots = vec![test_snapshot("1", 1000, &["daily-1"])];
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+        let fetched_at: jiff::Timestamp = "2025-08-14T01:00:00Z".parse().expect("valid timestamp");
+
+        let (map, source) = single_map(snapshots);
+        let output = map.generate_all_metrics_timestamped(now, fetched_at);
+        output.assert_contains_lines(&[
             "# TYPE kopia_snapshots_total gauge",
+            format!(
+                "kopia_snapshots_total{{source=\"{}\"}} 1 1755133200000",
+                source.as_str()
+            )
+            .as_str(),
         ]);
+        let samples = output
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in samples {
+            assert!(line.ends_with(" 1755133200000"), "{line}");
+        }
+    }
+
+    #[test]
+    fn label_values_escaped() {
+        let (map, _sources) = multi_map(vec![(
+            "user",
+            "host",
+            "C:\\Users\\\"quoted\"/ünïcode",
+            vec![test_snapshot("1", 1000, &["new\nline\ttab"])],
+        )]);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        // tab as-is, unlike the `\t` of `Debug`
+        map.generate_all_metrics(now).assert_contains_lines(&[
+            r#"kopia_snapshots_total{source="user@host:C:\\Users\\\"quoted\"/ünïcode"} 1"#,
+            concat!(
+                r#"kopia_snapshots_by_retention{source="user@host:C:\\Users\\\"quoted\"/ünïcode",retention_reason="new\nline"#,
+                "\t",
+                r#"tab"} 1"#,
+            ),
+        ]);
+    }
+
+    #[test]
+    fn output_is_deterministic() {
+        let sources = || {
+            vec![
+                (
+                    "bob",
+                    "nas",
+                    "/srv",
+                    vec![
+                        test_snapshot("b1", 1000, &["weekly-1", "daily-2"]),
+                        test_snapshot("b2", 2000, &["latest-1", "daily-1"]),
+                    ],
+                ),
+                (
+                    "alice",
+                    "laptop",
+                    "/home/alice",
+                    vec![test_snapshot("a1", 3000, &["latest-1", "monthly-1"])],
+                ),
+                ("alice", "nas", "/etc", vec![test_snapshot("a2", 4000, &[])]),
+            ]
+        };
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let (map, _sources) = multi_map(sources());
+        let mut reversed_sources = sources();
+        reversed_sources.reverse();
+        let (reversed, _sources) = multi_map(reversed_sources);
+
+        let expected = map.generate_all_metrics(now);
+        assert_eq!(map.generate_all_metrics(now), expected);
+        assert_eq!(reversed.generate_all_metrics(now), expected);
+        assert_eq!(map.prerender_metrics(None).render(&map, now), expected);
+        assert_eq!(
+            reversed.generate_all_metrics_json(now),
+            map.generate_all_metrics_json(now)
+        );
+        assert_eq!(
+            reversed.generate_remote_write(now),
+            map.generate_remote_write(now)
+        );
+
+        // sources sorted, labels in a fixed order
+        let retention: Vec<_> = expected
+            .lines()
+            .filter(|line| line.starts_with("kopia_snapshots_by_retention{"))
+            .collect();
+        assert_eq!(
+            retention,
+            [
+                r#"kopia_snapshots_by_retention{source="alice@laptop:/home/alice",retention_reason="latest-1"} 1"#,
+                r#"kopia_snapshots_by_retention{source="alice@laptop:/home/alice",retention_reason="monthly-1"} 1"#,
+                r#"kopia_snapshots_by_retention{source="bob@nas:/srv",retention_reason="daily-1"} 1"#,
+                r#"kopia_snapshots_by_retention{source="bob@nas:/srv",retention_reason="daily-2"} 1"#,
+                r#"kopia_snapshots_by_retention{source="bob@nas:/srv",retention_reason="latest-1"} 1"#,
+                r#"kopia_snapshots_by_retention{source="bob@nas:/srv",retention_reason="weekly-1"} 1"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn extreme_values_render() {
+        let mut oldest = test_snapshot("1", u64::MAX, &["daily-2"]);
+        oldest.end_time = jiff::Timestamp::MIN.to_string();
+        oldest.stats.error_count = u64::MAX;
+        let mut newest = test_snapshot("2", 0, &["daily-1"]);
+        newest.end_time = jiff::Timestamp::MAX.to_string();
+        let (map, source) = single_map(vec![oldest, newest]);
+
+        for now in [jiff::Timestamp::MIN, jiff::Timestamp::MAX] {
+            map.generate_all_metrics(now)
+                .assert_contains_snippets(&["\nkopia_snapshot_age_seconds{"]);
+            assert!(!map.generate_all_metrics_json(now).is_empty());
+            assert!(!map.generate_all_metrics_influx(now).is_empty());
+            assert!(!map.generate_remote_write(now).is_empty());
+        }
+        map.kopia_snapshot_size_bytes_change()
+            .expect("two snapshots")
+            .assert_contains_lines(&[&format!(
+                "kopia_snapshot_size_bytes_change{{source={source:?}}} -{}",
+                u64::MAX
+            )]);
+    }
+
+    #[test]
+    fn generate_all_metrics_json() {
+        let snapshots = vec![test_snapshot("1", 1000, &["daily-1"])];
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let (map, _source) = single_map(snapshots);
+        let json: serde_json::Value =
+            serde_json::from_str(&map.generate_all_metrics_json(now)).expect("valid JSON");
+        let samples = json.as_array().expect("array");
+
+        let age = samples
+            .iter()
+            .find(|sample| sample["name"] == "kopia_snapshot_age_seconds")
+            .expect("age sample present");
+        assert_eq!(
+            *age,
+            serde_json::json!({
+                "name": "kopia_snapshot_age_seconds",
+                "help": "Age of newest snapshot in seconds",
+                "type": "gauge",
+                "labels": { "source": "user_name@host:/path" },
+                "value": 3600,
+            })
+        );
+
+        let retention = samples
+            .iter()
+            .find(|sample| sample["name"] == "kopia_snapshots_by_retention")
+            .expect("retention sample present");
+        assert_eq!(
+            retention["labels"],
+            serde_json::json!({ "source": "user_name@host:/path", "retention_reason": "daily-1" })
+        );
+    }
+
+    #[test]
+    fn generate_all_metrics_influx() {
+        let snapshots = vec![test_snapshot("1", 1000, &["daily-1"])];
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let (map, _source) = single_map(snapshots);
+        map.generate_all_metrics_influx(now).assert_contains_lines(&[
+            r"kopia_snapshot_age_seconds,source=user_name@host:/path value=3600i 1755133260000000000",
+            r"kopia_snapshots_by_retention,source=user_name@host:/path,retention_reason=daily-1 value=1i 1755133260000000000",
+        ]);
+    }
+
+    #[test]
+    fn generate_remote_write() {
+        let snapshots = vec![test_snapshot("1", 1000, &["daily-1"])];
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let (map, _source) = single_map(snapshots);
+        let message = map.generate_remote_write(now);
+
+        // first time series: kopia_snapshots_by_retention
+        let label = |name: &str, value: &str| {
+            let mut label = vec![0x0A, u8::try_from(name.len()).expect("short")];
+            label.extend_from_slice(name.as_bytes());
+            label.extend_from_slice(&[0x12, u8::try_from(value.len()).expect("short")]);
+            label.extend_from_slice(value.as_bytes());
+            let mut field = vec![0x0A, u8::try_from(label.len()).expect("short")];
+            field.extend(label);
+            field
+        };
+        let mut series = label("__name__", "kopia_snapshots_by_retention");
+        series.extend(label("retention_reason", "daily-1"));
+        series.extend(label("source", "user_name@host:/path"));
+        // sample: value 1.0, timestamp 1755133260000 ms
+        series.extend_from_slice(&[0x12, 0x10, 0x09]);
+        series.extend_from_slice(&1.0f64.to_le_bytes());
+        series.extend_from_slice(&[0x10, 0xE0, 0xE1, 0xE0, 0xB0, 0x8A, 0x33]);
+
+        let mut expected = vec![0x0A, u8::try_from(series.len()).expect("short")];
+        expected.extend(series);
+        assert_eq!(message[..expected.len()], expected[..]);
+    }
+
+    #[test]
+    fn generate_statsd_lines() {
+        let snapshots = vec![test_snapshot("1", 1000, &["daily-1"])];
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let (map, _source) = single_map(snapshots);
+        let dogstatsd = map.generate_statsd_lines(now, StatsdFlavor::Dogstatsd);
+        assert_eq!(
+            dogstatsd,
+            [
+                "kopia_snapshot_age_seconds:3600|g|#source:user_name@host:/path",
+                "kopia_snapshot_size_bytes_total:1000|g|#source:user_name@host:/path",
+                "kopia_snapshot_errors_total:0|g|#source:user_name@host:/path",
+            ]
+        );
+
+        let plain = map.generate_statsd_lines(now, StatsdFlavor::Plain);
+        assert_eq!(
+            plain,
+            [
+                "kopia_snapshot_age_seconds.user_name_host__path:3600|g",
+                "kopia_snapshot_size_bytes_total.user_name_host__path:1000|g",
+                "kopia_snapshot_errors_total.user_name_host__path:0|g",
+            ]
+        );
+    }
+
+    #[test]
+    fn repo_id_label() {
+        let snapshots = vec![test_snapshot("1", 1000, &["daily-1"])];
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let (map, _source) = single_map(snapshots);
+        let map = map
+            .with_fetched_at(now)
+            .with_custom_metric(|_| {
+                Some(CustomMetric::new("custom", "Custom", MetricType::Gauge).with_sample(&[], 1))
+            })
+            .with_repo_id("home");
+        let output = map.generate_all_metrics(now);
+        output.assert_contains_lines(&[
+            "kopia_snapshots_total{repo_id=\"home\",source=\"user_name@host:/path\"} 1",
+            "kopia_sources_total{repo_id=\"home\"} 1",
+            "kopia_exporter_data_age_seconds{repo_id=\"home\"} 0",
+            "custom{repo_id=\"home\"} 1",
+        ]);
+        let samples = output
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in samples {
+            assert!(line.contains("{repo_id=\"home\""), "{line}");
+        }
+        assert_eq!(map.prerender_metrics(None).render(&map, now), output);
+
+        assert_eq!(
+            map.generate_statsd_lines(now, StatsdFlavor::Dogstatsd)[0],
+            "kopia_snapshot_age_seconds:3600|g|#repo_id:home,source:user_name@host:/path"
+        );
+        assert!(
+            map.generate_all_metrics_json(now)
+                .contains(r#""labels":{"repo_id":"home","source":"user_name@host:/path"}"#)
+        );
     }
 
     #[test]
     fn full_snapshot() {
         let sample_data = include_str!("sample_kopia-snapshot-list.json");
-        let snapshots = KopiaSnapshots::new_parse_json(sample_data, |e| eyre::bail!(e))
-            .expect("valid snapshot JSON");
+        let (snapshots, invalid_sources) =
+            KopiaSnapshots::new_from_reader_with_report(sample_data.as_bytes())
+                .expect("valid snapshot JSON");
+        assert!(invalid_sources.is_empty());
 
         let now: jiff::Timestamp = "2025-08-17T20:58:04.972143344Z"
             .parse()
@@ -258,6 +970,16 @@ mod tests {
             # TYPE kopia_snapshot_oldest_age_seconds gauge
             kopia_snapshot_oldest_age_seconds{source="kopia-system@milton:/persist-home"} 6735478
 
+            # HELP kopia_data_quality_issues_total Number of data quality issues in the snapshots list, by kind
+            # TYPE kopia_data_quality_issues_total gauge
+            kopia_data_quality_issues_total{kind="timestamp_missing"} 0
+            kopia_data_quality_issues_total{kind="timestamp_unparseable"} 0
+            kopia_data_quality_issues_total{kind="timestamp_out_of_range"} 0
+            kopia_data_quality_issues_total{kind="invalid_source"} 0
+            kopia_data_quality_issues_total{kind="malformed"} 0
+            kopia_data_quality_issues_total{kind="invalid_utf8"} 0
+            kopia_data_quality_issues_total{kind="duplicate"} 0
+
             # HELP kopia_snapshot_last_success_timestamp Unix timestamp of last successful snapshot
             # TYPE kopia_snapshot_last_success_timestamp gauge
             kopia_snapshot_last_success_timestamp{source="kopia-system@milton:/persist-home"} 1755129606
@@ -278,9 +1000,21 @@ mod tests {
             # TYPE kopia_snapshot_size_bytes_change gauge
             kopia_snapshot_size_bytes_change{source="kopia-system@milton:/persist-home"} 1116951
 
+            # HELP kopia_snapshot_excluded_size_change_bytes Change in excluded size from previous snapshot
+            # TYPE kopia_snapshot_excluded_size_change_bytes gauge
+            kopia_snapshot_excluded_size_change_bytes{source="kopia-system@milton:/persist-home"} 0
+
             # HELP kopia_snapshots_total Total number of snapshots
             # TYPE kopia_snapshots_total gauge
             kopia_snapshots_total{source="kopia-system@milton:/persist-home"} 17
+
+            # HELP kopia_sources_total Number of sources with snapshots
+            # TYPE kopia_sources_total gauge
+            kopia_sources_total 1
+
+            # HELP kopia_repository_empty Whether kopia listed no snapshots at all (1 empty, 0 otherwise)
+            # TYPE kopia_repository_empty gauge
+            kopia_repository_empty 0
             "#
         );
     }
@@ -0,0 +1,300 @@
+use super::{
+    MetricCategory, MetricFamily, MetricLabel, PrometheusText, SampleValue, SampleVisitor,
+};
+use crate::{KopiaSnapshots, SourceStr};
+use std::collections::BTreeSet;
+use std::fmt::{self, Write as _};
+
+/// Selection of metrics to render in the Prometheus text format
+///
+/// Selects all metrics by default. Selecting any category or metric restricts the output
+/// to the selected metrics, and excluded metrics are always omitted. Selecting a source
+/// restricts the output to the samples labeled with that source.
+///
+/// ```
+/// use kopia_exporter::metrics::{MetricCategory, MetricsBuilder};
+///
+/// let builder = MetricsBuilder::new()
+///     .with_category(MetricCategory::NEW_SNAPSHOT_HEALTH)
+///     .without("kopia_snapshot_last_success_timestamp");
+/// assert!(builder.includes("kopia_snapshot_age_seconds"));
+/// assert!(!builder.includes("kopia_snapshot_last_success_timestamp"));
+/// assert!(!builder.includes("kopia_snapshots_total"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MetricsBuilder {
+    categories: Vec<MetricCategory>,
+    included: BTreeSet<String>,
+    excluded: BTreeSet<String>,
+    source: Option<SourceStr>,
+    timestamp: Option<jiff::Timestamp>,
+}
+impl MetricsBuilder {
+    /// Creates a builder selecting all metrics
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects all metrics in the category
+    #[must_use]
+    pub fn with_category(mut self, category: MetricCategory) -> Self {
+        self.categories.push(category);
+        self
+    }
+
+    /// Selects the metric with the specified name
+    #[must_use]
+    pub fn with(mut self, metric_name: &str) -> Self {
+        self.included.insert(metric_name.to_owned());
+        self
+    }
+
+    /// Omits the metric with the specified name
+    #[must_use]
+    pub fn without(mut self, metric_name: &str) -> Self {
+        self.excluded.insert(metric_name.to_owned());
+        self
+    }
+
+    /// Selects only the samples of the source, omitting metrics without a `source` label
+    /// (e.g. to scrape sources at different intervals)
+    #[must_use]
+    pub fn with_source(mut self, source: SourceStr) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Appends the timestamp to every sample, if set (e.g. the fetch time, see
+    /// [`KopiaSnapshots::prerender_metrics`])
+    #[must_use]
+    pub fn with_timestamp(mut self, timestamp: Option<jiff::Timestamp>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Returns `true` if the metric with the specified name is selected
+    #[must_use]
+    pub fn includes(&self, metric_name: &str) -> bool {
+        let Self {
+            categories,
+            included,
+            excluded,
+            source: _,
+            timestamp: _,
+        } = self;
+        let selected = (categories.is_empty() && included.is_empty())
+            || included.contains(metric_name)
+            || categories
+                .iter()
+                .any(|category| category.contains(metric_name));
+        selected && !excluded.contains(metric_name)
+    }
+
+    /// Renders the selected metrics, in the order of
+    /// [`KopiaSnapshots::generate_all_metrics`]
+    #[must_use]
+    pub fn render(&self, snapshots: &KopiaSnapshots, now: jiff::Timestamp) -> String {
+        let mut output = String::new();
+        let timestamp_millis = self.timestamp.map(jiff::Timestamp::as_millisecond);
+        let metrics = snapshots
+            .all_metric_families(now)
+            .into_iter()
+            .filter(|metric| self.includes(metric.label().name()));
+        for metric in metrics {
+            let metric = match &self.source {
+                Some(source) => &SourceSamples {
+                    family: &*metric,
+                    source: source.as_str(),
+                    timestamp_millis,
+                },
+                None => &*metric,
+            };
+            if self.source.is_some() && !has_samples(metric) {
+                continue;
+            }
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            let metric = PrometheusText {
+                family: metric,
+                timestamp_millis,
+            };
+            write!(output, "{metric}").expect("infallible");
+        }
+        output
+    }
+}
+
+/// Samples of the family labeled with the source
+struct SourceSamples<'a> {
+    family: &'a dyn MetricFamily,
+    source: &'a str,
+    timestamp_millis: Option<i64>,
+}
+impl MetricFamily for SourceSamples<'_> {
+    fn label(&self) -> &MetricLabel {
+        self.family.label()
+    }
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        struct SourceVisitor<'a> {
+            source: &'a str,
+            inner: &'a mut dyn SampleVisitor,
+        }
+        impl SampleVisitor for SourceVisitor<'_> {
+            fn visit_suffixed(
+                &mut self,
+                suffix: &str,
+                labels: &[(&str, &str)],
+                value: SampleValue,
+            ) -> fmt::Result {
+                if labels.contains(&("source", self.source)) {
+                    self.inner.visit_suffixed(suffix, labels, value)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+        self.family.visit_samples(&mut SourceVisitor {
+            source: self.source,
+            inner: visitor,
+        })
+    }
+}
+impl fmt::Display for SourceSamples<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        PrometheusText {
+            family: self,
+            timestamp_millis: self.timestamp_millis,
+        }
+        .fmt(f)
+    }
+}
+
+/// Returns `true` if the family has any samples
+fn has_samples(family: &dyn MetricFamily) -> bool {
+    struct AnySample;
+    impl SampleVisitor for AnySample {
+        fn visit_suffixed(&mut self, _: &str, _: &[(&str, &str)], _: SampleValue) -> fmt::Result {
+            // abort at the first sample
+            Err(fmt::Error)
+        }
+    }
+    family.visit_samples(&mut AnySample).is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetricsBuilder;
+    use crate::{
+        metrics::MetricCategory,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    #[test]
+    fn default_matches_generate_all_metrics() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        assert_eq!(
+            MetricsBuilder::new().render(&map, now),
+            map.generate_all_metrics(now)
+        );
+    }
+
+    #[test]
+    fn selects_categories_and_metrics() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let output = MetricsBuilder::new()
+            .with_category(MetricCategory::NEW_SNAPSHOT_HEALTH)
+            .with("kopia_snapshots_total")
+            .without("kopia_snapshot_last_success_timestamp")
+            .render(&map, now);
+        let names: Vec<_> = output
+            .lines()
+            .filter_map(|line| line.strip_prefix("# TYPE "))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "kopia_snapshot_age_seconds gauge",
+                "kopia_snapshots_total gauge"
+            ]
+        );
+    }
+
+    #[test]
+    fn selects_source() {
+        let (map, sources) = multi_map(vec![
+            (
+                "alice",
+                "laptop",
+                "/home",
+                vec![test_snapshot("1", 1000, &["daily-1"])],
+            ),
+            (
+                "bob",
+                "nas",
+                "/data",
+                vec![test_snapshot("2", 2000, &["daily-1"])],
+            ),
+        ]);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let output = MetricsBuilder::new()
+            .with_source(sources[1].clone())
+            .without("kopia_snapshot_age_seconds")
+            .render(&map, now);
+        assert!(
+            output.contains("\nkopia_snapshots_total{source=\"bob@nas:/data\"} 1\n"),
+            "{output}"
+        );
+        assert!(!output.contains("alice"), "{output}");
+        assert!(!output.contains("kopia_snapshot_age_seconds"), "{output}");
+        // metrics without samples of the source are omitted
+        assert!(!output.contains("kopia_sources_total"), "{output}");
+        for line in output
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            assert!(line.contains("source=\"bob@nas:/data\""), "{line}");
+        }
+    }
+
+    #[test]
+    fn appends_timestamp() {
+        let (map, source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+        let fetched_at: jiff::Timestamp = "2025-08-14T01:00:00Z".parse().expect("valid timestamp");
+
+        let output = MetricsBuilder::new()
+            .with("kopia_snapshots_total")
+            .with_source(source)
+            .with_timestamp(Some(fetched_at))
+            .render(&map, now);
+        assert!(
+            output.contains(
+                "\nkopia_snapshots_total{source=\"user_name@host:/path\"} 1 1755133200000\n"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn categories_cover_all_metrics() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        for metric in map.all_metric_families(now) {
+            let name = metric.label().name();
+            assert!(
+                MetricCategory::ALL
+                    .iter()
+                    .any(|category| category.contains(name)),
+                "{name}"
+            );
+        }
+    }
+}
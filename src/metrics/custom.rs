@@ -0,0 +1,267 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{MetricFamily, MetricLabel, MetricType, PrometheusText, SampleValue, SampleVisitor},
+};
+use std::{fmt, sync::Arc};
+
+/// Metric computed by a consumer-provided function, see [`KopiaSnapshots::with_custom_metric`]
+///
+/// ```
+/// use kopia_exporter::metrics::{CustomMetric, MetricType};
+///
+/// let metric = CustomMetric::new("site_backup_sources", "Number of sources", MetricType::Gauge)
+///     .with_sample(&[("site", "home")], 2);
+/// let output = metric.to_string();
+/// assert!(output.contains("\nsite_backup_sources{site=\"home\"} 2\n"));
+/// ```
+pub struct CustomMetric {
+    label: MetricLabel,
+    samples: Vec<CustomSample>,
+}
+struct CustomSample {
+    /// Appended to the metric name, see [`SampleVisitor::visit_suffixed`]
+    suffix: &'static str,
+    labels: Vec<(String, String)>,
+    value: SampleValue,
+}
+impl CustomMetric {
+    /// Creates a metric without any samples
+    #[must_use]
+    pub fn new(name: &'static str, help_text: &'static str, ty: MetricType) -> Self {
+        Self {
+            label: MetricLabel::__from_macro(name, help_text, ty),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Appends a sample, identified by the label name/value pairs
+    #[must_use]
+    pub fn with_sample(self, labels: &[(&str, &str)], value: impl Into<SampleValue>) -> Self {
+        self.with_suffixed_sample("", labels, value.into())
+    }
+
+    /// Appends the samples of a histogram for [`MetricType::Histogram`], from the upper bound and
+    /// cumulative count of each bucket (in ascending order), and the sum and count of all
+    /// observations
+    ///
+    /// The `+Inf` bucket is appended with the total count.
+    ///
+    /// ```
+    /// use kopia_exporter::metrics::{CustomMetric, MetricType};
+    ///
+    /// let metric = CustomMetric::new("site_latency_seconds", "Latency", MetricType::Histogram)
+    ///     .with_histogram(&[], &[(0.1, 2), (1.0, 3)], 1.5, 4);
+    /// let output = metric.to_string();
+    /// assert!(output.contains("\nsite_latency_seconds_bucket{le=\"1\"} 3\n"));
+    /// assert!(output.contains("\nsite_latency_seconds_bucket{le=\"+Inf\"} 4\n"));
+    /// assert!(output.contains("\nsite_latency_seconds_count 4\n"));
+    /// ```
+    #[must_use]
+    pub fn with_histogram(
+        self,
+        labels: &[(&str, &str)],
+        buckets: &[(f64, u64)],
+        sum: f64,
+        count: u64,
+    ) -> Self {
+        let upper_bounds = buckets
+            .iter()
+            .map(|(upper_bound, cumulative)| (upper_bound.to_string(), *cumulative))
+            .chain([("+Inf".to_owned(), count)]);
+        let this = upper_bounds.fold(self, |this, (upper_bound, cumulative)| {
+            let mut labels = labels.to_vec();
+            labels.push(("le", &upper_bound));
+            this.with_suffixed_sample("_bucket", &labels, cumulative.into())
+        });
+        this.with_suffixed_sample("_sum", labels, sum.into())
+            .with_suffixed_sample("_count", labels, count.into())
+    }
+
+    fn with_suffixed_sample(
+        mut self,
+        suffix: &'static str,
+        labels: &[(&str, &str)],
+        value: SampleValue,
+    ) -> Self {
+        let labels = labels
+            .iter()
+            .map(|(label, label_value)| ((*label).to_owned(), (*label_value).to_owned()))
+            .collect();
+        self.samples.push(CustomSample {
+            suffix,
+            labels,
+            value,
+        });
+        self
+    }
+}
+impl MetricFamily for CustomMetric {
+    fn label(&self) -> &MetricLabel {
+        &self.label
+    }
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        for CustomSample {
+            suffix,
+            labels,
+            value,
+        } in &self.samples
+        {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(label, label_value)| (label.as_str(), label_value.as_str()))
+                .collect();
+            visitor.visit_suffixed(suffix, &labels, *value)?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for CustomMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        PrometheusText {
+            family: self,
+            timestamp_millis: None,
+        }
+        .fmt(f)
+    }
+}
+
+type CustomMetricFn = dyn Fn(&KopiaSnapshots) -> Option<CustomMetric> + Send + Sync;
+
+/// Functions registered by [`KopiaSnapshots::with_custom_metric`]
+#[derive(Clone, Default)]
+pub(crate) struct CustomMetricFns(Vec<Arc<CustomMetricFn>>);
+impl CustomMetricFns {
+    pub fn push(&mut self, metric_fn: Arc<CustomMetricFn>) {
+        let Self(metric_fns) = self;
+        metric_fns.push(metric_fn);
+    }
+    pub fn is_empty(&self) -> bool {
+        let Self(metric_fns) = self;
+        metric_fns.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        let Self(metric_fns) = self;
+        metric_fns.len()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &CustomMetricFn> {
+        let Self(metric_fns) = self;
+        metric_fns.iter().map(|metric_fn| &**metric_fn)
+    }
+}
+impl fmt::Debug for CustomMetricFns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(metric_fns) = self;
+        write!(f, "CustomMetricFns(<{} functions>)", metric_fns.len())
+    }
+}
+
+impl KopiaSnapshots {
+    /// Registers a function computing an additional metric, appended to the output of
+    /// [`Self::generate_all_metrics`] (and all other output formats) when present
+    ///
+    /// The function is called each time the metrics are generated (including from
+    /// [`PrerenderedMetrics`](crate::metrics::PrerenderedMetrics)), so it may report state
+    /// changing independently of the snapshots.
+    #[must_use]
+    pub fn with_custom_metric(
+        mut self,
+        metric_fn: impl Fn(&Self) -> Option<CustomMetric> + Send + Sync + 'static,
+    ) -> Self {
+        self.custom_metrics.push(Arc::new(metric_fn));
+        self
+    }
+
+    pub(super) fn custom_metric(&self, index: usize) -> Option<Box<dyn MetricFamily + '_>> {
+        let metric_fn = self.custom_metrics.iter().nth(index)?;
+        metric_fn(self).map(|metric| self.with_repo_label(Box::new(metric)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CustomMetric;
+    use crate::{
+        AssertContains as _,
+        metrics::MetricType,
+        test_util::{single_map, test_snapshot},
+    };
+
+    #[test]
+    fn appended_to_all_metrics() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let map = map
+            .with_custom_metric(|snapshots| {
+                let count = snapshots.sources().count();
+                let count = u64::try_from(count).ok()?;
+                Some(
+                    CustomMetric::new("site_sources", "Number of sources", MetricType::Gauge)
+                        .with_sample(&[], count),
+                )
+            })
+            .with_custom_metric(|_| None);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        let output = map.generate_all_metrics(now);
+        output
+            .assert_contains_lines(&["# TYPE site_sources gauge", "site_sources 1"])
+            .assert_matching_count(|line| line.starts_with("site_sources "), 1..=1);
+        assert!(output.ends_with("site_sources 1\n"), "{output}");
+        assert_eq!(map.prerender_metrics(None).render(&map, now), output);
+    }
+
+    #[test]
+    fn computed_for_each_render() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        };
+
+        let counter = Arc::new(AtomicU64::new(0));
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let map = map.with_custom_metric({
+            let counter = Arc::clone(&counter);
+            move |_| {
+                let value = counter.load(Ordering::Relaxed);
+                Some(
+                    CustomMetric::new("site_counter", "Counter", MetricType::Gauge)
+                        .with_sample(&[], value),
+                )
+            }
+        });
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+        let prerendered = map.prerender_metrics(None);
+
+        prerendered
+            .render(&map, now)
+            .assert_contains_lines(&["site_counter 0"]);
+        counter.store(5, Ordering::Relaxed);
+        prerendered
+            .render(&map, now)
+            .assert_contains_lines(&["site_counter 5"]);
+    }
+    #[test]
+    fn histogram_samples() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let map = map.with_custom_metric(|_| {
+            Some(
+                CustomMetric::new("site_latency_seconds", "Latency", MetricType::Histogram)
+                    .with_histogram(&[("site", "home")], &[(0.5, 1), (2.5, 3)], 3.25, 4),
+            )
+        });
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        map.generate_all_metrics(now).assert_contains_lines(&[
+            "# TYPE site_latency_seconds histogram",
+            r#"site_latency_seconds_bucket{site="home",le="0.5"} 1"#,
+            r#"site_latency_seconds_bucket{site="home",le="2.5"} 3"#,
+            r#"site_latency_seconds_bucket{site="home",le="+Inf"} 4"#,
+            r#"site_latency_seconds_sum{site="home"} 3.25"#,
+            r#"site_latency_seconds_count{site="home"} 4"#,
+        ]);
+        let json = map.generate_all_metrics_json(now);
+        assert!(
+            json.contains(r#"{"name":"site_latency_seconds_count","help":"Latency","type":"histogram","labels":{"site":"home"},"value":4}"#),
+            "{json}"
+        );
+    }
+}
@@ -0,0 +1,446 @@
+//! Statistics of the exporter process, reported as `kopia_exporter_*` metrics
+
+use crate::{
+    Error, KopiaSnapshots,
+    metrics::{CustomMetric, MetricType},
+};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Statistics of the exporter process, reported as `kopia_exporter_*` metrics
+///
+/// Recorded by the exporter while serving, and rendered with the snapshots once
+/// [registered](Self::register).
+#[derive(Debug, Default)]
+pub struct ExporterStats {
+    state: Mutex<ExporterStatsState>,
+}
+impl ExporterStats {
+    /// Label of the `kopia snapshot list` command
+    pub const SNAPSHOT_LIST: &'static str = "snapshot list";
+
+    /// Records the duration of a `/metrics` scrape
+    pub fn record_scrape(&self, duration: Duration) {
+        self.lock().scrape_duration = Some(duration);
+    }
+
+    /// Records the duration of a run of the `kopia` command, e.g. [`Self::SNAPSHOT_LIST`]
+    pub fn record_kopia_command(&self, command: &'static str, duration: Duration) {
+        self.lock()
+            .kopia_command_durations
+            .insert(command, duration);
+    }
+
+    /// Records the exit code of the `kopia` command fetching the snapshots, or -1 if it was
+    /// killed
+    pub fn record_fetch_exit_code(&self, exit_code: i32) {
+        self.lock().last_fetch_exit_code = Some(exit_code);
+    }
+    /// Records whether reading the snapshots file succeeded
+    pub fn record_snapshots_file_read(&self, success: bool) {
+        self.lock().snapshots_file_read_failed = Some(!success);
+    }
+
+    /// Records a failed fetch of the snapshots, counted by the cause of the `error`
+    pub fn record_fetch_error(&self, error: &Error) {
+        let kind = FetchErrorKind::of(error);
+        let mut state = self.lock();
+        let count = &mut state.fetch_errors[kind as usize];
+        *count = count.saturating_add(1);
+    }
+
+    /// Records a panic while handling an HTTP request
+    pub fn record_internal_panic(&self) {
+        let mut state = self.lock();
+        state.internal_panics = state.internal_panics.saturating_add(1);
+    }
+    /// Records a request served from the snapshots cache (`hit`), or fetching the snapshots
+    ///
+    /// Requests responded with a cached failure are recorded by
+    /// [`Self::record_cached_failure`] instead.
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let mut state = self.lock();
+        let count = if hit {
+            &mut state.cache_hits
+        } else {
+            &mut state.cache_misses
+        };
+        *count = count.saturating_add(1);
+    }
+
+    /// Records a request responded with a cached fetch failure, without running `kopia`
+    pub fn record_cached_failure(&self) {
+        let mut state = self.lock();
+        state.cached_failures = state.cached_failures.saturating_add(1);
+    }
+
+    /// Records an HTTP request by path and status code
+    pub fn record_http_request(&self, path: &'static str, status_code: u16, duration: Duration) {
+        let mut state = self.lock();
+        let count = state.http_requests.entry((path, status_code)).or_default();
+        *count = count.saturating_add(1);
+        state
+            .http_durations
+            .entry(path)
+            .or_default()
+            .observe(duration);
+    }
+
+    /// Registers the metrics, reporting the statistics current when rendered
+    #[must_use]
+    pub fn register(self: &Arc<Self>, snapshots: KopiaSnapshots) -> KopiaSnapshots {
+        type MetricFn = fn(&ExporterStatsState) -> Option<CustomMetric>;
+        let metric_fns: [MetricFn; 11] = [
+            ExporterStatsState::scrape_duration_metric,
+            ExporterStatsState::kopia_command_duration_metric,
+            ExporterStatsState::last_fetch_exit_code_metric,
+            ExporterStatsState::snapshots_file_read_failed_metric,
+            |state| Some(state.fetch_errors_metric()),
+            |state| Some(state.cache_hits_metric()),
+            |state| Some(state.cache_misses_metric()),
+            |state| Some(state.cached_failures_metric()),
+            ExporterStatsState::http_requests_metric,
+            ExporterStatsState::http_request_duration_metric,
+            |state| Some(state.internal_panics_metric()),
+        ];
+        metric_fns
+            .into_iter()
+            .fold(snapshots, |snapshots, metric_fn| {
+                let stats = Arc::clone(self);
+                snapshots.with_custom_metric(move |_| metric_fn(&stats.lock()))
+            })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ExporterStatsState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ExporterStatsState {
+    /// Duration of the previous `/metrics` scrape
+    scrape_duration: Option<Duration>,
+    /// Duration of the last run of each `kopia` command
+    kopia_command_durations: BTreeMap<&'static str, Duration>,
+    /// Exit code of the last `kopia` command fetching the snapshots, or -1 if it was killed
+    last_fetch_exit_code: Option<i32>,
+    /// Whether the last read of the `--snapshots-file` failed
+    snapshots_file_read_failed: Option<bool>,
+    /// Number of failed fetches of each [`FetchErrorKind`]
+    fetch_errors: [u64; FetchErrorKind::ALL.len()],
+    /// Number of requests served from the cache
+    cache_hits: u64,
+    /// Number of requests fetching the snapshots, as nothing was cached
+    cache_misses: u64,
+    /// Number of requests responded with a cached failure
+    cached_failures: u64,
+    /// Number of HTTP requests by path and status code
+    http_requests: BTreeMap<(&'static str, u16), u64>,
+    /// Durations of the HTTP requests by path
+    http_durations: BTreeMap<&'static str, DurationHistogram>,
+    /// Number of panics while handling HTTP requests
+    internal_panics: u64,
+}
+impl ExporterStatsState {
+    fn scrape_duration_metric(&self) -> Option<CustomMetric> {
+        let duration = self.scrape_duration?;
+        Some(
+            CustomMetric::new(
+                "kopia_exporter_scrape_duration_seconds",
+                "Duration of the previous scrape of /metrics in seconds",
+                MetricType::Gauge,
+            )
+            .with_sample(&[], duration.as_secs_f64()),
+        )
+    }
+
+    fn kopia_command_duration_metric(&self) -> Option<CustomMetric> {
+        if self.kopia_command_durations.is_empty() {
+            return None;
+        }
+        let metric = CustomMetric::new(
+            "kopia_exporter_kopia_command_duration_seconds",
+            "Duration of the last run of the kopia command in seconds",
+            MetricType::Gauge,
+        );
+        let metric =
+            self.kopia_command_durations
+                .iter()
+                .fold(metric, |metric, (command, duration)| {
+                    metric.with_sample(&[("command", command)], duration.as_secs_f64())
+                });
+        Some(metric)
+    }
+
+    fn snapshots_file_read_failed_metric(&self) -> Option<CustomMetric> {
+        let failed = self.snapshots_file_read_failed?;
+        Some(
+            CustomMetric::new(
+                "kopia_exporter_snapshots_file_reload_failed",
+                "Whether the last read of the snapshots file failed (1) while serving the previous snapshots, or succeeded (0)",
+                MetricType::Gauge,
+            )
+            .with_sample(&[], u8::from(failed)),
+        )
+    }
+    fn last_fetch_exit_code_metric(&self) -> Option<CustomMetric> {
+        let exit_code = self.last_fetch_exit_code?;
+        Some(
+            CustomMetric::new(
+                "kopia_exporter_last_fetch_exit_code",
+                "Exit code of the last kopia command fetching the snapshots, or -1 if it was killed",
+                MetricType::Gauge,
+            )
+            .with_sample(&[], exit_code),
+        )
+    }
+
+    fn fetch_errors_metric(&self) -> CustomMetric {
+        let metric = CustomMetric::new(
+            "kopia_exporter_fetch_errors_total",
+            "Number of failed kopia fetches since the exporter started, e.g. killed after the timeout",
+            MetricType::Counter,
+        );
+        FetchErrorKind::ALL
+            .into_iter()
+            .zip(self.fetch_errors)
+            .fold(metric, |metric, (kind, count)| {
+                metric.with_sample(&[("kind", kind.label())], count)
+            })
+    }
+
+    fn cache_hits_metric(&self) -> CustomMetric {
+        CustomMetric::new(
+            "kopia_exporter_cache_hits_total",
+            "Number of requests served from the snapshots cache",
+            MetricType::Counter,
+        )
+        .with_sample(&[], self.cache_hits)
+    }
+
+    fn cache_misses_metric(&self) -> CustomMetric {
+        CustomMetric::new(
+            "kopia_exporter_cache_misses_total",
+            "Number of requests running kopia, as the snapshots cache was empty",
+            MetricType::Counter,
+        )
+        .with_sample(&[], self.cache_misses)
+    }
+
+    fn cached_failures_metric(&self) -> CustomMetric {
+        CustomMetric::new(
+            "kopia_exporter_cached_failures_total",
+            "Number of requests responded with a cached kopia failure, without running kopia",
+            MetricType::Counter,
+        )
+        .with_sample(&[], self.cached_failures)
+    }
+
+    fn http_requests_metric(&self) -> Option<CustomMetric> {
+        if self.http_requests.is_empty() {
+            return None;
+        }
+        let metric = CustomMetric::new(
+            "kopia_exporter_http_requests_total",
+            "Number of HTTP requests to the exporter by path and status code",
+            MetricType::Counter,
+        );
+        let metric =
+            self.http_requests
+                .iter()
+                .fold(metric, |metric, ((path, status_code), count)| {
+                    let status_code = status_code.to_string();
+                    metric.with_sample(&[("path", path), ("code", &status_code)], *count)
+                });
+        Some(metric)
+    }
+
+    fn http_request_duration_metric(&self) -> Option<CustomMetric> {
+        if self.http_durations.is_empty() {
+            return None;
+        }
+        let metric = CustomMetric::new(
+            "kopia_exporter_http_request_duration_seconds",
+            "Duration of the HTTP requests to the exporter by path in seconds",
+            MetricType::Histogram,
+        );
+        let metric = self
+            .http_durations
+            .iter()
+            .fold(metric, |metric, (path, histogram)| {
+                histogram.add_samples(metric, &[("path", path)])
+            });
+        Some(metric)
+    }
+    fn internal_panics_metric(&self) -> CustomMetric {
+        CustomMetric::new(
+            "kopia_exporter_internal_panics_total",
+            "Number of panics while handling HTTP requests, each responded with status 500",
+            MetricType::Counter,
+        )
+        .with_sample(&[], self.internal_panics)
+    }
+}
+/// Observed durations, counted in the [`DurationHistogram::BUCKETS`]
+#[derive(Debug, Default)]
+struct DurationHistogram {
+    /// Cumulative count of each bucket
+    buckets: [u64; Self::BUCKETS.len()],
+    sum: Duration,
+    count: u64,
+}
+impl DurationHistogram {
+    /// Upper bounds of the buckets in seconds, as the default buckets of Prometheus clients
+    const BUCKETS: [f64; 11] = [
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+    ];
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (upper_bound, count) in Self::BUCKETS.iter().zip(&mut self.buckets) {
+            if seconds <= *upper_bound {
+                *count = count.saturating_add(1);
+            }
+        }
+        self.sum = self.sum.saturating_add(duration);
+        self.count = self.count.saturating_add(1);
+    }
+
+    fn add_samples(&self, metric: CustomMetric, labels: &[(&str, &str)]) -> CustomMetric {
+        let buckets: Vec<(f64, u64)> = Self::BUCKETS.into_iter().zip(self.buckets).collect();
+        metric.with_histogram(labels, &buckets, self.sum.as_secs_f64(), self.count)
+    }
+}
+
+/// Cause of a failed fetch of the snapshots
+#[derive(Clone, Copy, Debug)]
+enum FetchErrorKind {
+    /// The `kopia` command was killed after the timeout
+    Timeout,
+    /// The `kopia` command failed to run, or exited unsuccessfully
+    Exit,
+    /// The `kopia` output could not be parsed
+    Parse,
+}
+impl FetchErrorKind {
+    const ALL: [Self; 3] = [Self::Timeout, Self::Exit, Self::Parse];
+
+    fn of(error: &Error) -> Self {
+        match error {
+            Error::Timeout { .. } => Self::Timeout,
+            Error::Json(_) | Error::InvalidSource(_) => Self::Parse,
+            Error::File { source, .. } => Self::of(source),
+            _ => Self::Exit,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Exit => "exit",
+            Self::Parse => "parse",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DurationHistogram, ExporterStats, FetchErrorKind};
+    use crate::{
+        AssertContains as _, Error,
+        test_util::{single_map, test_snapshot},
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn duration_histogram() {
+        let mut histogram = DurationHistogram::default();
+        histogram.observe(Duration::from_millis(5));
+        histogram.observe(Duration::from_millis(300));
+        histogram.observe(Duration::from_mins(1));
+        assert_eq!(histogram.buckets, [1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2]);
+        assert_eq!(histogram.count, 3);
+        assert_eq!(histogram.sum, Duration::from_millis(60_305));
+    }
+
+    #[test]
+    fn fetch_error_kinds() {
+        let kind = |error: &Error| FetchErrorKind::of(error).label();
+
+        let timeout = Error::Timeout {
+            timeout: Duration::from_secs(1),
+            stderr: None,
+        };
+        assert_eq!(kind(&timeout), "timeout");
+        let failed = Error::CommandFailed {
+            exit_code: Some(1),
+            stderr: String::new(),
+        };
+        assert_eq!(kind(&failed), "exit");
+        let not_found = Error::Io(std::io::ErrorKind::NotFound.into());
+        assert_eq!(kind(&not_found), "exit");
+        let json = serde_json::from_str::<u64>("[").expect_err("truncated");
+        let file = Error::File {
+            path: "snapshots.json".into(),
+            source: Box::new(Error::Json(json)),
+        };
+        assert_eq!(kind(&file), "parse");
+    }
+
+    #[test]
+    fn registered_metrics() {
+        let stats = Arc::new(ExporterStats::default());
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let map = stats.register(map);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+
+        // only the counters are present before anything is recorded
+        map.generate_all_metrics(now)
+            .assert_contains_lines(&[
+                "kopia_exporter_fetch_errors_total{kind=\"timeout\"} 0",
+                "kopia_exporter_cache_hits_total 0",
+                "kopia_exporter_cache_misses_total 0",
+                "kopia_exporter_cached_failures_total 0",
+                "kopia_exporter_internal_panics_total 0",
+            ])
+            .assert_matching_count(
+                |line| line.starts_with("kopia_exporter_scrape_duration_seconds "),
+                0..=0,
+            );
+
+        stats.record_scrape(Duration::from_millis(250));
+        stats.record_kopia_command(ExporterStats::SNAPSHOT_LIST, Duration::from_secs(2));
+        stats.record_fetch_exit_code(1);
+        stats.record_snapshots_file_read(false);
+        stats.record_fetch_error(&Error::CommandFailed {
+            exit_code: Some(1),
+            stderr: String::new(),
+        });
+        stats.record_cache_lookup(true);
+        stats.record_cache_lookup(true);
+        stats.record_cache_lookup(false);
+        stats.record_cached_failure();
+        stats.record_http_request("/metrics", 200, Duration::from_millis(20));
+        stats.record_http_request("/metrics", 200, Duration::from_millis(300));
+        stats.record_internal_panic();
+
+        // computed again for each render
+        map.generate_all_metrics(now).assert_contains_lines(&[
+            "kopia_exporter_scrape_duration_seconds 0.25",
+            "kopia_exporter_kopia_command_duration_seconds{command=\"snapshot list\"} 2",
+            "kopia_exporter_last_fetch_exit_code 1",
+            "kopia_exporter_snapshots_file_reload_failed 1",
+            "kopia_exporter_fetch_errors_total{kind=\"exit\"} 1",
+            "kopia_exporter_cache_hits_total 2",
+            "kopia_exporter_cache_misses_total 1",
+            "kopia_exporter_cached_failures_total 1",
+            "kopia_exporter_http_requests_total{path=\"/metrics\",code=\"200\"} 2",
+            "kopia_exporter_http_request_duration_seconds_bucket{path=\"/metrics\",le=\"0.025\"} 1",
+            "kopia_exporter_http_request_duration_seconds_bucket{path=\"/metrics\",le=\"+Inf\"} 2",
+            "kopia_exporter_http_request_duration_seconds_count{path=\"/metrics\"} 2",
+            "kopia_exporter_internal_panics_total 1",
+        ]);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::metrics::{MetricFamily, SampleValue, SampleVisitor};
+use std::fmt::{self, Write as _};
+
+/// Renders the samples of all metrics in the Influx line protocol
+///
+/// Each metric name becomes the measurement, labels become tags, and the sample is
+/// stored in the `value` field (integers use the `i` suffix).
+pub(super) fn render(metrics: &[Box<dyn MetricFamily + '_>], now: jiff::Timestamp) -> String {
+    struct LineWriter<'a> {
+        measurement: &'a str,
+        timestamp_nanos: i128,
+        output: &'a mut String,
+    }
+    impl SampleVisitor for LineWriter<'_> {
+        fn visit_suffixed(
+            &mut self,
+            suffix: &str,
+            labels: &[(&str, &str)],
+            value: SampleValue,
+        ) -> fmt::Result {
+            if let SampleValue::Float(value) = value
+                && !value.is_finite()
+            {
+                // line protocol has no representation for NaN/infinity
+                return Ok(());
+            }
+            write_escaped(self.output, self.measurement, &[',', ' ']);
+            self.output.push_str(suffix);
+            for (name, value) in labels {
+                if value.is_empty() {
+                    // empty tag values are rejected by InfluxDB
+                    continue;
+                }
+                self.output.push(',');
+                write_escaped(self.output, name, &[',', '=', ' ']);
+                self.output.push('=');
+                write_escaped(self.output, value, &[',', '=', ' ']);
+            }
+            match value {
+                SampleValue::Integer(value) => write!(self.output, " value={value}i")?,
+                SampleValue::Float(value) => write!(self.output, " value={value}")?,
+            }
+            writeln!(self.output, " {}", self.timestamp_nanos)
+        }
+    }
+
+    let timestamp_nanos = now.as_nanosecond();
+    let mut output = String::new();
+    for metric in metrics {
+        metric
+            .visit_samples(&mut LineWriter {
+                measurement: metric.label().name(),
+                timestamp_nanos,
+                output: &mut output,
+            })
+            .expect("infallible");
+    }
+    output
+}
+
+fn write_escaped(output: &mut String, s: &str, special: &[char]) {
+    for c in s.chars() {
+        if c == '\\' || special.contains(&c) {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_escaped;
+
+    #[test]
+    fn escape_tag_value() {
+        let mut output = String::new();
+        write_escaped(&mut output, r"user@host:/my dir,a=b\c", &[',', '=', ' ']);
+        assert_eq!(output, r"user@host:/my\ dir\,a\=b\\c");
+    }
+}
@@ -0,0 +1,54 @@
+use crate::metrics::{MetricFamily, SampleValue, SampleVisitor};
+use std::{collections::BTreeMap, fmt};
+
+/// Single sample, annotated with the metric details
+#[derive(serde::Serialize)]
+struct JsonSample<'a> {
+    name: String,
+    help: &'a str,
+    #[serde(rename = "type")]
+    ty: &'a str,
+    labels: BTreeMap<String, String>,
+    value: SampleValue,
+}
+
+/// Renders the samples of all metrics as a JSON array
+pub(super) fn render(metrics: &[Box<dyn MetricFamily + '_>]) -> String {
+    struct Collector<'a, 'b> {
+        metric: &'a dyn MetricFamily,
+        samples: &'b mut Vec<JsonSample<'a>>,
+    }
+    impl SampleVisitor for Collector<'_, '_> {
+        fn visit_suffixed(
+            &mut self,
+            suffix: &str,
+            labels: &[(&str, &str)],
+            value: SampleValue,
+        ) -> fmt::Result {
+            let label = self.metric.label();
+            self.samples.push(JsonSample {
+                name: format!("{}{suffix}", label.name()),
+                help: label.help_text(),
+                ty: label.metric_type().name(),
+                labels: labels
+                    .iter()
+                    .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+                    .collect(),
+                value,
+            });
+            Ok(())
+        }
+    }
+
+    let mut samples = Vec::new();
+    for metric in metrics {
+        let metric = metric.as_ref();
+        metric
+            .visit_samples(&mut Collector {
+                metric,
+                samples: &mut samples,
+            })
+            .expect("infallible");
+    }
+    serde_json::to_string(&samples).expect("serializable")
+}
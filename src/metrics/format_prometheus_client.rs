@@ -0,0 +1,245 @@
+use crate::KopiaSnapshots;
+use crate::metrics::{MetricType, SampleValue, SampleVisitor};
+use prometheus_client::{
+    collector::Collector,
+    encoding::{DescriptorEncoder, MetricEncoder, NoLabelSet},
+};
+use std::fmt;
+
+/// Encodes all metrics of [`KopiaSnapshots::generate_all_metrics`] on each scrape, with the
+/// ages relative to the scrape time
+///
+/// Register the snapshots with an existing registry via
+/// [`Registry::register_collector`](prometheus_client::registry::Registry::register_collector).
+impl Collector for KopiaSnapshots {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> fmt::Result {
+        struct EncoderVisitor<'a, 'b> {
+            is_counter: bool,
+            encoder: &'a mut MetricEncoder<'b>,
+        }
+        impl SampleVisitor for EncoderVisitor<'_, '_> {
+            fn visit_suffixed(
+                &mut self,
+                _suffix: &str,
+                labels: &[(&str, &str)],
+                value: SampleValue,
+            ) -> fmt::Result {
+                let Self {
+                    is_counter,
+                    encoder,
+                } = self;
+                if labels.is_empty() {
+                    encode_value(encoder, *is_counter, value)
+                } else {
+                    encode_value(&mut encoder.encode_family(&labels)?, *is_counter, value)
+                }
+            }
+        }
+
+        let now = jiff::Timestamp::now();
+        for metric in self.all_metric_families(now) {
+            let label = metric.label();
+            let (name, metric_type) = match label.metric_type() {
+                // NOTE: the counter suffix is appended by the encoder
+                MetricType::Counter => (
+                    label.name().strip_suffix("_total").unwrap_or(label.name()),
+                    prometheus_client::metrics::MetricType::Counter,
+                ),
+                MetricType::Gauge => (label.name(), prometheus_client::metrics::MetricType::Gauge),
+                MetricType::Histogram => (
+                    label.name(),
+                    prometheus_client::metrics::MetricType::Histogram,
+                ),
+            };
+            let mut metric_encoder =
+                encoder.encode_descriptor(name, label.help_text(), None, metric_type)?;
+            if let MetricType::Histogram = label.metric_type() {
+                // the encoder requires all samples of each histogram at once
+                let mut histograms = HistogramCollector::default();
+                metric.visit_samples(&mut histograms)?;
+                histograms.encode(&mut metric_encoder)?;
+                continue;
+            }
+            let is_counter = matches!(label.metric_type(), MetricType::Counter);
+            metric.visit_samples(&mut EncoderVisitor {
+                is_counter,
+                encoder: &mut metric_encoder,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Samples of a [`MetricType::Histogram`], grouped by the labels (except `le`)
+#[derive(Default)]
+struct HistogramCollector {
+    histograms: Vec<HistogramSamples>,
+}
+#[derive(Default)]
+struct HistogramSamples {
+    labels: Vec<(String, String)>,
+    /// Upper bound and cumulative count of each bucket
+    buckets: Vec<(f64, u64)>,
+    sum: f64,
+    count: u64,
+}
+impl SampleVisitor for HistogramCollector {
+    fn visit_suffixed(
+        &mut self,
+        suffix: &str,
+        labels: &[(&str, &str)],
+        value: SampleValue,
+    ) -> fmt::Result {
+        let mut upper_bound = None;
+        let labels: Vec<(String, String)> = labels
+            .iter()
+            .filter(|(label, label_value)| {
+                let is_bound = *label == "le";
+                if is_bound {
+                    upper_bound = Some(*label_value);
+                }
+                !is_bound
+            })
+            .map(|(label, label_value)| ((*label).to_owned(), (*label_value).to_owned()))
+            .collect();
+        let index = if let Some(index) = self.histograms.iter().position(|h| h.labels == labels) {
+            index
+        } else {
+            self.histograms.push(HistogramSamples {
+                labels,
+                ..HistogramSamples::default()
+            });
+            self.histograms.len() - 1
+        };
+        let histogram = &mut self.histograms[index];
+        match (suffix, upper_bound) {
+            ("_bucket", Some(upper_bound)) => {
+                // NOTE: the encoder renders `f64::MAX` as `+Inf`
+                let upper_bound = match upper_bound {
+                    "+Inf" => f64::MAX,
+                    upper_bound => upper_bound.parse().map_err(|_| fmt::Error)?,
+                };
+                histogram.buckets.push((upper_bound, count_value(value)));
+            }
+            ("_sum", None) => histogram.sum = value.as_f64(),
+            ("_count", None) => histogram.count = count_value(value),
+            _ => return Err(fmt::Error),
+        }
+        Ok(())
+    }
+}
+impl HistogramCollector {
+    fn encode(self, encoder: &mut MetricEncoder<'_>) -> fmt::Result {
+        for histogram in self.histograms {
+            let HistogramSamples {
+                labels,
+                buckets,
+                sum,
+                count,
+            } = histogram;
+            // the encoder accumulates the counts of the buckets
+            let mut previous = 0;
+            let buckets: Vec<(f64, u64)> = buckets
+                .into_iter()
+                .map(|(upper_bound, cumulative)| {
+                    let count = cumulative.saturating_sub(previous);
+                    previous = cumulative;
+                    (upper_bound, count)
+                })
+                .collect();
+            if labels.is_empty() {
+                encoder.encode_histogram::<NoLabelSet>(sum, count, &buckets, None)?;
+            } else {
+                encoder
+                    .encode_family(&labels)?
+                    .encode_histogram::<NoLabelSet>(sum, count, &buckets, None)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // counts are non-negative
+fn count_value(value: SampleValue) -> u64 {
+    match value {
+        SampleValue::Integer(integer) => u64::try_from(integer).unwrap_or(u64::MAX),
+        SampleValue::Float(float) => float as u64,
+    }
+}
+
+fn encode_value(
+    encoder: &mut MetricEncoder<'_>,
+    is_counter: bool,
+    value: SampleValue,
+) -> fmt::Result {
+    if is_counter {
+        match value {
+            SampleValue::Integer(integer) => match u64::try_from(integer) {
+                Ok(integer) => encoder.encode_counter::<NoLabelSet, _, f64>(&integer, None),
+                Err(_) => encoder.encode_counter::<NoLabelSet, _, f64>(&value.as_f64(), None),
+            },
+            SampleValue::Float(float) => encoder.encode_counter::<NoLabelSet, _, f64>(&float, None),
+        }
+    } else {
+        match value {
+            SampleValue::Integer(integer) => match i64::try_from(integer) {
+                Ok(integer) => encoder.encode_gauge(&integer),
+                Err(_) => encoder.encode_gauge(&value.as_f64()),
+            },
+            SampleValue::Float(float) => encoder.encode_gauge(&float),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        test_util::{single_map, test_snapshot},
+    };
+    use prometheus_client::{encoding::text::encode, registry::Registry};
+
+    #[test]
+    fn registry_encodes_snapshots() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+
+        let mut registry = Registry::default();
+        registry.register_collector(Box::new(map));
+        let mut output = String::new();
+        encode(&mut output, &registry).expect("infallible");
+
+        output.assert_contains_lines(&[
+            "# HELP kopia_snapshots_total Total number of snapshots",
+            "# TYPE kopia_snapshots_total gauge",
+            r#"kopia_snapshots_total{source="user_name@host:/path"} 1"#,
+            r#"kopia_snapshot_size_bytes_total{source="user_name@host:/path"} 1000"#,
+            "# EOF",
+        ]);
+    }
+    #[test]
+    fn registry_encodes_histogram() {
+        use crate::metrics::{CustomMetric, MetricType};
+
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["daily-1"])]);
+        let map = map.with_custom_metric(|_| {
+            Some(
+                CustomMetric::new("site_latency_seconds", "Latency", MetricType::Histogram)
+                    .with_histogram(&[("site", "home")], &[(0.5, 1), (2.5, 3)], 3.25, 4),
+            )
+        });
+
+        let mut registry = Registry::default();
+        registry.register_collector(Box::new(map));
+        let mut output = String::new();
+        encode(&mut output, &registry).expect("infallible");
+
+        output.assert_contains_lines(&[
+            "# TYPE site_latency_seconds histogram",
+            r#"site_latency_seconds_sum{site="home"} 3.25"#,
+            r#"site_latency_seconds_count{site="home"} 4"#,
+            r#"site_latency_seconds_bucket{le="0.5",site="home"} 1"#,
+            r#"site_latency_seconds_bucket{le="2.5",site="home"} 3"#,
+            r#"site_latency_seconds_bucket{le="+Inf",site="home"} 4"#,
+        ]);
+    }
+}
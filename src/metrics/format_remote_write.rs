@@ -0,0 +1,105 @@
+use crate::metrics::{MetricFamily, SampleValue, SampleVisitor};
+use std::fmt;
+
+/// Encodes all samples as a Prometheus remote write `WriteRequest` protobuf message
+///
+/// The message is uncompressed, the remote write protocol requires snappy block
+/// compression before sending.
+pub(super) fn render(metrics: &[Box<dyn MetricFamily + '_>], now: jiff::Timestamp) -> Vec<u8> {
+    struct Encoder<'a> {
+        name: &'a str,
+        timestamp_millis: i64,
+        output: &'a mut Vec<u8>,
+    }
+    impl SampleVisitor for Encoder<'_> {
+        fn visit_suffixed(
+            &mut self,
+            suffix: &str,
+            labels: &[(&str, &str)],
+            value: SampleValue,
+        ) -> fmt::Result {
+            // labels must be sorted by name, including `__name__`
+            let name = format!("{}{suffix}", self.name);
+            let mut labels: Vec<(&str, &str)> = labels.to_vec();
+            labels.push(("__name__", &name));
+            labels.sort_unstable();
+
+            let mut series = Vec::new();
+            for (name, value) in labels {
+                let mut label = Vec::new();
+                write_bytes_field(&mut label, 1, name.as_bytes());
+                write_bytes_field(&mut label, 2, value.as_bytes());
+                write_bytes_field(&mut series, 1, &label);
+            }
+            let mut sample = Vec::new();
+            write_key(&mut sample, 1, WIRE_FIXED64);
+            sample.extend_from_slice(&value.as_f64().to_le_bytes());
+            write_key(&mut sample, 2, WIRE_VARINT);
+            // int64 fields encode negative values as two's complement
+            write_varint(&mut sample, self.timestamp_millis.cast_unsigned());
+            write_bytes_field(&mut series, 2, &sample);
+
+            write_bytes_field(self.output, 1, &series);
+            Ok(())
+        }
+    }
+
+    let timestamp_millis = now.as_millisecond();
+    let mut output = Vec::new();
+    for metric in metrics {
+        metric
+            .visit_samples(&mut Encoder {
+                name: metric.label().name(),
+                timestamp_millis,
+                output: &mut output,
+            })
+            .expect("infallible");
+    }
+    output
+}
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+
+fn write_key(output: &mut Vec<u8>, field: u8, wire_type: u8) {
+    output.push((field << 3) | wire_type);
+}
+
+fn write_bytes_field(output: &mut Vec<u8>, field: u8, bytes: &[u8]) {
+    write_key(output, field, WIRE_LEN);
+    write_varint(output, bytes.len() as u64);
+    output.extend_from_slice(bytes);
+}
+
+/// Writes the `value` as a base 128 varint (also used by the snappy encoder)
+#[expect(clippy::cast_possible_truncation)] // only the low 7 bits are kept
+pub(crate) fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_varint;
+
+    #[test]
+    fn varint() {
+        for (value, expected) in [
+            (0, &[0x00][..]),
+            (1, &[0x01]),
+            (300, &[0xAC, 0x02]),
+            (
+                u64::MAX,
+                &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01],
+            ),
+        ] {
+            let mut output = Vec::new();
+            write_varint(&mut output, value);
+            assert_eq!(output, expected, "value {value}");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::metrics::{MetricFamily, SampleValue, SampleVisitor};
+use std::fmt::{self, Write as _};
+
+/// Dialect of the `StatsD` line format
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatsdFlavor {
+    /// Plain `StatsD`, label values are appended to the metric name (`name.label_value`)
+    Plain,
+    /// `DogStatsD`, labels are sent as tags (`name:1|g|#label:value`)
+    #[default]
+    Dogstatsd,
+}
+impl StatsdFlavor {
+    const ALL: &[Self] = &[Self::Plain, Self::Dogstatsd];
+
+    /// Returns the name used for parsing and display
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Plain => "statsd",
+            Self::Dogstatsd => "dogstatsd",
+        }
+    }
+}
+impl fmt::Display for StatsdFlavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+impl std::str::FromStr for StatsdFlavor {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|flavor| flavor.name() == s)
+            .ok_or_else(|| {
+                let expected: Vec<_> = Self::ALL.iter().map(|flavor| flavor.name()).collect();
+                format!(
+                    "invalid statsd flavor {s:?}, expected one of: {}",
+                    expected.join(", ")
+                )
+            })
+    }
+}
+
+/// Renders each sample as a `StatsD` gauge line
+pub(super) fn render(metrics: &[Box<dyn MetricFamily + '_>], flavor: StatsdFlavor) -> Vec<String> {
+    struct LineWriter<'a> {
+        name: &'a str,
+        flavor: StatsdFlavor,
+        lines: &'a mut Vec<String>,
+    }
+    impl SampleVisitor for LineWriter<'_> {
+        fn visit_suffixed(
+            &mut self,
+            suffix: &str,
+            labels: &[(&str, &str)],
+            value: SampleValue,
+        ) -> fmt::Result {
+            let mut line = format!("{}{suffix}", self.name);
+            match self.flavor {
+                StatsdFlavor::Plain => {
+                    for (_, label_value) in labels {
+                        line.push('.');
+                        push_sanitized(&mut line, label_value, |c| {
+                            c.is_ascii_alphanumeric() || c == '_' || c == '-'
+                        });
+                    }
+                    write!(line, ":{value}|g")?;
+                }
+                StatsdFlavor::Dogstatsd => {
+                    write!(line, ":{value}|g")?;
+                    for (index, (label_name, label_value)) in labels.iter().enumerate() {
+                        line.push_str(if index == 0 { "|#" } else { "," });
+                        write!(line, "{label_name}:")?;
+                        push_sanitized(&mut line, label_value, |c| {
+                            !matches!(c, ',' | '|' | '#' | '\n')
+                        });
+                    }
+                }
+            }
+            self.lines.push(line);
+            Ok(())
+        }
+    }
+
+    let mut lines = Vec::new();
+    for metric in metrics {
+        metric
+            .visit_samples(&mut LineWriter {
+                name: metric.label().name(),
+                flavor,
+                lines: &mut lines,
+            })
+            .expect("infallible");
+    }
+    lines
+}
+
+/// Appends `s`, replacing characters which are not allowed with `_`
+fn push_sanitized(output: &mut String, s: &str, allowed: impl Fn(char) -> bool) {
+    output.extend(s.chars().map(|c| if allowed(c) { c } else { '_' }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatsdFlavor;
+
+    #[test]
+    fn parse_round_trip() {
+        for flavor in StatsdFlavor::ALL {
+            let parsed: StatsdFlavor = flavor.to_string().parse().expect("valid");
+            assert_eq!(parsed, *flavor);
+        }
+        let err = "graphite".parse::<StatsdFlavor>().expect_err("invalid");
+        assert!(err.contains("dogstatsd"), "{err}");
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    KopiaSnapshots,
+    health::HealthReport,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct BackupHealthy(HealthReport);
+impl DisplayMetric for BackupHealthy {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(report) = self;
+        for source in &report.sources {
+            let healthy = u8::from(source.is_healthy());
+            visitor.visit(&[("source", source.source.as_str())], healthy.into())?;
+        }
+        Ok(())
+    }
+}
+impl BackupHealthy {
+    /// Implementation for [`KopiaSnapshots::kopia_backup_healthy`]
+    pub fn new(ks: &KopiaSnapshots, now: jiff::Timestamp) -> Option<Self> {
+        let thresholds = ks.health_thresholds.as_ref()?;
+        Some(Self(ks.evaluate_health(now, thresholds)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        health::HealthThresholds,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    fn now() -> jiff::Timestamp {
+        "2025-08-14T01:01:00Z".parse().expect("valid timestamp")
+    }
+
+    #[test]
+    fn backup_healthy_unconfigured() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &[])]);
+        assert!(map.kopia_backup_healthy(now()).is_none());
+        assert!(map.kopia_backup_healthy_all(now()).is_none());
+    }
+
+    #[test]
+    fn backup_healthy() {
+        let mut failed = test_snapshot("2", 1000, &[]);
+        failed.stats.error_count = 1;
+        let (map, _sources) = multi_map(vec![
+            ("alice", "host", "/ok", vec![test_snapshot("1", 1000, &[])]),
+            ("bob", "host", "/failed", vec![failed]),
+        ]);
+        let map = map.with_health_thresholds(HealthThresholds {
+            max_errors: Some(0),
+            ..HealthThresholds::default()
+        });
+
+        map.kopia_backup_healthy(now())
+            .expect("configured")
+            .assert_contains_lines(&[
+                "# TYPE kopia_backup_healthy gauge",
+                r#"kopia_backup_healthy{source="alice@host:/ok"} 1"#,
+                r#"kopia_backup_healthy{source="bob@host:/failed"} 0"#,
+            ]);
+        map.kopia_backup_healthy_all(now())
+            .expect("configured")
+            .assert_contains_lines(&["kopia_backup_healthy_all 0"]);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct BackupHealthyAll(bool);
+impl DisplayMetric for BackupHealthyAll {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(healthy) = self;
+        visitor.visit(&[], u8::from(*healthy).into())
+    }
+}
+impl BackupHealthyAll {
+    /// Implementation for [`KopiaSnapshots::kopia_backup_healthy_all`]
+    pub fn new(ks: &KopiaSnapshots, now: jiff::Timestamp) -> Option<Self> {
+        let thresholds = ks.health_thresholds.as_ref()?;
+        Some(Self(ks.evaluate_health(now, thresholds).is_healthy()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        health::HealthThresholds,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    fn now() -> jiff::Timestamp {
+        "2025-08-14T01:01:00Z".parse().expect("valid timestamp")
+    }
+
+    #[test]
+    fn backup_healthy_all() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &[])]);
+        map.with_health_thresholds(HealthThresholds {
+            max_age: Some(jiff::SignedDuration::from_hours(2)),
+            ..HealthThresholds::default()
+        })
+        .kopia_backup_healthy_all(now())
+        .expect("configured")
+        .assert_contains_lines(&[
+            "# TYPE kopia_backup_healthy_all gauge",
+            "kopia_backup_healthy_all 1",
+        ]);
+    }
+
+    #[test]
+    fn backup_healthy_all_no_sources() {
+        let (map, _sources) = multi_map(vec![]);
+        map.with_health_thresholds(HealthThresholds::default())
+            .kopia_backup_healthy_all(now())
+            .expect("configured")
+            .assert_contains_lines(&["kopia_backup_healthy_all 0"]);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    KopiaSnapshots,
+    kopia::TimestampIssueCounts,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct DataQualityIssues {
+    timestamps: TimestampIssueCounts,
+    invalid_sources: u64,
+    malformed: u64,
+    invalid_utf8: u64,
+    duplicates: u64,
+}
+impl DisplayMetric for DataQualityIssues {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self {
+            timestamps:
+                TimestampIssueCounts {
+                    missing,
+                    unparseable,
+                    out_of_range,
+                },
+            invalid_sources,
+            malformed,
+            invalid_utf8,
+            duplicates,
+        } = self;
+        for (kind, count) in [
+            ("timestamp_missing", missing),
+            ("timestamp_unparseable", unparseable),
+            ("timestamp_out_of_range", out_of_range),
+            ("invalid_source", invalid_sources),
+            ("malformed", malformed),
+            ("invalid_utf8", invalid_utf8),
+            ("duplicate", duplicates),
+        ] {
+            visitor.visit(&[("kind", kind)], (*count).into())?;
+        }
+        Ok(())
+    }
+}
+impl DataQualityIssues {
+    /// Implementation for [`KopiaSnapshots::kopia_data_quality_issues_total`]
+    pub fn new(ks: &KopiaSnapshots) -> Self {
+        let mut timestamps = TimestampIssueCounts::default();
+        for (_, folded) in &ks.folded {
+            timestamps.merge(folded.timestamp_issues);
+        }
+        for (_, snapshots) in &ks.snapshots_map {
+            for issue in snapshots.iter().flat_map(crate::Snapshot::timestamp_issues) {
+                timestamps.record(issue);
+            }
+        }
+
+        let invalid_sources = ks.invalid_sources();
+        let invalid_sources = invalid_sources
+            .user_names()
+            .chain(invalid_sources.hosts())
+            .chain(invalid_sources.paths())
+            .fold(0u64, |total, (_, count)| total.saturating_add(count));
+
+        Self {
+            timestamps,
+            invalid_sources,
+            malformed: ks.malformed.count(),
+            invalid_utf8: ks.utf8_replacements(),
+            duplicates: ks.duplicate_snapshots(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _, KopiaSnapshots, LatestSnapshotPolicy, ParseOptions,
+        test_util::{single_map, test_snapshot},
+    };
+
+    #[test]
+    fn no_issues() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
+        map.kopia_data_quality_issues_total().assert_contains_lines(&[
+            "# TYPE kopia_data_quality_issues_total gauge",
+            "kopia_data_quality_issues_total{kind=\"timestamp_missing\"} 0",
+            "kopia_data_quality_issues_total{kind=\"timestamp_unparseable\"} 0",
+            "kopia_data_quality_issues_total{kind=\"timestamp_out_of_range\"} 0",
+            "kopia_data_quality_issues_total{kind=\"invalid_source\"} 0",
+            "kopia_data_quality_issues_total{kind=\"malformed\"} 0",
+            "kopia_data_quality_issues_total{kind=\"invalid_utf8\"} 0",
+            "kopia_data_quality_issues_total{kind=\"duplicate\"} 0",
+        ]);
+    }
+
+    #[test]
+    fn timestamp_issues() {
+        let mut missing = test_snapshot("1", 1000, &["daily-1"]);
+        missing.start_time = String::new();
+        let mut unparseable = test_snapshot("2", 1000, &["daily-2"]);
+        unparseable.start_time = "yesterday".to_string();
+        unparseable.end_time = "today".to_string();
+        let mut out_of_range = test_snapshot("3", 1000, &["latest-1"]);
+        out_of_range.end_time = "0001-01-01T00:00:00Z".to_string();
+
+        let (map, _source) = single_map(vec![missing, unparseable, out_of_range]);
+        map.kopia_data_quality_issues_total().assert_contains_lines(&[
+            "kopia_data_quality_issues_total{kind=\"timestamp_missing\"} 1",
+            "kopia_data_quality_issues_total{kind=\"timestamp_unparseable\"} 2",
+            "kopia_data_quality_issues_total{kind=\"timestamp_out_of_range\"} 1",
+        ]);
+        // treated as absent by the other metrics
+        map.kopia_snapshot_parse_errors_timestamp_total()
+            .expect("end time errors")
+            .assert_contains_lines(&[
+                "kopia_snapshot_parse_errors_timestamp_total{source=\"user_name@host:/path\"} 2",
+            ]);
+    }
+
+    #[test]
+    fn other_issues() {
+        let mut invalid_source = test_snapshot("1", 1000, &["latest-1"]);
+        invalid_source.source.host = "bad:host".to_string();
+        let snapshots = vec![
+            test_snapshot("2", 1000, &["latest-1"]),
+            test_snapshot("2", 1000, &["latest-1"]),
+            invalid_source,
+        ];
+        let mut json = serde_json::to_vec(&snapshots).expect("serializable");
+        json.pop();
+        json.extend_from_slice(b",{\"id\":3}]");
+        let mangled = json
+            .windows(b"/path".len())
+            .position(|window| window == b"/path")
+            .expect("path present");
+        json[mangled + 1] = 0xff;
+
+        let (map, _invalid_sources) =
+            KopiaSnapshots::new_from_reader_with_report(json.as_slice()).expect("valid");
+        map.kopia_data_quality_issues_total().assert_contains_lines(&[
+            "kopia_data_quality_issues_total{kind=\"invalid_source\"} 1",
+            "kopia_data_quality_issues_total{kind=\"malformed\"} 1",
+            "kopia_data_quality_issues_total{kind=\"invalid_utf8\"} 1",
+            "kopia_data_quality_issues_total{kind=\"duplicate\"} 1",
+        ]);
+    }
+
+    #[test]
+    fn folded_timestamp_issues() {
+        let snapshots: Vec<_> = (0..5)
+            .map(|index| {
+                let mut snapshot = test_snapshot(&index.to_string(), 1000, &["daily-1"]);
+                if index > 0 {
+                    snapshot.start_time = String::new();
+                }
+                snapshot
+            })
+            .collect();
+        let json = serde_json::to_string(&snapshots).expect("serializable");
+        let (map, _) = KopiaSnapshots::new_from_reader_with_options(
+            json.as_bytes(),
+            ParseOptions::aggregate_only(LatestSnapshotPolicy::Newest),
+        )
+        .expect("valid");
+
+        map.kopia_data_quality_issues_total().assert_contains_lines(&[
+            "kopia_data_quality_issues_total{kind=\"timestamp_missing\"} 4",
+        ]);
+    }
+}
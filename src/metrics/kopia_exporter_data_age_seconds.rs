@@ -0,0 +1,48 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct DataAgeSeconds(i64);
+impl DisplayMetric for DataAgeSeconds {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(age_seconds) = self;
+        visitor.visit(&[], (*age_seconds).into())
+    }
+}
+impl DataAgeSeconds {
+    pub fn new(ks: &KopiaSnapshots, now: jiff::Timestamp) -> Option<Self> {
+        let fetched_at = ks.fetched_at?;
+        Some(Self(now.as_second() - fetched_at.as_second()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        test_util::{single_map, test_snapshot},
+    };
+
+    #[test]
+    fn data_age_unknown() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
+        let now = "2025-08-14T00:01:00Z".parse().expect("valid timestamp");
+        assert!(map.kopia_exporter_data_age_seconds(now).is_none());
+    }
+
+    #[test]
+    fn data_age() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
+        let fetched_at = "2025-08-14T00:01:00Z".parse().expect("valid timestamp");
+        let now = "2025-08-14T00:02:30Z".parse().expect("valid timestamp");
+        map.with_fetched_at(fetched_at)
+            .kopia_exporter_data_age_seconds(now)
+            .expect("fetch time known")
+            .assert_contains_lines(&[
+                "# TYPE kopia_exporter_data_age_seconds gauge",
+                "kopia_exporter_data_age_seconds 90",
+            ]);
+    }
+}
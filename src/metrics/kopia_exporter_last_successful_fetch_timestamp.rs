@@ -0,0 +1,42 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct LastSuccessfulFetchTimestamp(i64);
+impl DisplayMetric for LastSuccessfulFetchTimestamp {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(timestamp) = self;
+        visitor.visit(&[], (*timestamp).into())
+    }
+}
+impl LastSuccessfulFetchTimestamp {
+    pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
+        let fetched_at = ks.fetched_at?;
+        Some(Self(fetched_at.as_second()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        test_util::{single_map, test_snapshot},
+    };
+
+    #[test]
+    fn last_successful_fetch() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
+        assert!(map.kopia_exporter_last_successful_fetch_timestamp().is_none());
+
+        let fetched_at = "2025-08-14T00:01:00Z".parse().expect("valid timestamp");
+        map.with_fetched_at(fetched_at)
+            .kopia_exporter_last_successful_fetch_timestamp()
+            .expect("fetch time known")
+            .assert_contains_lines(&[
+                "# TYPE kopia_exporter_last_successful_fetch_timestamp gauge",
+                "kopia_exporter_last_successful_fetch_timestamp 1755129660",
+            ]);
+    }
+}
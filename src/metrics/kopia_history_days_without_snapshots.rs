@@ -0,0 +1,102 @@
+use crate::{
+    KopiaSnapshots, SourceMap,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::{collections::BTreeSet, fmt};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+pub(super) struct HistoryDaysWithoutSnapshots(SourceMap<u32>);
+impl DisplayMetric for HistoryDaysWithoutSnapshots {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(days_map) = self;
+        for (source, days) in days_map {
+            visitor.visit(&[("source", source.as_str())], (*days).into())?;
+        }
+        Ok(())
+    }
+}
+impl HistoryDaysWithoutSnapshots {
+    /// Implementation for [`KopiaSnapshots::kopia_history_days_without_snapshots`]
+    pub fn new(ks: &KopiaSnapshots, now: jiff::Timestamp) -> Option<Self> {
+        let history = ks.history.as_ref()?;
+        let days_map: SourceMap<_> = history
+            .iter()
+            .filter_map(|(source, snapshots)| {
+                let days_ago = |end_time: jiff::Timestamp| {
+                    now.duration_since(end_time).as_secs() / SECONDS_PER_DAY
+                };
+                // only days since the first recorded snapshot
+                let first_end_time = snapshots.iter().find_map(|snapshot| snapshot.end_time)?;
+                let recorded_days = u32::try_from(days_ago(first_end_time).checked_add(1)?)
+                    .ok()?
+                    .min(history.window_days());
+                let days_with_snapshots: BTreeSet<i64> = history
+                    .iter_window(snapshots, now)
+                    .map(|(end_time, _)| days_ago(end_time))
+                    .collect();
+                let days_with_snapshots = u32::try_from(days_with_snapshots.len()).ok()?;
+                Some((
+                    source.clone(),
+                    recorded_days.saturating_sub(days_with_snapshots),
+                ))
+            })
+            .collect();
+        days_map.map_nonempty(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        history::{HistoricSnapshot, SnapshotHistory},
+        test_util::single_map,
+    };
+
+    fn historic(id: &str, hours_ago: i64) -> HistoricSnapshot {
+        let end_time =
+            jiff::Timestamp::from_second(86_400 * 1000 - 3600 * hours_ago).expect("valid timestamp");
+        HistoricSnapshot {
+            id: id.to_string(),
+            start_time: None,
+            end_time: Some(end_time),
+            total_size: 1000,
+            error_count: 0,
+            last_observed_at: end_time,
+        }
+    }
+
+    #[test]
+    fn days_without_snapshots() {
+        let now = jiff::Timestamp::from_second(86_400 * 1000).expect("valid timestamp");
+        let (map, source) = single_map(vec![]);
+        assert!(map.kopia_history_days_without_snapshots(now).is_none());
+
+        // recorded for 10 days (first 9.5 days ago), with snapshots on 4 of them
+        let snapshots = vec![
+            historic("1", 9 * 24 + 12),
+            historic("2", 5 * 24 + 1),
+            historic("3", 5 * 24 + 2),
+            historic("4", 2 * 24),
+            historic("5", 1),
+        ];
+        let history = SnapshotHistory::new([(source, snapshots)].into_iter().collect());
+        let map = map.with_history(history.clone());
+        map.kopia_history_days_without_snapshots(now)
+            .expect("recorded snapshots")
+            .assert_contains_snippets(&["# HELP kopia_history_days_without_snapshots"])
+            .assert_contains_lines(&[
+                "# TYPE kopia_history_days_without_snapshots gauge",
+                "kopia_history_days_without_snapshots{source=\"user_name@host:/path\"} 6",
+            ]);
+
+        // the last 3 days, with snapshots on 2 of them
+        map.with_history(history.with_window_days(3))
+            .kopia_history_days_without_snapshots(now)
+            .expect("recorded snapshots")
+            .assert_contains_lines(&[
+                "kopia_history_days_without_snapshots{source=\"user_name@host:/path\"} 1",
+            ]);
+    }
+}
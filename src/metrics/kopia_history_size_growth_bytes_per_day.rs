@@ -0,0 +1,96 @@
+use super::kopia_repository_estimated_days_until_full::{SECONDS_PER_DAY, growth_rate};
+use crate::{
+    KopiaSnapshots, SourceMap,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct HistorySizeGrowth(SourceMap<f64>);
+impl DisplayMetric for HistorySizeGrowth {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(bytes_per_day_map) = self;
+        for (source, bytes_per_day) in bytes_per_day_map {
+            visitor.visit(&[("source", source.as_str())], (*bytes_per_day).into())?;
+        }
+        Ok(())
+    }
+}
+impl HistorySizeGrowth {
+    /// Implementation for [`KopiaSnapshots::kopia_history_size_growth_bytes_per_day`]
+    pub fn new(ks: &KopiaSnapshots, now: jiff::Timestamp) -> Option<Self> {
+        let history = ks.history.as_ref()?;
+        let bytes_per_day_map: SourceMap<_> = history
+            .iter()
+            .filter_map(|(source, snapshots)| {
+                let sizes = history
+                    .iter_window(snapshots, now)
+                    .map(|(end_time, snapshot)| (end_time, snapshot.total_size));
+                let bytes_per_second = growth_rate(sizes)?;
+                Some((source.clone(), bytes_per_second * SECONDS_PER_DAY))
+            })
+            .collect();
+        bytes_per_day_map.map_nonempty(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _, SourceStr,
+        history::{HistoricSnapshot, SnapshotHistory},
+        test_util::single_map,
+    };
+
+    fn historic(id: &str, total_size: u64, days_ago: i64) -> HistoricSnapshot {
+        let end_time =
+            jiff::Timestamp::from_second(86_400 * (1000 - days_ago)).expect("valid timestamp");
+        HistoricSnapshot {
+            id: id.to_string(),
+            start_time: None,
+            end_time: Some(end_time),
+            total_size,
+            error_count: 0,
+            last_observed_at: end_time,
+        }
+    }
+
+    fn history(source: &SourceStr, snapshots: Vec<HistoricSnapshot>) -> SnapshotHistory {
+        SnapshotHistory::new([(source.clone(), snapshots)].into_iter().collect())
+    }
+
+    #[test]
+    fn size_growth() {
+        let now = jiff::Timestamp::from_second(86_400 * 1000).expect("valid timestamp");
+        let (map, source) = single_map(vec![]);
+        assert!(map.kopia_history_size_growth_bytes_per_day(now).is_none());
+
+        // the snapshot before the window is ignored
+        let history = history(
+            &source,
+            vec![
+                historic("0", 100_000, 40),
+                historic("1", 1000, 20),
+                historic("2", 2000, 10),
+                historic("3", 3000, 0),
+            ],
+        );
+        map.with_history(history)
+            .kopia_history_size_growth_bytes_per_day(now)
+            .expect("growing")
+            .assert_contains_snippets(&["# HELP kopia_history_size_growth_bytes_per_day"])
+            .assert_contains_lines(&[
+                "# TYPE kopia_history_size_growth_bytes_per_day gauge",
+                "kopia_history_size_growth_bytes_per_day{source=\"user_name@host:/path\"} 100",
+            ]);
+    }
+
+    #[test]
+    fn size_growth_window() {
+        let now = jiff::Timestamp::from_second(86_400 * 1000).expect("valid timestamp");
+        let (map, source) = single_map(vec![]);
+        let history = history(&source, vec![historic("1", 1000, 20), historic("2", 3000, 10)]);
+
+        let map = map.with_history(history.with_window_days(15));
+        assert!(map.kopia_history_size_growth_bytes_per_day(now).is_none());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{
+    KopiaSnapshots, SourceMap,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct HistorySuccessRatio(SourceMap<f64>);
+impl DisplayMetric for HistorySuccessRatio {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(ratio_map) = self;
+        for (source, ratio) in ratio_map {
+            visitor.visit(&[("source", source.as_str())], (*ratio).into())?;
+        }
+        Ok(())
+    }
+}
+impl HistorySuccessRatio {
+    /// Implementation for [`KopiaSnapshots::kopia_history_success_ratio`]
+    pub fn new(ks: &KopiaSnapshots, now: jiff::Timestamp) -> Option<Self> {
+        let history = ks.history.as_ref()?;
+        let ratio_map: SourceMap<_> = history
+            .iter()
+            .filter_map(|(source, snapshots)| {
+                let (total, successful) = history.iter_window(snapshots, now).fold(
+                    (0u32, 0u32),
+                    |(total, successful), (_, snapshot)| {
+                        let success = u32::from(snapshot.error_count == 0);
+                        (total.saturating_add(1), successful.saturating_add(success))
+                    },
+                );
+                (total > 0).then(|| {
+                    let ratio = f64::from(successful) / f64::from(total);
+                    (source.clone(), ratio)
+                })
+            })
+            .collect();
+        ratio_map.map_nonempty(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        history::{HistoricSnapshot, SnapshotHistory},
+        test_util::single_map,
+    };
+
+    fn historic(id: &str, error_count: u64, days_ago: i64) -> HistoricSnapshot {
+        let end_time =
+            jiff::Timestamp::from_second(86_400 * (1000 - days_ago)).expect("valid timestamp");
+        HistoricSnapshot {
+            id: id.to_string(),
+            start_time: None,
+            end_time: Some(end_time),
+            total_size: 1000,
+            error_count,
+            last_observed_at: end_time,
+        }
+    }
+
+    #[test]
+    fn success_ratio() {
+        let now = jiff::Timestamp::from_second(86_400 * 1000).expect("valid timestamp");
+        let (map, source) = single_map(vec![]);
+        assert!(map.kopia_history_success_ratio(now).is_none());
+
+        // the failed snapshot before the window is ignored
+        let snapshots = vec![
+            historic("0", 5, 31),
+            historic("1", 0, 20),
+            historic("2", 2, 10),
+            historic("3", 0, 5),
+            historic("4", 0, 0),
+        ];
+        let history = SnapshotHistory::new([(source, snapshots)].into_iter().collect());
+        map.with_history(history)
+            .kopia_history_success_ratio(now)
+            .expect("snapshots in window")
+            .assert_contains_snippets(&["# HELP kopia_history_success_ratio"])
+            .assert_contains_lines(&[
+                "# TYPE kopia_history_success_ratio gauge",
+                "kopia_history_success_ratio{source=\"user_name@host:/path\"} 0.75",
+            ]);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct RepositoryEmpty(bool);
+impl DisplayMetric for RepositoryEmpty {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(empty) = self;
+        visitor.visit(&[], u8::from(*empty).into())
+    }
+}
+impl RepositoryEmpty {
+    /// Implementation for [`KopiaSnapshots::kopia_repository_empty`]
+    pub fn new(ks: &KopiaSnapshots) -> Self {
+        let KopiaSnapshots {
+            snapshots_map,
+            malformed,
+            ..
+        } = ks;
+        // skipped snapshots were still listed, so the repository is not empty
+        let empty = snapshots_map
+            .iter()
+            .all(|(_, snapshots)| snapshots.is_empty())
+            && ks.invalid_sources().is_empty()
+            && malformed.is_empty();
+        Self(empty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _, KopiaSnapshots,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    #[test]
+    fn repository_empty() {
+        let (map, _sources) = multi_map(vec![]);
+        map.kopia_repository_empty().assert_contains_lines(&[
+            "# TYPE kopia_repository_empty gauge",
+            "kopia_repository_empty 1",
+        ]);
+    }
+
+    #[test]
+    fn repository_not_empty() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
+        map.kopia_repository_empty()
+            .assert_contains_lines(&["kopia_repository_empty 0"]);
+    }
+
+    #[test]
+    fn repository_only_malformed_not_empty() {
+        let (map, _) = KopiaSnapshots::new_from_reader_with_report(r#"[{"id":1}]"#.as_bytes())
+            .expect("valid JSON");
+        map.kopia_repository_empty()
+            .assert_contains_lines(&["kopia_repository_empty 0"]);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) const SECONDS_PER_DAY: f64 = 86_400.0;
+
+pub(super) struct EstimatedDaysUntilFull(f64);
+impl DisplayMetric for EstimatedDaysUntilFull {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(days) = *self;
+        visitor.visit(&[], days.into())
+    }
+}
+impl EstimatedDaysUntilFull {
+    /// Implementation for [`KopiaSnapshots::kopia_repository_estimated_days_until_full`]
+    pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
+        let quota_bytes = ks.repo_quota_bytes?;
+        let policy = ks.latest_policy;
+        let mut used_bytes = 0u64;
+        let mut bytes_per_second = 0.0;
+        for (_, snapshots) in &ks.snapshots_map {
+            let Some(latest) = policy.select_latest(snapshots) else {
+                continue;
+            };
+            used_bytes = used_bytes.saturating_add(latest.stats.total_size);
+            let sizes = policy
+                .iter_newest_first(snapshots)
+                .filter_map(|snapshot| Some((snapshot.end_time?, snapshot.stats.total_size)));
+            bytes_per_second += growth_rate(sizes).unwrap_or(0.0);
+        }
+        if used_bytes >= quota_bytes {
+            return Some(Self(0.0));
+        }
+        // never full if not growing
+        (bytes_per_second > 0.0).then(|| {
+            #[expect(clippy::cast_precision_loss)] // estimate only
+            let remaining_bytes = (quota_bytes - used_bytes) as f64;
+            Self(remaining_bytes / bytes_per_second / SECONDS_PER_DAY)
+        })
+    }
+}
+
+/// Returns the least squares fit of the `(end time, total size)` points, in bytes per second
+///
+/// Only present for at least two distinct end times.
+pub(super) fn growth_rate(sizes: impl Iterator<Item = (jiff::Timestamp, u64)>) -> Option<f64> {
+    #[expect(clippy::cast_precision_loss)] // estimate only
+    let points: Vec<(f64, f64)> = sizes
+        .map(|(end_time, total_size)| (end_time.as_second() as f64, total_size as f64))
+        .collect();
+    let (first_seconds, _) = *points.first()?;
+    #[expect(clippy::cast_precision_loss)] // few snapshots
+    let count = points.len() as f64;
+    // relative to the first time, to keep the precision of the squares
+    let mean_x = points.iter().map(|(x, _)| x - first_seconds).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+    let (covariance, variance) =
+        points
+            .iter()
+            .fold((0.0, 0.0), |(covariance, variance), &(x, y)| {
+                let dx = x - first_seconds - mean_x;
+                (covariance + dx * (y - mean_y), variance + dx * dx)
+            });
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _, KopiaSnapshots, SnapshotJson,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    fn snapshot_at(id: &str, total_size: u64, days_ago: i64) -> SnapshotJson {
+        let end_time =
+            jiff::Timestamp::from_second(86_400 * (1000 - days_ago)).expect("valid timestamp");
+        let mut snapshot = test_snapshot(id, total_size, &[]);
+        snapshot.end_time = end_time.to_string();
+        snapshot
+    }
+
+    fn days_until_full(map: &KopiaSnapshots) -> Option<String> {
+        let metric = map.kopia_repository_estimated_days_until_full()?;
+        metric
+            .to_string()
+            .lines()
+            .find_map(|line| line.strip_prefix("kopia_repository_estimated_days_until_full "))
+            .map(str::to_owned)
+    }
+
+    #[test]
+    fn days_until_full_growing() {
+        let (map, _sources) = multi_map(vec![
+            ("alice", "hostA", "/data", vec![
+                snapshot_at("1", 1000, 2),
+                snapshot_at("2", 2000, 1),
+                snapshot_at("3", 3000, 0),
+            ]),
+            ("bob", "hostB", "/backup", vec![
+                snapshot_at("4", 4000, 10),
+                snapshot_at("5", 5000, 0),
+            ]),
+        ]);
+        assert_eq!(days_until_full(&map), None);
+
+        // 8000 used, growing by 1100 per day
+        let map = map.with_repo_quota_bytes(8000 + 5500);
+        map.kopia_repository_estimated_days_until_full()
+            .expect("quota configured")
+            .assert_contains_lines(&[
+                "# TYPE kopia_repository_estimated_days_until_full gauge",
+                "kopia_repository_estimated_days_until_full 5",
+            ]);
+    }
+
+    #[test]
+    fn days_until_full_exceeded() {
+        let (map, _source) = single_map(vec![snapshot_at("1", 1000, 1), snapshot_at("2", 900, 0)]);
+        let map = map.with_repo_quota_bytes(500);
+        assert_eq!(days_until_full(&map).as_deref(), Some("0"));
+    }
+
+    #[test]
+    fn days_until_full_not_growing() {
+        let (map, _source) = single_map(vec![snapshot_at("1", 1000, 1), snapshot_at("2", 900, 0)]);
+        let map = map.with_repo_quota_bytes(5000);
+        assert_eq!(days_until_full(&map), None);
+
+        let (map, _source) = single_map(vec![snapshot_at("1", 1000, 0)]);
+        let map = map.with_repo_quota_bytes(5000);
+        assert_eq!(days_until_full(&map), None);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    KopiaSnapshots, SourceMap,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct RetentionDeficit<'a> {
+    deficits: SourceMap<Vec<(&'a str, u32)>>,
+}
+impl DisplayMetric for RetentionDeficit<'_> {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self { deficits } = self;
+        for (source, class_deficits) in deficits {
+            for &(class, deficit) in class_deficits {
+                visitor.visit(
+                    &[("source", source.as_str()), ("class", class)],
+                    deficit.into(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+impl<'a> RetentionDeficit<'a> {
+    /// Implementation for [`KopiaSnapshots::kopia_retention_deficit`]
+    pub fn new(ks: &'a KopiaSnapshots) -> Option<Self> {
+        if ks.expected_retention.is_empty() {
+            return None;
+        }
+        let deficits: SourceMap<_> = ks
+            .get_retention_class_counts()
+            .into_iter()
+            .map(|(source, class_counts)| {
+                let class_deficits = ks
+                    .expected_retention
+                    .iter()
+                    .map(|(class, &expected)| {
+                        let retained = class_counts.get(class).copied().unwrap_or(0);
+                        let retained = u32::try_from(retained).unwrap_or(u32::MAX);
+                        (class.as_str(), expected.saturating_sub(retained))
+                    })
+                    .collect();
+                (source, class_deficits)
+            })
+            .collect();
+        deficits.map_nonempty(|deficits| Self { deficits })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    #[test]
+    fn retention_deficit() {
+        let (map, _sources) = multi_map(vec![
+            (
+                "alice",
+                "hostA",
+                "/data",
+                vec![
+                    test_snapshot("1", 1000, &["daily-3", "weekly-1"]),
+                    test_snapshot("2", 1000, &["daily-2"]),
+                    test_snapshot("3", 1000, &["latest-1", "daily-1"]),
+                ],
+            ),
+            (
+                "bob",
+                "hostB",
+                "/backup",
+                vec![test_snapshot("4", 1000, &["latest-1", "daily-1"])],
+            ),
+        ]);
+        assert!(map.kopia_retention_deficit().is_none());
+
+        map.with_expected_retention("daily", 3)
+            .with_expected_retention("weekly", 2)
+            .kopia_retention_deficit()
+            .expect("expected retention configured")
+            .assert_contains_snippets(&["# HELP kopia_retention_deficit"])
+            .assert_contains_lines(&[
+                "# TYPE kopia_retention_deficit gauge",
+                "kopia_retention_deficit{source=\"alice@hostA:/data\",class=\"daily\"} 0",
+                "kopia_retention_deficit{source=\"alice@hostA:/data\",class=\"weekly\"} 1",
+                "kopia_retention_deficit{source=\"bob@hostB:/backup\",class=\"daily\"} 2",
+                "kopia_retention_deficit{source=\"bob@hostB:/backup\",class=\"weekly\"} 2",
+            ]);
+    }
+
+    #[test]
+    fn retention_deficit_empty() {
+        let (map, _source) = single_map(vec![]);
+        let map = map.with_expected_retention("daily", 7);
+        assert!(map.kopia_retention_deficit().is_none());
+    }
+}
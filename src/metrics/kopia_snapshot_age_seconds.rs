@@ -1,12 +1,12 @@
-use crate::{KopiaSnapshots, Snapshot, SourceMap, metrics::DisplayMetric};
+use crate::{KopiaSnapshots, Snapshot, SourceMap, metrics::{DisplayMetric, SampleVisitor}};
 use std::fmt::{self};
 
 pub(super) struct SnapshotAgeSeconds(SourceMap<i64>);
 impl DisplayMetric for SnapshotAgeSeconds {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
         let Self(age_seconds_map) = self;
         for (source, age_seconds) in age_seconds_map {
-            writeln!(f, "{name}{{source={source:?}}} {age_seconds}")?;
+            visitor.visit(&[("source", source.as_str())], (*age_seconds).into())?;
         }
 
         Ok(())
@@ -25,13 +25,10 @@ impl SnapshotAgeSeconds {
             .filter_map(|(source, snapshots)| {
                 let last = select_fn(snapshots)?;
                 let age_seconds = {
-                    let age = now - last.end_time?;
-                    let age_seconds = age
-                        .total(jiff::Unit::Second)
-                        .expect("relative reference time given");
-                    #[expect(clippy::cast_possible_truncation)]
+                    let age = now.duration_since(last.end_time?);
+                    #[expect(clippy::cast_possible_truncation)] // timestamps are within range
                     {
-                        age_seconds.round() as i64
+                        age.as_secs_f64().round() as i64
                     }
                 };
                 Some((source.clone(), age_seconds))
@@ -49,7 +46,10 @@ mod tests {
     };
 
     fn test_snapshot_time(end_time: impl std::fmt::Display) -> SnapshotJson {
-        let mut snapshot = crate::test_util::test_snapshot("1", 1000, &["latest-1"]);
+        // unique IDs, as duplicates are skipped
+        static NEXT_ID: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let mut snapshot = crate::test_util::test_snapshot(&id.to_string(), 1000, &["latest-1"]);
         snapshot.end_time = end_time.to_string();
         snapshot
     }
@@ -0,0 +1,103 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct SnapshotDuplicates(u64);
+impl DisplayMetric for SnapshotDuplicates {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(count) = self;
+        visitor.visit(&[], (*count).into())
+    }
+}
+impl SnapshotDuplicates {
+    pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
+        let duplicates = ks.duplicate_snapshots();
+        (duplicates > 0).then_some(Self(duplicates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _, KopiaSnapshots, LatestSnapshotPolicy, ParseOptions,
+        test_util::{source_str, test_snapshot},
+    };
+
+    #[test]
+    fn duplicates_skipped() {
+        let (map, _) = KopiaSnapshots::new_from_snapshots_with_report(vec![
+            test_snapshot("1", 1000, &["daily-1"]),
+            test_snapshot("2", 2000, &["latest-1"]),
+            test_snapshot("1", 1000, &["daily-1"]),
+        ]);
+        assert_eq!(map.duplicate_snapshots(), 1);
+        map.kopia_snapshot_duplicates_total()
+            .expect("duplicates")
+            .assert_contains_lines(&[
+                "# TYPE kopia_snapshot_duplicates_total gauge",
+                "kopia_snapshot_duplicates_total 1",
+            ]);
+        map.kopia_snapshots_total()
+            .assert_contains_lines(&["kopia_snapshots_total{source=\"user_name@host:/path\"} 2"]);
+        map.kopia_snapshots_by_retention().assert_contains_lines(&[
+            "kopia_snapshots_by_retention{source=\"user_name@host:/path\",retention_reason=\"daily-1\"} 1",
+        ]);
+    }
+
+    #[test]
+    fn duplicates_absent() {
+        let (map, _) = KopiaSnapshots::new_from_snapshots_with_report(vec![
+            test_snapshot("1", 1000, &["daily-1"]),
+            test_snapshot("2", 2000, &["latest-1"]),
+        ]);
+        assert!(map.kopia_snapshot_duplicates_total().is_none());
+    }
+
+    #[test]
+    fn duplicates_merged() {
+        let fetch = |ids: &[&str]| {
+            let snapshots = ids
+                .iter()
+                .map(|id| test_snapshot(id, 1000, &["latest-1"]))
+                .collect();
+            KopiaSnapshots::new_from_snapshots_with_report(snapshots).0
+        };
+        let map = fetch(&["1", "2", "2"]).merge(fetch(&["2", "3"]));
+        assert_eq!(map.duplicate_snapshots(), 2);
+        let ids: Vec<_> = map
+            .snapshots_for(&source_str("user_name@host:/path"))
+            .expect("present")
+            .iter()
+            .map(|snapshot| snapshot.id.as_str())
+            .collect();
+        assert_eq!(ids, ["1", "2", "3"]);
+
+        // survives the round trip, and still skips merged duplicates
+        let json = serde_json::to_string(&map).expect("serializable");
+        let map: KopiaSnapshots = serde_json::from_str(&json).expect("deserializable");
+        let map = map.merge(fetch(&["3"]));
+        assert_eq!(map.duplicate_snapshots(), 3);
+    }
+
+    #[test]
+    fn duplicates_aggregated() {
+        // only the IDs of the kept snapshots (oldest, latest and previous) are remembered
+        let json = serde_json::to_string(
+            &(0..5)
+                .chain([0, 3, 4])
+                .map(|index| test_snapshot(&index.to_string(), 1000, &["daily-1"]))
+                .collect::<Vec<_>>(),
+        )
+        .expect("serializable");
+        let (map, _) = KopiaSnapshots::new_from_reader_with_options(
+            json.as_bytes(),
+            ParseOptions::aggregate_only(LatestSnapshotPolicy::NewestComplete),
+        )
+        .expect("valid");
+        assert_eq!(map.duplicate_snapshots(), 3);
+        map.kopia_snapshots_total()
+            .assert_contains_lines(&["kopia_snapshots_total{source=\"user_name@host:/path\"} 5"]);
+    }
+}
@@ -34,6 +34,31 @@ mod tests {
             ]);
     }
 
+    #[test]
+    fn snapshot_errors_latest_policy() {
+        use crate::LatestSnapshotPolicy;
+
+        let mut good = test_snapshot("1", 1000, &[]);
+        good.stats.error_count = 0;
+        let mut failed = test_snapshot("2", 1000, &["latest-1"]);
+        failed.stats.error_count = 4;
+
+        let (map, _source) = single_map(vec![good, failed]);
+        let map = map.with_latest_policy(LatestSnapshotPolicy::NewestComplete);
+        map.kopia_snapshot_errors_total()
+            .expect("nonempty")
+            .assert_contains_lines(&[
+                "kopia_snapshot_errors_total{source=\"user_name@host:/path\"} 4",
+            ]);
+
+        let map = map.with_latest_policy(LatestSnapshotPolicy::NewestWithoutErrors);
+        map.kopia_snapshot_errors_total()
+            .expect("nonempty")
+            .assert_contains_lines(&[
+                "kopia_snapshot_errors_total{source=\"user_name@host:/path\"} 0",
+            ]);
+    }
+
     #[test]
     fn snapshot_errors_metrics_empty() {
         let snapshots = vec![];
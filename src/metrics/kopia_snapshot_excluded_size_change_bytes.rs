@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _, SnapshotJson,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    fn test_snapshot_excluded(id: &str, excluded_total_size: u64) -> SnapshotJson {
+        let mut snapshot = test_snapshot(id, 1000, &[]);
+        snapshot.stats.excluded_total_size = excluded_total_size;
+        snapshot
+    }
+
+    #[test]
+    fn excluded_size_change() {
+        let (map, _sources) = multi_map(vec![
+            (
+                "alice",
+                "hostA",
+                "/data",
+                vec![
+                    test_snapshot_excluded("1", 100),
+                    test_snapshot_excluded("2", 5000),
+                ],
+            ),
+            (
+                "bob",
+                "hostB",
+                "/backup",
+                vec![
+                    test_snapshot_excluded("3", 800),
+                    test_snapshot_excluded("4", 300),
+                ],
+            ),
+        ]);
+
+        map.kopia_snapshot_excluded_size_change_bytes()
+            .expect("nonempty")
+            .assert_contains_snippets(&["# HELP kopia_snapshot_excluded_size_change_bytes"])
+            .assert_contains_lines(&[
+                "# TYPE kopia_snapshot_excluded_size_change_bytes gauge",
+                "kopia_snapshot_excluded_size_change_bytes{source=\"alice@hostA:/data\"} 4900",
+                "kopia_snapshot_excluded_size_change_bytes{source=\"bob@hostB:/backup\"} -500",
+            ]);
+    }
+
+    #[test]
+    fn excluded_size_change_single_snapshot() {
+        let (map, _source) = single_map(vec![test_snapshot_excluded("1", 100)]);
+        assert!(map.kopia_snapshot_excluded_size_change_bytes().is_none());
+    }
+}
@@ -8,7 +8,7 @@ mod tests {
     #[test]
     fn snapshot_failed_files_metrics() {
         let mut snapshot = test_snapshot("1", 1000, &["latest-1"]);
-        snapshot.root_entry.summ.num_failed = 3;
+        snapshot.root_entry.as_mut().expect("root entry").summ.num_failed = 3;
 
         let (map, _source) = single_map(vec![snapshot]);
         map.kopia_snapshot_failed_files_total()
@@ -44,10 +44,10 @@ mod tests {
     #[test]
     fn snapshot_failed_files_multi_source() {
         let mut snapshot1 = test_snapshot("1", 1000, &["latest-1"]);
-        snapshot1.root_entry.summ.num_failed = 5;
+        snapshot1.root_entry.as_mut().expect("root entry").summ.num_failed = 5;
 
         let mut snapshot2 = test_snapshot("2", 2000, &["latest-1"]);
-        snapshot2.root_entry.summ.num_failed = 2;
+        snapshot2.root_entry.as_mut().expect("root entry").summ.num_failed = 2;
 
         let (map, _sources) = multi_map(vec![
             ("alice", "hostA", "/data", vec![snapshot1]),
@@ -1,14 +1,14 @@
 //! **New snapshot health:** Unix timestamp of last successful snapshot
 
-use crate::{KopiaSnapshots, SourceMap, metrics::DisplayMetric};
+use crate::{KopiaSnapshots, SourceMap, metrics::{DisplayMetric, SampleVisitor}};
 use std::fmt::{self};
 
 pub(super) struct SnapshotLastSuccessTimestamp(SourceMap<i64>);
 impl DisplayMetric for SnapshotLastSuccessTimestamp {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
         let Self(timestamps) = self;
         for (source, timestamp) in timestamps {
-            writeln!(f, "{name}{{source={source:?}}} {timestamp}")?;
+            visitor.visit(&[("source", source.as_str())], (*timestamp).into())?;
         }
         Ok(())
     }
@@ -19,7 +19,7 @@ impl SnapshotLastSuccessTimestamp {
             .snapshots_map
             .iter()
             .filter_map(|(source, snapshots)| {
-                let last = snapshots.last()?;
+                let last = ks.latest_policy.select_latest(snapshots)?;
                 let end_time = last.end_time?;
                 Some((source.clone(), end_time.as_second()))
             })
@@ -100,6 +100,26 @@ mod tests {
         assert!(metrics.is_none());
     }
 
+    #[test]
+    fn snapshot_last_success_timestamp_offset() {
+        let mut snapshot = test_snapshot("1", 1000, &["latest-1"]);
+        snapshot.end_time = "2025-01-02T14:30:00.25+02:00".to_string();
+
+        let (map, _source) = single_map(vec![snapshot]);
+
+        let expected_timestamp: i64 = "2025-01-02T12:30:00Z"
+            .parse::<jiff::Timestamp>()
+            .expect("valid timestamp")
+            .as_second();
+
+        map.kopia_snapshot_last_success_timestamp()
+            .expect("nonempty")
+            .assert_contains_lines(&[&format!(
+                "kopia_snapshot_last_success_timestamp{{source=\"user_name@host:/path\"}} {expected_timestamp}"
+            )]);
+        assert!(map.kopia_snapshot_parse_errors_timestamp_total().is_none());
+    }
+
     #[test]
     fn snapshot_last_success_timestamp_invalid_time() {
         let mut snapshot = test_snapshot("1", 1000, &["latest-1"]);
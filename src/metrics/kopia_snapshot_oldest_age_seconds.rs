@@ -7,7 +7,10 @@ mod tests {
     use jiff::ToSpan as _;
 
     fn test_snapshot_time(end_time: impl std::fmt::Display) -> SnapshotJson {
-        let mut snapshot = crate::test_util::test_snapshot("1", 1000, &["latest-1"]);
+        // unique IDs, as duplicates are skipped
+        static NEXT_ID: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let mut snapshot = crate::test_util::test_snapshot(&id.to_string(), 1000, &["latest-1"]);
         snapshot.end_time = end_time.to_string();
         snapshot
     }
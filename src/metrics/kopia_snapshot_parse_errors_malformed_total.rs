@@ -0,0 +1,62 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct SnapshotParseErrorsMalformed(u64);
+impl DisplayMetric for SnapshotParseErrorsMalformed {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(count) = self;
+        visitor.visit(&[], (*count).into())
+    }
+}
+impl SnapshotParseErrorsMalformed {
+    pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
+        let malformed = ks.malformed_snapshots();
+        (!malformed.is_empty()).then(|| Self(malformed.count()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AssertContains as _, KopiaSnapshots, test_util::test_snapshot};
+
+    #[test]
+    fn malformed_snapshots_counted() {
+        let mut wrong_type = serde_json::to_value(test_snapshot("2", 2000, &[])).expect("valid");
+        wrong_type["stats"]["errorCount"] = serde_json::json!(-1);
+        let json = serde_json::to_string(&serde_json::json!([
+            test_snapshot("1", 1000, &["latest-1"]),
+            wrong_type,
+            {"id": "3"},
+        ]))
+        .expect("serializable");
+
+        let (map, invalid_sources) =
+            KopiaSnapshots::new_from_reader_with_report(json.as_bytes()).expect("valid array");
+        assert!(invalid_sources.is_empty());
+        assert_eq!(map.malformed_snapshots().count(), 2);
+        let first_error = map.malformed_snapshots().first_error().expect("malformed");
+        assert!(first_error.starts_with("snapshot 1: invalid value"), "{first_error}");
+
+        map.kopia_snapshot_parse_errors_malformed_total()
+            .expect("malformed")
+            .assert_contains_lines(&[
+                "# TYPE kopia_snapshot_parse_errors_malformed_total gauge",
+                "kopia_snapshot_parse_errors_malformed_total 2",
+            ]);
+        map.kopia_snapshots_total()
+            .assert_contains_lines(&["kopia_snapshots_total{source=\"user_name@host:/path\"} 1"]);
+    }
+
+    #[test]
+    fn malformed_snapshots_absent() {
+        let (map, _) = KopiaSnapshots::new_from_snapshots_with_report(vec![test_snapshot(
+            "1",
+            1000,
+            &["latest-1"],
+        )]);
+        assert!(map.kopia_snapshot_parse_errors_malformed_total().is_none());
+    }
+}
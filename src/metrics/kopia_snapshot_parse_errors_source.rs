@@ -1,40 +1,30 @@
-use crate::{KopiaSnapshots, metrics::DisplayMetric};
+use crate::{
+    InvalidSources, KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
 use std::fmt;
 
-pub(super) struct SnapshotParseErrorsSource<'a> {
-    invalid_user_names: &'a std::collections::BTreeMap<String, u32>,
-    invalid_hosts: &'a std::collections::BTreeMap<String, u32>,
-}
+pub(super) struct SnapshotParseErrorsSource<'a>(InvalidSources<'a>);
 impl<'a> SnapshotParseErrorsSource<'a> {
     pub fn new(ks: &'a KopiaSnapshots) -> Option<Self> {
-        let KopiaSnapshots {
-            invalid_user_names,
-            invalid_hosts,
-            ..
-        } = ks;
-        if invalid_user_names.is_empty() && invalid_hosts.is_empty() {
-            None
-        } else {
-            Some(Self {
-                invalid_user_names,
-                invalid_hosts,
-            })
-        }
+        let invalid_sources = ks.invalid_sources();
+        (!invalid_sources.is_empty()).then_some(Self(invalid_sources))
     }
 }
 impl DisplayMetric for SnapshotParseErrorsSource<'_> {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self {
-            invalid_user_names,
-            invalid_hosts,
-        } = self;
-
-        for (invalid_user, count) in *invalid_user_names {
-            writeln!(f, "{name}{{invalid_user={invalid_user:?}}} {count}")?;
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(invalid_sources) = self;
+
+        for (invalid_user, count) in invalid_sources.user_names() {
+            visitor.visit(&[("invalid_user", invalid_user)], count.into())?;
+        }
+
+        for (invalid_host, count) in invalid_sources.hosts() {
+            visitor.visit(&[("invalid_host", invalid_host)], count.into())?;
         }
 
-        for (invalid_host, count) in *invalid_hosts {
-            writeln!(f, "{name}{{invalid_host={invalid_host:?}}} {count}")?;
+        for (invalid_path, count) in invalid_sources.paths() {
+            visitor.visit(&[("invalid_path", invalid_path)], count.into())?;
         }
 
         Ok(())
@@ -61,8 +51,7 @@ mod tests {
             path: "/test2".to_string(),
         };
 
-        let map =
-            KopiaSnapshots::new_from_snapshots(vec![snap1, snap2], |_| Ok(())).expect("valid");
+        let (map, _invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(vec![snap1, snap2]);
         map.kopia_snapshot_parse_errors_source()
             .expect("has errors")
             .assert_contains_snippets(&["# HELP kopia_snapshot_parse_errors_source"])
@@ -81,7 +70,7 @@ mod tests {
             path: "/test".to_string(),
         };
 
-        let map = KopiaSnapshots::new_from_snapshots(vec![snap], |_| Ok(())).expect("valid");
+        let (map, _invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(vec![snap]);
         map.kopia_snapshot_parse_errors_source()
             .expect("has errors")
             .assert_contains_lines(&[
@@ -89,11 +78,33 @@ mod tests {
             ]);
     }
 
+    #[test]
+    fn source_parse_errors_invalid_path() {
+        let mut snap = test_snapshot("1", 1000, &["latest-1"]);
+        snap.source = Source {
+            host: "myhost".to_string(),
+            user_name: "user".to_string(),
+            path: "/new\nline".to_string(),
+        };
+        // `@` and `:` are unambiguous in the path
+        let mut valid = test_snapshot("2", 1000, &["latest-1"]);
+        valid.source.path = "C:\\user@host:/path".to_string();
+
+        let (map, _invalid_sources) =
+            KopiaSnapshots::new_from_snapshots_with_report(vec![snap, valid]);
+        map.kopia_snapshot_parse_errors_source()
+            .expect("has errors")
+            .assert_contains_lines(&[
+                "kopia_snapshot_parse_errors_source{invalid_path=\"/new\\nline\"} 1",
+            ]);
+        assert_eq!(map.sources().count(), 1);
+    }
+
     #[test]
     fn source_parse_errors_none() {
         let snap = test_snapshot("1", 1000, &["latest-1"]);
 
-        let map = KopiaSnapshots::new_from_snapshots(vec![snap], |_| Ok(())).expect("valid");
+        let (map, _invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(vec![snap]);
         let metrics = map.kopia_snapshot_parse_errors_source();
 
         assert!(metrics.is_none());
@@ -115,8 +126,7 @@ mod tests {
             path: "/test".to_string(),
         };
 
-        let map =
-            KopiaSnapshots::new_from_snapshots(vec![snap1, snap2], |_| Ok(())).expect("valid");
+        let (map, _invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(vec![snap1, snap2]);
         map.kopia_snapshot_parse_errors_source()
             .expect("has errors")
             .assert_contains_lines(&[
@@ -1,12 +1,15 @@
-use crate::{KopiaSnapshots, SourceMap, metrics::DisplayMetric};
+use crate::{
+    KopiaSnapshots, SourceMap,
+    metrics::{DisplayMetric, SampleVisitor},
+};
 use std::fmt;
 
-pub(super) struct ParseErrorCountsTimestamp(SourceMap<u32>);
+pub(super) struct ParseErrorCountsTimestamp(SourceMap<u64>);
 impl DisplayMetric for ParseErrorCountsTimestamp {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
         let Self(error_counts) = self;
         for (source, error_count) in error_counts {
-            writeln!(f, "{name}{{source={source:?}}} {error_count}")?;
+            visitor.visit(&[("source", source.as_str())], (*error_count).into())?;
         }
         Ok(())
     }
@@ -14,14 +17,19 @@ impl DisplayMetric for ParseErrorCountsTimestamp {
 
 impl ParseErrorCountsTimestamp {
     pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
-        let error_counts: SourceMap<u32> = ks
+        let error_counts: SourceMap<u64> = ks
             .snapshots_map
             .iter()
             .filter_map(|(source, snapshots)| {
+                let folded_count = ks
+                    .folded
+                    .get(source)
+                    .map_or(0, |folded| folded.timestamp_parse_errors);
                 let error_count = snapshots
                     .iter()
                     .map(|snapshot| if snapshot.end_time.is_none() { 1 } else { 0 })
-                    .sum::<u32>();
+                    .sum::<u64>()
+                    + folded_count;
 
                 (error_count > 0).then(|| (source.clone(), error_count))
             })
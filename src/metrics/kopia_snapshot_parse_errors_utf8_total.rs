@@ -0,0 +1,58 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct SnapshotParseErrorsUtf8(u64);
+impl DisplayMetric for SnapshotParseErrorsUtf8 {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(count) = self;
+        visitor.visit(&[], (*count).into())
+    }
+}
+impl SnapshotParseErrorsUtf8 {
+    pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
+        let replacements = ks.utf8_replacements();
+        (replacements > 0).then_some(Self(replacements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AssertContains as _, KopiaSnapshots, test_util::test_snapshot};
+
+    #[test]
+    fn invalid_utf8_replaced() {
+        let mut mangled = test_snapshot("2", 2000, &["latest-1"]);
+        mangled.source.path = "/caf~/na~ve".to_string();
+        // encode the accented characters as Latin-1, as printed in a mismatched locale
+        let json: Vec<u8> = serde_json::to_vec(&vec![test_snapshot("1", 1000, &[]), mangled])
+            .expect("serializable")
+            .into_iter()
+            .map(|byte| if byte == b'~' { 0xe9 } else { byte })
+            .collect();
+
+        let (map, invalid_sources) =
+            KopiaSnapshots::new_from_reader_with_report(json.as_slice()).expect("lossy UTF-8");
+        assert!(invalid_sources.is_empty());
+        assert_eq!(map.utf8_replacements(), 2);
+        map.kopia_snapshot_parse_errors_utf8_total()
+            .expect("replaced")
+            .assert_contains_lines(&[
+                "# TYPE kopia_snapshot_parse_errors_utf8_total gauge",
+                "kopia_snapshot_parse_errors_utf8_total 2",
+            ]);
+        map.kopia_snapshots_total().assert_contains_lines(&[
+            "kopia_snapshots_total{source=\"user_name@host:/caf\u{FFFD}/na\u{FFFD}ve\"} 1",
+        ]);
+    }
+
+    #[test]
+    fn valid_utf8_absent() {
+        let json = serde_json::to_vec(&vec![test_snapshot("1", 1000, &[])]).expect("serializable");
+        let (map, _) =
+            KopiaSnapshots::new_from_reader_with_report(json.as_slice()).expect("valid");
+        assert!(map.kopia_snapshot_parse_errors_utf8_total().is_none());
+    }
+}
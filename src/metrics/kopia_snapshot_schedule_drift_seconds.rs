@@ -0,0 +1,98 @@
+use crate::{
+    KopiaSnapshots, SourceMap,
+    health::ExpectedInterval,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct ScheduleDriftSeconds(SourceMap<i64>);
+impl DisplayMetric for ScheduleDriftSeconds {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(drift_seconds_map) = self;
+        for (source, drift_seconds) in drift_seconds_map {
+            visitor.visit(&[("source", source.as_str())], (*drift_seconds).into())?;
+        }
+        Ok(())
+    }
+}
+impl ScheduleDriftSeconds {
+    /// Implementation for [`KopiaSnapshots::kopia_snapshot_schedule_drift_seconds`]
+    pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
+        let drift_seconds_map: SourceMap<_> = ks
+            .snapshots_map
+            .iter()
+            .filter_map(|(source, snapshots)| {
+                let expected = ExpectedInterval::find(&ks.expected_intervals, source.as_str())?;
+                let mut iter = ks.latest_policy.iter_newest_first(snapshots);
+                let latest = iter.next()?.start_time?;
+                let previous = iter.next()?.start_time?;
+                let drift = latest.duration_since(previous) - expected;
+                Some((source.clone(), drift.as_secs()))
+            })
+            .collect();
+        drift_seconds_map.map_nonempty(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _, SnapshotJson,
+        health::ExpectedInterval,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    fn snapshot_started(id: &str, start_time: &str) -> SnapshotJson {
+        let mut snapshot = test_snapshot(id, 1000, &[]);
+        snapshot.start_time = start_time.to_string();
+        snapshot.end_time = start_time.to_string();
+        snapshot
+    }
+
+    fn expected(s: &str) -> ExpectedInterval {
+        s.parse().expect("valid expected interval")
+    }
+
+    #[test]
+    fn schedule_drift() {
+        let (map, _sources) = multi_map(vec![
+            (
+                "alice",
+                "hostA",
+                "/data",
+                vec![
+                    snapshot_started("1", "2025-08-13T00:00:00Z"),
+                    snapshot_started("2", "2025-08-14T01:30:00Z"),
+                ],
+            ),
+            (
+                "bob",
+                "hostB",
+                "/backup",
+                vec![
+                    snapshot_started("3", "2025-08-13T23:00:00Z"),
+                    snapshot_started("4", "2025-08-13T23:50:00Z"),
+                ],
+            ),
+        ]);
+        assert!(map.kopia_snapshot_schedule_drift_seconds().is_none());
+
+        map.with_expected_interval(expected("*=1h"))
+            .with_expected_interval(expected("alice@hostA:/data=1d"))
+            .kopia_snapshot_schedule_drift_seconds()
+            .expect("expected interval configured")
+            .assert_contains_snippets(&["# HELP kopia_snapshot_schedule_drift_seconds"])
+            .assert_contains_lines(&[
+                "# TYPE kopia_snapshot_schedule_drift_seconds gauge",
+                "kopia_snapshot_schedule_drift_seconds{source=\"alice@hostA:/data\"} 5400",
+                "kopia_snapshot_schedule_drift_seconds{source=\"bob@hostB:/backup\"} -600",
+            ]);
+    }
+
+    #[test]
+    fn schedule_drift_single_snapshot() {
+        let (map, _source) = single_map(vec![snapshot_started("1", "2025-08-13T00:00:00Z")]);
+        let map = map.with_expected_interval(expected("*=1h"));
+        assert!(map.kopia_snapshot_schedule_drift_seconds().is_none());
+    }
+}
@@ -1,33 +1,34 @@
-use crate::{KopiaSnapshots, SourceMap, metrics::DisplayMetric};
+use crate::{KopiaSnapshots, Snapshot, SourceMap, metrics::{DisplayMetric, SampleVisitor}};
 use std::fmt;
 
 pub(super) struct SnapshotSizeByteChanges(SourceMap<i128>);
 impl DisplayMetric for SnapshotSizeByteChanges {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
         let Self(size_changes) = self;
         for (source, size_change) in size_changes {
-            writeln!(f, "{name}{{source={source:?}}} {size_change}")?;
+            visitor.visit(&[("source", source.as_str())], (*size_change).into())?;
         }
         Ok(())
     }
 }
 
 impl SnapshotSizeByteChanges {
-    pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
+    /// Implementation for [`KopiaSnapshots::kopia_snapshot_size_bytes_change`] and
+    /// [`KopiaSnapshots::kopia_snapshot_excluded_size_change_bytes`], with the size of each
+    /// snapshot from `size_fn`
+    pub fn new(ks: &KopiaSnapshots, size_fn: impl Fn(&Snapshot) -> u64) -> Option<Self> {
         let size_changes: SourceMap<i128> = ks
             .snapshots_map
             .iter()
             .filter_map(|(source, snapshots)| {
-                let mut iter = snapshots.iter().rev();
+                let mut iter = ks.latest_policy.iter_newest_first(snapshots);
                 let latest = iter.next()?;
                 let previous = iter.next()?;
 
-                let latest_size: u64 = latest.stats.total_size;
-                let previous_size: u64 = previous.stats.total_size;
+                let latest_size = size_fn(latest);
+                let previous_size = size_fn(previous);
 
-                let size_change = u128::from(latest_size)
-                    .checked_signed_diff(u128::from(previous_size))
-                    .expect("u64 diff fits in i128");
+                let size_change = i128::from(latest_size) - i128::from(previous_size);
                 Some((source.clone(), size_change))
             })
             .collect();
@@ -72,6 +73,26 @@ mod tests {
             ]);
     }
 
+    #[test]
+    fn snapshot_size_change_latest_policy() {
+        use crate::LatestSnapshotPolicy;
+
+        let mut incomplete = test_snapshot("3", 9000, &[]);
+        incomplete.incomplete = Some("checkpoint".to_string());
+        let (map, _source) = single_map(vec![
+            test_snapshot("1", 1000, &["daily-2"]),
+            test_snapshot("2", 2500, &["daily-1"]),
+            incomplete,
+        ]);
+
+        map.with_latest_policy(LatestSnapshotPolicy::NewestComplete)
+            .kopia_snapshot_size_bytes_change()
+            .expect("nonempty")
+            .assert_contains_lines(&[
+                "kopia_snapshot_size_bytes_change{source=\"user_name@host:/path\"} 1500",
+            ]);
+    }
+
     #[test]
     fn snapshot_size_change_single_snapshot() {
         let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
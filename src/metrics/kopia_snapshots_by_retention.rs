@@ -1,17 +1,17 @@
-use crate::{KopiaSnapshots, SourceMap, metrics::DisplayMetric};
+use crate::{KopiaSnapshots, SourceMap, metrics::{DisplayMetric, SampleVisitor}};
 use std::{collections::BTreeMap, fmt};
 
 pub(super) struct SnapshotsByRetention {
-    retention_counts: SourceMap<BTreeMap<String, u32>>,
+    retention_counts: SourceMap<BTreeMap<String, u64>>,
 }
 impl DisplayMetric for SnapshotsByRetention {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
         let Self { retention_counts } = self;
         for (source, reason_counts) in retention_counts {
             for (reason, count) in reason_counts {
-                writeln!(
-                    f,
-                    "{name}{{source={source:?},retention_reason={reason:?}}} {count}"
+                visitor.visit(
+                    &[("source", source.as_str()), ("retention_reason", reason)],
+                    (*count).into(),
                 )?;
             }
         }
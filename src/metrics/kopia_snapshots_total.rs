@@ -1,15 +1,26 @@
-use crate::{KopiaSnapshots, Snapshot, SourceMap, metrics::DisplayMetric};
+use crate::{
+    KopiaSnapshots, Snapshot, SourceMap,
+    kopia::FoldedSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
 use std::fmt;
 
 pub(super) struct SnapshotsTotal<'a> {
     snapshots_map: &'a SourceMap<Vec<Snapshot>>,
+    folded: &'a SourceMap<FoldedSnapshots>,
 }
 impl DisplayMetric for SnapshotsTotal<'_> {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self { snapshots_map } = *self;
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self {
+            snapshots_map,
+            folded,
+        } = *self;
         for (source, snapshots) in snapshots_map {
-            let count = snapshots.len();
-            writeln!(f, "{name}{{source={source:?}}} {count}")?;
+            let folded_count = folded.get(source).map_or(0, |folded| folded.count);
+            let count = u64::try_from(snapshots.len())
+                .unwrap_or(u64::MAX)
+                .saturating_add(folded_count);
+            visitor.visit(&[("source", source.as_str())], count.into())?;
         }
         Ok(())
     }
@@ -17,17 +28,25 @@ impl DisplayMetric for SnapshotsTotal<'_> {
 
 impl<'a> SnapshotsTotal<'a> {
     pub fn new(ks: &'a KopiaSnapshots) -> Self {
-        let KopiaSnapshots { snapshots_map, .. } = ks;
-        Self { snapshots_map }
+        let KopiaSnapshots {
+            snapshots_map,
+            folded,
+            ..
+        } = ks;
+        Self {
+            snapshots_map,
+            folded,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        AssertContains as _,
-        test_util::{multi_map, single_map, test_snapshot},
+        AssertContains as _, KopiaSnapshots, LatestSnapshotPolicy, ParseOptions,
+        test_util::{multi_map, single_map, source_str, test_snapshot},
     };
+    use std::num::NonZeroUsize;
 
     #[test]
     fn snapshots_total_metrics() {
@@ -90,4 +109,32 @@ mod tests {
                 "kopia_snapshots_total{source=\"bob@hostB:/backup\"} 3",
             ]);
     }
+
+    #[test]
+    fn snapshots_total_limited() {
+        let json = serde_json::to_string(
+            &(1..=6)
+                .map(|id| test_snapshot(&id.to_string(), id * 1000, &["daily-1"]))
+                .collect::<Vec<_>>(),
+        )
+        .expect("serializable");
+        let (map, _) = KopiaSnapshots::new_from_reader_with_options(
+            json.as_bytes(),
+            ParseOptions {
+                latest_policy: LatestSnapshotPolicy::Newest,
+                max_snapshots_per_source: NonZeroUsize::new(3),
+            },
+        )
+        .expect("valid");
+        let source = source_str("user_name@host:/path");
+        let ids: Vec<_> = map
+            .snapshots_for(&source)
+            .expect("present")
+            .iter()
+            .map(|snapshot| snapshot.id.as_str())
+            .collect();
+        assert_eq!(ids, ["1", "4", "5", "6"]);
+        map.kopia_snapshots_total()
+            .assert_contains_lines(&["kopia_snapshots_total{source=\"user_name@host:/path\"} 6"]);
+    }
 }
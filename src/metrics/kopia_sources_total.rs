@@ -0,0 +1,52 @@
+use crate::{
+    KopiaSnapshots,
+    metrics::{DisplayMetric, SampleVisitor},
+};
+use std::fmt;
+
+pub(super) struct SourcesTotal(usize);
+impl DisplayMetric for SourcesTotal {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(count) = *self;
+        let count = u64::try_from(count).unwrap_or(u64::MAX);
+        visitor.visit(&[], count.into())
+    }
+}
+impl SourcesTotal {
+    /// Implementation for [`KopiaSnapshots::kopia_sources_total`]
+    pub fn new(ks: &KopiaSnapshots) -> Self {
+        let count = ks
+            .snapshots_map
+            .iter()
+            .filter(|(_, snapshots)| !snapshots.is_empty())
+            .count();
+        Self(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        test_util::{multi_map, test_snapshot},
+    };
+
+    #[test]
+    fn sources_total() {
+        let (map, _sources) = multi_map(vec![
+            ("alice", "hostA", "/data", vec![test_snapshot("1", 1000, &[])]),
+            ("bob", "hostB", "/backup", vec![test_snapshot("2", 2000, &[])]),
+        ]);
+        map.kopia_sources_total().assert_contains_lines(&[
+            "# TYPE kopia_sources_total gauge",
+            "kopia_sources_total 2",
+        ]);
+    }
+
+    #[test]
+    fn sources_total_empty() {
+        let (map, _sources) = multi_map(vec![]);
+        map.kopia_sources_total()
+            .assert_contains_lines(&["kopia_sources_total 0"]);
+    }
+}
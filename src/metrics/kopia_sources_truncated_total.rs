@@ -0,0 +1,65 @@
+use crate::{KopiaSnapshots, metrics::{DisplayMetric, SampleVisitor}};
+use std::fmt;
+
+pub(super) struct SourcesTruncatedTotal(u32);
+impl DisplayMetric for SourcesTruncatedTotal {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        let Self(truncated) = self;
+        visitor.visit(&[], (*truncated).into())
+    }
+}
+impl SourcesTruncatedTotal {
+    pub fn new(ks: &KopiaSnapshots) -> Option<Self> {
+        ks.sources_truncated.map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    #[test]
+    fn sources_truncated_unconfigured() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
+        assert!(map.kopia_sources_truncated_total().is_none());
+    }
+
+    #[test]
+    fn sources_truncated_within_limit() {
+        let (map, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
+        map.with_max_sources(1)
+            .kopia_sources_truncated_total()
+            .expect("configured")
+            .assert_contains_lines(&[
+                "# TYPE kopia_sources_truncated_total gauge",
+                "kopia_sources_truncated_total 0",
+            ]);
+    }
+
+    #[test]
+    fn sources_truncated_overflow() {
+        let mut late = test_snapshot("3", 3000, &["latest-1"]);
+        late.end_time = "2025-08-15T00:00:00Z".to_string();
+        let (map, _sources) = multi_map(vec![
+            ("alice", "hostA", "/data", vec![test_snapshot("1", 1000, &["latest-1"])]),
+            ("bob", "hostB", "/backup", vec![late]),
+            ("carol", "hostC", "/home", vec![test_snapshot("2", 2000, &["latest-1"])]),
+        ]);
+        let map = map.with_max_sources(1);
+
+        map.kopia_sources_truncated_total()
+            .expect("configured")
+            .assert_contains_lines(&["kopia_sources_truncated_total 2"]);
+        map.kopia_snapshots_total().assert_contains_lines(&[
+            "kopia_snapshots_total{source=\"alice@hostA:/data\"} 1",
+            "kopia_snapshots_total{source=\"_overflow\"} 2",
+        ]);
+        // latest of the overflow bucket is the newest across all aggregated sources
+        map.kopia_snapshot_size_bytes_total()
+            .expect("nonempty")
+            .assert_contains_lines(&["kopia_snapshot_size_bytes_total{source=\"_overflow\"} 3000"]);
+    }
+}
@@ -1,20 +1,24 @@
-use crate::{KopiaSnapshots, Snapshot, SourceMap, SourceStr, metrics::DisplayMetric};
-use std::fmt::{self, Display};
+use crate::{
+    KopiaSnapshots, LatestSnapshotPolicy, Snapshot, SourceMap, SourceStr,
+    metrics::{DisplayMetric, SampleValue, SampleVisitor},
+};
+use std::fmt;
 
 #[derive(Clone, Copy)]
 struct LastSnapshots<'a> {
     map: &'a SourceMap<Vec<Snapshot>>,
+    policy: LatestSnapshotPolicy,
 }
 impl<'a> LastSnapshots<'a> {
-    fn new(map: &'a SourceMap<Vec<Snapshot>>) -> Option<Self> {
-        map.iter()
-            .any(|(_source, snapshots)| !snapshots.is_empty())
-            .then_some(Self { map })
+    fn new(map: &'a SourceMap<Vec<Snapshot>>, policy: LatestSnapshotPolicy) -> Option<Self> {
+        let this = Self { map, policy };
+        this.iter().next().is_some().then_some(this)
     }
     fn iter(self) -> impl Iterator<Item = (&'a SourceStr, &'a Snapshot)> {
-        let Self { map } = self;
-        map.iter()
-            .filter_map(|(source, snapshots)| snapshots.last().map(|last| (source, last)))
+        let Self { map, policy } = self;
+        map.iter().filter_map(move |(source, snapshots)| {
+            policy.select_latest(snapshots).map(|last| (source, last))
+        })
     }
 }
 
@@ -25,10 +29,10 @@ pub struct MetricLastSnapshots<'a, F> {
 impl<'a, F, T> MetricLastSnapshots<'a, F>
 where
     F: Fn(&Snapshot) -> T,
-    T: Display,
+    T: Into<SampleValue>,
 {
     pub fn new(ks: &'a KopiaSnapshots, stat_fn: F) -> Option<Self> {
-        let last_snapshots = LastSnapshots::new(&ks.snapshots_map)?;
+        let last_snapshots = LastSnapshots::new(&ks.snapshots_map, ks.latest_policy)?;
         Some(Self {
             last_snapshots,
             stat_fn,
@@ -38,16 +42,16 @@ where
 impl<F, T> DisplayMetric for MetricLastSnapshots<'_, F>
 where
     F: Fn(&Snapshot) -> T,
-    T: Display,
+    T: Into<SampleValue>,
 {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
         let Self {
             last_snapshots,
             stat_fn,
         } = self;
         for (source, last) in last_snapshots.iter() {
             let stat = stat_fn(last);
-            writeln!(f, "{name}{{source={source:?}}} {stat}")?;
+            visitor.visit(&[("source", source.as_str())], stat.into())?;
         }
         Ok(())
     }
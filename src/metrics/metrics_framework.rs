@@ -15,14 +15,240 @@ where
     T: DisplayMetric,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self { label, inner } = self;
+        PrometheusText {
+            family: self,
+            timestamp_millis: None,
+        }
+        .fmt(f)
+    }
+}
+
+/// Prometheus text format of a [`MetricFamily`], optionally with a timestamp on every sample
+pub(crate) struct PrometheusText<'a> {
+    pub family: &'a dyn MetricFamily,
+    pub timestamp_millis: Option<i64>,
+}
+impl fmt::Display for PrometheusText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct TextVisitor<'a, 'b> {
+            name: &'a str,
+            timestamp_millis: Option<i64>,
+            f: &'a mut fmt::Formatter<'b>,
+        }
+        impl SampleVisitor for TextVisitor<'_, '_> {
+            fn visit_suffixed(
+                &mut self,
+                suffix: &str,
+                labels: &[(&str, &str)],
+                value: SampleValue,
+            ) -> fmt::Result {
+                let Self {
+                    name,
+                    timestamp_millis,
+                    f,
+                } = self;
+                write!(f, "{name}{suffix}")?;
+                if !labels.is_empty() {
+                    write!(f, "{{")?;
+                    for (index, (label, label_value)) in labels.iter().enumerate() {
+                        if index > 0 {
+                            write!(f, ",")?;
+                        }
+                        write!(f, "{label}=\"")?;
+                        write_escaped(f, label_value, true)?;
+                        write!(f, "\"")?;
+                    }
+                    write!(f, "}}")?;
+                }
+                write!(f, " {value}")?;
+                if let Some(timestamp_millis) = timestamp_millis {
+                    write!(f, " {timestamp_millis}")?;
+                }
+                writeln!(f)
+            }
+        }
+
+        let Self {
+            family,
+            timestamp_millis,
+        } = *self;
+        let label = family.label();
 
         // format label
         writeln!(f, "{label}")?;
 
         // format inner
         let name = label.name();
-        inner.fmt(name, f)
+        family.visit_samples(&mut TextVisitor {
+            name,
+            timestamp_millis,
+            f,
+        })
+    }
+}
+
+/// Writes the text escaped per the Prometheus text format: backslash, line feed and (for label
+/// values) double quote
+///
+/// Other characters, including non-ASCII, are written as-is (UTF-8), unlike [`fmt::Debug`].
+fn write_escaped(f: &mut fmt::Formatter<'_>, text: &str, escape_quote: bool) -> fmt::Result {
+    use fmt::Write as _;
+    for c in text.chars() {
+        match c {
+            '\\' => f.write_str(r"\\")?,
+            '\n' => f.write_str(r"\n")?,
+            '"' if escape_quote => f.write_str(r#"\""#)?,
+            c => f.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+impl<T> MetricFamily for Metrics<T>
+where
+    T: DisplayMetric,
+{
+    fn label(&self) -> &MetricLabel {
+        &self.label
+    }
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        self.inner.visit_samples(visitor)
+    }
+}
+
+/// [`MetricFamily`] with an additional label (first) on every sample
+pub(crate) struct WithLabel<'a> {
+    pub family: Box<dyn MetricFamily + 'a>,
+    pub label: (&'static str, &'a str),
+}
+impl MetricFamily for WithLabel<'_> {
+    fn label(&self) -> &MetricLabel {
+        self.family.label()
+    }
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        struct LabelVisitor<'a> {
+            label: (&'a str, &'a str),
+            inner: &'a mut dyn SampleVisitor,
+        }
+        impl SampleVisitor for LabelVisitor<'_> {
+            fn visit_suffixed(
+                &mut self,
+                suffix: &str,
+                labels: &[(&str, &str)],
+                value: SampleValue,
+            ) -> fmt::Result {
+                let labels: Vec<_> = std::iter::once(self.label)
+                    .chain(labels.iter().copied())
+                    .collect();
+                self.inner.visit_suffixed(suffix, &labels, value)
+            }
+        }
+        self.family.visit_samples(&mut LabelVisitor {
+            label: self.label,
+            inner: visitor,
+        })
+    }
+}
+impl fmt::Display for WithLabel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        PrometheusText {
+            family: self,
+            timestamp_millis: None,
+        }
+        .fmt(f)
+    }
+}
+
+/// Metric with a label and samples, independent of the output format
+///
+/// The [`fmt::Display`] implementation renders the Prometheus text format.
+pub trait MetricFamily: fmt::Display {
+    /// Returns the label (name, type, and help text)
+    fn label(&self) -> &MetricLabel;
+    /// Visits each sample of the metric
+    ///
+    /// # Errors
+    /// Returns an error if the visitor returns an error
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result;
+}
+
+/// Receives the samples of a [`MetricFamily`]
+pub trait SampleVisitor {
+    /// Receives a single sample, identified by the label name/value pairs
+    ///
+    /// # Errors
+    /// Returns an error to abort visiting the remaining samples
+    fn visit(&mut self, labels: &[(&str, &str)], value: SampleValue) -> fmt::Result {
+        self.visit_suffixed("", labels, value)
+    }
+    /// Receives a single sample named with the suffix appended to the metric name, e.g. the
+    /// `_bucket`, `_sum` and `_count` samples of a [`MetricType::Histogram`]
+    ///
+    /// # Errors
+    /// Returns an error to abort visiting the remaining samples
+    fn visit_suffixed(
+        &mut self,
+        suffix: &str,
+        labels: &[(&str, &str)],
+        value: SampleValue,
+    ) -> fmt::Result;
+}
+
+/// Numeric value of a single sample
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SampleValue {
+    /// Exact integer value
+    Integer(i128),
+    /// Floating point value
+    Float(f64),
+}
+impl SampleValue {
+    /// Returns the value as a float, rounding integers beyond 2^53
+    #[must_use]
+    #[expect(clippy::cast_precision_loss)] // metric values are well within range
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Self::Integer(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+}
+impl fmt::Display for SampleValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(value) => write!(f, "{value}"),
+            // Prometheus text format spells non-finite values `+Inf`, `-Inf` and `NaN`
+            Self::Float(value) if value.is_nan() => write!(f, "NaN"),
+            Self::Float(value) if value.is_infinite() => {
+                write!(f, "{}Inf", if value.is_sign_positive() { '+' } else { '-' })
+            }
+            Self::Float(value) => write!(f, "{value}"),
+        }
+    }
+}
+impl serde::Serialize for SampleValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Self::Integer(value) => serializer.serialize_i128(value),
+            Self::Float(value) => serializer.serialize_f64(value),
+        }
+    }
+}
+macro_rules! impl_from_integer {
+    ($($ty:ty),+) => {
+        $(
+            impl From<$ty> for SampleValue {
+                fn from(value: $ty) -> Self {
+                    Self::Integer(i128::from(value))
+                }
+            }
+        )+
+    };
+}
+impl_from_integer!(u8, u32, u64, i32, i64, i128);
+impl From<f64> for SampleValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
     }
 }
 
@@ -40,6 +266,20 @@ pub enum MetricType {
     Counter,
     /// Single numerical value that can arbitrarily go up and down
     Gauge,
+    /// Counts of observations in cumulative buckets (`_bucket` samples with an `le` label),
+    /// with the `_sum` and `_count` of all observations
+    Histogram,
+}
+impl MetricType {
+    /// Returns the name used in the `# TYPE` line
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+            Self::Histogram => "histogram",
+        }
+    }
 }
 
 impl MetricLabel {
@@ -60,6 +300,16 @@ impl MetricLabel {
     pub fn name(&self) -> &str {
         self.name
     }
+    /// Returns the help text of the metric
+    #[must_use]
+    pub fn help_text(&self) -> &str {
+        self.help_text
+    }
+    /// Returns the type of the metric
+    #[must_use]
+    pub fn metric_type(&self) -> &MetricType {
+        &self.ty
+    }
 }
 impl fmt::Display for MetricLabel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -68,12 +318,10 @@ impl fmt::Display for MetricLabel {
             help_text,
             ty,
         } = self;
-        let ty = match ty {
-            MetricType::Counter => "counter",
-            MetricType::Gauge => "gauge",
-        };
+        let ty = ty.name();
 
-        write!(f, "# HELP {name} {help_text}")?;
+        write!(f, "# HELP {name} ")?;
+        write_escaped(f, help_text, false)?;
         writeln!(f)?;
         write!(f, "# TYPE {name} {ty}")?;
 
@@ -81,9 +329,43 @@ impl fmt::Display for MetricLabel {
     }
 }
 
-/// [`std::fmt::Display`], but with an additional supplied metric name
+/// Category of metrics, listing the names of its metrics
+///
+/// Each category is an associated constant, matching the category constants of [`Metrics`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetricCategory {
+    name: &'static str,
+    metric_names: &'static [&'static str],
+}
+impl MetricCategory {
+    /// Internal constructor for use by the `define_metric_categories!` macro.
+    ///
+    /// This method should not be called directly. Use the `define_metric_categories!` macro instead.
+    #[doc(hidden)]
+    #[must_use]
+    pub const fn __from_macro(name: &'static str, metric_names: &'static [&'static str]) -> Self {
+        Self { name, metric_names }
+    }
+    /// Returns the name of the category (e.g. `NEW_SNAPSHOT_HEALTH`)
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+    /// Returns the names of the metrics in the category
+    #[must_use]
+    pub fn metric_names(&self) -> &'static [&'static str] {
+        self.metric_names
+    }
+    /// Returns `true` if the category contains the metric
+    #[must_use]
+    pub fn contains(&self, metric_name: &str) -> bool {
+        self.metric_names.contains(&metric_name)
+    }
+}
+
+/// Samples of a metric, with the metric name supplied externally by [`Metrics`]
 pub trait DisplayMetric {
-    fn fmt(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result;
 }
 
 /// Helper to construct [`Metrics`] from various disjoint types
@@ -120,14 +402,14 @@ impl<T> AttachMetricLabel for Option<T> {
 ///         ///
 ///         /// Returns metrics showing the age in seconds of the most recent snapshot for each source.
 ///         /// Only present if snapshots list is not empty.
-///         pub fn kopia_snapshot_age_seconds<Gauge>(&self, now: jiff::Timestamp) -> Option<impl Display> {
+///         pub fn kopia_snapshot_age_seconds<Gauge>(&self, now: jiff::Timestamp) -> Option<impl MetricFamily> {
 ///             SnapshotAgeSeconds::new(self, now)
 ///         }
 ///         /// Unix timestamp of last successful snapshot
 ///         ///
 ///         /// Generates Prometheus metrics for the last successful snapshot timestamp.
 ///         /// Only present if snapshots list is not empty.
-///         pub fn kopia_snapshot_last_success_timestamp<Gauge>(&self) -> Option<impl Display> {
+///         pub fn kopia_snapshot_last_success_timestamp<Gauge>(&self) -> Option<impl MetricFamily> {
 ///             SnapshotLastSuccessTimestamp::new(self)
 ///         }
 ///     }
@@ -180,6 +462,14 @@ macro_rules! define_metric_categories {
                 )+
             }
 
+            impl $crate::metrics::MetricCategory {
+                #[doc = $category]
+                pub const $category_ident: Self = Self::__from_macro(
+                    stringify!($category_ident),
+                    &[$(stringify!($name)),+],
+                );
+            }
+
             // Import each metric implementation module, not exported
             //
             // Items in the implementation module are automatically imported
@@ -213,3 +503,18 @@ macro_rules! define_metric_categories {
         )+
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SampleValue;
+
+    #[test]
+    fn display_sample_value() {
+        let display = |value: SampleValue| value.to_string();
+        assert_eq!(display(SampleValue::Integer(-42)), "-42");
+        assert_eq!(display(SampleValue::Float(0.25)), "0.25");
+        assert_eq!(display(SampleValue::Float(f64::INFINITY)), "+Inf");
+        assert_eq!(display(SampleValue::Float(f64::NEG_INFINITY)), "-Inf");
+        assert_eq!(display(SampleValue::Float(f64::NAN)), "NaN");
+    }
+}
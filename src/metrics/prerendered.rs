@@ -0,0 +1,147 @@
+use super::{FamilyEntry, NowMetricFn, PrometheusText};
+use crate::KopiaSnapshots;
+
+/// Prometheus metrics with the metrics not depending on the current time rendered ahead
+/// of time, see [`KopiaSnapshots::prerender_metrics`]
+///
+/// With many sources, formatting dominates the time to serve cached snapshots. Rendering
+/// from this only formats the few time-dependent metrics (ages and health) and the custom
+/// metrics.
+#[derive(Debug)]
+pub struct PrerenderedMetrics {
+    parts: Vec<Part>,
+    timestamp_millis: Option<i64>,
+}
+#[derive(Debug)]
+enum Part {
+    Rendered(String),
+    Now(NowMetricFn),
+    Custom(usize),
+}
+
+impl PrerenderedMetrics {
+    pub(super) fn new(snapshots: &KopiaSnapshots, timestamp: Option<jiff::Timestamp>) -> Self {
+        let timestamp_millis = timestamp.map(jiff::Timestamp::as_millisecond);
+        let parts = snapshots
+            .metric_family_entries()
+            .into_iter()
+            .map(|entry| match entry {
+                FamilyEntry::Fixed(metric) => Part::Rendered(
+                    PrometheusText {
+                        family: &*metric,
+                        timestamp_millis,
+                    }
+                    .to_string(),
+                ),
+                FamilyEntry::Now(metric_fn) => Part::Now(metric_fn),
+                FamilyEntry::Custom(index) => Part::Custom(index),
+            })
+            .collect();
+        Self {
+            parts,
+            timestamp_millis,
+        }
+    }
+
+    /// Renders the same output as [`KopiaSnapshots::generate_all_metrics`] (or
+    /// [`KopiaSnapshots::generate_all_metrics_timestamped`], if created with a timestamp)
+    ///
+    /// The `snapshots` must be the ones this was created from.
+    #[must_use]
+    pub fn render(&self, snapshots: &KopiaSnapshots, now: jiff::Timestamp) -> String {
+        let mut output = String::new();
+        let Ok(()) = self.for_each_part::<std::convert::Infallible>(snapshots, now, |part| {
+            output.push_str(part);
+            Ok(())
+        });
+        output
+    }
+
+    /// Writes the output of [`Self::render`] to the `writer`, without buffering it in full
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the `writer` fails
+    pub fn render_to(
+        &self,
+        snapshots: &KopiaSnapshots,
+        now: jiff::Timestamp,
+        writer: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        self.for_each_part(snapshots, now, |part| writer.write_all(part.as_bytes()))
+    }
+
+    fn for_each_part<E>(
+        &self,
+        snapshots: &KopiaSnapshots,
+        now: jiff::Timestamp,
+        mut part_fn: impl FnMut(&str) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let Self {
+            parts,
+            timestamp_millis,
+        } = self;
+        let mut first = true;
+        for part in parts {
+            let rendered;
+            let metric = match part {
+                Part::Rendered(metric) => metric.as_str(),
+                Part::Now(_) | Part::Custom(_) => {
+                    let metric = match part {
+                        Part::Now(metric_fn) => snapshots.now_metric(*metric_fn, now),
+                        Part::Custom(index) => snapshots.custom_metric(*index),
+                        Part::Rendered(_) => None,
+                    };
+                    let Some(metric) = metric else {
+                        continue;
+                    };
+                    rendered = PrometheusText {
+                        family: &*metric,
+                        timestamp_millis: *timestamp_millis,
+                    }
+                    .to_string();
+                    &rendered
+                }
+            };
+            if !first {
+                part_fn("\n")?;
+            }
+            first = false;
+            part_fn(metric)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{KopiaSnapshots, test_util::test_snapshot};
+
+    #[test]
+    fn matches_generate_all_metrics() {
+        let snapshots = vec![
+            test_snapshot("1", 1000, &["daily"]),
+            test_snapshot("2", 2000, &["latest-1"]),
+        ];
+        let (map, _invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(snapshots);
+        let map = map.with_fetched_at("2025-08-14T00:00:00Z".parse().expect("valid timestamp"));
+        let prerendered = map.prerender_metrics(None);
+        let fetched_at = "2025-08-14T00:00:00Z".parse().expect("valid timestamp");
+        let prerendered_timestamped = map.prerender_metrics(Some(fetched_at));
+
+        for now in ["2025-08-14T01:01:00Z", "2025-08-15T02:03:04Z"] {
+            let now: jiff::Timestamp = now.parse().expect("valid timestamp");
+            assert_eq!(prerendered.render(&map, now), map.generate_all_metrics(now));
+            assert_eq!(
+                prerendered_timestamped.render(&map, now),
+                map.generate_all_metrics_timestamped(now, fetched_at)
+            );
+
+            let mut written = Vec::new();
+            prerendered
+                .render_to(&map, now, &mut written)
+                .expect("infallible");
+            assert_eq!(written, map.generate_all_metrics(now).into_bytes());
+        }
+    }
+}
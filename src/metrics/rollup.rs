@@ -0,0 +1,160 @@
+use crate::{
+    KopiaSnapshots, SourceStr,
+    metrics::{DisplayMetric, SampleValue, SampleVisitor},
+};
+use std::fmt;
+
+/// Combination of the per-source samples into the `source="_all"` rollup sample
+#[derive(Clone, Copy)]
+pub(super) enum Rollup {
+    Sum,
+    Max,
+}
+impl Rollup {
+    /// Wraps the per-source metric, adding the rollup sample if enabled by
+    /// [`KopiaSnapshots::with_source_rollups`]
+    pub fn wrap<M>(self, ks: &KopiaSnapshots, inner: M) -> SourceRollup<M> {
+        let rollup = ks.source_rollups.then_some(self);
+        SourceRollup { inner, rollup }
+    }
+    fn combine(self, a: SampleValue, b: SampleValue) -> SampleValue {
+        match (self, a, b) {
+            (Self::Sum, SampleValue::Integer(a), SampleValue::Integer(b)) => {
+                SampleValue::Integer(a.saturating_add(b))
+            }
+            (Self::Max, SampleValue::Integer(a), SampleValue::Integer(b)) => {
+                SampleValue::Integer(a.max(b))
+            }
+            (Self::Sum, a, b) => SampleValue::Float(a.as_f64() + b.as_f64()),
+            (Self::Max, a, b) => SampleValue::Float(a.as_f64().max(b.as_f64())),
+        }
+    }
+}
+
+/// Per-source metric followed by the (optional) rollup across all sources
+pub(super) struct SourceRollup<M> {
+    inner: M,
+    rollup: Option<Rollup>,
+}
+impl<M> DisplayMetric for SourceRollup<M>
+where
+    M: DisplayMetric,
+{
+    fn visit_samples(&self, visitor: &mut dyn SampleVisitor) -> fmt::Result {
+        struct RollupVisitor<'a> {
+            rollup: Rollup,
+            total: Option<SampleValue>,
+            inner: &'a mut dyn SampleVisitor,
+        }
+        impl SampleVisitor for RollupVisitor<'_> {
+            fn visit_suffixed(
+                &mut self,
+                suffix: &str,
+                labels: &[(&str, &str)],
+                value: SampleValue,
+            ) -> fmt::Result {
+                if let [("source", _)] = labels {
+                    self.total = Some(match self.total {
+                        Some(total) => self.rollup.combine(total, value),
+                        None => value,
+                    });
+                }
+                self.inner.visit_suffixed(suffix, labels, value)
+            }
+        }
+
+        let Self { inner, rollup } = self;
+        let Some(rollup) = *rollup else {
+            return inner.visit_samples(visitor);
+        };
+        let mut rollup_visitor = RollupVisitor {
+            rollup,
+            total: None,
+            inner: visitor,
+        };
+        inner.visit_samples(&mut rollup_visitor)?;
+        match rollup_visitor.total {
+            Some(total) => visitor.visit(&[("source", SourceStr::all().as_str())], total),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssertContains as _,
+        test_util::{multi_map, test_snapshot},
+    };
+
+    #[test]
+    fn source_rollups() {
+        let mut snapshot_1 = test_snapshot("1", 2500, &["latest-1"]);
+        snapshot_1.stats.error_count = 2;
+        let mut snapshot_2 = test_snapshot("3", 8000, &["latest-1"]);
+        snapshot_2.stats.error_count = 3;
+        let (map, _sources) = multi_map(vec![
+            (
+                "alice",
+                "hostA",
+                "/data",
+                vec![test_snapshot("0", 1000, &["daily-2"]), snapshot_1],
+            ),
+            ("bob", "hostB", "/backup", vec![snapshot_2]),
+        ]);
+
+        let rollup_lines = [
+            "kopia_snapshot_size_bytes_total{source=\"_all\"} 10500",
+            "kopia_snapshots_total{source=\"_all\"} 3",
+            "kopia_snapshot_errors_total{source=\"_all\"} 5",
+        ];
+        let metrics = map.generate_all_metrics(jiff::Timestamp::now());
+        for line in rollup_lines {
+            assert!(!metrics.contains(line), "{line}");
+        }
+
+        let map = map.with_source_rollups();
+        map.kopia_snapshot_size_bytes_total()
+            .expect("nonempty")
+            .assert_contains_lines(&[
+                "kopia_snapshot_size_bytes_total{source=\"alice@hostA:/data\"} 2500",
+                "kopia_snapshot_size_bytes_total{source=\"bob@hostB:/backup\"} 8000",
+            ]);
+        map.generate_all_metrics(jiff::Timestamp::now())
+            .assert_contains_lines(&rollup_lines);
+    }
+
+    #[test]
+    fn source_rollup_max_age() {
+        use jiff::ToSpan as _;
+
+        let now = jiff::Timestamp::now();
+        let snapshot_at = |id: &str, time: jiff::Timestamp| {
+            let mut snapshot = test_snapshot(id, 1000, &["latest-1"]);
+            snapshot.end_time = time.to_string();
+            snapshot
+        };
+        let (map, _sources) = multi_map(vec![
+            (
+                "alice",
+                "hostA",
+                "/data",
+                vec![snapshot_at("1", now - 2.hours())],
+            ),
+            (
+                "bob",
+                "hostB",
+                "/backup",
+                vec![snapshot_at("2", now - 5.hours())],
+            ),
+        ]);
+
+        map.with_source_rollups()
+            .kopia_snapshot_age_seconds(now)
+            .expect("nonempty")
+            .assert_contains_lines(&[
+                "kopia_snapshot_age_seconds{source=\"alice@hostA:/data\"} 7200",
+                "kopia_snapshot_age_seconds{source=\"_all\"} 18000",
+            ]);
+    }
+}
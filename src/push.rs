@@ -0,0 +1,519 @@
+//! Pushing metrics to remote collectors
+//!
+//! Uses a minimal plain-HTTP client for `http://` URLs, intended for collectors on a trusted
+//! local network, and [`minreq`] (with `rustls`) for `https://` URLs, e.g. public services.
+
+use base64::prelude::*;
+use eyre::{Result, eyre};
+use std::fmt::Write as _;
+use std::io::{Read as _, Write as _};
+use std::net::{TcpStream, ToSocketAddrs as _, UdpSocket};
+use std::time::Duration;
+
+pub use self::config::{NotifyConfig, PushConfig};
+
+mod config;
+pub mod gotify;
+pub mod home_assistant;
+pub mod mqtt;
+pub mod ntfy;
+pub mod snappy;
+pub mod webhook;
+
+/// Destination URL for HTTP pushes, validated up front
+#[derive(Clone, Debug)]
+pub struct PushTarget {
+    /// Whether the URL is `https://`
+    tls: bool,
+    host: String,
+    port: u16,
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+/// Response from a [`PushTarget`]
+#[derive(Clone, Debug)]
+pub struct PushResponse {
+    /// HTTP status code
+    pub status_code: u16,
+    /// Response body (lossy UTF-8)
+    pub body: String,
+}
+
+impl PushTarget {
+    /// Parses an `http://host[:port][/path][?query]` or `https://...` URL
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is not `http://` or `https://`, or the port is invalid
+    pub fn parse(url: &str) -> Result<Self> {
+        let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(eyre!(
+                "unsupported push URL {url:?}, only http:// and https:// URLs are supported"
+            ));
+        };
+        let (authority, path) = rest
+            .find(['/', '?'])
+            .map_or((rest, "/"), |index| rest.split_at(index));
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{path}")
+        };
+        if authority.is_empty() || authority.contains('@') {
+            return Err(eyre!("invalid host in push URL {url:?}"));
+        }
+        // split off the port, ignoring colons inside an IPv6 literal `[::1]`
+        let port_start = authority
+            .rfind(':')
+            .filter(|&index| !authority[index..].contains(']'));
+        let (host, port) = match port_start {
+            Some(index) => {
+                let port = authority[index + 1..]
+                    .parse()
+                    .map_err(|e| eyre!("invalid port in push URL {url:?}: {e}"))?;
+                (authority[..index].to_string(), port)
+            }
+            None => (authority.to_string(), if tls { 443 } else { 80 }),
+        };
+        Ok(Self {
+            tls,
+            host,
+            port,
+            path,
+            headers: vec![],
+        })
+    }
+
+    /// Parses the Pushgateway base URL and appends the grouping key path for the job
+    /// and (optional) instance
+    ///
+    /// Values containing characters other than `[A-Za-z0-9._~-]` are base64-encoded
+    /// using the `label@base64` syntax.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is invalid (see [`Self::parse`]) or the job is empty
+    pub fn pushgateway(base_url: &str, job: &str, instance: Option<&str>) -> Result<Self> {
+        fn push_label(path: &mut String, name: &str, value: &str) {
+            let is_plain = !value.is_empty()
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '~' | '-'));
+            if is_plain {
+                write!(path, "/{name}/{value}").expect("infallible");
+            } else {
+                let encoded = BASE64_URL_SAFE.encode(value);
+                write!(path, "/{name}@base64/{encoded}").expect("infallible");
+            }
+        }
+
+        if job.is_empty() {
+            return Err(eyre!("pushgateway job name must not be empty"));
+        }
+        let mut target = Self::parse(base_url)?;
+        if target.path.contains('?') {
+            return Err(eyre!(
+                "pushgateway URL {base_url:?} must not contain a query string"
+            ));
+        }
+        let mut path = target.path.trim_end_matches('/').to_string();
+        path.push_str("/metrics");
+        push_label(&mut path, "job", job);
+        if let Some(instance) = instance {
+            push_label(&mut path, "instance", instance);
+        }
+        target.path = path;
+        Ok(target)
+    }
+
+    /// Adds a header sent with every request
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sends the body with the specified method, failing on non-2xx status codes
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails, the response is malformed, or the
+    /// status code is not successful (2xx)
+    pub fn send(
+        &self,
+        method: &str,
+        content_type: &str,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<PushResponse> {
+        let response = self.send_raw(method, content_type, body, timeout)?;
+        if (200..300).contains(&response.status_code) {
+            Ok(response)
+        } else {
+            Err(eyre!(
+                "push to {}:{}{} failed with status {}: {}",
+                self.host,
+                self.port,
+                self.path,
+                response.status_code,
+                response.body.trim()
+            ))
+        }
+    }
+
+    fn send_raw(
+        &self,
+        method: &str,
+        content_type: &str,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<PushResponse> {
+        let Self {
+            tls,
+            host,
+            port,
+            path,
+            headers,
+        } = self;
+        if *tls {
+            return self.send_tls(method, content_type, body, timeout);
+        }
+        let addr = (host.trim_start_matches('[').trim_end_matches(']'), *port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| eyre!("no addresses found for push host {host:?}"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, timeout)
+            .map_err(|e| eyre!("failed to connect to push host {host}:{port}: {e}"))?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: {host}:{port}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            body.len()
+        );
+        for (name, value) in headers {
+            write!(request, "{name}: {value}\r\n").expect("infallible");
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_response(&response)
+    }
+
+    fn send_tls(
+        &self,
+        method: &str,
+        content_type: &str,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<PushResponse> {
+        let Self {
+            tls: _,
+            host,
+            port,
+            path,
+            headers,
+        } = self;
+        let url = format!("https://{host}:{port}{path}");
+        let request = minreq::Request::new(minreq::Method::Custom(method.to_string()), url)
+            .with_header("Content-Type", content_type)
+            .with_headers(headers.iter().map(|(name, value)| (name, value)))
+            .with_body(body)
+            // whole seconds, rounded up to never time out immediately
+            .with_timeout(timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0));
+        let response = request
+            .send()
+            .map_err(|e| eyre!("failed to push to {host}:{port}: {e}"))?;
+        Ok(PushResponse {
+            status_code: u16::try_from(response.status_code).map_err(|_| {
+                eyre!(
+                    "invalid HTTP status {} from push host",
+                    response.status_code
+                )
+            })?,
+            body: String::from_utf8_lossy(response.as_bytes()).into_owned(),
+        })
+    }
+}
+
+/// Destination for `StatsD` datagrams over UDP
+#[derive(Debug)]
+pub struct StatsdTarget {
+    socket: UdpSocket,
+}
+
+impl StatsdTarget {
+    /// Maximum datagram payload, to avoid IP fragmentation on common networks
+    const MAX_PACKET_LEN: usize = 1432;
+
+    /// Resolves the `host:port` address and binds a local UDP socket
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be resolved or the socket cannot be bound
+    pub fn connect(addr: &str) -> Result<Self> {
+        let addr = addr
+            .to_socket_addrs()
+            .map_err(|e| eyre!("invalid statsd address {addr:?}: {e}"))?
+            .next()
+            .ok_or_else(|| eyre!("no addresses found for statsd address {addr:?}"))?;
+        let bind_addr = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(bind_addr)?;
+        socket.connect(addr)?;
+        Ok(Self { socket })
+    }
+
+    /// Sends the lines, packing as many newline-separated lines per datagram as fit
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending a datagram fails
+    pub fn send_lines(&self, lines: &[String]) -> Result<()> {
+        let mut packet = String::new();
+        for line in lines {
+            if !packet.is_empty() && packet.len() + 1 + line.len() > Self::MAX_PACKET_LEN {
+                self.socket.send(packet.as_bytes())?;
+                packet.clear();
+            }
+            if !packet.is_empty() {
+                packet.push('\n');
+            }
+            packet.push_str(line);
+        }
+        if !packet.is_empty() {
+            self.socket.send(packet.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Destination file for the `node_exporter` textfile collector
+#[derive(Clone, Debug)]
+pub struct TextfileTarget {
+    path: std::path::PathBuf,
+}
+
+impl TextfileTarget {
+    /// Creates a target writing to the specified path (should end with `.prom`)
+    #[must_use]
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Atomically replaces the file contents
+    ///
+    /// Writes to a temporary file in the same directory, then renames it over the
+    /// destination so readers never observe a partially-written file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing or renaming the temporary file fails
+    pub fn write(&self, contents: &str) -> Result<()> {
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let temp_path = std::path::PathBuf::from(temp_path);
+
+        std::fs::write(&temp_path, contents)
+            .map_err(|e| eyre!("failed to write {}: {e}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &self.path).map_err(|e| {
+            eyre!(
+                "failed to rename {} to {}: {e}",
+                temp_path.display(),
+                self.path.display()
+            )
+        })
+    }
+}
+
+fn parse_response(response: &[u8]) -> Result<PushResponse> {
+    let response = String::from_utf8_lossy(response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status_line = head.lines().next().unwrap_or_default();
+    let status_code = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| eyre!("invalid HTTP status line from push host: {status_line:?}"))?;
+    Ok(PushResponse {
+        status_code,
+        body: body.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PushTarget, StatsdTarget, TextfileTarget};
+    use std::io::{Read as _, Write as _};
+    use std::net::{TcpListener, UdpSocket};
+    use std::time::Duration;
+
+    #[test]
+    fn parse_url() {
+        let target = PushTarget::parse("http://influx.lan:8086/api/v2/write?bucket=kopia")
+            .expect("valid URL");
+        assert_eq!(target.host, "influx.lan");
+        assert_eq!(target.port, 8086);
+        assert_eq!(target.path, "/api/v2/write?bucket=kopia");
+
+        let target = PushTarget::parse("http://localhost").expect("valid URL");
+        assert_eq!((target.host.as_str(), target.port), ("localhost", 80));
+        assert_eq!(target.path, "/");
+
+        let target = PushTarget::parse("http://[::1]:9091?a=b").expect("valid URL");
+        assert_eq!((target.host.as_str(), target.port), ("[::1]", 9091));
+        assert_eq!(target.path, "/?a=b");
+
+        let target = PushTarget::parse("https://ntfy.sh/backups").expect("valid URL");
+        assert!(target.tls);
+        assert_eq!((target.host.as_str(), target.port), ("ntfy.sh", 443));
+        assert_eq!(target.path, "/backups");
+
+        let err = PushTarget::parse("ftp://influx.lan").expect_err("unsupported");
+        assert!(
+            err.to_string().contains("only http:// and https://"),
+            "{err}"
+        );
+        let err = PushTarget::parse("http://influx.lan:port").expect_err("invalid port");
+        assert!(err.to_string().contains("invalid port"), "{err}");
+    }
+
+    #[test]
+    fn pushgateway_path() {
+        let target = PushTarget::pushgateway("http://pgw:9091/", "kopia_exporter", Some("nas"))
+            .expect("valid URL");
+        assert_eq!(target.path, "/metrics/job/kopia_exporter/instance/nas");
+
+        let target =
+            PushTarget::pushgateway("http://pgw:9091", "backup/nightly", None).expect("valid URL");
+        assert_eq!(target.path, "/metrics/job@base64/YmFja3VwL25pZ2h0bHk=");
+
+        let err = PushTarget::pushgateway("http://pgw:9091", "", None).expect_err("empty job");
+        assert!(err.to_string().contains("must not be empty"), "{err}");
+    }
+
+    #[test]
+    fn textfile_write() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("kopia.prom");
+        let target = TextfileTarget::new(&path);
+
+        target.write("first 1\n").expect("write");
+        target.write("second 2\n").expect("write");
+
+        let contents = std::fs::read_to_string(&path).expect("read");
+        assert_eq!(contents, "second 2\n");
+        let files: Vec<_> = std::fs::read_dir(dir.path())
+            .expect("read dir")
+            .map(|entry| entry.expect("entry").file_name())
+            .collect();
+        assert_eq!(files, ["kopia.prom"]);
+    }
+
+    fn serve_once(response: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            // read until the full body (small test bodies) has arrived
+            while !String::from_utf8_lossy(&request).contains("\r\n\r\nbody") {
+                let len = stream.read(&mut buf).expect("read");
+                if len == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..len]);
+            }
+            stream.write_all(response.as_bytes()).expect("write");
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (format!("http://{addr}/write?db=kopia"), handle)
+    }
+
+    #[test]
+    fn send_request() {
+        let (url, handle) = serve_once("HTTP/1.1 204 No Content\r\n\r\n");
+        let target = PushTarget::parse(&url)
+            .expect("valid URL")
+            .with_header("Authorization", "Token secret");
+        let response = target
+            .send("POST", "text/plain", b"body", Duration::from_secs(5))
+            .expect("push succeeds");
+        assert_eq!(response.status_code, 204);
+
+        let request = handle.join().expect("server thread");
+        assert!(
+            request.starts_with("POST /write?db=kopia HTTP/1.1\r\n"),
+            "{request}"
+        );
+        assert!(
+            request.contains("\r\nAuthorization: Token secret\r\n"),
+            "{request}"
+        );
+        assert!(request.contains("\r\nContent-Length: 4\r\n"), "{request}");
+    }
+
+    #[test]
+    fn send_error_status() {
+        let (url, handle) = serve_once("HTTP/1.1 400 Bad Request\r\n\r\nunable to parse");
+        let target = PushTarget::parse(&url).expect("valid URL");
+        let err = target
+            .send("POST", "text/plain", b"body", Duration::from_secs(5))
+            .expect_err("bad status");
+        let err = err.to_string();
+        assert!(err.contains("status 400"), "{err}");
+        assert!(err.contains("unable to parse"), "{err}");
+        handle.join().expect("server thread");
+    }
+
+    #[test]
+    fn send_tls() {
+        // a plain-TCP server closing the connection fails the TLS handshake
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let handle = std::thread::spawn(move || drop(listener.accept().expect("accept")));
+        let target = PushTarget::parse(&format!("https://{addr}/write")).expect("valid URL");
+        let err = target
+            .send("POST", "text/plain", b"body", Duration::from_secs(5))
+            .expect_err("TLS handshake fails");
+        assert!(err.to_string().contains("failed to push"), "{err}");
+        handle.join().expect("server thread");
+    }
+
+    #[test]
+    fn statsd_packets() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").expect("bind");
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("set timeout");
+        let addr = receiver.local_addr().expect("local addr");
+
+        let line = "x".repeat(1421);
+        let lines = vec!["a:1|g".to_string(), line.clone(), "b:2|g".to_string()];
+        StatsdTarget::connect(&addr.to_string())
+            .expect("connect")
+            .send_lines(&lines)
+            .expect("send");
+
+        let mut buf = [0; 2048];
+        let mut recv = || {
+            let len = receiver.recv(&mut buf).expect("recv");
+            String::from_utf8_lossy(&buf[..len]).to_string()
+        };
+        assert_eq!(recv(), format!("a:1|g\n{line}"));
+        assert_eq!(recv(), "b:2|g");
+    }
+}
@@ -0,0 +1,273 @@
+//! Destinations of the periodic pushes and notifications
+
+use super::gotify::GotifyTarget;
+use super::home_assistant::HomeAssistantTopics;
+use super::mqtt::MqttTarget;
+use super::ntfy::NtfyTarget;
+use super::{PushTarget, StatsdTarget, TextfileTarget, snappy, webhook};
+use crate::KopiaSnapshots;
+use crate::health::{HealthThresholds, HealthTracker};
+use crate::metrics::StatsdFlavor;
+use std::time::Duration;
+
+/// Remote destinations to periodically push metrics
+#[derive(Debug)]
+pub struct PushConfig {
+    /// Interval between pushes
+    pub interval: Duration,
+    /// Influx line protocol write endpoint
+    pub influx: Option<PushTarget>,
+    /// Prometheus remote write endpoint
+    pub remote_write: Option<PushTarget>,
+    /// Prometheus Pushgateway group, see [`PushTarget::pushgateway`]
+    pub pushgateway: Option<PushTarget>,
+    /// Prometheus text file for the node exporter textfile collector
+    pub textfile: Option<TextfileTarget>,
+    /// MQTT broker, with the Home Assistant topics
+    pub mqtt: Option<(MqttTarget, HomeAssistantTopics)>,
+    /// `StatsD` server, with the metric naming flavor
+    pub statsd: Option<(StatsdTarget, StatsdFlavor)>,
+    /// Notifications about the backup health
+    pub notify: NotifyConfig,
+}
+impl PushConfig {
+    /// Timeout of each push
+    pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Returns true if no destination is configured
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        let Self {
+            interval: _,
+            influx,
+            remote_write,
+            pushgateway,
+            textfile,
+            mqtt,
+            statsd,
+            notify,
+        } = self;
+        influx.is_none()
+            && remote_write.is_none()
+            && pushgateway.is_none()
+            && textfile.is_none()
+            && mqtt.is_none()
+            && statsd.is_none()
+            && notify.is_empty()
+    }
+
+    /// Pushes the metrics of the snapshots to each destination, then sends the notifications
+    ///
+    /// Failures are logged, without affecting the other destinations.
+    pub fn push(
+        &self,
+        snapshots: &KopiaSnapshots,
+        now: jiff::Timestamp,
+        tracker: &mut HealthTracker,
+    ) {
+        if let Some(influx) = &self.influx {
+            let body = snapshots.generate_all_metrics_influx(now);
+            if let Err(e) = influx.send(
+                "POST",
+                "text/plain; charset=utf-8",
+                body.as_bytes(),
+                Self::TIMEOUT,
+            ) {
+                eprintln!("Error pushing metrics to influx: {e}");
+            }
+        }
+        if let Some(remote_write) = &self.remote_write {
+            let body = snappy::compress_block(&snapshots.generate_remote_write(now));
+            if let Err(e) =
+                remote_write.send("POST", "application/x-protobuf", &body, Self::TIMEOUT)
+            {
+                eprintln!("Error pushing metrics to remote write: {e}");
+            }
+        }
+        if let Some(pushgateway) = &self.pushgateway {
+            // PUT replaces all metrics in the group, dropping sources which disappeared
+            let body = snapshots.generate_all_metrics(now);
+            if let Err(e) = pushgateway.send(
+                "PUT",
+                "text/plain; version=0.0.4",
+                body.as_bytes(),
+                Self::TIMEOUT,
+            ) {
+                eprintln!("Error pushing metrics to pushgateway: {e}");
+            }
+        }
+        if let Some(textfile) = &self.textfile
+            && let Err(e) = textfile.write(&snapshots.generate_all_metrics(now))
+        {
+            eprintln!("Error writing metrics textfile: {e}");
+        }
+        if let Some((mqtt, topics)) = &self.mqtt {
+            let messages = snapshots.generate_home_assistant_messages(now, topics);
+            if let Err(e) = mqtt.publish_retained(&messages, Self::TIMEOUT) {
+                eprintln!("Error publishing metrics to MQTT: {e}");
+            }
+        }
+        if let Some((statsd, flavor)) = &self.statsd {
+            let lines = snapshots.generate_statsd_lines(now, *flavor);
+            if let Err(e) = statsd.send_lines(&lines) {
+                eprintln!("Error sending metrics to statsd: {e}");
+            }
+        }
+        self.notify.notify(snapshots, now, tracker);
+    }
+}
+
+/// Notifications about the backup health, sent each push interval
+#[derive(Debug)]
+pub struct NotifyConfig {
+    /// Consecutive unhealthy evaluations before notifying, see [`HealthTracker::new`]
+    pub unhealthy_after: u32,
+    /// Consecutive healthy evaluations before notifying of the recovery
+    pub healthy_after: u32,
+    /// Webhook receiving the health transitions as JSON
+    pub webhook: Option<PushTarget>,
+    /// Dead man's switch URL, requested while the backups are healthy
+    pub heartbeat: Option<PushTarget>,
+    /// ntfy topic receiving the health transitions
+    pub ntfy: Option<NtfyTarget>,
+    /// Gotify server receiving the health transitions
+    pub gotify: Option<GotifyTarget>,
+}
+impl NotifyConfig {
+    /// Returns a tracker of the health transitions, with the configured debouncing
+    #[must_use]
+    pub fn tracker(&self) -> HealthTracker {
+        HealthTracker::new(self.unhealthy_after, self.healthy_after)
+    }
+
+    /// Returns true if no notification is configured
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        let Self {
+            unhealthy_after: _,
+            healthy_after: _,
+            webhook,
+            heartbeat,
+            ntfy,
+            gotify,
+        } = self;
+        webhook.is_none() && heartbeat.is_none() && ntfy.is_none() && gotify.is_none()
+    }
+
+    /// Evaluates the health of the snapshots, sending the transitions (and the heartbeat
+    /// while healthy)
+    ///
+    /// Failures are logged, without affecting the other notifications.
+    pub fn notify(
+        &self,
+        snapshots: &KopiaSnapshots,
+        now: jiff::Timestamp,
+        tracker: &mut HealthTracker,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let default_thresholds = HealthThresholds::default();
+        let thresholds = snapshots.health_thresholds().unwrap_or(&default_thresholds);
+        let report = snapshots.evaluate_health(now, thresholds);
+        let transitions = tracker.update(&report);
+        if !transitions.is_empty() {
+            if let Some(webhook) = &self.webhook {
+                let body = webhook::webhook_payload(&transitions);
+                if let Err(e) = webhook.send(
+                    "POST",
+                    "application/json",
+                    body.as_bytes(),
+                    PushConfig::TIMEOUT,
+                ) {
+                    eprintln!("Error sending webhook notification: {e}");
+                }
+            }
+            if let Some(ntfy) = &self.ntfy
+                && let Err(e) = ntfy.send(&transitions, PushConfig::TIMEOUT)
+            {
+                eprintln!("Error sending ntfy notification: {e}");
+            }
+            if let Some(gotify) = &self.gotify
+                && let Err(e) = gotify.send(&transitions, PushConfig::TIMEOUT)
+            {
+                eprintln!("Error sending Gotify notification: {e}");
+            }
+        }
+        if let Some(heartbeat) = &self.heartbeat {
+            if report.is_healthy() {
+                if let Err(e) = heartbeat.send("GET", "text/plain", &[], PushConfig::TIMEOUT) {
+                    eprintln!("Error sending heartbeat: {e}");
+                }
+            } else {
+                eprintln!("Skipping heartbeat, backup status is {}", report.status());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NotifyConfig, PushConfig};
+    use crate::push::{PushTarget, TextfileTarget};
+    use crate::test_util::{single_map, test_snapshot};
+    use std::time::Duration;
+
+    fn empty_notify() -> NotifyConfig {
+        NotifyConfig {
+            unhealthy_after: 1,
+            healthy_after: 1,
+            webhook: None,
+            heartbeat: None,
+            ntfy: None,
+            gotify: None,
+        }
+    }
+
+    fn empty_push() -> PushConfig {
+        PushConfig {
+            interval: Duration::from_mins(1),
+            influx: None,
+            remote_write: None,
+            pushgateway: None,
+            textfile: None,
+            mqtt: None,
+            statsd: None,
+            notify: empty_notify(),
+        }
+    }
+
+    #[test]
+    fn empty_config() {
+        assert!(empty_push().is_empty());
+
+        let heartbeat = PushTarget::parse("https://hc-ping.com/uuid").expect("valid URL");
+        let notify = NotifyConfig {
+            heartbeat: Some(heartbeat),
+            ..empty_notify()
+        };
+        assert!(!notify.is_empty());
+        let push = PushConfig {
+            notify,
+            ..empty_push()
+        };
+        assert!(!push.is_empty());
+    }
+
+    #[test]
+    fn push_textfile() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("kopia.prom");
+        let push = PushConfig {
+            textfile: Some(TextfileTarget::new(&path)),
+            ..empty_push()
+        };
+        assert!(!push.is_empty());
+
+        let (snapshots, _source) = single_map(vec![test_snapshot("1", 1000, &["latest-1"])]);
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+        push.push(&snapshots, now, &mut push.notify.tracker());
+        let contents = std::fs::read_to_string(&path).expect("textfile written");
+        assert_eq!(contents, snapshots.generate_all_metrics(now));
+    }
+}
@@ -0,0 +1,78 @@
+//! Notifications via [Gotify](https://gotify.net)
+
+use crate::health::HealthTransition;
+use crate::push::PushTarget;
+use eyre::Result;
+use std::time::Duration;
+
+/// Gotify server and application token
+#[derive(Clone, Debug)]
+pub struct GotifyTarget {
+    target: PushTarget,
+}
+
+impl GotifyTarget {
+    /// Priority of messages about unhealthy sources (Gotify clients alert from 8)
+    const UNHEALTHY_PRIORITY: u8 = 8;
+    /// Priority of messages about recovered sources
+    const RECOVERED_PRIORITY: u8 = 4;
+
+    /// Creates a target for the server, e.g. `https://gotify.example.com` or
+    /// `http://gotify.lan`, and application token
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server URL is invalid (see [`PushTarget::parse`])
+    pub fn new(server: &str, token: &str) -> Result<Self> {
+        let url = format!("{}/message", server.trim_end_matches('/'));
+        Ok(Self {
+            target: PushTarget::parse(&url)?.with_header("X-Gotify-Key", token),
+        })
+    }
+
+    /// Creates one message per transition
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending fails
+    pub fn send(&self, transitions: &[HealthTransition], timeout: Duration) -> Result<()> {
+        for transition in transitions {
+            let priority = if transition.healthy {
+                Self::RECOVERED_PRIORITY
+            } else {
+                Self::UNHEALTHY_PRIORITY
+            };
+            let body = serde_json::json!({
+                "title": transition.title(),
+                "message": transition.message(),
+                "priority": priority,
+            });
+            self.target.send(
+                "POST",
+                "application/json",
+                body.to_string().as_bytes(),
+                timeout,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GotifyTarget;
+
+    #[test]
+    fn message_url() {
+        let gotify = GotifyTarget::new("http://gotify.lan/", "secret").expect("valid URL");
+        assert_eq!(gotify.target.path, "/message");
+        assert_eq!(
+            gotify.target.headers,
+            [("X-Gotify-Key".to_string(), "secret".to_string())]
+        );
+
+        let gotify = GotifyTarget::new("https://gotify.example.com", "secret").expect("valid URL");
+        assert!(gotify.target.tls);
+        assert_eq!(gotify.target.path, "/message");
+    }
+}
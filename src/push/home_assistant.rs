@@ -0,0 +1,166 @@
+//! Home Assistant MQTT discovery and state messages
+
+use crate::KopiaSnapshots;
+use crate::push::mqtt::MqttMessage;
+
+/// Topic prefixes for Home Assistant publishing
+#[derive(Clone, Debug)]
+pub struct HomeAssistantTopics {
+    /// Prefix for per-source state topics, e.g. `kopia_exporter`
+    pub state_prefix: String,
+    /// Home Assistant discovery prefix, e.g. `homeassistant`
+    pub discovery_prefix: String,
+}
+
+/// Sensor published for each source
+struct Sensor {
+    key: &'static str,
+    name: &'static str,
+    device_class: &'static str,
+    unit: Option<&'static str>,
+}
+const SENSORS: &[Sensor] = &[
+    Sensor {
+        key: "age_seconds",
+        name: "Backup age",
+        device_class: "duration",
+        unit: Some("s"),
+    },
+    Sensor {
+        key: "size_bytes",
+        name: "Backup size",
+        device_class: "data_size",
+        unit: Some("B"),
+    },
+    Sensor {
+        key: "last_snapshot",
+        name: "Last snapshot",
+        device_class: "timestamp",
+        unit: None,
+    },
+];
+
+impl KopiaSnapshots {
+    /// Generates retained MQTT messages for Home Assistant: discovery configs and the
+    /// state of the latest snapshot (freshness and size) for each source
+    #[must_use]
+    pub fn generate_home_assistant_messages(
+        &self,
+        now: jiff::Timestamp,
+        topics: &HomeAssistantTopics,
+    ) -> Vec<MqttMessage> {
+        let HomeAssistantTopics {
+            state_prefix,
+            discovery_prefix,
+        } = topics;
+
+        let mut messages = vec![];
+        for (source, snapshots) in &self.snapshots_map {
+            let object_id = object_id(source.as_str());
+            let state_topic = format!("{state_prefix}/{object_id}/state");
+            let device = serde_json::json!({
+                "identifiers": [format!("kopia_{object_id}")],
+                "name": format!("Kopia {}", source.as_str()),
+                "manufacturer": "kopia-exporter",
+            });
+
+            for sensor in SENSORS {
+                let mut config = serde_json::json!({
+                    "name": sensor.name,
+                    "unique_id": format!("kopia_{object_id}_{}", sensor.key),
+                    "state_topic": state_topic,
+                    "value_template": format!("{{{{ value_json.{} }}}}", sensor.key),
+                    "device_class": sensor.device_class,
+                    "device": device,
+                });
+                if let Some(unit) = sensor.unit {
+                    config["unit_of_measurement"] = unit.into();
+                    config["state_class"] = "measurement".into();
+                }
+                messages.push(MqttMessage {
+                    topic: format!(
+                        "{discovery_prefix}/sensor/kopia_{object_id}/{}/config",
+                        sensor.key
+                    ),
+                    payload: config.to_string(),
+                });
+            }
+
+            let latest = self.latest_policy.select_latest(snapshots);
+            let end_time = latest.and_then(|snapshot| snapshot.end_time);
+            let state = serde_json::json!({
+                "age_seconds": end_time.map(|end_time| now.as_second() - end_time.as_second()),
+                "size_bytes": latest.map(|snapshot| snapshot.stats.total_size),
+                "last_snapshot": end_time.map(|end_time| end_time.to_string()),
+            });
+            messages.push(MqttMessage {
+                topic: state_topic,
+                payload: state.to_string(),
+            });
+        }
+        messages
+    }
+}
+
+/// Converts the source to an identifier safe for MQTT topics and Home Assistant IDs
+fn object_id(source: &str) -> String {
+    source
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HomeAssistantTopics;
+    use crate::test_util::{single_map, test_snapshot};
+
+    #[test]
+    fn messages() {
+        let snapshots = vec![test_snapshot("1", 1000, &["daily-1"])];
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+        let (map, _source) = single_map(snapshots);
+
+        let topics = HomeAssistantTopics {
+            state_prefix: "kopia_exporter".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        };
+        let messages = map.generate_home_assistant_messages(now, &topics);
+        let topic_names: Vec<_> = messages.iter().map(|m| m.topic.as_str()).collect();
+        assert_eq!(
+            topic_names,
+            [
+                "homeassistant/sensor/kopia_user_name_host__path/age_seconds/config",
+                "homeassistant/sensor/kopia_user_name_host__path/size_bytes/config",
+                "homeassistant/sensor/kopia_user_name_host__path/last_snapshot/config",
+                "kopia_exporter/user_name_host__path/state",
+            ]
+        );
+
+        let age_config: serde_json::Value =
+            serde_json::from_str(&messages[0].payload).expect("valid JSON");
+        assert_eq!(
+            age_config["state_topic"],
+            "kopia_exporter/user_name_host__path/state"
+        );
+        assert_eq!(age_config["value_template"], "{{ value_json.age_seconds }}");
+        assert_eq!(age_config["unit_of_measurement"], "s");
+
+        let state: serde_json::Value =
+            serde_json::from_str(&messages[3].payload).expect("valid JSON");
+        assert_eq!(
+            state,
+            serde_json::json!({
+                "age_seconds": 3600,
+                "size_bytes": 1000,
+                "last_snapshot": "2025-08-14T00:01:00Z",
+            })
+        );
+    }
+}
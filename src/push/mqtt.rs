@@ -0,0 +1,266 @@
+//! Minimal MQTT 3.1.1 client, publishing retained messages at `QoS` 0
+
+use eyre::{Result, eyre};
+use std::io::{Read as _, Write as _};
+use std::net::{TcpStream, ToSocketAddrs as _};
+use std::time::Duration;
+
+/// Message to publish to an MQTT broker
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MqttMessage {
+    /// Topic name
+    pub topic: String,
+    /// Payload (typically JSON)
+    pub payload: String,
+}
+
+/// MQTT broker connection settings
+#[derive(Clone, Debug)]
+pub struct MqttTarget {
+    addr: String,
+    client_id: String,
+    credentials: Option<(String, String)>,
+}
+
+impl MqttTarget {
+    const KEEP_ALIVE_SECONDS: u16 = 60;
+
+    /// Creates a target for the broker `host:port`
+    #[must_use]
+    pub fn new(addr: impl Into<String>, client_id: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            client_id: client_id.into(),
+            credentials: None,
+        }
+    }
+
+    /// Authenticates with the username and password
+    #[must_use]
+    pub fn with_credentials(mut self, username: String, password: String) -> Self {
+        self.credentials = Some((username, password));
+        self
+    }
+
+    /// Connects to the broker, publishes all messages as retained (`QoS` 0), then disconnects
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a string field (e.g. the topic or client ID) exceeds the 65535
+    /// bytes of MQTT strings, the connection fails, or the broker rejects the connection
+    pub fn publish_retained(&self, messages: &[MqttMessage], timeout: Duration) -> Result<()> {
+        // encoded before connecting, to fail without sending anything
+        let connect = self.encode_connect()?;
+        let publishes = messages
+            .iter()
+            .map(encode_publish_retained)
+            .collect::<Result<Vec<_>>>()?;
+
+        let addr = self
+            .addr
+            .to_socket_addrs()
+            .map_err(|e| eyre!("invalid MQTT broker address {:?}: {e}", self.addr))?
+            .next()
+            .ok_or_else(|| eyre!("no addresses found for MQTT broker {:?}", self.addr))?;
+        let mut stream = TcpStream::connect_timeout(&addr, timeout)
+            .map_err(|e| eyre!("failed to connect to MQTT broker {}: {e}", self.addr))?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        stream.write_all(&connect)?;
+        let mut connack = [0; 4];
+        stream
+            .read_exact(&mut connack)
+            .map_err(|e| eyre!("failed to read MQTT CONNACK: {e}"))?;
+        match connack {
+            [0x20, 0x02, _, 0] => {}
+            [0x20, 0x02, _, code] => {
+                return Err(eyre!("MQTT broker refused connection, return code {code}"));
+            }
+            _ => return Err(eyre!("invalid MQTT CONNACK packet: {connack:?}")),
+        }
+
+        for publish in publishes {
+            stream.write_all(&publish)?;
+        }
+        // DISCONNECT
+        stream.write_all(&[0xE0, 0x00])?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn encode_connect(&self) -> Result<Vec<u8>> {
+        const CLEAN_SESSION: u8 = 0x02;
+        const PASSWORD: u8 = 0x40;
+        const USERNAME: u8 = 0x80;
+
+        let mut body = Vec::new();
+        write_string(&mut body, "protocol name", "MQTT")?;
+        body.push(4); // protocol level 3.1.1
+        body.push(match self.credentials {
+            Some(_) => CLEAN_SESSION | USERNAME | PASSWORD,
+            None => CLEAN_SESSION,
+        });
+        body.extend_from_slice(&Self::KEEP_ALIVE_SECONDS.to_be_bytes());
+        write_string(&mut body, "client ID", &self.client_id)?;
+        if let Some((username, password)) = &self.credentials {
+            write_string(&mut body, "username", username)?;
+            write_string(&mut body, "password", password)?;
+        }
+        Ok(encode_packet(0x10, &body))
+    }
+}
+
+fn encode_publish_retained(message: &MqttMessage) -> Result<Vec<u8>> {
+    const PUBLISH_RETAIN: u8 = 0x31;
+
+    let mut body = Vec::new();
+    write_string(&mut body, "topic", &message.topic)?;
+    body.extend_from_slice(message.payload.as_bytes());
+    Ok(encode_packet(PUBLISH_RETAIN, &body))
+}
+
+fn encode_packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    // remaining length, 7 bits per byte
+    let mut len = body.len();
+    loop {
+        let byte = u8::try_from(len % 0x80).expect("below 128");
+        len /= 0x80;
+        if len == 0 {
+            packet.push(byte);
+            break;
+        }
+        packet.push(byte | 0x80);
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn write_string(output: &mut Vec<u8>, field: &str, s: &str) -> Result<()> {
+    // strings are limited to u16::MAX bytes
+    let len = u16::try_from(s.len()).map_err(|_| {
+        eyre!(
+            "MQTT {field} is {} bytes, longer than the limit of {} bytes",
+            s.len(),
+            u16::MAX
+        )
+    })?;
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MqttMessage, MqttTarget, encode_packet, encode_publish_retained};
+    use std::io::{Read as _, Write as _};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    #[test]
+    fn remaining_length() {
+        assert_eq!(encode_packet(0xE0, &[]), [0xE0, 0x00]);
+        let packet = encode_packet(0x30, &[0; 321]);
+        assert_eq!(packet[..3], [0x30, 0xC1, 0x02]);
+        assert_eq!(packet.len(), 3 + 321);
+    }
+
+    #[test]
+    fn publish_packet() {
+        let packet = encode_publish_retained(&MqttMessage {
+            topic: "a/b".to_string(),
+            payload: "on".to_string(),
+        })
+        .expect("short topic");
+        assert_eq!(packet, [0x31, 7, 0, 3, b'a', b'/', b'b', b'o', b'n']);
+    }
+
+    #[test]
+    fn overlong_strings() {
+        let long = "a".repeat(usize::from(u16::MAX) + 1);
+        let error = encode_publish_retained(&MqttMessage {
+            topic: long.clone(),
+            payload: String::new(),
+        })
+        .expect_err("topic too long");
+        assert_eq!(
+            error.to_string(),
+            "MQTT topic is 65536 bytes, longer than the limit of 65535 bytes"
+        );
+        let longest = encode_publish_retained(&MqttMessage {
+            topic: long[1..].to_string(),
+            payload: String::new(),
+        })
+        .expect("topic at the limit");
+        assert_eq!(longest[4..6], [0xFF, 0xFF]);
+
+        // fails before connecting to the (unreachable) broker
+        let error = MqttTarget::new("127.0.0.1:1", long)
+            .publish_retained(&[], Duration::from_secs(5))
+            .expect_err("client ID too long");
+        assert_eq!(
+            error.to_string(),
+            "MQTT client ID is 65536 bytes, longer than the limit of 65535 bytes"
+        );
+    }
+
+    #[test]
+    fn publish_to_broker() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let broker = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut connect = [0; 2];
+            stream.read_exact(&mut connect).expect("read header");
+            let mut body = vec![0; usize::from(connect[1])];
+            stream.read_exact(&mut body).expect("read connect");
+            stream
+                .write_all(&[0x20, 0x02, 0x00, 0x00])
+                .expect("write connack");
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).expect("read publish");
+            (body, rest)
+        });
+
+        let target = MqttTarget::new(addr.to_string(), "kopia")
+            .with_credentials("user".to_string(), "pass".to_string());
+        let message = MqttMessage {
+            topic: "t".to_string(),
+            payload: "p".to_string(),
+        };
+        target
+            .publish_retained(&[message], Duration::from_secs(5))
+            .expect("publish");
+
+        let (connect, rest) = broker.join().expect("broker thread");
+        assert_eq!(connect[..7], [0, 4, b'M', b'Q', b'T', b'T', 4]);
+        assert_eq!(connect[7], 0xC2, "flags");
+        assert!(
+            connect.ends_with(b"\x00\x04user\x00\x04pass"),
+            "{connect:?}"
+        );
+        assert_eq!(rest, [0x31, 4, 0, 1, b't', b'p', 0xE0, 0x00]);
+    }
+
+    #[test]
+    fn connection_refused_code() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let broker = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut buf = [0; 64];
+            let _ = stream.read(&mut buf).expect("read connect");
+            // bad username or password
+            stream
+                .write_all(&[0x20, 0x02, 0x00, 0x04])
+                .expect("write connack");
+        });
+
+        let err = MqttTarget::new(addr.to_string(), "kopia")
+            .publish_retained(&[], Duration::from_secs(5))
+            .expect_err("refused");
+        assert!(err.to_string().contains("return code 4"), "{err}");
+        broker.join().expect("broker thread");
+    }
+}
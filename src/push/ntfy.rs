@@ -0,0 +1,88 @@
+//! Notifications via [ntfy](https://ntfy.sh)
+
+use crate::health::HealthTransition;
+use crate::push::PushTarget;
+use eyre::Result;
+use std::time::Duration;
+
+/// ntfy server and topic
+#[derive(Clone, Debug)]
+pub struct NtfyTarget {
+    target: PushTarget,
+}
+
+impl NtfyTarget {
+    /// Creates a target publishing to the topic on the server, e.g. `https://ntfy.sh` or
+    /// `http://ntfy.lan`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server URL is invalid (see [`PushTarget::parse`])
+    pub fn new(server: &str, topic: &str) -> Result<Self> {
+        let url = format!("{}/{topic}", server.trim_end_matches('/'));
+        Ok(Self {
+            target: PushTarget::parse(&url)?,
+        })
+    }
+
+    /// Authenticates with the access token
+    #[must_use]
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.target = self
+            .target
+            .with_header("Authorization", format!("Bearer {token}"));
+        self
+    }
+
+    /// Publishes one message per transition, with high priority for unhealthy sources
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if publishing fails
+    pub fn send(&self, transitions: &[HealthTransition], timeout: Duration) -> Result<()> {
+        for transition in transitions {
+            let (priority, tags) = if transition.healthy {
+                ("default", "white_check_mark")
+            } else {
+                ("high", "warning")
+            };
+            self.target
+                .clone()
+                .with_header("Title", transition.title())
+                .with_header("Priority", priority)
+                .with_header("Tags", tags)
+                .send(
+                    "POST",
+                    "text/plain; charset=utf-8",
+                    transition.message().as_bytes(),
+                    timeout,
+                )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NtfyTarget;
+
+    #[test]
+    fn topic_url() {
+        let ntfy = NtfyTarget::new("http://ntfy.lan:8080/", "backups")
+            .expect("valid URL")
+            .with_token("tk_secret");
+        assert_eq!(ntfy.target.host, "ntfy.lan");
+        assert_eq!(ntfy.target.port, 8080);
+        assert_eq!(ntfy.target.path, "/backups");
+        assert_eq!(
+            ntfy.target.headers,
+            [("Authorization".to_string(), "Bearer tk_secret".to_string())]
+        );
+
+        // the public service is https:// only
+        let ntfy = NtfyTarget::new("https://ntfy.sh", "backups").expect("valid URL");
+        assert!(ntfy.target.tls);
+        assert_eq!(ntfy.target.port, 443);
+        assert_eq!(ntfy.target.path, "/backups");
+    }
+}
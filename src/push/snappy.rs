@@ -0,0 +1,57 @@
+//! Minimal snappy block format encoder
+//!
+//! Emits the input as uncompressed literals, which any snappy decoder accepts. The
+//! pushed payloads are small, so skipping actual compression avoids a dependency.
+
+use crate::metrics::write_varint;
+
+/// Encodes the input in the snappy block format (without compression)
+#[must_use]
+pub fn compress_block(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() + 16);
+    // preamble: uncompressed length as varint
+    write_varint(&mut output, input.len() as u64);
+
+    // literals hold at most 2^32 bytes
+    let max_literal_len = usize::try_from(u32::MAX).unwrap_or(usize::MAX);
+    for chunk in input.chunks(max_literal_len) {
+        let len_minus_one = u32::try_from(chunk.len() - 1).unwrap_or(u32::MAX);
+        let len_bytes = len_minus_one.to_le_bytes();
+        // tags 60..=63 specify the length in 1 to 4 following bytes
+        let extra_bytes: u8 = match len_minus_one {
+            0..60 => 0,
+            60..=0xFF => 1,
+            0x100..=0xFFFF => 2,
+            0x1_0000..=0xFF_FFFF => 3,
+            _ => 4,
+        };
+        if extra_bytes == 0 {
+            output.push(len_bytes[0] << 2);
+        } else {
+            output.push((59 + extra_bytes) << 2);
+            output.extend_from_slice(&len_bytes[..usize::from(extra_bytes)]);
+        }
+        output.extend_from_slice(chunk);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compress_block;
+
+    #[test]
+    fn short_literal() {
+        assert_eq!(compress_block(b""), [0x00]);
+        assert_eq!(compress_block(b"abc"), [0x03, 0x08, b'a', b'b', b'c']);
+    }
+
+    #[test]
+    fn long_literal() {
+        let input = vec![b'x'; 300];
+        let output = compress_block(&input);
+        // varint 300, tag for 2-byte length, 299 little-endian
+        assert_eq!(output[..5], [0xAC, 0x02, 61 << 2, 0x2B, 0x01]);
+        assert_eq!(output[5..], input[..]);
+    }
+}
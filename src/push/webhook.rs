@@ -0,0 +1,73 @@
+//! JSON payload for health change webhooks
+
+use crate::health::HealthTransition;
+
+/// Formats the transitions as a webhook JSON payload
+///
+/// The summary is included as both `text` (Slack, Mattermost) and `content` (Discord),
+/// alongside the structured `transitions`.
+#[must_use]
+pub fn webhook_payload(transitions: &[HealthTransition]) -> String {
+    let lines: Vec<String> = transitions
+        .iter()
+        .map(|transition| format!("{}: {}", transition.title(), transition.message()))
+        .collect();
+    let text = lines.join("\n");
+    let transitions: Vec<_> = transitions
+        .iter()
+        .map(|transition| {
+            serde_json::json!({
+                "source": transition.source.as_str(),
+                "healthy": transition.healthy,
+                "status": transition.status.name(),
+                "problems": transition.problems,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "text": text,
+        "content": text,
+        "transitions": transitions,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::webhook_payload;
+    use crate::health::{HealthStatus, HealthTransition};
+    use crate::test_util::single_map;
+
+    #[test]
+    fn payload() {
+        let (_map, source) = single_map(vec![]);
+        let transitions = [
+            HealthTransition {
+                source: source.clone(),
+                healthy: false,
+                status: HealthStatus::Critical,
+                problems: vec!["errors 3 > 0".to_string()],
+            },
+            HealthTransition {
+                source,
+                healthy: true,
+                status: HealthStatus::Ok,
+                problems: vec![],
+            },
+        ];
+        let payload: serde_json::Value =
+            serde_json::from_str(&webhook_payload(&transitions)).expect("valid JSON");
+        let text = "Kopia backup CRITICAL: user_name@host:/path (errors 3 > 0)\nKopia backup recovered: user_name@host:/path";
+        assert_eq!(payload["text"], text);
+        assert_eq!(payload["content"], text);
+        assert_eq!(
+            payload["transitions"][0],
+            serde_json::json!({
+                "source": "user_name@host:/path",
+                "healthy": false,
+                "status": "CRITICAL",
+                "problems": ["errors 3 > 0"],
+            })
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Round-trip serialization of [`KopiaSnapshots`]
+//!
+//! Snapshots are serialized in the `kopia` JSON format (with the timestamps as reported),
+//! so deserializing reproduces any timestamp parse errors.
+
+use crate::{
+    KopiaSnapshots, MalformedSnapshots, RetentionReason, RootEntry, Snapshot, SnapshotJson, Source,
+    SourceMap, Stats,
+    kopia::{Compaction, FoldedSnapshots, SeenSnapshotIds},
+};
+use serde::{Deserialize, Serialize, de};
+use std::collections::BTreeMap;
+
+/// Serialized fields of [`KopiaSnapshots`]
+///
+/// NOTE: the health thresholds, repository quota, expected retention and expected intervals
+/// are configuration, not parsed state, and are omitted (as is the snapshot history, stored
+/// separately)
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Fields<S, P> {
+    snapshots: SourceMap<Vec<S>>,
+    invalid_user_names: BTreeMap<String, u64>,
+    invalid_hosts: BTreeMap<String, u64>,
+    #[serde(default)]
+    invalid_paths: BTreeMap<String, u64>,
+    #[serde(default)]
+    malformed_snapshots: MalformedSnapshots,
+    #[serde(default)]
+    duplicate_snapshots: BTreeMap<String, u64>,
+    #[serde(default)]
+    utf8_replacements: u64,
+    #[serde(default)]
+    kopia_stderr: Option<String>,
+    latest_policy: P,
+    sources_truncated: Option<u32>,
+    fetched_at: Option<String>,
+    #[serde(default)]
+    repo_id: Option<String>,
+    #[serde(default)]
+    source_rollups: bool,
+    aggregate_policy: Option<P>,
+    /// Newest snapshots kept per source with the aggregate policy, if not aggregate-only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keep_newest: Option<std::num::NonZeroUsize>,
+    folded: SourceMap<FoldedSnapshots>,
+}
+
+/// [`SnapshotJson`] borrowed from a [`Snapshot`]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotJsonRef<'a> {
+    id: &'a str,
+    source: &'a Source,
+    description: &'a str,
+    start_time: &'a str,
+    end_time: &'a str,
+    stats: &'a Stats,
+    root_entry: Option<&'a RootEntry>,
+    retention_reason: &'a [RetentionReason],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    incomplete: Option<&'a str>,
+}
+impl<'a> From<&'a Snapshot> for SnapshotJsonRef<'a> {
+    fn from(snapshot: &'a Snapshot) -> Self {
+        let Snapshot {
+            id,
+            source,
+            description,
+            start_time: _,
+            end_time: _,
+            stats,
+            root_entry,
+            root_mtime: _,
+            retention_reason,
+            incomplete,
+            raw_times,
+        } = snapshot;
+        Self {
+            id,
+            source,
+            description,
+            start_time: &raw_times.start_time,
+            end_time: &raw_times.end_time,
+            stats,
+            root_entry: root_entry.as_ref(),
+            retention_reason,
+            incomplete: incomplete.as_deref(),
+        }
+    }
+}
+
+/// Serializes the parsed snapshots, invalid source counts, and the latest policy, for
+/// caching to disk or passing between processes.
+///
+/// The [health thresholds](KopiaSnapshots::with_health_thresholds),
+/// [snapshot history](KopiaSnapshots::with_history) and
+/// [custom metrics](KopiaSnapshots::with_custom_metric) are not included.
+impl Serialize for KopiaSnapshots {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Self {
+            snapshots_map,
+            invalid_user_names,
+            invalid_hosts,
+            invalid_paths,
+            malformed,
+            seen_ids,
+            utf8_replacements,
+            kopia_stderr,
+            latest_policy,
+            sources_truncated,
+            health_thresholds: _,
+            repo_quota_bytes: _,
+            expected_retention: _,
+            expected_intervals: _,
+            history: _,
+            custom_metrics: _,
+            fetched_at,
+            repo_id,
+            source_rollups,
+            compaction,
+            folded,
+        } = self;
+        Fields {
+            snapshots: snapshots_map
+                .iter()
+                .map(|(source, snapshots)| {
+                    let snapshots = snapshots.iter().map(SnapshotJsonRef::from).collect();
+                    (source.clone(), snapshots)
+                })
+                .collect(),
+            invalid_user_names: invalid_user_names.clone(),
+            invalid_hosts: invalid_hosts.clone(),
+            invalid_paths: invalid_paths.clone(),
+            malformed_snapshots: malformed.clone(),
+            duplicate_snapshots: seen_ids.duplicates().clone(),
+            utf8_replacements: *utf8_replacements,
+            kopia_stderr: kopia_stderr.clone(),
+            latest_policy: latest_policy.name(),
+            sources_truncated: *sources_truncated,
+            fetched_at: fetched_at.map(|fetched_at| fetched_at.to_string()),
+            repo_id: repo_id.clone(),
+            source_rollups: *source_rollups,
+            aggregate_policy: compaction.map(|compaction| compaction.policy.name()),
+            keep_newest: compaction
+                .map(|compaction| compaction.keep_newest)
+                .filter(|&keep_newest| keep_newest != Compaction::AGGREGATE_KEEP_NEWEST),
+            folded: folded.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for KopiaSnapshots {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Fields::<SnapshotJson, String> {
+            snapshots,
+            invalid_user_names,
+            invalid_hosts,
+            invalid_paths,
+            malformed_snapshots,
+            duplicate_snapshots,
+            utf8_replacements,
+            kopia_stderr,
+            latest_policy,
+            sources_truncated,
+            fetched_at,
+            repo_id,
+            source_rollups,
+            aggregate_policy,
+            keep_newest,
+            folded,
+        } = Fields::deserialize(deserializer)?;
+
+        let parse_policy = |policy: String| policy.parse().map_err(de::Error::custom);
+        // NOTE: the IDs of folded snapshots are not serialized, only those kept
+        let mut seen_ids = SeenSnapshotIds::with_duplicates(duplicate_snapshots);
+        Ok(Self {
+            snapshots_map: snapshots
+                .into_iter()
+                .map(|(source, snapshots)| {
+                    let snapshots = snapshots
+                        .into_iter()
+                        .inspect(|snapshot| {
+                            seen_ids.insert(&snapshot.id);
+                        })
+                        .map(Snapshot::from)
+                        .collect();
+                    (source, snapshots)
+                })
+                .collect(),
+            invalid_user_names,
+            invalid_hosts,
+            invalid_paths,
+            malformed: malformed_snapshots,
+            seen_ids,
+            utf8_replacements,
+            kopia_stderr,
+            latest_policy: parse_policy(latest_policy)?,
+            sources_truncated,
+            health_thresholds: None,
+            repo_quota_bytes: None,
+            expected_retention: std::collections::BTreeMap::new(),
+            expected_intervals: Vec::new(),
+            history: None,
+            custom_metrics: crate::metrics::CustomMetricFns::default(),
+            fetched_at: fetched_at
+                .map(|fetched_at| fetched_at.parse().map_err(de::Error::custom))
+                .transpose()?,
+            repo_id,
+            source_rollups,
+            compaction: aggregate_policy
+                .map(|policy| {
+                    Ok::<_, D::Error>(Compaction {
+                        policy: parse_policy(policy)?,
+                        keep_newest: keep_newest.unwrap_or(Compaction::AGGREGATE_KEEP_NEWEST),
+                    })
+                })
+                .transpose()?,
+            folded,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        KopiaSnapshots, LatestSnapshotPolicy, ParseOptions, Source,
+        test_util::{source_str, test_snapshot},
+    };
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn round_trip() {
+        let mut invalid = test_snapshot("3", 1000, &["daily-1"]);
+        invalid.source = Source {
+            host: "bad:host".to_string(),
+            user_name: "user".to_string(),
+            path: "/test".to_string(),
+        };
+        let mut bad_time = test_snapshot("4", 1000, &["daily-2"]);
+        bad_time.end_time = "not a timestamp".to_string();
+        let json = serde_json::to_string(&vec![
+            test_snapshot("0", 1000, &["daily-4"]),
+            test_snapshot("1", 1000, &["daily-3"]),
+            bad_time,
+            test_snapshot("2", 2000, &["latest-1", "daily-1"]),
+            invalid,
+        ])
+        .expect("serializable");
+        let fetched_at: jiff::Timestamp = "2025-08-14T01:00:00Z".parse().expect("valid timestamp");
+        let (snapshots, _invalid_sources) = KopiaSnapshots::new_from_reader_with_options(
+            json.as_bytes(),
+            ParseOptions::aggregate_only(LatestSnapshotPolicy::NewestComplete),
+        )
+        .expect("valid");
+        let snapshots = snapshots.with_fetched_at(fetched_at);
+
+        let serialized = serde_json::to_string(&snapshots).expect("serializable");
+        let deserialized: KopiaSnapshots =
+            serde_json::from_str(&serialized).expect("deserializable");
+
+        let now: jiff::Timestamp = "2025-08-14T01:01:00Z".parse().expect("valid timestamp");
+        assert_eq!(
+            deserialized.generate_all_metrics(now),
+            snapshots.generate_all_metrics(now)
+        );
+        assert_eq!(deserialized.latest_policy(), snapshots.latest_policy());
+        assert_eq!(deserialized.fetched_at(), Some(fetched_at));
+        let source = source_str("user_name@host:/path");
+        let raw_end_times = |snapshots: &KopiaSnapshots| {
+            snapshots
+                .snapshots_for(&source)
+                .expect("present")
+                .iter()
+                .map(|snapshot| snapshot.raw_times.end_time.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(raw_end_times(&deserialized), raw_end_times(&snapshots));
+        assert_eq!(
+            serde_json::to_string(&deserialized).expect("serializable"),
+            serialized
+        );
+    }
+
+    #[test]
+    fn round_trip_limited() {
+        let json = serde_json::to_string(
+            &(1..=6)
+                .map(|id| test_snapshot(&id.to_string(), 1000, &["daily-1"]))
+                .collect::<Vec<_>>(),
+        )
+        .expect("serializable");
+        let (snapshots, _invalid_sources) = KopiaSnapshots::new_from_reader_with_options(
+            json.as_bytes(),
+            ParseOptions {
+                latest_policy: LatestSnapshotPolicy::Newest,
+                max_snapshots_per_source: NonZeroUsize::new(3),
+            },
+        )
+        .expect("valid");
+
+        let serialized = serde_json::to_string(&snapshots).expect("serializable");
+        assert!(serialized.contains("\"keepNewest\":3"), "{serialized}");
+        let deserialized: KopiaSnapshots =
+            serde_json::from_str(&serialized).expect("deserializable");
+        assert_eq!(
+            serde_json::to_string(&deserialized).expect("serializable"),
+            serialized
+        );
+    }
+}
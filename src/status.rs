@@ -0,0 +1,296 @@
+//! Human-readable HTML status page, summarizing the latest snapshot of each source
+//!
+//! Ages are color-coded against the age thresholds of the [health](crate::health) check
+//! (green when within, yellow beyond the warning and red beyond the critical threshold).
+//! The size trend is drawn as an inline SVG sparkline of the sizes of the retained snapshots,
+//! or of all snapshots in the [history](KopiaSnapshots::with_history) if set.
+
+use crate::{
+    KopiaSnapshots,
+    health::{HealthStatus, HealthThresholds},
+};
+use std::fmt::{self, Write as _};
+
+mod sparkline;
+
+impl KopiaSnapshots {
+    /// Renders a standalone HTML page with a table of sources: the time, age, size and errors
+    /// of the latest snapshot, the size trend of the retained snapshots, and the number of
+    /// retained snapshots of each retention class
+    #[must_use]
+    pub fn generate_status_html(
+        &self,
+        now: jiff::Timestamp,
+        thresholds: &HealthThresholds,
+    ) -> String {
+        let report = self.evaluate_health(now, thresholds);
+        let class_counts = self.get_retention_class_counts();
+
+        let mut rows = String::new();
+        for health in &report.sources {
+            let snapshots = self.snapshots_for(&health.source).unwrap_or_default();
+            let end_time = self
+                .latest_policy
+                .select_latest(snapshots)
+                .and_then(|snapshot| snapshot.end_time);
+            let (time, age) = match (end_time, health.age_seconds) {
+                (Some(end_time), Some(age_seconds)) => {
+                    let age = jiff::SignedDuration::from_secs(age_seconds);
+                    let class = age_status(age, thresholds).map_or("", status_class);
+                    (end_time.to_string(), format!("<td{class}>{age:#}</td>"))
+                }
+                _ => (String::new(), "<td></td>".to_string()),
+            };
+            let size = health.size_bytes.map(format_size).unwrap_or_default();
+            let history = self
+                .history
+                .as_ref()
+                .and_then(|history| history.snapshots_for(&health.source));
+            let size_points: Vec<_> = match history {
+                Some(history) => history
+                    .iter()
+                    .filter_map(|snapshot| {
+                        let end_time = snapshot.end_time?;
+                        Some((end_time.as_second(), snapshot.total_size))
+                    })
+                    .collect(),
+                None => snapshots
+                    .iter()
+                    .filter_map(|snapshot| {
+                        let end_time = snapshot.end_time?;
+                        Some((end_time.as_second(), snapshot.stats.total_size))
+                    })
+                    .collect(),
+            };
+            let size_trend = sparkline::render(&size_points).unwrap_or_default();
+            let errors = health
+                .errors
+                .map(|errors| errors.to_string())
+                .unwrap_or_default();
+            let retention = class_counts
+                .get(&health.source)
+                .map(|classes| {
+                    classes
+                        .iter()
+                        .map(|(class, count)| format!("{class}: {count}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                })
+                .unwrap_or_default();
+            let silenced = if health.silenced { " (silenced)" } else { "" };
+            writeln!(
+                rows,
+                "<tr><td>{source}</td><td{status_class}>{status}{silenced}</td><td>{time}</td>{age}\
+                 <td class=\"num\">{size}</td><td>{size_trend}</td><td class=\"num\">{errors}</td>\
+                 <td>{retention}</td></tr>",
+                source = Html(health.source.as_str()),
+                status_class = status_class(health.status),
+                status = health.status,
+                retention = Html(&retention),
+            )
+            .expect("infallible");
+        }
+        if report.sources.is_empty() {
+            rows.push_str("<tr><td colspan=\"8\">No snapshots found</td></tr>\n");
+        }
+
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Kopia Exporter Status</title>
+<style>
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: left; }}
+td.num {{ text-align: right; }}
+.ok {{ background: #c8e6c9; }}
+.warning {{ background: #fff59d; }}
+.critical, .unknown {{ background: #ef9a9a; }}
+.sparkline {{ display: block; color: #1e88e5; }}
+</style>
+</head>
+<body>
+<h1>Kopia Exporter Status: {status}</h1>
+<p>Generated at {now}</p>
+<table>
+<tr><th>Source</th><th>Status</th><th>Last snapshot</th><th>Age</th><th>Size</th><th>Size trend</th><th>Errors</th><th>Retention</th></tr>
+{rows}</table>
+</body>
+</html>
+"#,
+            status = report.status(),
+        )
+    }
+}
+
+/// Returns the status of the age against the age thresholds, if any are set
+fn age_status(age: jiff::SignedDuration, thresholds: &HealthThresholds) -> Option<HealthStatus> {
+    let HealthThresholds {
+        max_age,
+        warn_max_age,
+        ..
+    } = thresholds;
+    if max_age.is_none() && warn_max_age.is_none() {
+        None
+    } else if max_age.is_some_and(|limit| age > limit) {
+        Some(HealthStatus::Critical)
+    } else if warn_max_age.is_some_and(|limit| age > limit) {
+        Some(HealthStatus::Warning)
+    } else {
+        Some(HealthStatus::Ok)
+    }
+}
+
+/// Returns the `class` attribute styling the status
+fn status_class(status: HealthStatus) -> &'static str {
+    match status {
+        HealthStatus::Ok => " class=\"ok\"",
+        HealthStatus::Warning => " class=\"warning\"",
+        HealthStatus::Critical => " class=\"critical\"",
+        HealthStatus::Unknown => " class=\"unknown\"",
+    }
+}
+
+/// Formats the size in binary units, e.g. `1.5 GiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[expect(clippy::cast_precision_loss)] // display only
+    let mut value = bytes as f64 / 1024.0;
+    let mut units = UNITS.iter();
+    let mut unit = units.next().expect("nonempty");
+    for next_unit in units {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// Text escaped for HTML content and attribute values
+struct Html<'a>(&'a str);
+impl fmt::Display for Html<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(text) = self;
+        for c in text.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Html, format_size};
+    use crate::{
+        AssertContains as _,
+        health::HealthThresholds,
+        history::{HistoricSnapshot, SnapshotHistory},
+        test_util::{multi_map, single_map, test_snapshot},
+    };
+
+    #[test]
+    fn status_html() {
+        let now: jiff::Timestamp = "2025-08-14T02:01:00Z".parse().expect("valid timestamp");
+        let mut failed = test_snapshot("3", 5000, &["latest-1"]);
+        failed.stats.error_count = 2;
+        failed.end_time = "2025-08-13T00:01:00Z".to_string();
+        let mut previous = test_snapshot("1", 1000, &["daily-2"]);
+        previous.end_time = "2025-08-13T00:01:00Z".to_string();
+        let (map, _sources) = multi_map(vec![
+            (
+                "alice",
+                "host",
+                "/data",
+                vec![previous, test_snapshot("2", 1536, &["latest-1", "daily-1"])],
+            ),
+            ("bob<script>", "host", "/backup", vec![failed]),
+        ]);
+        let hours = jiff::SignedDuration::from_hours;
+        let thresholds = HealthThresholds {
+            max_age: Some(hours(25)),
+            warn_max_age: Some(hours(1)),
+            ..HealthThresholds::default()
+        };
+
+        map.generate_status_html(now, &thresholds)
+            .assert_contains_lines(&[
+                "<h1>Kopia Exporter Status: CRITICAL</h1>",
+                "<p>Generated at 2025-08-14T02:01:00Z</p>",
+                "<tr><td>alice@host:/data</td><td class=\"warning\">WARNING</td>\
+                 <td>2025-08-14T00:01:00Z</td><td class=\"warning\">2h</td>\
+                 <td class=\"num\">1.5 KiB</td><td><svg class=\"sparkline\" width=\"120\" \
+                 height=\"24\" viewBox=\"0 0 120 24\"><polyline fill=\"none\" \
+                 stroke=\"currentColor\" points=\"2.0,22.0 118.0,2.0\"/></svg></td>\
+                 <td class=\"num\">0</td>\
+                 <td>daily: 2, latest: 1</td></tr>",
+                "<tr><td>bob&lt;script&gt;@host:/backup</td><td class=\"critical\">CRITICAL</td>\
+                 <td>2025-08-13T00:01:00Z</td><td class=\"critical\">26h</td>\
+                 <td class=\"num\">4.9 KiB</td><td></td><td class=\"num\">2</td>\
+                 <td>latest: 1</td></tr>",
+            ]);
+
+        let (map, _sources) = multi_map(vec![]);
+        map.generate_status_html(now, &HealthThresholds::default())
+            .assert_contains_lines(&[
+                "<h1>Kopia Exporter Status: UNKNOWN</h1>",
+                "<tr><td colspan=\"8\">No snapshots found</td></tr>",
+            ]);
+    }
+
+    #[test]
+    fn status_html_history() {
+        let now: jiff::Timestamp = "2025-08-14T02:01:00Z".parse().expect("valid timestamp");
+        let (map, source) = single_map(vec![test_snapshot("2", 2000, &["latest-1"])]);
+        let historic = |id: &str, end_time: &str, total_size| HistoricSnapshot {
+            id: id.to_string(),
+            start_time: None,
+            end_time: Some(end_time.parse().expect("valid timestamp")),
+            total_size,
+            error_count: 0,
+            last_observed_at: now,
+        };
+        let history = SnapshotHistory::new(
+            [(
+                source,
+                vec![
+                    historic("2", "2025-08-14T00:01:00Z", 2000),
+                    historic("1", "2025-08-12T00:01:00Z", 1000),
+                    historic("0", "2025-08-10T00:01:00Z", 1500),
+                ],
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        let html = map.generate_status_html(now, &HealthThresholds::default());
+        html.assert_contains_snippets(&["<td class=\"num\">2.0 KiB</td><td></td>"]);
+        map.with_history(history)
+            .generate_status_html(now, &HealthThresholds::default())
+            .assert_contains_snippets(&["points=\"2.0,12.0 60.0,22.0 118.0,2.0\""]);
+    }
+
+    #[test]
+    fn size_and_escaping() {
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+        assert_eq!(format_size(u64::MAX), "16.0 EiB");
+        assert_eq!(
+            Html(r#"<a href="x">'&'</a>"#).to_string(),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+}
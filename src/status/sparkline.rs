@@ -0,0 +1,80 @@
+use std::fmt::Write as _;
+
+const WIDTH: f64 = 120.0;
+const HEIGHT: f64 = 24.0;
+/// Margin keeping the stroke of extreme points within the image
+const PADDING: f64 = 2.0;
+
+/// Renders an inline SVG line chart of the `(time, value)` points, in time order
+///
+/// Returns `None` for fewer than two points. Points at the same time are spread evenly, and
+/// constant values are drawn at mid-height.
+pub(super) fn render(points: &[(i64, u64)]) -> Option<String> {
+    if points.len() < 2 {
+        return None;
+    }
+    let mut points = points.to_vec();
+    points.sort_unstable();
+
+    #[expect(clippy::cast_precision_loss)] // drawing only
+    let scaled: Vec<(f64, f64)> = {
+        let (first_time, last_time) = (points[0].0, points[points.len() - 1].0);
+        let min_value = points.iter().map(|&(_, value)| value).min()?;
+        let max_value = points.iter().map(|&(_, value)| value).max()?;
+        let last_index = (points.len() - 1) as f64;
+        points
+            .iter()
+            .enumerate()
+            .map(|(index, &(time, value))| {
+                let x = if first_time == last_time {
+                    index as f64 / last_index
+                } else {
+                    (time - first_time) as f64 / (last_time - first_time) as f64
+                };
+                let y = if min_value == max_value {
+                    0.5
+                } else {
+                    (value - min_value) as f64 / (max_value - min_value) as f64
+                };
+                (x, y)
+            })
+            .collect()
+    };
+
+    let mut polyline = String::new();
+    for (x, y) in scaled {
+        let x = PADDING + x * (WIDTH - 2.0 * PADDING);
+        let y = HEIGHT - PADDING - y * (HEIGHT - 2.0 * PADDING);
+        if !polyline.is_empty() {
+            polyline.push(' ');
+        }
+        write!(polyline, "{x:.1},{y:.1}").expect("infallible");
+    }
+    Some(format!(
+        "<svg class=\"sparkline\" width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+         viewBox=\"0 0 {WIDTH} {HEIGHT}\"><polyline fill=\"none\" stroke=\"currentColor\" \
+         points=\"{polyline}\"/></svg>"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn sparkline() {
+        assert_eq!(render(&[]), None);
+        assert_eq!(render(&[(0, 10)]), None);
+
+        let svg = render(&[(200, 30), (0, 10), (100, 10)]).expect("multiple points");
+        assert_eq!(
+            svg,
+            "<svg class=\"sparkline\" width=\"120\" height=\"24\" viewBox=\"0 0 120 24\">\
+             <polyline fill=\"none\" stroke=\"currentColor\" \
+             points=\"2.0,22.0 60.0,22.0 118.0,2.0\"/></svg>"
+        );
+
+        let svg = render(&[(0, 10), (0, 10)]).expect("multiple points");
+        assert!(svg.contains("points=\"2.0,12.0 118.0,12.0\""), "{svg}");
+    }
+}
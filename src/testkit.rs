@@ -0,0 +1,305 @@
+//! Black-box test harness running the `kopia-exporter` binary, for smoke tests of a build
+//!
+//! Starts the exporter as a child process on a free local port, with a (typically fake)
+//! `kopia` binary, then queries it over HTTP:
+//!
+//! ```no_run
+//! use kopia_exporter::testkit::{ServerConfig, TestServer};
+//!
+//! # fn main() -> eyre::Result<()> {
+//! let config = ServerConfig::new("/usr/bin/kopia-exporter", "/usr/bin/fake-kopia")?
+//!     .with_args(["--cache-seconds", "0"]);
+//! let server = TestServer::start(config)?;
+//! let response = server.get("/metrics")?;
+//! assert_eq!(response.status_code, 200);
+//! assert!(response.as_str()?.contains("kopia_snapshot_size_bytes_total"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`PushReceiver`] captures the requests of the push outputs (e.g. `--webhook-url`).
+
+use eyre::Result;
+use std::ffi::OsStr;
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Configuration for starting a [`TestServer`]
+pub struct ServerConfig {
+    command: Command,
+    bind_address: String,
+    capture_stderr: bool,
+}
+
+impl ServerConfig {
+    /// Creates a configuration running the `exporter_bin` with the `kopia_bin`, on a free
+    /// local port
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no local port is available
+    pub fn new(exporter_bin: impl AsRef<OsStr>, kopia_bin: impl AsRef<OsStr>) -> Result<Self> {
+        let bind_address = free_bind_address()?;
+
+        let mut command = Command::new(exporter_bin);
+        command
+            .arg("--kopia-bin")
+            .arg(kopia_bin)
+            .args(["--bind", &bind_address])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        Ok(Self {
+            command,
+            bind_address,
+            capture_stderr: false,
+        })
+    }
+
+    /// Captures the standard error output, see [`TestServer::kill_and_read_stderr`]
+    #[must_use]
+    pub fn with_stderr_capture(mut self) -> Self {
+        self.capture_stderr = true;
+        self
+    }
+
+    /// Adds command line arguments
+    #[must_use]
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.command.args(args);
+        self
+    }
+
+    /// Sets an environment variable, inherited by the `kopia` process
+    #[must_use]
+    pub fn with_env<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.command.env(key, val);
+        self
+    }
+}
+
+/// Running `kopia-exporter` process, killed when dropped
+pub struct TestServer {
+    process: Option<Child>,
+    bind_address: String,
+}
+
+impl TestServer {
+    /// Starts the server, waiting briefly for it to bind the port
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the process fails to start
+    pub fn start(mut config: ServerConfig) -> Result<Self> {
+        if config.capture_stderr {
+            config.command.stderr(Stdio::piped());
+        }
+
+        let process = config.command.spawn()?;
+        let bind_address = config.bind_address;
+
+        // Wait for server to start
+        thread::sleep(Duration::from_millis(500));
+
+        Ok(Self {
+            process: Some(process),
+            bind_address,
+        })
+    }
+
+    /// Returns the address the server is bound to, e.g. `127.0.0.1:1234`
+    #[must_use]
+    pub fn bind_address(&self) -> &str {
+        &self.bind_address
+    }
+
+    /// Returns the process ID of the server
+    #[must_use]
+    pub fn id(&self) -> u32 {
+        self.process.as_ref().map_or(0, Child::id)
+    }
+
+    /// Kills the server and returns its standard error output
+    ///
+    /// Returns an empty string if stderr was not captured (see
+    /// [`ServerConfig::with_stderr_capture`]).
+    #[must_use]
+    pub fn kill_and_read_stderr(mut self) -> String {
+        let Some(mut process) = self.process.take() else {
+            return String::new();
+        };
+        let _ = process.kill();
+        process
+            .wait_with_output()
+            .map(|output| String::from_utf8_lossy(&output.stderr).into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Sends `SIGTERM` to the server (using `kill`) and waits for it to exit
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the signal or waiting for the process fails
+    pub fn terminate(mut self) -> Result<std::process::ExitStatus> {
+        let status = Command::new("kill")
+            .args(["-TERM", &self.id().to_string()])
+            .status()?;
+        eyre::ensure!(status.success(), "kill failed: {status}");
+        let Some(mut process) = self.process.take() else {
+            eyre::bail!("process already exited");
+        };
+        Ok(process.wait()?)
+    }
+
+    /// Waits for the server to exit on its own (e.g. after a request to `/-/quit`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if waiting for the process fails
+    pub fn wait(mut self) -> Result<std::process::ExitStatus> {
+        let Some(mut process) = self.process.take() else {
+            eyre::bail!("process already exited");
+        };
+        Ok(process.wait()?)
+    }
+
+    /// Makes an HTTP GET request to the server
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails
+    pub fn get(&self, path: &str) -> Result<minreq::Response> {
+        let url = format!("http://{}{}", self.bind_address, path);
+        Ok(minreq::get(&url).send()?)
+    }
+
+    /// Makes an HTTP GET request to the server, with the `Authorization` header
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails
+    pub fn get_with_auth(&self, path: &str, auth_header: &str) -> Result<minreq::Response> {
+        let url = format!("http://{}{}", self.bind_address, path);
+        Ok(minreq::get(&url)
+            .with_header("Authorization", auth_header)
+            .send()?)
+    }
+
+    /// Makes an HTTP POST request to the server with an Authorization header
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails
+    pub fn post_with_auth(&self, path: &str, auth_header: &str) -> Result<minreq::Response> {
+        let url = format!("http://{}{}", self.bind_address, path);
+        Ok(minreq::post(&url)
+            .with_header("Authorization", auth_header)
+            .send()?)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(ref mut process) = self.process {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+}
+
+/// HTTP request captured by a [`PushReceiver`]
+#[derive(Debug)]
+pub struct ReceivedRequest {
+    /// Request line and headers
+    pub head: String,
+    /// Request body
+    pub body: String,
+}
+
+/// Local HTTP server capturing requests pushed by the exporter
+pub struct PushReceiver {
+    address: String,
+    requests: std::sync::mpsc::Receiver<ReceivedRequest>,
+}
+
+impl PushReceiver {
+    /// Starts listening on a free local port, responding `204 No Content` to all requests
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no local port is available
+    pub fn start() -> Result<Self> {
+        use std::io::{BufRead as _, BufReader, Read as _, Write as _};
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?.to_string();
+        let (sender, requests) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let mut reader = BufReader::new(&stream);
+                let mut head = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        content_length = value.trim().parse().unwrap_or(0);
+                    }
+                    head.push_str(&line);
+                }
+                let mut body = vec![0; content_length];
+                let _ = reader.read_exact(&mut body);
+                let _ = (&stream).write_all(b"HTTP/1.1 204 No Content\r\n\r\n");
+                let request = ReceivedRequest {
+                    head,
+                    body: String::from_utf8_lossy(&body).to_string(),
+                };
+                if sender.send(request).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { address, requests })
+    }
+
+    /// Returns the base URL of the receiver, e.g. `http://127.0.0.1:1234`
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Waits for the next pushed request
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no request is received within the `timeout`
+    pub fn recv(&self, timeout: Duration) -> Result<ReceivedRequest> {
+        Ok(self.requests.recv_timeout(timeout)?)
+    }
+}
+
+/// Returns a free local address (e.g. `127.0.0.1:1234`), assigned by the OS
+///
+/// # Errors
+///
+/// Returns an error if no local port is available
+pub fn free_bind_address() -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    Ok(format!("127.0.0.1:{port}"))
+}
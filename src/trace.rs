@@ -0,0 +1,382 @@
+//! Tracing spans of the fetch and render pipeline, encoded as OTLP/HTTP JSON
+//!
+//! A minimal span recorder (no `tracing` or `opentelemetry` dependencies), to see where the
+//! time of a slow scrape goes: running `kopia`, parsing its output, or rendering the
+//! response. Spans are only recorded once a sink is [installed](set_sink), e.g. exporting
+//! batches with [`encode_otlp_json`] to an OpenTelemetry collector.
+//!
+//! Each thread tracks its current span, so a [`Span`] entered while another is active
+//! becomes its child. Work moved to another thread passes the [`SpanContext`] explicitly,
+//! see [`Span::enter_with_parent`].
+
+use std::cell::Cell;
+use std::hash::{BuildHasher as _, Hasher as _, RandomState};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Receives each finished span
+type Sink = Box<dyn Fn(SpanData) + Send + Sync>;
+
+static SINK: OnceLock<Sink> = OnceLock::new();
+
+thread_local! {
+    static CURRENT: Cell<Option<SpanContext>> = const { Cell::new(None) };
+}
+
+/// Installs the function receiving each finished span, for the lifetime of the process
+///
+/// # Errors
+///
+/// Returns an error if a sink is already installed
+pub fn set_sink(sink: impl Fn(SpanData) + Send + Sync + 'static) -> Result<(), SinkInstalled> {
+    SINK.set(Box::new(sink)).map_err(|_| SinkInstalled)
+}
+
+/// Error from [`set_sink`] when a sink is already installed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SinkInstalled;
+impl std::fmt::Display for SinkInstalled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "trace sink already installed")
+    }
+}
+impl std::error::Error for SinkInstalled {}
+
+/// Returns the span currently active on this thread, if any
+#[must_use]
+pub fn current() -> Option<SpanContext> {
+    CURRENT.get()
+}
+
+/// Identifies a span within its trace
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanContext {
+    /// Trace shared by all spans of the same root (non-zero)
+    pub trace_id: u128,
+    /// Span within the trace (non-zero)
+    pub span_id: u64,
+}
+impl SpanContext {
+    /// Formats as a W3C `traceparent` header (sampled), e.g. for passing the context to
+    /// a subprocess in the `TRACEPARENT` environment variable
+    #[must_use]
+    pub fn traceparent(self) -> String {
+        let Self { trace_id, span_id } = self;
+        format!("00-{trace_id:032x}-{span_id:016x}-01")
+    }
+}
+
+/// Value of a span attribute
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttributeValue {
+    /// Text value
+    String(String),
+    /// Integer value
+    Int(i64),
+}
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+impl From<u16> for AttributeValue {
+    fn from(value: u16) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+/// Finished span, passed to the [sink](set_sink)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpanData {
+    /// Identifiers of this span
+    pub context: SpanContext,
+    /// Span ID of the parent, absent for the root span of a trace
+    pub parent_span_id: Option<u64>,
+    /// Operation name, e.g. `fetch`
+    pub name: &'static str,
+    /// Start time, in nanoseconds since the Unix epoch
+    pub start_unix_nanos: u64,
+    /// End time, in nanoseconds since the Unix epoch
+    pub end_unix_nanos: u64,
+    /// Attributes, in the order they were set
+    pub attributes: Vec<(&'static str, AttributeValue)>,
+    /// Error message, if the operation failed
+    pub error: Option<String>,
+}
+
+/// Active span, recorded when dropped
+///
+/// Becomes the current span of the thread until dropped, so spans must be dropped in the
+/// reverse order they were entered (as with scoped guards).
+#[derive(Debug)]
+#[must_use = "the span ends when dropped"]
+pub struct Span {
+    data: SpanData,
+    /// Current span of the thread before entering this one
+    previous: Option<SpanContext>,
+}
+impl Span {
+    /// Enters a span, as a child of the thread's current span (or as the root of a new trace)
+    pub fn enter(name: &'static str) -> Self {
+        Self::enter_with_parent(name, current())
+    }
+
+    /// Enters a span with the specified parent, e.g. the [current] span of the
+    /// thread which started the work
+    pub fn enter_with_parent(name: &'static str, parent: Option<SpanContext>) -> Self {
+        let context = SpanContext {
+            trace_id: parent.map_or_else(
+                || u128::from(random_id()) << 64 | u128::from(random_id()),
+                |parent| parent.trace_id,
+            ),
+            span_id: random_id(),
+        };
+        let previous = CURRENT.replace(Some(context));
+        Self {
+            data: SpanData {
+                context,
+                parent_span_id: parent.map(|parent| parent.span_id),
+                name,
+                start_unix_nanos: unix_nanos_now(),
+                end_unix_nanos: 0,
+                attributes: vec![],
+                error: None,
+            },
+            previous,
+        }
+    }
+
+    /// Returns the identifiers of this span
+    #[must_use]
+    pub fn context(&self) -> SpanContext {
+        self.data.context
+    }
+
+    /// Sets an attribute, e.g. `http.route`
+    pub fn set_attribute(&mut self, key: &'static str, value: impl Into<AttributeValue>) {
+        self.data.attributes.push((key, value.into()));
+    }
+
+    /// Marks the span as failed, with the error message
+    pub fn set_error(&mut self, message: impl std::fmt::Display) {
+        self.data.error = Some(message.to_string());
+    }
+}
+impl Drop for Span {
+    fn drop(&mut self) {
+        CURRENT.set(self.previous);
+        if let Some(sink) = SINK.get() {
+            self.data.end_unix_nanos = unix_nanos_now();
+            sink(self.data.clone());
+        }
+    }
+}
+
+/// Returns a random non-zero ID
+fn random_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    // randomly keyed SipHash of a counter, unique without a `rand` dependency
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish().max(1)
+}
+
+fn unix_nanos_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX)
+        })
+}
+
+/// Encodes the spans as an OTLP/HTTP JSON `ExportTraceServiceRequest`, for `POST`ing to a
+/// collector's `/v1/traces` endpoint with `Content-Type: application/json`
+#[must_use]
+pub fn encode_otlp_json(spans: &[SpanData], service_name: &str, service_version: &str) -> String {
+    let spans: Vec<_> = spans
+        .iter()
+        .map(|span| {
+            let SpanData {
+                context: SpanContext { trace_id, span_id },
+                parent_span_id,
+                name,
+                start_unix_nanos,
+                end_unix_nanos,
+                attributes,
+                error,
+            } = span;
+            let attributes: Vec<_> = attributes
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        AttributeValue::String(value) => {
+                            serde_json::json!({ "stringValue": value })
+                        }
+                        // 64-bit integers are strings in the protobuf JSON mapping
+                        AttributeValue::Int(value) => {
+                            serde_json::json!({ "intValue": value.to_string() })
+                        }
+                    };
+                    serde_json::json!({ "key": key, "value": value })
+                })
+                .collect();
+            // STATUS_CODE_ERROR, otherwise unset
+            let status = error.as_ref().map_or_else(
+                || serde_json::json!({}),
+                |message| serde_json::json!({ "code": 2, "message": message }),
+            );
+            serde_json::json!({
+                "traceId": format!("{trace_id:032x}"),
+                "spanId": format!("{span_id:016x}"),
+                "parentSpanId": parent_span_id.map_or_else(String::new, |id| format!("{id:016x}")),
+                "name": name,
+                // SPAN_KIND_INTERNAL
+                "kind": 1,
+                "startTimeUnixNano": start_unix_nanos.to_string(),
+                "endTimeUnixNano": end_unix_nanos.to_string(),
+                "attributes": attributes,
+                "status": status,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": service_name } },
+                    { "key": "service.version", "value": { "stringValue": service_version } },
+                ],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "kopia_exporter::trace" },
+                "spans": spans,
+            }],
+        }],
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttributeValue, Span, SpanContext, SpanData, current, encode_otlp_json};
+
+    #[test]
+    fn nesting() {
+        assert_eq!(current(), None);
+        let outer = Span::enter("outer");
+        assert_eq!(current(), Some(outer.context()));
+        let inner = Span::enter("inner");
+        assert_eq!(inner.context().trace_id, outer.context().trace_id);
+        assert_eq!(inner.data.parent_span_id, Some(outer.context().span_id));
+        assert_ne!(inner.context().span_id, outer.context().span_id);
+
+        let parent = current();
+        let thread_span = std::thread::spawn(move || {
+            assert_eq!(current(), None);
+            let span = Span::enter_with_parent("thread", parent);
+            (span.context().trace_id, span.data.parent_span_id)
+        })
+        .join()
+        .expect("thread should not panic");
+        assert_eq!(
+            thread_span,
+            (outer.context().trace_id, Some(inner.context().span_id))
+        );
+
+        drop(inner);
+        assert_eq!(current(), Some(outer.context()));
+        drop(outer);
+        assert_eq!(current(), None);
+
+        let other = Span::enter("other");
+        assert_eq!(other.data.parent_span_id, None);
+        assert_ne!(other.context().trace_id, parent.map_or(0, |p| p.trace_id));
+    }
+
+    #[test]
+    fn traceparent() {
+        let context = SpanContext {
+            trace_id: 0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736,
+            span_id: 0x00f0_67aa_0ba9_02b7,
+        };
+        assert_eq!(
+            context.traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+    }
+
+    #[test]
+    fn otlp_json() {
+        let spans = [
+            SpanData {
+                context: SpanContext {
+                    trace_id: 1,
+                    span_id: 2,
+                },
+                parent_span_id: None,
+                name: "respond",
+                start_unix_nanos: 1_000,
+                end_unix_nanos: 3_000,
+                attributes: vec![
+                    ("http.route", AttributeValue::from("/metrics")),
+                    ("http.response.status_code", AttributeValue::from(200_u16)),
+                ],
+                error: None,
+            },
+            SpanData {
+                context: SpanContext {
+                    trace_id: 1,
+                    span_id: 3,
+                },
+                parent_span_id: Some(2),
+                name: "fetch",
+                start_unix_nanos: 1_500,
+                end_unix_nanos: 2_500,
+                attributes: vec![],
+                error: Some("timed out".to_owned()),
+            },
+        ];
+        let json: serde_json::Value =
+            serde_json::from_str(&encode_otlp_json(&spans, "kopia-exporter", "1.2.3"))
+                .expect("valid JSON");
+        let resource_spans = &json["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0]["value"]["stringValue"],
+            "kopia-exporter"
+        );
+        let encoded = &resource_spans["scopeSpans"][0]["spans"];
+        assert_eq!(
+            encoded[0],
+            serde_json::json!({
+                "traceId": "00000000000000000000000000000001",
+                "spanId": "0000000000000002",
+                "parentSpanId": "",
+                "name": "respond",
+                "kind": 1,
+                "startTimeUnixNano": "1000",
+                "endTimeUnixNano": "3000",
+                "attributes": [
+                    { "key": "http.route", "value": { "stringValue": "/metrics" } },
+                    { "key": "http.response.status_code", "value": { "intValue": "200" } },
+                ],
+                "status": {},
+            })
+        );
+        assert_eq!(encoded[1]["parentSpanId"], "0000000000000002");
+        assert_eq!(
+            encoded[1]["status"],
+            serde_json::json!({ "code": 2, "message": "timed out" })
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Data-quality findings for a snapshot listing, e.g. a saved `kopia snapshot list --json`
+//! output
+//!
+//! Explains unexpected metrics without access to the repository: snapshots skipped for an
+//! invalid source or malformed JSON, timestamps which failed to parse, incomplete snapshots
+//! and duplicate snapshot IDs.
+
+use crate::{InvalidSourceReport, KopiaSnapshots, MalformedSnapshots, SourceStr, SourceStrError};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Data-quality findings for all snapshots, with a summary of each source
+#[derive(Debug)]
+pub struct ValidationReport {
+    sources: Vec<SourceSummary>,
+    invalid_sources: InvalidSourceReport,
+    malformed: MalformedSnapshots,
+}
+
+/// Summary of the snapshots of a single source
+#[derive(Debug)]
+pub struct SourceSummary {
+    /// Source of the snapshots
+    pub source: SourceStr,
+    /// Number of snapshots
+    pub snapshot_count: usize,
+    /// Earliest valid end time
+    pub oldest_end_time: Option<jiff::Timestamp>,
+    /// Latest valid end time
+    pub newest_end_time: Option<jiff::Timestamp>,
+    /// Data-quality findings, in the order of the snapshots
+    pub findings: Vec<Finding>,
+}
+
+/// Data-quality problem of a snapshot
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// Timestamp which failed to parse
+    BadTimestamp {
+        /// Snapshot ID
+        id: String,
+        /// Name of the timestamp field (`startTime` or `endTime`)
+        field: &'static str,
+        /// Timestamp as reported by `kopia`
+        value: String,
+    },
+    /// Snapshot which did not complete
+    Incomplete {
+        /// Snapshot ID
+        id: String,
+        /// Reason reported by `kopia` (e.g. `"checkpoint"`)
+        reason: String,
+    },
+    /// Snapshot ID listed more than once
+    DuplicateId {
+        /// Snapshot ID
+        id: String,
+        /// Number of snapshots with the ID
+        count: usize,
+    },
+}
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadTimestamp { id, field, value } => {
+                write!(f, "snapshot {id}: invalid {field} {value:?}")
+            }
+            Self::Incomplete { id, reason } => write!(f, "snapshot {id}: incomplete ({reason})"),
+            Self::DuplicateId { id, count } => write!(f, "snapshot {id}: listed {count} times"),
+        }
+    }
+}
+
+impl ValidationReport {
+    /// Collects the findings for the snapshots, parsed with the `invalid_sources` reported
+    /// by one of the `_with_report` constructors (e.g.
+    /// [`KopiaSnapshots::new_from_path_with_report`])
+    #[must_use]
+    pub fn new(snapshots: &KopiaSnapshots, invalid_sources: InvalidSourceReport) -> Self {
+        let sources = snapshots
+            .sources()
+            .map(|source| {
+                let source_snapshots = snapshots.snapshots_for(source).unwrap_or_default();
+                let mut findings = vec![];
+                // duplicates were skipped while parsing, only the first is listed
+                let mut duplicate_counts = BTreeMap::<&str, usize>::new();
+                for snapshot in source_snapshots {
+                    let id = &snapshot.id;
+                    if let Some(&skipped) = snapshots.seen_ids.duplicates().get(id) {
+                        let count = usize::try_from(skipped).unwrap_or(usize::MAX);
+                        duplicate_counts.insert(id, count.saturating_add(1));
+                    }
+                    let times = [
+                        (
+                            "startTime",
+                            snapshot.start_time,
+                            &snapshot.raw_times.start_time,
+                        ),
+                        ("endTime", snapshot.end_time, &snapshot.raw_times.end_time),
+                    ];
+                    for (field, parsed, value) in times {
+                        if parsed.is_none() {
+                            findings.push(Finding::BadTimestamp {
+                                id: id.clone(),
+                                field,
+                                value: value.clone(),
+                            });
+                        }
+                    }
+                    if let Some(reason) = &snapshot.incomplete {
+                        findings.push(Finding::Incomplete {
+                            id: id.clone(),
+                            reason: reason.clone(),
+                        });
+                    }
+                }
+                findings.extend(duplicate_counts.into_iter().map(|(id, count)| {
+                    Finding::DuplicateId {
+                        id: id.to_owned(),
+                        count,
+                    }
+                }));
+                let end_times = source_snapshots.iter().filter_map(|s| s.end_time);
+                SourceSummary {
+                    source: source.clone(),
+                    snapshot_count: source_snapshots.len(),
+                    oldest_end_time: end_times.clone().min(),
+                    newest_end_time: end_times.max(),
+                    findings,
+                }
+            })
+            .collect();
+        Self {
+            sources,
+            invalid_sources,
+            malformed: snapshots.malformed_snapshots().clone(),
+        }
+    }
+
+    /// Returns the summary of each source
+    #[must_use]
+    pub fn sources(&self) -> &[SourceSummary] {
+        &self.sources
+    }
+
+    /// Returns the errors of the snapshots skipped for an invalid source
+    #[must_use]
+    pub fn invalid_sources(&self) -> &[SourceStrError] {
+        self.invalid_sources.errors()
+    }
+
+    /// Returns the snapshots skipped for malformed JSON
+    #[must_use]
+    pub fn malformed_snapshots(&self) -> &MalformedSnapshots {
+        &self.malformed
+    }
+
+    /// Returns the total number of findings, including snapshots with an invalid source or
+    /// malformed JSON
+    #[must_use]
+    pub fn finding_count(&self) -> usize {
+        let Self {
+            sources,
+            invalid_sources,
+            malformed,
+        } = self;
+        let source_findings: usize = sources.iter().map(|source| source.findings.len()).sum();
+        let malformed_count = usize::try_from(malformed.count()).unwrap_or(usize::MAX);
+        (source_findings + invalid_sources.errors().len()).saturating_add(malformed_count)
+    }
+}
+
+/// Human-readable report, listing each source followed by its findings
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            sources,
+            invalid_sources,
+            malformed,
+        } = self;
+        for summary in sources {
+            let SourceSummary {
+                source,
+                snapshot_count,
+                oldest_end_time,
+                newest_end_time,
+                findings,
+            } = summary;
+            write!(f, "{}: {snapshot_count} snapshots", source.as_str())?;
+            if let (Some(oldest), Some(newest)) = (oldest_end_time, newest_end_time) {
+                write!(f, ", ending {oldest} to {newest}")?;
+            }
+            writeln!(f)?;
+            for finding in findings {
+                writeln!(f, "  {finding}")?;
+            }
+        }
+        for error in invalid_sources.errors() {
+            writeln!(f, "skipped snapshot: {error}")?;
+        }
+        if let Some(first_error) = malformed.first_error() {
+            let count = malformed.count();
+            writeln!(
+                f,
+                "skipped {count} malformed snapshots, first: {first_error}"
+            )?;
+        }
+        let finding_count = self.finding_count();
+        write!(f, "{} sources, {finding_count} findings", sources.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Finding, ValidationReport};
+    use crate::{AssertContains as _, KopiaSnapshots, test_util::test_snapshot};
+
+    #[test]
+    fn reports_findings() {
+        let mut bad_time = test_snapshot("2", 1000, &[]);
+        bad_time.end_time = "not a time".to_string();
+        let mut incomplete = test_snapshot("3", 1000, &[]);
+        incomplete.incomplete = Some("checkpoint".to_string());
+        let mut invalid = test_snapshot("4", 1000, &[]);
+        invalid.source.user_name = "bad@user".to_string();
+        let (snapshots, invalid_sources) = KopiaSnapshots::new_from_snapshots_with_report(vec![
+            test_snapshot("1", 1000, &["latest-1"]),
+            bad_time,
+            incomplete,
+            test_snapshot("1", 1000, &[]),
+            invalid,
+        ]);
+
+        let report = ValidationReport::new(&snapshots, invalid_sources);
+        assert_eq!(report.sources().len(), 1);
+        let summary = &report.sources()[0];
+        assert_eq!(summary.snapshot_count, 3);
+        assert_eq!(
+            summary.findings,
+            [
+                Finding::BadTimestamp {
+                    id: "2".to_string(),
+                    field: "endTime",
+                    value: "not a time".to_string(),
+                },
+                Finding::Incomplete {
+                    id: "3".to_string(),
+                    reason: "checkpoint".to_string(),
+                },
+                Finding::DuplicateId {
+                    id: "1".to_string(),
+                    count: 2,
+                },
+            ]
+        );
+        assert_eq!(report.invalid_sources().len(), 1);
+        assert_eq!(report.finding_count(), 4);
+
+        report.to_string().assert_contains_lines(&[
+            "user_name@host:/path: 3 snapshots, ending 2025-08-14T00:01:00Z to 2025-08-14T00:01:00Z",
+            r#"  snapshot 2: invalid endTime "not a time""#,
+            "  snapshot 3: incomplete (checkpoint)",
+            "  snapshot 1: listed 2 times",
+            "1 sources, 4 findings",
+        ]);
+    }
+
+    #[test]
+    fn reports_malformed() {
+        let json = serde_json::to_string(&serde_json::json!([
+            test_snapshot("1", 1000, &["latest-1"]),
+            {"id": "2"},
+        ]))
+        .expect("serializable");
+        let (snapshots, invalid_sources) =
+            KopiaSnapshots::new_from_reader_with_report(json.as_bytes()).expect("valid array");
+
+        let report = ValidationReport::new(&snapshots, invalid_sources);
+        assert_eq!(report.malformed_snapshots().count(), 1);
+        assert_eq!(report.finding_count(), 1);
+        report.to_string().assert_contains_lines(&[
+            "skipped 1 malformed snapshots, first: snapshot 1: missing field `source` at line 1 column 10",
+            "1 sources, 1 findings",
+        ]);
+    }
+}
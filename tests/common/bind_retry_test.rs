@@ -43,7 +43,7 @@ fn test_bind_retry_with_occupied_port() {
         .expect("Failed to wait for process");
 
     // Should fail immediately with no retries
-    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(75), "{output:?}");
     let stderr = String::from_utf8(output.stderr).unwrap();
     assert!(stderr.contains("Failed to bind to"));
 
@@ -101,3 +101,56 @@ fn test_bind_retry_success_after_port_freed() {
         Err(e) => panic!("Error checking process status: {e}"),
     }
 }
+
+#[test]
+fn test_invalid_bind_address_fails_early() {
+    for (bind, expected) in [
+        ("127.0.0.1:99999", "invalid port value"),
+        ("127.0.0.1", "expected HOST:PORT"),
+    ] {
+        let start = std::time::Instant::now();
+        let output = Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .args(["--bind", bind, "--max-bind-retries", "5"])
+            .output()
+            .expect("Failed to run command");
+
+        assert_eq!(output.status.code(), Some(78), "{output:?}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(expected), "{bind}: {stderr}");
+        assert!(!stderr.contains("Retrying"), "{bind}: {stderr}");
+        assert!(start.elapsed() < Duration::from_secs(1), "{bind}");
+    }
+}
+
+#[test]
+fn test_bind_address_not_local() {
+    // TEST-NET-1, not assigned to any local interface
+    let output = Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .args(["--bind", "192.0.2.1:9090", "--max-bind-retries", "0"])
+        .output()
+        .expect("Failed to run command");
+
+    assert_eq!(output.status.code(), Some(75), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("cannot assign the address"), "{stderr}");
+}
+
+#[test]
+fn test_bind_hostname() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .args(["--bind", &format!("localhost:{port}"), "--dry-run"])
+        .args(["--kopia-bin", env!("CARGO_BIN_EXE_fake-kopia")])
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!("Would serve metrics on localhost:{port} (")),
+        "{stdout}"
+    );
+}
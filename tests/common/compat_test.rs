@@ -0,0 +1,102 @@
+//! Compatibility with the shapes of `kopia snapshot list --json` outputs, see
+//! `tests/corpus/README.md`
+
+use eyre::Result;
+use kopia_exporter::validate::ValidationReport;
+use kopia_exporter::{KopiaSnapshots, SourceStr};
+use std::path::PathBuf;
+
+/// Fixture with only the base manifest fields
+const BASE_SHAPE: &str = "shape-base";
+
+fn corpus_files() -> Result<Vec<PathBuf>> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[test]
+fn test_corpus_parses() -> Result<()> {
+    let files = corpus_files()?;
+    assert!(
+        files
+            .iter()
+            .any(|path| path.file_stem().is_some_and(|stem| stem == BASE_SHAPE)),
+        "{files:?}"
+    );
+
+    for path in &files {
+        let name = path.display();
+        let raw: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        let (snapshots, invalid_sources) = KopiaSnapshots::new_from_path_with_report(path)?;
+        assert!(invalid_sources.is_empty(), "{name}");
+
+        let parsed_count: usize = snapshots
+            .sources()
+            .map(|source| snapshots.snapshots_for(source).map_or(0, <[_]>::len))
+            .sum();
+        assert_eq!(parsed_count, raw.len(), "{name}");
+
+        let report = ValidationReport::new(&snapshots, invalid_sources);
+        assert_eq!(report.finding_count(), 0, "{name}:\n{report}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_corpus_renders_sane_metrics() -> Result<()> {
+    let now: jiff::Timestamp = "2025-06-16T00:00:00Z".parse()?;
+    let home = SourceStr::new_unchecked("alice@nas:/home/alice".to_string());
+
+    for path in corpus_files()? {
+        let name = path.display();
+        let (snapshots, _) = KopiaSnapshots::new_from_path_with_report(&path)?;
+
+        let metrics = snapshots.generate_all_metrics(now);
+        for line in metrics.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let value = line.rsplit_once(' ').map(|(_, value)| value.parse::<f64>());
+            assert!(
+                matches!(value, Some(Ok(value)) if value.is_finite() && value >= 0.0),
+                "{name}: invalid sample {line:?}"
+            );
+        }
+
+        // captures of real listings have their own sources, see the README
+        let is_shape = path
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy().starts_with("shape-"));
+        if !is_shape {
+            continue;
+        }
+        let latest = snapshots
+            .snapshots_for(&home)
+            .and_then(<[_]>::last)
+            .expect("home source in every shape fixture");
+        assert_eq!(latest.stats.total_size, 10_004_938_268, "{name}");
+        assert_eq!(latest.stats.error_count, 0, "{name}");
+        for expected in [
+            r#"kopia_snapshots_total{source="alice@nas:/home/alice"} 5"#,
+            r#"kopia_snapshots_total{source="root@nas:/etc"} 3"#,
+            r#"kopia_snapshot_size_bytes_total{source="alice@nas:/home/alice"} 10004938268"#,
+            r#"kopia_snapshots_by_retention{source="alice@nas:/home/alice",retention_reason="latest-1"} 1"#,
+            r#"kopia_snapshot_errors_total{source="alice@nas:/home/alice"} 0"#,
+        ] {
+            assert!(
+                metrics.lines().any(|line| line == expected),
+                "{name}: expected line {expected:?} in:\n{metrics}"
+            );
+        }
+    }
+    Ok(())
+}
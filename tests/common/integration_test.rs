@@ -2,8 +2,8 @@
 
 #![expect(clippy::unwrap_used)] // tests can unwrap
 
-use crate::FAKE_KOPIA_BIN;
-use crate::test_helpers::{ServerConfig, TestServer, assertions, get_test_log_path};
+use crate::test_helpers::{PushReceiver, ServerConfig, TestServer, assertions, get_test_log_path};
+use crate::{FAKE_KOPIA_BIN, KOPIA_EXPORTER_BIN};
 use eyre::Result;
 use kopia_exporter::{KopiaSnapshots, SourceStr};
 use std::fs;
@@ -16,8 +16,9 @@ fn test_subprocess_with_fake_kopia() {
 
     let source = SourceStr::new_unchecked("kopia-system@milton:/persist-home".to_string());
 
-    let snapshots =
-        KopiaSnapshots::new_from_command(FAKE_KOPIA_BIN, timeout, |e| eyre::bail!(e)).unwrap();
+    let (snapshots, invalid_sources) =
+        KopiaSnapshots::new_from_command_with_report(FAKE_KOPIA_BIN, timeout).unwrap();
+    assert!(invalid_sources.is_empty());
 
     let retention_counts = snapshots
         .get_retention_counts()
@@ -37,9 +38,109 @@ fn test_subprocess_with_fake_kopia() {
     }
 }
 
+#[test]
+fn test_new_from_path() -> Result<()> {
+    let output = std::process::Command::new(FAKE_KOPIA_BIN)
+        .args(["snapshot", "list", "--json"])
+        .output()?;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("snapshots.json");
+    fs::write(&path, output.stdout)?;
+
+    let (snapshots, invalid_sources) = KopiaSnapshots::new_from_path_with_report(&path)?;
+    assert!(invalid_sources.is_empty());
+    let source = SourceStr::new_unchecked("kopia-system@milton:/persist-home".to_string());
+    let snapshots = snapshots
+        .into_inner_map()
+        .into_expect_only(&source)
+        .unwrap();
+    assert_eq!(snapshots.len(), 17);
+
+    let (snapshots, invalid_sources) = KopiaSnapshots::new_from_path_with_options(
+        &path,
+        kopia_exporter::ParseOptions::aggregate_only(kopia_exporter::LatestSnapshotPolicy::Newest),
+    )?;
+    assert!(invalid_sources.is_empty());
+    // only the oldest, previous and latest snapshots are kept
+    assert_eq!(snapshots.snapshots_for(&source).map(<[_]>::len), Some(3));
+
+    let missing = dir.path().join("missing.json");
+    let error = KopiaSnapshots::new_from_path_with_report(&missing).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("missing.json"), "{message}");
+    assert!(
+        matches!(&error, kopia_exporter::Error::File { source, .. }
+            if matches!(**source, kopia_exporter::Error::Io(_))),
+        "{error:?}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_kopia_bin_error() {
+    let timeout = Duration::from_secs(15);
+    let error =
+        KopiaSnapshots::new_from_command_with_report("/nonexistent/kopia", timeout).unwrap_err();
+    assert!(matches!(error, kopia_exporter::Error::Io(_)), "{error:?}");
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_new_from_command_async() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let source = SourceStr::new_unchecked("kopia-system@milton:/persist-home".to_string());
+
+    let (snapshots, invalid_sources) = runtime.block_on(KopiaSnapshots::new_from_command_async(
+        FAKE_KOPIA_BIN,
+        Duration::from_secs(15),
+    ))?;
+    assert!(invalid_sources.is_empty());
+    assert_eq!(snapshots.snapshots_for(&source).map(<[_]>::len), Some(17));
+
+    let options =
+        kopia_exporter::ParseOptions::aggregate_only(kopia_exporter::LatestSnapshotPolicy::Newest);
+    let (snapshots, invalid_sources) =
+        runtime.block_on(KopiaSnapshots::new_from_command_with_options_async(
+            FAKE_KOPIA_BIN,
+            Duration::from_secs(15),
+            options,
+        ))?;
+    assert!(invalid_sources.is_empty());
+    // only the oldest, previous and latest snapshots are kept
+    assert_eq!(snapshots.snapshots_for(&source).map(<[_]>::len), Some(3));
+
+    let dir = tempfile::tempdir()?;
+    let slow_kopia = dir.path().join("slow-kopia");
+    fs::write(&slow_kopia, "#!/bin/sh\necho starting >&2\nsleep 10\n")?;
+    fs::set_permissions(&slow_kopia, fs::Permissions::from_mode(0o755))?;
+    let timeout = Duration::from_millis(500);
+    let error = runtime
+        .block_on(KopiaSnapshots::new_from_command_async(
+            slow_kopia.to_string_lossy().into_owned(),
+            timeout,
+        ))
+        .unwrap_err();
+    let kopia_exporter::Error::Timeout {
+        timeout: error_timeout,
+        stderr,
+    } = error
+    else {
+        eyre::bail!("expected timeout, found {error:?}");
+    };
+    assert_eq!(error_timeout, timeout);
+    assert_eq!(stderr.as_deref(), Some("starting\n"));
+
+    Ok(())
+}
+
 #[test]
 fn test_web_server_integration() -> Result<()> {
-    let config = ServerConfig::new(FAKE_KOPIA_BIN)?;
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?;
     let server = TestServer::start(config)?;
 
     // Test the root endpoint
@@ -52,6 +153,45 @@ fn test_web_server_integration() -> Result<()> {
     assert_eq!(metrics_response.status_code, 200);
     assertions::assert_prometheus_metrics(metrics_response.as_str()?);
 
+    // Test the JSON metrics endpoint
+    let json_response = server.get("/metrics.json")?;
+    assert_eq!(json_response.status_code, 200);
+    let json: serde_json::Value = serde_json::from_str(json_response.as_str()?)?;
+    let samples = json.as_array().expect("metrics JSON is an array");
+    assert!(
+        samples
+            .iter()
+            .any(|sample| sample["name"] == "kopia_snapshots_total"),
+        "missing kopia_snapshots_total in {json}"
+    );
+
+    // Test the Influx line protocol endpoint
+    let influx_response = server.get("/metrics.influx")?;
+    assert_eq!(influx_response.status_code, 200);
+    assert!(
+        influx_response
+            .as_str()?
+            .contains("kopia_snapshots_total,source=kopia-system@milton:/persist-home value="),
+        "{}",
+        influx_response.as_str()?
+    );
+
+    // Test the snapshots NDJSON endpoint
+    let ndjson_response = server.get("/snapshots.ndjson")?;
+    assert_eq!(ndjson_response.status_code, 200);
+    let ndjson = ndjson_response.as_str()?;
+    assert!(!ndjson.is_empty());
+    for line in ndjson.lines() {
+        let snapshot: serde_json::Value = serde_json::from_str(line)?;
+        assert!(snapshot["id"].is_string(), "{line}");
+    }
+
+    // Test the health endpoint (no thresholds configured)
+    let health_response = server.get("/health")?;
+    assert_eq!(health_response.status_code, 200);
+    let health: serde_json::Value = serde_json::from_str(health_response.as_str()?)?;
+    assert_eq!(health["healthy"], true);
+
     // Test 404 endpoint
     let not_found_response = server.get("/nonexistent")?;
     assert_eq!(not_found_response.status_code, 404);
@@ -63,7 +203,7 @@ fn test_web_server_integration() -> Result<()> {
 fn test_caching_reduces_subprocess_calls() -> Result<()> {
     // Test with caching enabled (1 second cache for quick testing)
     let (_tempdir, log_file_cached) = get_test_log_path("cache");
-    let cached_config = ServerConfig::new(FAKE_KOPIA_BIN)?
+    let cached_config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
         .with_args(["--cache-seconds", "1"])
         .with_env("FAKE_KOPIA_LOG", &log_file_cached);
     let cached_server = TestServer::start(cached_config)?;
@@ -81,7 +221,7 @@ fn test_caching_reduces_subprocess_calls() -> Result<()> {
 
     // Test with caching disabled
     let (_tempdir, log_file_no_cache) = get_test_log_path("no-cache");
-    let no_cache_config = ServerConfig::new(FAKE_KOPIA_BIN)?
+    let no_cache_config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
         .with_args(["--cache-seconds", "0"])
         .with_env("FAKE_KOPIA_LOG", &log_file_no_cache);
     let no_cache_server = TestServer::start(no_cache_config)?;
@@ -117,7 +257,7 @@ fn test_caching_reduces_subprocess_calls() -> Result<()> {
 
 #[test]
 fn test_basic_auth_integration() -> Result<()> {
-    let config = ServerConfig::new(FAKE_KOPIA_BIN)?.with_args([
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
         "--auth-username",
         "testuser",
         "--auth-password",
@@ -151,8 +291,8 @@ fn test_basic_auth_credentials_file_integration() -> Result<()> {
     writeln!(temp_file, "fileuser:filepass")?;
     let temp_path = temp_file.path().to_string_lossy().to_string();
 
-    let config =
-        ServerConfig::new(FAKE_KOPIA_BIN)?.with_args(["--auth-credentials-file", &temp_path]);
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_args(["--auth-credentials-file", &temp_path]);
     let server = TestServer::start(config)?;
 
     // Test unauthenticated request - should get 401
@@ -182,7 +322,7 @@ fn run_timeout_test(
     let (_tempdir, log_file) = get_test_log_path(test_suffix);
 
     // Configure server with specified sleep and 0.5 second timeout
-    let config = ServerConfig::new(FAKE_KOPIA_BIN)?
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
         .with_env("FAKE_KOPIA_SLEEP_FOR_SECS", sleep_value)
         .with_env("FAKE_KOPIA_LOG", &log_file)
         .with_args(["--timeout", "0.5"]);
@@ -218,7 +358,7 @@ fn test_kopia_timeout_forever_returns_500() -> Result<()> {
 #[test]
 fn test_timeout_prints_stdout_and_stderr() -> Result<()> {
     // Configure server to trigger timeout and capture stderr
-    let config = ServerConfig::new(FAKE_KOPIA_BIN)?
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
         .with_args(["--timeout", "0.1"])
         .with_env("FAKE_KOPIA_WRITE_TEST_OUTPUT", "1")
         .with_env("FAKE_KOPIA_SLEEP_FOR_SECS", "10")
@@ -246,7 +386,7 @@ fn test_timeout_prints_stdout_and_stderr() -> Result<()> {
 #[test]
 fn test_large_json_output_success() -> Result<()> {
     // Configure server with fake-kopia generating ~1MB of JSON
-    let config = ServerConfig::new(FAKE_KOPIA_BIN)?
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
         .with_env("FAKE_KOPIA_LARGE_OUTPUT_MB", "1")
         .with_args(["--timeout", "15"]);
 
@@ -274,3 +414,1701 @@ fn test_large_json_output_success() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_influx_push() -> Result<()> {
+    let receiver = PushReceiver::start()?;
+    let influx_url = format!("{}/api/v2/write?bucket=kopia", receiver.url());
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--influx-url",
+        &influx_url,
+        "--push-interval",
+        "1",
+    ]);
+    let _server = TestServer::start(config)?;
+
+    let request = receiver.recv(Duration::from_secs(10))?;
+    assert!(
+        request
+            .head
+            .starts_with("POST /api/v2/write?bucket=kopia HTTP/1.1"),
+        "{}",
+        request.head
+    );
+    assert!(
+        request
+            .body
+            .contains("kopia_snapshots_total,source=kopia-system@milton:/persist-home value="),
+        "{}",
+        request.body
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_remote_write_push() -> Result<()> {
+    let receiver = PushReceiver::start()?;
+    let remote_write_url = format!("{}/api/v1/write", receiver.url());
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--remote-write-url",
+        &remote_write_url,
+        "--push-interval",
+        "1",
+    ]);
+    let _server = TestServer::start(config)?;
+
+    let request = receiver.recv(Duration::from_secs(10))?;
+    let head = &request.head;
+    assert!(head.starts_with("POST /api/v1/write HTTP/1.1"), "{head}");
+    assert!(head.contains("Content-Encoding: snappy"), "{head}");
+    assert!(
+        head.contains("Content-Type: application/x-protobuf"),
+        "{head}"
+    );
+    assert!(
+        request.body.contains("kopia_snapshots_total"),
+        "{}",
+        request.body
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_pushgateway_push() -> Result<()> {
+    let receiver = PushReceiver::start()?;
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--pushgateway-url",
+        &receiver.url(),
+        "--pushgateway-instance",
+        "nas",
+        "--push-interval",
+        "1",
+    ]);
+    let _server = TestServer::start(config)?;
+
+    let request = receiver.recv(Duration::from_secs(10))?;
+    let head = &request.head;
+    assert!(
+        head.starts_with("PUT /metrics/job/kopia_exporter/instance/nas HTTP/1.1"),
+        "{head}"
+    );
+    assertions::assert_prometheus_metrics(&request.body);
+
+    Ok(())
+}
+
+#[test]
+fn test_webhook_notification() -> Result<()> {
+    let receiver = PushReceiver::start()?;
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--webhook-url",
+        &receiver.url(),
+        "--max-age",
+        "1h",
+        "--push-interval",
+        "1",
+    ]);
+    let _server = TestServer::start(config)?;
+
+    let request = receiver.recv(Duration::from_secs(10))?;
+    assert!(
+        request.head.starts_with("POST / HTTP/1.1"),
+        "{}",
+        request.head
+    );
+    let payload: serde_json::Value = serde_json::from_str(&request.body)?;
+    assert_eq!(
+        payload["transitions"][0]["source"],
+        "kopia-system@milton:/persist-home"
+    );
+    assert_eq!(payload["transitions"][0]["healthy"], false);
+
+    Ok(())
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn test_history_db() -> Result<()> {
+    use kopia_exporter::history::HistoryDb;
+
+    let dir = tempfile::tempdir()?;
+    let history_db = dir.path().join("history.sqlite");
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_args(["--cache-seconds", "0"])
+        .with_args(["--history-db".as_ref(), history_db.as_os_str()]);
+    let server = TestServer::start(config)?;
+    let metrics = server.get("/metrics")?;
+    assert_eq!(metrics.status_code, 200);
+    let metrics = metrics.as_str()?;
+    assert!(
+        metrics.contains("# TYPE kopia_history_days_without_snapshots gauge"),
+        "{metrics}"
+    );
+    let status = server.get("/status")?;
+    assert_eq!(status.status_code, 200);
+    assert!(status.as_str()?.contains("<svg class=\"sparkline\""));
+    drop(server);
+
+    let history = HistoryDb::open(&history_db)?.load()?;
+    let sources: Vec<_> = history.iter().map(|(source, _)| source.as_str()).collect();
+    assert!(
+        sources.contains(&"kopia-system@milton:/persist-home"),
+        "{sources:?}"
+    );
+    for (source, snapshots) in history.iter() {
+        assert!(!snapshots.is_empty(), "{source:?}");
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "otel")]
+#[test]
+fn test_otlp_traces_export() -> Result<()> {
+    let receiver = PushReceiver::start()?;
+    let traces_url = format!("{}/v1/traces", receiver.url());
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_args(["--otlp-traces-url", &traces_url]);
+    let server = TestServer::start(config)?;
+    assert_eq!(server.get("/metrics")?.status_code, 200);
+
+    let request = receiver.recv(Duration::from_secs(15))?;
+    let head = &request.head;
+    assert!(head.starts_with("POST /v1/traces HTTP/1.1"), "{head}");
+    assert!(head.contains("Content-Type: application/json"), "{head}");
+    let payload: serde_json::Value = serde_json::from_str(&request.body)?;
+    let spans = payload["resourceSpans"][0]["scopeSpans"][0]["spans"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let span = |name: &str| {
+        spans
+            .iter()
+            .find(|span| span["name"] == name)
+            .expect("span exported")
+    };
+    let respond = span("respond");
+    assert_eq!(respond["parentSpanId"], "");
+    // the first scrape fetches while responding
+    for (name, parent) in [
+        ("fetch", respond),
+        ("kopia", span("fetch")),
+        ("parse", span("kopia")),
+        ("render", respond),
+    ] {
+        assert_eq!(span(name)["traceId"], respond["traceId"], "{name}");
+        assert_eq!(span(name)["parentSpanId"], parent["spanId"], "{name}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_ntfy_notification() -> Result<()> {
+    let receiver = PushReceiver::start()?;
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--ntfy-server",
+        &receiver.url(),
+        "--ntfy-topic",
+        "backups",
+        "--max-age",
+        "1h",
+        "--push-interval",
+        "1",
+    ]);
+    let _server = TestServer::start(config)?;
+
+    let request = receiver.recv(Duration::from_secs(10))?;
+    let head = &request.head;
+    assert!(head.starts_with("POST /backups HTTP/1.1"), "{head}");
+    assert!(
+        head.contains("\r\nTitle: Kopia backup CRITICAL\r\n"),
+        "{head}"
+    );
+    assert!(head.contains("\r\nPriority: high\r\n"), "{head}");
+    assert!(
+        request
+            .body
+            .starts_with("kopia-system@milton:/persist-home (age "),
+        "{}",
+        request.body
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_heartbeat_only_when_healthy() -> Result<()> {
+    let receiver = PushReceiver::start()?;
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--heartbeat-url",
+        &format!("{}/ping/abc", receiver.url()),
+        "--max-errors",
+        "1000",
+        "--push-interval",
+        "1",
+    ]);
+    let server = TestServer::start(config)?;
+    let request = receiver.recv(Duration::from_secs(10))?;
+    assert!(
+        request.head.starts_with("GET /ping/abc HTTP/1.1"),
+        "{}",
+        request.head
+    );
+    drop(server);
+
+    let receiver = PushReceiver::start()?;
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--heartbeat-url",
+        &receiver.url(),
+        "--max-age",
+        "1h",
+        "--push-interval",
+        "1",
+    ]);
+    let _server = TestServer::start(config)?;
+    assert!(receiver.recv(Duration::from_secs(3)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_textfile_output_without_http() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let textfile = dir.path().join("kopia.prom");
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--no-http".as_ref(),
+        "--textfile-output".as_ref(),
+        textfile.as_os_str(),
+    ]);
+    let server = TestServer::start(config)?;
+
+    // HTTP server is disabled
+    assert!(server.get("/metrics").is_err());
+
+    let mut contents = None;
+    for _ in 0..50 {
+        if let Ok(found) = fs::read_to_string(&textfile) {
+            contents = Some(found);
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let contents = contents.expect("textfile written");
+    assertions::assert_prometheus_metrics(&contents);
+
+    Ok(())
+}
+
+#[test]
+fn test_push_shares_cache() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let textfile = dir.path().join("kopia.prom");
+    let audit_log = dir.path().join("audit.jsonl");
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--textfile-output".as_ref(),
+        textfile.as_os_str(),
+        "--push-interval".as_ref(),
+        "1".as_ref(),
+        "--cache-seconds".as_ref(),
+        "3600".as_ref(),
+        "--kopia-audit-log".as_ref(),
+        audit_log.as_os_str(),
+    ]);
+    let server = TestServer::start(config)?;
+    server.get("/metrics")?;
+
+    // several pushes and requests, all from the same kopia run
+    thread::sleep(Duration::from_millis(2500));
+    server.get("/metrics")?;
+    assertions::assert_prometheus_metrics(&fs::read_to_string(&textfile)?);
+    let log = fs::read_to_string(&audit_log)?;
+    assert_eq!(log.lines().count(), 1, "{log}");
+
+    Ok(())
+}
+
+#[test]
+fn test_check_nagios() -> Result<()> {
+    let check = |kopia_bin: &str, extra_args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .args(["check", "--format", "nagios", "--kopia-bin", kopia_bin])
+            .args(extra_args)
+            .output()
+    };
+
+    let output = check(FAKE_KOPIA_BIN, &["--max-errors", "0"])?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.starts_with("KOPIA OK - "), "{stdout}");
+    assert!(
+        stdout.contains(" | 'kopia-system@milton:/persist-home age'="),
+        "{stdout}"
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = check(FAKE_KOPIA_BIN, &["--max-age", "1h"])?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.starts_with("KOPIA CRITICAL - "), "{stdout}");
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = check("/nonexistent/kopia", &[])?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.starts_with("KOPIA UNKNOWN - "), "{stdout}");
+    assert_eq!(output.status.code(), Some(3));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_human() -> Result<()> {
+    let check = |extra_args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .args(["check", "--kopia-bin", FAKE_KOPIA_BIN])
+            .args(extra_args)
+            .output()
+    };
+
+    let output = check(&["--max-errors", "0"])?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.starts_with("OK: "), "{stdout}");
+    assert!(
+        stdout.contains("\n  [OK] kopia-system@milton:/persist-home: age "),
+        "{stdout}"
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = check(&["--warn-max-age", "1h"])?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.starts_with("WARNING: "), "{stdout}");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = check(&["--max-age", "1h"])?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.starts_with("CRITICAL: "), "{stdout}");
+    assert_eq!(output.status.code(), Some(2));
+
+    Ok(())
+}
+
+#[test]
+fn test_generate_alerts() -> Result<()> {
+    let generate = |extra_args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .args(["generate", "alerts"])
+            .args(extra_args)
+            .output()
+    };
+
+    let output = generate(&["--max-age", "26h", "--max-errors", "0"])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.starts_with("groups:\n"), "{stdout}");
+    assert!(
+        stdout.contains("expr: kopia_snapshot_age_seconds > 93600\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("expr: kopia_snapshot_errors_total > 0\n"),
+        "{stdout}"
+    );
+
+    let output = generate(&[])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("No thresholds configured"), "{stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn test_dry_run() -> Result<()> {
+    // the port is not bound, so an address in use does not fail
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let bind = listener.local_addr()?.to_string();
+    let (_tempdir, audit_log) = get_test_log_path("audit");
+    let dry_run = |kopia_bin: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .args(["--dry-run", "--max-bind-retries", "0", "--bind", &bind])
+            .args(["--kopia-bin", kopia_bin])
+            .arg("--kopia-audit-log")
+            .arg(&audit_log)
+            // https:// URLs are accepted (and not pinged by the dry run)
+            .args(["--heartbeat-url", "https://hc-ping.com/kopia-exporter-test"])
+            .output()
+    };
+
+    let output = dry_run(FAKE_KOPIA_BIN)?;
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(
+        stdout.contains("Fetched 17 snapshots of 1 sources in "),
+        "{stdout}"
+    );
+    assert!(stdout.ends_with("Dry run OK\n"), "{stdout}");
+    // the trial fetch is not recorded
+    assert!(!audit_log.exists(), "{}", audit_log.display());
+
+    let output = dry_run("/nonexistent/kopia")?;
+    assert_eq!(output.status.code(), Some(69), "{output:?}");
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("/nonexistent/kopia"), "{stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshots_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let snapshots_file = dir.path().join("snapshots.json");
+    let write_snapshots = |json: &str, modified: &str| -> Result<()> {
+        fs::write(&snapshots_file, json)?;
+        let modified: jiff::Timestamp = modified.parse()?;
+        fs::File::options()
+            .write(true)
+            .open(&snapshots_file)?
+            .set_modified(modified.into())?;
+        Ok(())
+    };
+    write_snapshots(
+        include_str!("../../src/sample_kopia-snapshot-list.json"),
+        "2025-08-14T00:00:00Z",
+    )?;
+    // never runs kopia
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, "/nonexistent/kopia")?
+        .with_args(["--snapshots-file".as_ref(), snapshots_file.as_os_str()]);
+    let server = TestServer::start(config)?;
+
+    let metrics = server.get("/metrics")?.as_str()?.to_owned();
+    assert!(
+        metrics
+            .contains("\nkopia_snapshots_total{source=\"kopia-system@milton:/persist-home\"} 17\n"),
+        "{metrics}"
+    );
+    // the time the file was written
+    assert!(
+        metrics.contains("\nkopia_exporter_last_successful_fetch_timestamp 1755129600\n"),
+        "{metrics}"
+    );
+
+    assert!(
+        metrics.contains("\nkopia_exporter_snapshots_file_reload_failed 0\n"),
+        "{metrics}"
+    );
+
+    // reloaded once changed, without waiting for a request
+    let wait_for = |expected: &str| -> Result<String> {
+        let start = std::time::Instant::now();
+        loop {
+            let metrics = server.get("/metrics")?.as_str()?.to_owned();
+            if metrics.contains(expected) {
+                return Ok(metrics);
+            }
+            assert!(start.elapsed() < Duration::from_secs(10), "{metrics}");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    };
+    write_snapshots(
+        include_str!("../corpus/shape-windows.json"),
+        "2025-08-15T00:00:00Z",
+    )?;
+    let metrics = wait_for("\nkopia_exporter_last_successful_fetch_timestamp 1755216000\n")?;
+    assert!(!metrics.contains("kopia-system@milton"), "{metrics}");
+
+    // a failed reload keeps the previous snapshots
+    write_snapshots("[{", "2025-08-16T00:00:00Z")?;
+    let metrics = wait_for("\nkopia_exporter_snapshots_file_reload_failed 1\n")?;
+    assert!(
+        metrics.contains("\nkopia_exporter_last_successful_fetch_timestamp 1755216000\n"),
+        "{metrics}"
+    );
+
+    // replaced by renaming another file
+    let replacement = dir.path().join("snapshots.json.tmp");
+    fs::write(
+        &replacement,
+        include_str!("../../src/sample_kopia-snapshot-list.json"),
+    )?;
+    fs::rename(&replacement, &snapshots_file)?;
+    let metrics = wait_for("\nkopia_exporter_snapshots_file_reload_failed 0\n")?;
+    assert!(metrics.contains("kopia-system@milton"), "{metrics}");
+
+    Ok(())
+}
+
+#[test]
+fn test_pid_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let pid_file = dir.path().join("kopia-exporter.pid");
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_args(["--pid-file".as_ref(), pid_file.as_os_str()]);
+    let server = TestServer::start(config)?;
+    assert_eq!(server.get("/metrics")?.status_code, 200);
+
+    let pid: u32 = fs::read_to_string(&pid_file)?.trim().parse()?;
+    assert_eq!(pid, server.id());
+
+    let status = server.terminate()?;
+    assert!(status.success(), "{status}");
+    assert!(!pid_file.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_quit_endpoint() -> Result<()> {
+    const AUTH: &str = "Basic dGVzdHVzZXI6dGVzdHBhc3M="; // testuser:testpass
+
+    // disabled by default
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?;
+    let server = TestServer::start(config)?;
+    assert_eq!(server.post_with_auth("/-/quit", AUTH)?.status_code, 404);
+    drop(server);
+
+    // requires authentication
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .args(["--kopia-bin", FAKE_KOPIA_BIN, "--enable-quit"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(78), "{output:?}");
+
+    let dir = tempfile::tempdir()?;
+    let pid_file = dir.path().join("kopia-exporter.pid");
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_args(["--auth-username", "testuser", "--auth-password", "testpass"])
+        .with_args([
+            "--enable-quit".as_ref(),
+            "--pid-file".as_ref(),
+            pid_file.as_os_str(),
+        ]);
+    let server = TestServer::start(config)?;
+    let bad_auth = "Basic aW52YWxpZDppbnZhbGlk"; // invalid:invalid
+    assert_eq!(server.post_with_auth("/-/quit", bad_auth)?.status_code, 401);
+    assert_eq!(server.get_with_auth("/-/quit", AUTH)?.status_code, 404);
+    assert!(pid_file.exists());
+
+    let response = server.post_with_auth("/-/quit", AUTH)?;
+    assert_eq!(response.status_code, 200);
+    assert_eq!(response.as_str()?, "Shutting down");
+    let status = server.wait()?;
+    assert!(status.success(), "{status}");
+    assert!(!pid_file.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_kopia_env() -> Result<()> {
+    let (tempdir, log_file) = get_test_log_path("kopia-env");
+    let env_file = tempdir.path().join("kopia.env");
+    fs::write(
+        &env_file,
+        "# logs each invocation\nFAKE_KOPIA_LOG=/nonexistent/log\n",
+    )?;
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .args(["print", "--kopia-bin", FAKE_KOPIA_BIN])
+        .arg("--kopia-env-file")
+        .arg(&env_file)
+        .arg("--kopia-env")
+        .arg(format!("FAKE_KOPIA_LOG={}", log_file.display()))
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(fs::read_to_string(&log_file)?, "invocation, None\n");
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .args(["print", "--kopia-bin", FAKE_KOPIA_BIN, "--kopia-env-file"])
+        .arg(tempdir.path().join("missing.env"))
+        .output()?;
+    assert_eq!(output.status.code(), Some(78), "{output:?}");
+
+    Ok(())
+}
+
+#[test]
+fn test_kopia_audit_log() -> Result<()> {
+    let (_tempdir, audit_log) = get_test_log_path("audit");
+    let print = |kopia_bin: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .args(["print", "--kopia-bin", kopia_bin, "--kopia-audit-log"])
+            .arg(&audit_log)
+            .output()
+    };
+    let output = print(FAKE_KOPIA_BIN)?;
+    assert!(output.status.success(), "{output:?}");
+    let output = print("false")?;
+    assert!(!output.status.success(), "{output:?}");
+
+    let log = fs::read_to_string(&audit_log)?;
+    let records: Vec<serde_json::Value> = log
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(records.len(), 2, "{log}");
+    let sample_len = include_str!("../../src/sample_kopia-snapshot-list.json").len();
+    assert_eq!(records[0]["argv"][0], FAKE_KOPIA_BIN);
+    assert_eq!(records[0]["exit_code"], 0);
+    assert_eq!(records[0]["stdout_bytes"], sample_len);
+    assert_eq!(records[1]["argv"][0], "false");
+    assert_eq!(records[1]["exit_code"], 1);
+    assert_eq!(records[1]["timed_out"], false);
+
+    Ok(())
+}
+
+#[test]
+fn test_kopia_audit_log_unwritable() -> Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .args(["print", "--kopia-bin", FAKE_KOPIA_BIN, "--kopia-audit-log"])
+        .arg(tempdir.path().join("missing").join("audit.jsonl"))
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("failed to write kopia audit log"),
+        "{stderr}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fake_kopia_scenarios() -> Result<()> {
+    use kopia_exporter::{SnapshotJson, kopia::Source};
+
+    let dir = tempfile::tempdir()?;
+    let snapshot = |host: &str| {
+        SnapshotJson::builder()
+            .id(host)
+            .source(Source {
+                host: host.to_string(),
+                user_name: "root".to_string(),
+                path: "/data".to_string(),
+            })
+            .retention_reasons(&["latest-1"])
+            .build()
+    };
+    let multi_source = serde_json::to_string(&[snapshot("alpha"), snapshot("beta")])?;
+    fs::write(dir.path().join("multi-source.json"), &multi_source)?;
+    fs::write(dir.path().join("empty.json"), "[]")?;
+
+    let print = |fixture: &std::path::Path, scenario: Option<&str>| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"));
+        command
+            .args(["print", "--kopia-bin", FAKE_KOPIA_BIN])
+            .env("FAKE_KOPIA_SNAPSHOTS_FILE", fixture);
+        if let Some(scenario) = scenario {
+            command.env("FAKE_KOPIA_SCENARIO", scenario);
+        }
+        command.output()
+    };
+
+    let output = print(dir.path(), Some("multi-source"))?;
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains(r#"source="root@alpha:/data""#), "{stdout}");
+    assert!(stdout.contains(r#"source="root@beta:/data""#), "{stdout}");
+
+    let output = print(&dir.path().join("multi-source.json"), None)?;
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8(output.stdout)?.contains(r#"source="root@beta:/data""#));
+
+    let output = print(dir.path(), Some("empty"))?;
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(!stdout.contains("source="), "{stdout}");
+    assert!(stdout.contains("\nkopia_repository_empty 1\n"), "{stdout}");
+    assert!(stdout.contains("\nkopia_sources_total 0\n"), "{stdout}");
+
+    let output = print(dir.path(), Some("missing"))?;
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("missing.json"), "{stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn test_fake_kopia_failure_modes() -> Result<()> {
+    let print = |failure: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .args(["print", "--kopia-bin", FAKE_KOPIA_BIN])
+            .env("FAKE_KOPIA_FAILURE", failure)
+            .env("FAKE_KOPIA_EXIT_CODE", "3")
+            .env("FAKE_KOPIA_STDERR", "ERROR repository not connected")
+            .output()
+    };
+    for (failure, expected_stderr) in [
+        (
+            "exit",
+            "kopia command failed with exit code: 3\n    stderr: ERROR repository not connected",
+        ),
+        ("malformed-json", "expected ident"),
+        ("truncated-json", "EOF while parsing"),
+        ("empty", "EOF while parsing a value"),
+    ] {
+        let output = print(failure)?;
+        assert_eq!(output.status.code(), Some(69), "{failure}: {output:?}");
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(stderr.contains(expected_stderr), "{failure}: {stderr}");
+    }
+
+    // failures are served as errors, and cached
+    let (_tempdir, log_file) = get_test_log_path("failure-modes");
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_env("FAKE_KOPIA_FAILURE", "truncated-json")
+        .with_env("FAKE_KOPIA_LOG", &log_file)
+        .with_args(["--error-cache-seconds", "60"]);
+    let server = TestServer::start(config)?;
+    for _ in 0..2 {
+        let response = server.get("/metrics")?;
+        assert_eq!(response.status_code, 500);
+    }
+    drop(server);
+    let log = fs::read_to_string(&log_file).unwrap_or_default();
+    assert_eq!(log.lines().count(), 1, "{log}");
+
+    Ok(())
+}
+
+#[test]
+fn test_fake_kopia_repository_and_maintenance() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let maintenance_file = dir.path().join("maintenance.json");
+    fs::write(&maintenance_file, r#"{"owner":"alice@host"}"#)?;
+    fs::write(dir.path().join("readonly.json"), r#"{"readonly":true}"#)?;
+
+    let fake_kopia = |args: &[&str], envs: &[(&str, &std::path::Path)]| {
+        std::process::Command::new(FAKE_KOPIA_BIN)
+            .args(args)
+            .envs(envs.iter().copied())
+            .output()
+    };
+    let json = |output: std::process::Output| -> Result<serde_json::Value> {
+        assert!(output.status.success(), "{output:?}");
+        Ok(serde_json::from_slice(&output.stdout)?)
+    };
+
+    let status = json(fake_kopia(&["repository", "status", "--json"], &[])?)?;
+    assert_eq!(status["clientOptions"]["hostname"], "milton");
+    let maintenance = json(fake_kopia(&["maintenance", "info", "--json"], &[])?)?;
+    assert_eq!(maintenance["owner"], "kopia-system@milton");
+    assert_eq!(maintenance["runs"]["snapshot-gc"][0]["success"], true);
+
+    let maintenance = json(fake_kopia(
+        &["maintenance", "info", "--json"],
+        &[("FAKE_KOPIA_MAINTENANCE_INFO_FILE", &maintenance_file)],
+    )?)?;
+    assert_eq!(maintenance["owner"], "alice@host");
+    let status = json(fake_kopia(
+        &["repository", "status", "--json"],
+        &[
+            ("FAKE_KOPIA_REPOSITORY_STATUS_FILE", dir.path()),
+            ("FAKE_KOPIA_SCENARIO", "readonly".as_ref()),
+        ],
+    )?)?;
+    assert_eq!(status["readonly"], true);
+
+    let output = fake_kopia(
+        &["repository", "status", "--json"],
+        &[("FAKE_KOPIA_FAILURE", "exit".as_ref())],
+    )?;
+    assert_eq!(output.status.code(), Some(1));
+
+    Ok(())
+}
+
+#[test]
+fn test_fake_kopia_latency_and_flakiness() -> Result<()> {
+    use std::time::Instant;
+
+    let dir = tempfile::tempdir()?;
+    let counter_file = dir.path().join("counter");
+    let fake_kopia = |envs: &[(&str, &str)]| {
+        let start = Instant::now();
+        let output = std::process::Command::new(FAKE_KOPIA_BIN)
+            .args(["snapshot", "list", "--json"])
+            .env("FAKE_KOPIA_COUNTER_FILE", &counter_file)
+            .envs(envs.iter().copied())
+            .output()?;
+        Ok::<_, eyre::Report>((output.status.success(), start.elapsed()))
+    };
+
+    // every 3rd invocation is slow
+    let spike = [("FAKE_KOPIA_LATENCY", "spike:3:0.5")];
+    let elapsed: Vec<Duration> = (0..3)
+        .map(|_| fake_kopia(&spike).map(|(_, elapsed)| elapsed))
+        .collect::<Result<_>>()?;
+    assert!(elapsed[0] < Duration::from_millis(500), "{elapsed:?}");
+    assert!(elapsed[1] < Duration::from_millis(500), "{elapsed:?}");
+    assert!(elapsed[2] >= Duration::from_millis(500), "{elapsed:?}");
+
+    let (_, elapsed) = fake_kopia(&[("FAKE_KOPIA_LATENCY", "uniform:0.2:0.3")])?;
+    assert!(elapsed >= Duration::from_millis(200), "{elapsed:?}");
+
+    assert!(!fake_kopia(&[("FAKE_KOPIA_FAILURE_RATE", "1")])?.0);
+    assert!(fake_kopia(&[("FAKE_KOPIA_FAILURE_RATE", "0")])?.0);
+    let outcomes: Vec<bool> = (0..20)
+        .map(|_| {
+            fake_kopia(&[("FAKE_KOPIA_FAILURE_RATE", "0.5"), ("FAKE_KOPIA_SEED", "7")])
+                .map(|(success, _)| success)
+        })
+        .collect::<Result<_>>()?;
+    assert!(outcomes.contains(&true), "{outcomes:?}");
+    assert!(outcomes.contains(&false), "{outcomes:?}");
+
+    Ok(())
+}
+
+#[test]
+fn test_fake_kopia_generated_snapshots() -> Result<()> {
+    let output = std::process::Command::new(FAKE_KOPIA_BIN)
+        .args(["snapshot", "list", "--json"])
+        .env("FAKE_KOPIA_SNAPSHOT_COUNT", "50")
+        .env("FAKE_KOPIA_SOURCE_COUNT", "3")
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+
+    let (snapshots, invalid_sources) =
+        KopiaSnapshots::new_from_reader_with_report(output.stdout.as_slice())?;
+    assert!(invalid_sources.is_empty());
+    let counts: Vec<usize> = snapshots
+        .sources()
+        .map(|source| snapshots.snapshots_for(source).map_or(0, <[_]>::len))
+        .collect();
+    assert_eq!(counts, [17, 17, 16]);
+
+    Ok(())
+}
+
+#[test]
+fn test_version() -> Result<()> {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .arg("version")
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let version = format!("kopia-exporter {}\n", env!("CARGO_PKG_VERSION"));
+    assert!(stdout.starts_with(&version), "{stdout}");
+    assert!(stdout.contains("\nfeatures: cli"), "{stdout}");
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?;
+    let server = TestServer::start(config)?;
+    let response = server.get("/metrics")?;
+    let metrics = response.as_str()?;
+    let build_info = format!(
+        r#"kopia_exporter_build_info{{version="{}","#,
+        env!("CARGO_PKG_VERSION")
+    );
+    assert!(metrics.contains(&build_info), "{metrics}");
+
+    Ok(())
+}
+
+#[test]
+fn test_duration_metrics() -> Result<()> {
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?;
+    let server = TestServer::start(config)?;
+
+    let first = server.get("/metrics")?;
+    let first = first.as_str()?;
+    assert!(
+        first.contains("kopia_exporter_kopia_command_duration_seconds{command=\"snapshot list\"} "),
+        "{first}"
+    );
+    // no previous scrape to report yet
+    assert!(
+        !first.contains("kopia_exporter_scrape_duration_seconds "),
+        "{first}"
+    );
+
+    let second = server.get("/metrics")?;
+    let second = second.as_str()?;
+    assert!(
+        second.contains("# TYPE kopia_exporter_scrape_duration_seconds gauge"),
+        "{second}"
+    );
+    assert!(
+        second.contains("kopia_exporter_scrape_duration_seconds "),
+        "{second}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fetch_errors_counted() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let snapshots_file = dir.path().join("snapshots.json");
+    fs::write(&snapshots_file, "[{")?;
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_env("FAKE_KOPIA_SNAPSHOTS_FILE", &snapshots_file)
+        .with_args(["--cache-seconds", "0", "--error-cache-seconds", "0"]);
+    let server = TestServer::start(config)?;
+
+    for _ in 0..2 {
+        let response = server.get("/metrics")?;
+        assert_eq!(response.status_code, 500);
+    }
+
+    fs::write(
+        &snapshots_file,
+        include_str!("../../src/sample_kopia-snapshot-list.json"),
+    )?;
+    let response = server.get("/metrics")?;
+    assert_eq!(response.status_code, 200);
+    let metrics = response.as_str()?;
+    for expected in [
+        "# TYPE kopia_exporter_fetch_errors_total counter\n",
+        "\nkopia_exporter_fetch_errors_total{kind=\"timeout\"} 0\n",
+        "\nkopia_exporter_fetch_errors_total{kind=\"exit\"} 0\n",
+        "\nkopia_exporter_fetch_errors_total{kind=\"parse\"} 2\n",
+        // kopia succeeded, even when its output could not be parsed
+        "\nkopia_exporter_last_fetch_exit_code 0\n",
+    ] {
+        assert!(metrics.contains(expected), "{expected:?} in {metrics}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_http_request_metrics() -> Result<()> {
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?;
+    let server = TestServer::start(config)?;
+
+    for path in ["/nonexistent", "/other?query", "/", "/metrics"] {
+        let _ = server.get(path)?;
+    }
+    let response = server.get("/metrics")?;
+    let metrics = response.as_str()?;
+    for expected in [
+        "# TYPE kopia_exporter_http_requests_total counter\n",
+        "\nkopia_exporter_http_requests_total{path=\"/\",code=\"200\"} 1\n",
+        "\nkopia_exporter_http_requests_total{path=\"/metrics\",code=\"200\"} 1\n",
+        "\nkopia_exporter_http_requests_total{path=\"other\",code=\"404\"} 2\n",
+        "# TYPE kopia_exporter_http_request_duration_seconds histogram\n",
+        "\nkopia_exporter_http_request_duration_seconds_bucket{path=\"other\",le=\"+Inf\"} 2\n",
+        "\nkopia_exporter_http_request_duration_seconds_count{path=\"/metrics\"} 1\n",
+        "\nkopia_exporter_internal_panics_total 0\n",
+    ] {
+        assert!(metrics.contains(expected), "{expected:?} in {metrics}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_source_metrics_endpoint() -> Result<()> {
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?;
+    let server = TestServer::start(config)?;
+
+    let response = server.get("/metrics/source/kopia-system%40milton%3A%2Fpersist-home")?;
+    assert_eq!(response.status_code, 200);
+    let metrics = response.as_str()?;
+    assert!(
+        metrics
+            .contains("\nkopia_snapshots_total{source=\"kopia-system@milton:/persist-home\"} 17\n"),
+        "{metrics}"
+    );
+    // only the series of the source
+    assert!(!metrics.contains("kopia_sources_total"), "{metrics}");
+    assert!(!metrics.contains("kopia_exporter_"), "{metrics}");
+
+    let response = server.get(
+        "/metrics/source/kopia-system@milton:/persist-home?collect[]=kopia_snapshot_age_seconds",
+    )?;
+    assert_eq!(response.status_code, 200);
+    let metrics = response.as_str()?;
+    assert!(
+        metrics.starts_with("# HELP kopia_snapshot_age_seconds "),
+        "{metrics}"
+    );
+    assert!(!metrics.contains("kopia_snapshots_total"), "{metrics}");
+
+    let response = server.get("/metrics/source/nobody%40nowhere%3A%2F")?;
+    assert_eq!(response.status_code, 404);
+
+    let response = server.get("/metrics")?;
+    let metrics = response.as_str()?;
+    assert!(
+        metrics.contains(
+            "\nkopia_exporter_http_requests_total{path=\"/metrics/source/{source}\",code=\"404\"} 1\n"
+        ),
+        "{metrics}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_repo_id_label() -> Result<()> {
+    let config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--repo-id", "nas"]);
+    let server = TestServer::start(config)?;
+
+    let metrics = server.get("/metrics")?.as_str()?.to_owned();
+    assert!(
+        metrics.contains(
+            "\nkopia_snapshots_total{repo_id=\"nas\",source=\"kopia-system@milton:/persist-home\"} 17\n"
+        ),
+        "{metrics}"
+    );
+    let samples = metrics
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    for line in samples {
+        assert!(line.contains("{repo_id=\"nas\""), "{line}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_source_rollups() -> Result<()> {
+    let config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--source-rollups"]);
+    let server = TestServer::start(config)?;
+
+    let metrics = server.get("/metrics")?.as_str()?.to_owned();
+    for name in [
+        "kopia_snapshot_size_bytes_total",
+        "kopia_snapshots_total",
+        "kopia_snapshot_errors_total",
+        "kopia_snapshot_age_seconds",
+    ] {
+        assert!(
+            metrics.contains(&format!("\n{name}{{source=\"_all\"}} ")),
+            "{name}: {metrics}"
+        );
+    }
+    assert!(
+        metrics.contains("\nkopia_snapshots_total{source=\"_all\"} 17\n"),
+        "{metrics}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_repo_quota_days_until_full() -> Result<()> {
+    let server = TestServer::start(ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?)?;
+    let metrics = server.get("/metrics")?.as_str()?.to_owned();
+    assert!(
+        !metrics.contains("kopia_repository_estimated_days_until_full"),
+        "{metrics}"
+    );
+    drop(server);
+
+    // already exceeded by the latest snapshot
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_args(["--repo-quota-bytes", "1000"]);
+    let server = TestServer::start(config)?;
+    let metrics = server.get("/metrics")?.as_str()?.to_owned();
+    assert!(
+        metrics.contains("\nkopia_repository_estimated_days_until_full 0\n"),
+        "{metrics}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_expected_retention() -> Result<()> {
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args([
+        "--expected-retention",
+        "latest=1",
+        "--expected-retention",
+        "annual=100",
+    ]);
+    let server = TestServer::start(config)?;
+
+    let metrics = server.get("/metrics")?.as_str()?.to_owned();
+    assert!(
+        metrics.contains(
+            "\nkopia_retention_deficit{source=\"kopia-system@milton:/persist-home\",class=\"latest\"} 0\n"
+        ),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains(
+            "\nkopia_retention_deficit{source=\"kopia-system@milton:/persist-home\",class=\"annual\"} "
+        ),
+        "{metrics}"
+    );
+    assert!(
+        !metrics.contains(
+            "\nkopia_retention_deficit{source=\"kopia-system@milton:/persist-home\",class=\"annual\"} 0\n"
+        ),
+        "{metrics}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_schedule_drift() -> Result<()> {
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_args(["--expected-interval", "*=1h"]);
+    let server = TestServer::start(config)?;
+
+    let metrics = server.get("/metrics")?.as_str()?.to_owned();
+    assert!(
+        metrics.contains(
+            "\nkopia_snapshot_schedule_drift_seconds{source=\"kopia-system@milton:/persist-home\"} "
+        ),
+        "{metrics}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_cache_counters() -> Result<()> {
+    let config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--cache-seconds", "60"]);
+    let server = TestServer::start(config)?;
+
+    let mut metrics = String::new();
+    for _ in 0..3 {
+        metrics = server.get("/metrics")?.as_str()?.to_owned();
+    }
+    // the first request fetched, the others were served from the cache
+    assert!(
+        metrics.contains("\nkopia_exporter_cache_misses_total 1\n"),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains("\nkopia_exporter_cache_hits_total 2\n"),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains("\nkopia_exporter_data_age_seconds "),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains("\nkopia_exporter_last_successful_fetch_timestamp "),
+        "{metrics}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_generate_man() -> Result<()> {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .args(["generate", "man"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains(".TH kopia-exporter 1"), "{stdout}");
+    assert!(stdout.contains(r"\fB\-\-kopia\-bin\fR"), "{stdout}");
+
+    let dir = tempfile::tempdir()?;
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+        .args(["generate", "man", "--out-dir"])
+        .arg(dir.path())
+        .output()?;
+    assert!(output.status.success());
+    assert!(dir.path().join("kopia-exporter.1").exists());
+    assert!(dir.path().join("kopia-exporter-serve.1").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_diff() -> Result<()> {
+    let diff = |old: &std::path::Path, new: &std::path::Path, format: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .arg("diff")
+            .args([old, new])
+            .args(["--format", format])
+            .output()
+    };
+    let output = std::process::Command::new(FAKE_KOPIA_BIN)
+        .args(["snapshot", "list", "--json"])
+        .output()?;
+    let dir = tempfile::tempdir()?;
+    let old = dir.path().join("old.json");
+    fs::write(&old, &output.stdout)?;
+
+    let output = diff(&old, &old, "human")?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "0 sources changed\n");
+
+    // drop the oldest snapshot, and move the next one to another source
+    let json: Vec<serde_json::Value> = serde_json::from_slice(&fs::read(&old)?)?;
+    let mut json: Vec<_> = json.into_iter().skip(1).collect();
+    json[0]["source"]["host"] = "other".into();
+    let new = dir.path().join("new.json");
+    fs::write(&new, serde_json::to_vec(&json)?)?;
+
+    let output = diff(&old, &new, "human")?;
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(
+        stdout.contains("\n+ kopia-system@other:/persist-home: 1 snapshots\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.starts_with("~ kopia-system@milton:/persist-home: 17 -> 15 snapshots\n"),
+        "{stdout}"
+    );
+    assert!(stdout.ends_with("2 sources changed\n"), "{stdout}");
+
+    let output = diff(&old, &new, "json")?;
+    assert_eq!(output.status.code(), Some(1));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(json["sources"][1]["change"], "added", "{json}");
+    assert_eq!(json["sources"][0]["newSnapshotCount"], 15, "{json}");
+
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> Result<()> {
+    let validate = |path: &std::path::Path| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_kopia-exporter"))
+            .arg("validate")
+            .arg(path)
+            .output()
+    };
+    let output = std::process::Command::new(FAKE_KOPIA_BIN)
+        .args(["snapshot", "list", "--json"])
+        .output()?;
+    let dir = tempfile::tempdir()?;
+
+    let path = dir.path().join("snapshots.json");
+    fs::write(&path, &output.stdout)?;
+    let output = validate(&path)?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(
+        stdout.starts_with("kopia-system@milton:/persist-home: 17 snapshots, ending "),
+        "{stdout}"
+    );
+    assert!(stdout.ends_with("1 sources, 0 findings\n"), "{stdout}");
+
+    let json = fs::read_to_string(&path)?.replacen("\"kopia-system\"", "\"bad@user\"", 1);
+    let path = dir.path().join("invalid.json");
+    fs::write(&path, json)?;
+    let output = validate(&path)?;
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("16 snapshots"), "{stdout}");
+    assert!(
+        stdout.contains("skipped snapshot: invalid char '@'"),
+        "{stdout}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_backup_healthy_metrics() -> Result<()> {
+    let config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--max-age", "1h"]);
+    let server = TestServer::start(config)?;
+
+    let response = server.get("/metrics")?;
+    assert_eq!(response.status_code, 200);
+    let metrics = response.as_str()?;
+    assert!(
+        metrics.contains(r#"kopia_backup_healthy{source="kopia-system@milton:/persist-home"} 0"#),
+        "{metrics}"
+    );
+    assert!(metrics.contains("kopia_backup_healthy_all 0"), "{metrics}");
+
+    let health_response = server.get("/health")?;
+    assert_eq!(health_response.status_code, 503);
+    let health: serde_json::Value = serde_json::from_str(health_response.as_str()?)?;
+    assert_eq!(health["status"], "CRITICAL");
+    assert_eq!(
+        health["failing"][0]["source"],
+        "kopia-system@milton:/persist-home"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_status_page() -> Result<()> {
+    let config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--max-age", "1h"]);
+    let server = TestServer::start(config)?;
+
+    let response = server.get("/status")?;
+    assert_eq!(response.status_code, 200);
+    assert_eq!(
+        response.headers.get("content-type").map(String::as_str),
+        Some("text/html; charset=utf-8")
+    );
+    let html = response.as_str()?;
+    assert!(
+        html.contains("<h1>Kopia Exporter Status: CRITICAL</h1>"),
+        "{html}"
+    );
+    assert!(
+        html.contains(
+            "<tr><td>kopia-system@milton:/persist-home</td><td class=\"critical\">CRITICAL</td>"
+        ),
+        "{html}"
+    );
+    assert!(html.contains("<svg class=\"sparkline\""), "{html}");
+
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_only_metrics() -> Result<()> {
+    // ages depend on the time of the request, and the exporter's own statistics on the fetch
+    let stable_metrics = |args: &[&str]| -> Result<Vec<String>> {
+        let config =
+            ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(args.iter().copied());
+        let server = TestServer::start(config)?;
+        let response = server.get("/metrics")?;
+        assert_eq!(response.status_code, 200);
+        Ok(response
+            .as_str()?
+            .lines()
+            .filter(|line| !line.contains("age_seconds") && !line.starts_with("kopia_exporter_"))
+            .map(str::to_owned)
+            .collect())
+    };
+
+    let full = stable_metrics(&[])?;
+    let aggregated = stable_metrics(&["--aggregate-only"])?;
+    assert_eq!(full, aggregated);
+    let limited = stable_metrics(&["--max-snapshots-per-source", "2"])?;
+    assert_eq!(full, limited);
+
+    Ok(())
+}
+
+#[test]
+fn test_kopia_nice() -> Result<()> {
+    let config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--kopia-nice", "5"]);
+    let server = TestServer::start(config)?;
+
+    let response = server.get("/metrics")?;
+    assert_eq!(response.status_code, 200);
+    let metrics = response.as_str()?;
+    assert!(
+        metrics.contains(r#"kopia_snapshots_total{source="kopia-system@milton:/persist-home"}"#),
+        "{metrics}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_timestamps() -> Result<()> {
+    let config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--sample-timestamps"]);
+    let server = TestServer::start(config)?;
+
+    // every Prometheus endpoint, including the selections
+    for path in [
+        "/metrics",
+        "/metrics?collect[]=kopia_snapshots_total",
+        "/metrics/source/kopia-system@milton:/persist-home",
+    ] {
+        let response = server.get(path)?;
+        assert_eq!(response.status_code, 200, "{path}");
+        let metrics = response.as_str()?;
+        let line = metrics
+            .lines()
+            .find(|line| line.starts_with("kopia_snapshots_total{"))
+            .ok_or_else(|| eyre::eyre!("{path}: missing kopia_snapshots_total: {metrics}"))?;
+        assert_eq!(line.split(' ').count(), 3, "{path}: {line}");
+        let timestamp_millis: i64 = line.rsplit(' ').next().unwrap_or_default().parse()?;
+        let fetched_at = jiff::Timestamp::from_millisecond(timestamp_millis)?;
+        let age = jiff::Timestamp::now().duration_since(fetched_at);
+        assert!(age.as_secs().abs() < 60, "{path}: {line}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_metrics_collect_selection() -> Result<()> {
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?;
+    let server = TestServer::start(config)?;
+
+    let response =
+        server.get("/metrics?collect[]=kopia_snapshots_total&collect[]=new_snapshot_health")?;
+    assert_eq!(response.status_code, 200);
+    let types: Vec<_> = response
+        .as_str()?
+        .lines()
+        .filter_map(|line| line.strip_prefix("# TYPE "))
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "kopia_snapshot_age_seconds gauge",
+            "kopia_snapshot_last_success_timestamp gauge",
+            "kopia_snapshots_total gauge",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_stale_while_revalidate() -> Result<()> {
+    let data_age = |metrics: &str| -> Result<i64> {
+        let value = metrics
+            .lines()
+            .find_map(|line| line.strip_prefix("kopia_exporter_data_age_seconds "))
+            .ok_or_else(|| eyre::eyre!("missing data age: {metrics}"))?;
+        Ok(value.parse()?)
+    };
+    let config =
+        ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?.with_args(["--cache-seconds", "1"]);
+    let server = TestServer::start(config)?;
+
+    let response = server.get("/metrics")?;
+    assert_eq!(response.status_code, 200);
+    assert!(data_age(response.as_str()?)? <= 1);
+
+    // expired data is served immediately, while refreshing in the background
+    thread::sleep(Duration::from_millis(2100));
+    let response = server.get("/metrics")?;
+    assert!(data_age(response.as_str()?)? >= 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_warm_up() -> Result<()> {
+    let (_tempdir, log_file) = get_test_log_path("warm-up");
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_env("FAKE_KOPIA_LOG", &log_file)
+        .with_args(["--warm-up-timeout", "5"]);
+    let server = TestServer::start(config)?;
+
+    // fetched at startup, before any request
+    let log = fs::read_to_string(&log_file).unwrap_or_default();
+    assert_eq!(log.lines().count(), 1, "{log}");
+
+    let response = server.get("/metrics")?;
+    assert_eq!(response.status_code, 200);
+    drop(server);
+
+    let log = fs::read_to_string(&log_file).unwrap_or_default();
+    assert_eq!(log.lines().count(), 1, "{log}");
+
+    Ok(())
+}
+
+#[test]
+fn test_error_cache() -> Result<()> {
+    let (_tempdir, log_file) = get_test_log_path("error-cache");
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_env("FAKE_KOPIA_SLEEP_FOR_SECS", "1")
+        .with_env("FAKE_KOPIA_LOG", &log_file)
+        .with_args(["--timeout", "0.2", "--error-cache-seconds", "60"]);
+    let server = TestServer::start(config)?;
+
+    for _ in 0..3 {
+        let response = server.get("/metrics")?;
+        assert_eq!(response.status_code, 500);
+    }
+    drop(server);
+
+    // the failure is served from the cache, without running kopia again
+    let log = fs::read_to_string(&log_file).unwrap_or_default();
+    assert_eq!(log.lines().count(), 1, "{log}");
+
+    Ok(())
+}
+
+#[test]
+fn test_malformed_snapshot_skipped() -> Result<()> {
+    let sample: Vec<serde_json::Value> =
+        serde_json::from_str(include_str!("../../src/sample_kopia-snapshot-list.json"))?;
+    let mut malformed = sample[0].clone();
+    malformed["stats"]["totalSize"] = serde_json::json!("not a number");
+    let mut snapshots = vec![malformed];
+    snapshots.extend(sample);
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("snapshots.json");
+    fs::write(&path, serde_json::to_string(&snapshots)?)?;
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_env("FAKE_KOPIA_SNAPSHOTS_FILE", &path)
+        .with_stderr_capture();
+    let server = TestServer::start(config)?;
+
+    let response = server.get("/metrics")?;
+    assert_eq!(response.status_code, 200);
+    let body = response.as_str()?;
+    assertions::assert_prometheus_metrics(body);
+    assert!(
+        body.contains("\nkopia_snapshot_parse_errors_malformed_total 1\n"),
+        "{body}"
+    );
+
+    let stderr = server.kill_and_read_stderr();
+    assert!(
+        stderr.contains("skipped 1 malformed snapshots, first: snapshot 0: invalid type"),
+        "{stderr}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_listing_shapes() -> Result<()> {
+    let sample: Vec<serde_json::Value> =
+        serde_json::from_str(include_str!("../../src/sample_kopia-snapshot-list.json"))?;
+    let ndjson = sample
+        .iter()
+        .map(|snapshot| Ok(serde_json::to_string(snapshot)? + "\n"))
+        .collect::<Result<String>>()?;
+    let wrapped = serde_json::to_string(&serde_json::json!({ "snapshots": sample }))?;
+    let dir = tempfile::tempdir()?;
+
+    let print = |name: &str, content: &str| -> Result<String> {
+        let path = dir.path().join(name);
+        fs::write(&path, content)?;
+        let output = std::process::Command::new(KOPIA_EXPORTER_BIN)
+            .args(["print", "--kopia-bin", FAKE_KOPIA_BIN])
+            .env("FAKE_KOPIA_SNAPSHOTS_FILE", &path)
+            .output()?;
+        assert!(output.status.success(), "{name}: {output:?}");
+        // ages depend on the current time, and the exporter's own statistics on the fetch
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout
+            .lines()
+            .filter(|line| !line.contains("age_seconds") && !line.starts_with("kopia_exporter_"))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    };
+
+    let expected = print("array.json", &serde_json::to_string(&sample)?)?;
+    assert!(expected.contains("\nkopia_snapshots_total{"), "{expected}");
+    assert_eq!(print("ndjson.json", &ndjson)?, expected);
+    assert_eq!(print("wrapped.json", &wrapped)?, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_scrapes_byte_stable() -> Result<()> {
+    // uncached, so each scrape re-runs kopia and re-parses the listing
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_args(["--cache-seconds", "0"])
+        .with_env("FAKE_KOPIA_SNAPSHOT_COUNT", "200")
+        .with_env("FAKE_KOPIA_SOURCE_COUNT", "7");
+    let server = TestServer::start(config)?;
+
+    let scrape = || -> Result<String> {
+        let response = server.get("/metrics")?;
+        assert_eq!(response.status_code, 200);
+        // ages depend on the current time, and the exporter's own statistics on the scrapes
+        Ok(response
+            .as_str()?
+            .lines()
+            .filter(|line| !line.contains("age_seconds") && !line.starts_with("kopia_exporter_"))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    };
+
+    // the first scrape has no previous scrape or requests to report
+    scrape()?;
+    let expected = scrape()?;
+    assert!(expected.contains("\nkopia_snapshots_total{"), "{expected}");
+    for _ in 0..2 {
+        assert_eq!(scrape()?, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_utf8_replaced() -> Result<()> {
+    let json = include_str!("../../src/sample_kopia-snapshot-list.json")
+        .replacen("/persist-home", "/persist-h~me", 1)
+        .into_bytes()
+        .into_iter()
+        .map(|byte| if byte == b'~' { 0xf6 } else { byte })
+        .collect::<Vec<u8>>();
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("snapshots.json");
+    fs::write(&path, json)?;
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_env("FAKE_KOPIA_SNAPSHOTS_FILE", &path)
+        .with_stderr_capture();
+    let server = TestServer::start(config)?;
+
+    let response = server.get("/metrics")?;
+    assert_eq!(response.status_code, 200);
+    let body = response.as_str()?;
+    assertions::assert_prometheus_metrics(body);
+    assert!(
+        body.contains("\nkopia_snapshot_parse_errors_utf8_total 1\n"),
+        "{body}"
+    );
+    assert!(
+        body.contains(
+            "kopia_snapshots_total{source=\"kopia-system@milton:/persist-h\u{FFFD}me\"} 1"
+        ),
+        "{body}"
+    );
+
+    let stderr = server.kill_and_read_stderr();
+    assert!(
+        stderr.contains("replaced 1 invalid UTF-8 sequences in the kopia output"),
+        "{stderr}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_kopia_stderr_truncated() -> Result<()> {
+    let long_output = format!("WARNING {}", "x".repeat(10_000));
+    let expected = format!("{} (truncated)", &long_output[..64]);
+
+    let config = ServerConfig::new(KOPIA_EXPORTER_BIN, FAKE_KOPIA_BIN)?
+        .with_env("FAKE_KOPIA_WARNING", &long_output)
+        .with_args(["--kopia-stderr-limit", "64"])
+        .with_stderr_capture();
+    let server = TestServer::start(config)?;
+    let response = server.get("/metrics")?;
+    assert_eq!(response.status_code, 200);
+    let stderr = server.kill_and_read_stderr();
+    assert!(
+        stderr.contains(&format!(
+            "Warning: kopia succeeded with output on stderr: {expected}\n"
+        )),
+        "{stderr}"
+    );
+
+    let output = std::process::Command::new(KOPIA_EXPORTER_BIN)
+        .args(["print", "--kopia-bin", FAKE_KOPIA_BIN])
+        .args(["--kopia-stderr-limit", "64"])
+        .env("FAKE_KOPIA_FAILURE", "exit")
+        .env("FAKE_KOPIA_STDERR", &long_output)
+        .output()?;
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains(&format!("stderr: {expected}")), "{stderr}");
+    assert!(!stderr.contains(&long_output[..100]), "{stderr}");
+
+    Ok(())
+}
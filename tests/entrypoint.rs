@@ -1,9 +1,11 @@
 //! Single test binary to allow integration to run in parallel
 
+const KOPIA_EXPORTER_BIN: &str = env!("CARGO_BIN_EXE_kopia-exporter");
 const FAKE_KOPIA_BIN: &str = env!("CARGO_BIN_EXE_fake-kopia");
 
 mod common {
     mod bind_retry_test;
+    mod compat_test;
     mod integration_test;
 }
 